@@ -0,0 +1,118 @@
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use sha1::{Digest, Sha1};
+use zookeeper::{Acl, perms};
+
+
+/// Parses a comma separated list of ACL entries, each one of:
+///
+/// - `open`, `read-only` or `creator-all`, as in ZooKeeper's predefined ACLs
+/// - `world:anyone:<perms>` or `auth::<perms>`
+/// - `ip:<address>[/<bits>]:<perms>`, IPv4 or IPv6
+/// - `digest:<user>:<password>:<perms>`, sent as the hashed password
+/// - `sasl:<id>:<perms>` or `x509:<id>:<perms>`
+///
+/// Perms are letters out of cdrwa (or all). Nothing is sent to the server
+/// if any entry is wrong.
+pub fn parse(spec: &str) -> Result<Vec<Acl>, String> {
+    let mut acl = vec![];
+    for entry in spec.split(',').map(|entry| entry.trim()) {
+        match parse_entry(entry) {
+            Ok(parsed) => acl.push(parsed),
+            Err(err) => return Err(format!("Bad ACL entry '{}': {}", entry, err)),
+        }
+    }
+    Ok(acl)
+}
+
+fn entry(scheme: &str, id: &str, perms: i32) -> Acl {
+    Acl { perms: perms, scheme: scheme.to_string(), id: id.to_string() }
+}
+
+fn parse_entry(spec: &str) -> Result<Acl, String> {
+    match spec {
+        "open" => return Ok(entry("world", "anyone", perms::ALL)),
+        "read-only" => return Ok(entry("world", "anyone", perms::READ)),
+        "creator-all" => return Ok(entry("auth", "", perms::ALL)),
+        _ => (),
+    }
+
+    // the id may have colons of its own (i.e.: IPv6 or user:password)
+    let (scheme, rest) = match spec.find(':') {
+        Some(idx) => (&spec[..idx], &spec[idx + 1..]),
+        None => return Err("expected scheme:id:perms or open, read-only, creator-all".to_string()),
+    };
+    let (id, perms) = match rest.rfind(':') {
+        Some(idx) => (&rest[..idx], try!(parse_perms(&rest[idx + 1..]))),
+        None => return Err("missing perms, i.e.: cdrwa".to_string()),
+    };
+
+    match scheme {
+        "world" if id == "anyone" => Ok(entry(scheme, id, perms)),
+        "world" => Err("the only world id is anyone".to_string()),
+        "auth" => Ok(entry(scheme, "", perms)),
+        "ip" => {
+            try!(check_ip(id));
+            Ok(entry(scheme, id, perms))
+        },
+        "digest" => match id.find(':') {
+            Some(idx) if idx > 0 => Ok(entry(scheme, &*digest(&id[..idx], &id[idx + 1..]), perms)),
+            _ => Err("expected digest:user:password:perms".to_string()),
+        },
+        "sasl" | "x509" if !id.is_empty() => Ok(entry(scheme, id, perms)),
+        "sasl" | "x509" => Err(format!("missing the {} id", scheme)),
+        _ => Err(format!("unknown scheme {}", scheme)),
+    }
+}
+
+/// Perms as letters out of cdrwa, i.e.: rw, or all.
+pub fn parse_perms(letters: &str) -> Result<i32, String> {
+    if letters == "all" {
+        return Ok(perms::ALL);
+    }
+    if letters.is_empty() {
+        return Err("no perms given, i.e.: cdrwa".to_string());
+    }
+    let mut granted = 0;
+    for c in letters.chars() {
+        granted |= match c {
+            'c' => perms::CREATE,
+            'd' => perms::DELETE,
+            'r' => perms::READ,
+            'w' => perms::WRITE,
+            'a' => perms::ADMIN,
+            c => return Err(format!("unknown perm {}, expected letters out of cdrwa", c)),
+        };
+    }
+    Ok(granted)
+}
+
+// an address, optionally followed by /bits
+fn check_ip(id: &str) -> Result<(), String> {
+    let (address, bits) = match id.find('/') {
+        Some(idx) => (&id[..idx], Some(&id[idx + 1..])),
+        None => (id, None),
+    };
+    let max_bits = if address.contains(':') {
+        if address.parse::<::std::net::Ipv6Addr>().is_err() {
+            return Err(format!("bad IPv6 address {}", address));
+        }
+        128
+    } else {
+        if address.parse::<::std::net::Ipv4Addr>().is_err() {
+            return Err(format!("bad IPv4 address {}", address));
+        }
+        32
+    };
+    match bits.map(|bits| bits.parse::<u32>()) {
+        Some(Ok(bits)) if bits > max_bits => Err(format!("at most {} bits for {}", max_bits, address)),
+        Some(Err(_)) => Err(format!("bad prefix length in {}", id)),
+        _ => Ok(()),
+    }
+}
+
+/// The id ZooKeeper's digest scheme expects: user:base64(sha1(user:password)).
+pub fn digest(user: &str, password: &str) -> String {
+    let hash = Sha1::digest(format!("{}:{}", user, password).as_bytes());
+    format!("{}:{}", user, STANDARD.encode(hash))
+}
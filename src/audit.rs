@@ -0,0 +1,39 @@
+use std::env;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+
+use time;
+
+
+/// Appends a line per executed command to a file, so what was done (and
+/// where) can be pieced together afterwards:
+///
+/// ```text
+/// 2015-06-01T10:00:00+02:00  alice  zk1:2181  ok  rm /services/foo
+/// ```
+///
+/// Fields are tab separated; failed commands are marked FAILED.
+pub struct AuditLog {
+    file: File,
+    user: String,
+}
+
+impl AuditLog {
+    pub fn open(path: &Path) -> io::Result<AuditLog> {
+        let file = try!(OpenOptions::new().create(true).append(true).open(path));
+        let user = env::var("USER").or(env::var("USERNAME")).unwrap_or("-".to_string());
+        Ok(AuditLog { file: file, user: user })
+    }
+
+    pub fn record(&mut self, hosts: &str, line: &str, succeeded: bool) {
+        let hosts = if hosts.is_empty() { "-" } else { hosts };
+        let status = if succeeded { "ok" } else { "FAILED" };
+        // a broken audit log shouldn't break the shell, but it shouldn't
+        // go unnoticed either
+        if let Err(err) = writeln!(self.file, "{}\t{}\t{}\t{}\t{}",
+                                   time::now().rfc3339(), self.user, hosts, status, line) {
+            warn!("failed to write to the audit log: {}", err);
+        }
+    }
+}
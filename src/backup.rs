@@ -0,0 +1,231 @@
+//! Logical backups: a gzipped file with a manifest line followed by a line
+//! per znode, parents before their children, all of them JSON:
+//!
+//! ```text
+//! {"format":"zk-shell-backup","version":1,"created":"2015-06-01T10:00:00Z","root":"/","znodes":1}
+//! {"path":"/config","data":"x=1","acl":[{"scheme":"world","id":"anyone","perms":31}]}
+//! ```
+//!
+//! Values that aren't UTF-8 are kept as hex digits (hex instead of data).
+
+use std::str;
+
+use serde_json::{self, Value};
+use time;
+use zookeeper::{Acl, CreateMode, ZkError, ZkResult};
+
+use client::ZkClient;
+use gzip;
+use traversal::walk;
+
+
+pub const FORMAT: &'static str = "zk-shell-backup";
+pub const VERSION: i64 = 1;
+
+// what an archive may decompress to: the whole tree, which restore holds in
+// memory anyway
+const MAX_SIZE: u64 = 4 * 1024 * 1024 * 1024;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Znode {
+    pub path: String,
+    pub data: Vec<u8>,
+    pub acl: Vec<Acl>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Manifest {
+    /// When the backup was taken, as RFC 3339.
+    pub created: String,
+    pub root: String,
+    pub znodes: usize,
+}
+
+impl Manifest {
+    pub fn new(root: &str, znodes: usize) -> Manifest {
+        Manifest { created: time::now_utc().rfc3339().to_string(), root: root.to_string(), znodes: znodes }
+    }
+}
+
+/// What restore did: znodes created, znodes whose data was overwritten and
+/// znodes left alone because they were already there.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Restored {
+    pub created: usize,
+    pub updated: usize,
+    pub skipped: usize,
+}
+
+/// What restore would do, znode by znode: create them (with their bytes),
+/// overwrite their data (with overwrite, when it differs), leave them as they
+/// are (with overwrite, when it doesn't) or skip them (already there).
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Preview {
+    pub created: usize,
+    pub created_bytes: usize,
+    pub updated: usize,
+    pub updated_bytes: usize,
+    pub unchanged: usize,
+    pub skipped: usize,
+}
+
+/// Reads root and its descendants, except for the subtrees in exclude and
+/// for ephemerals (they belong to their sessions). / itself isn't kept,
+/// there's no creating it. Znodes that vanish while reading are skipped.
+pub fn collect(zk: &ZkClient, root: &str, exclude: &[&str]) -> ZkResult<Vec<Znode>> {
+    let excluded = |path: &str| exclude.iter().any(|ex| path == *ex || path.starts_with(&*format!("{}/", ex)));
+    let mut paths = vec![];
+    try!(walk(zk, root, None, &mut |path, _| if path != "/" && !excluded(path) { paths.push(path.to_string()) }));
+
+    let mut znodes = Vec::with_capacity(paths.len());
+    for path in paths {
+        let (data, stat) = match zk.get_data(&*path, false) {
+            Ok(read) => read,
+            Err(ZkError::NoNode) => continue,
+            Err(err) => return Err(err),
+        };
+        if stat.ephemeral_owner != 0 {
+            continue;
+        }
+        let acl = match zk.get_acl(&*path) {
+            Ok((acl, _)) => acl,
+            Err(ZkError::NoNode) => continue,
+            Err(err) => return Err(err),
+        };
+        znodes.push(Znode { path: path, data: data, acl: acl });
+    }
+    Ok(znodes)
+}
+
+/// Creates the znodes in order, with their ACLs. The ones that already
+/// exist are skipped, or get their data overwritten with overwrite (their
+/// ACLs stay as they are).
+pub fn restore(zk: &ZkClient, znodes: &[Znode], overwrite: bool) -> ZkResult<Restored> {
+    let mut restored = Restored::default();
+    for znode in znodes {
+        match zk.create(&*znode.path, znode.data.clone(), znode.acl.clone(), CreateMode::Persistent) {
+            Ok(_) => restored.created += 1,
+            Err(ZkError::NodeExists) if overwrite => {
+                try!(zk.set_data(&*znode.path, znode.data.clone(), -1));
+                restored.updated += 1;
+            },
+            Err(ZkError::NodeExists) => restored.skipped += 1,
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(restored)
+}
+
+/// What restore(zk, znodes, overwrite) would do, without doing it.
+pub fn preview(zk: &ZkClient, znodes: &[Znode], overwrite: bool) -> ZkResult<Preview> {
+    let mut preview = Preview::default();
+    for znode in znodes {
+        match zk.get_data(&*znode.path, false) {
+            Ok(_) if !overwrite => preview.skipped += 1,
+            Ok((ref data, _)) if *data == znode.data => preview.unchanged += 1,
+            Ok(_) => {
+                preview.updated += 1;
+                preview.updated_bytes += znode.data.len();
+            },
+            Err(ZkError::NoNode) => {
+                preview.created += 1;
+                preview.created_bytes += znode.data.len();
+            },
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(preview)
+}
+
+/// The archive for znodes, gzipped.
+pub fn encode(manifest: &Manifest, znodes: &[Znode]) -> Vec<u8> {
+    let header = json!({
+        "format": FORMAT,
+        "version": VERSION,
+        "created": manifest.created,
+        "root": manifest.root,
+        "znodes": manifest.znodes,
+    });
+    let mut out = format!("{}\n", header);
+    for znode in znodes {
+        let acl: Vec<Value> = znode.acl.iter().map(|acl| json!({
+            "scheme": acl.scheme,
+            "id": acl.id,
+            "perms": acl.perms,
+        })).collect();
+        let line = match str::from_utf8(&znode.data[..]) {
+            Ok(data) => json!({"path": znode.path, "data": data, "acl": acl}),
+            Err(_) => {
+                let hex: String = znode.data.iter().map(|b| format!("{:02x}", b)).collect();
+                json!({"path": znode.path, "hex": hex, "acl": acl})
+            },
+        };
+        out.push_str(&*format!("{}\n", line));
+    }
+    gzip::compress(out.as_bytes())
+}
+
+/// The inverse of encode, checking the manifest's format and count.
+pub fn decode(archive: &[u8]) -> Result<(Manifest, Vec<Znode>), String> {
+    let text = try!(gzip::decompress(archive, MAX_SIZE).map_err(|err| format!("not a backup: {}", err)));
+    let text = try!(String::from_utf8(text).map_err(|_| "not a backup: not UTF-8".to_string()));
+    let mut lines = text.lines().filter(|line| !line.is_empty());
+
+    let header: Value = try!(serde_json::from_str(lines.next().unwrap_or("")).map_err(|err| format!("bad manifest: {}", err)));
+    if header["format"].as_str() != Some(FORMAT) {
+        return Err("not a backup: no manifest".to_string());
+    }
+    match header.get("version") {
+        Some(version) if version.as_i64() == Some(VERSION) => (),
+        Some(version) => return Err(format!("unsupported backup version: {}", version)),
+        None => return Err("bad manifest: no version".to_string()),
+    }
+    let manifest = match (header["created"].as_str(), header["root"].as_str(), header["znodes"].as_u64()) {
+        (Some(created), Some(root), Some(znodes)) => {
+            Manifest { created: created.to_string(), root: root.to_string(), znodes: znodes as usize }
+        },
+        _ => return Err("bad manifest: expected created, root and znodes".to_string()),
+    };
+
+    let mut znodes = Vec::with_capacity(manifest.znodes);
+    for (i, line) in lines.enumerate() {
+        match serde_json::from_str(line).ok().and_then(|value| parse_znode(&value)) {
+            Some(znode) => znodes.push(znode),
+            None => return Err(format!("bad znode (#{})", i + 1)),
+        }
+    }
+    if znodes.len() != manifest.znodes {
+        return Err(format!("truncated: {} znodes out of {}", znodes.len(), manifest.znodes));
+    }
+    Ok((manifest, znodes))
+}
+
+fn parse_znode(value: &Value) -> Option<Znode> {
+    let path = match value["path"].as_str() {
+        Some(path) if path.starts_with('/') => path.to_string(),
+        _ => return None,
+    };
+    let data = match (value.get("data").map(Value::as_str), value.get("hex").map(Value::as_str)) {
+        (Some(Some(data)), None) => data.as_bytes().to_vec(),
+        (None, Some(Some(hex))) if hex.len() % 2 == 0 && hex.chars().all(|c| c.is_digit(16)) => {
+            (0..hex.len() / 2).map(|i| u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).unwrap()).collect()
+        },
+        _ => return None,
+    };
+    let acl = match value["acl"].as_array() {
+        Some(entries) => {
+            let mut acl = vec![];
+            for entry in entries {
+                match (entry["scheme"].as_str(), entry["id"].as_str(), entry["perms"].as_i64()) {
+                    (Some(scheme), Some(id), Some(perms)) => {
+                        acl.push(Acl { perms: perms as i32, scheme: scheme.to_string(), id: id.to_string() });
+                    },
+                    _ => return None,
+                }
+            }
+            acl
+        },
+        _ => return None,
+    };
+    Some(Znode { path: path, data: data, acl: acl })
+}
@@ -0,0 +1,569 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use time;
+use zookeeper::{Acl, CreateMode, Stat, Watcher, WatchedEvent, ZkError, ZkResult, ZooKeeper};
+
+use metrics::Metrics;
+use shell::output;
+use util::{format_acl, parent_path};
+
+
+/// The ZooKeeper operations the shell relies on, so commands can run against
+/// something other than a live ensemble (i.e.: `mem::MemClient` in tests).
+///
+/// Watchers are boxed, to keep the trait usable as an object.
+pub trait ZkClient: Send + Sync {
+    fn create(&self, path: &str, data: Vec<u8>, acl: Vec<Acl>, mode: CreateMode) -> ZkResult<String>;
+
+    fn delete(&self, path: &str, version: i32) -> ZkResult<()>;
+
+    fn exists(&self, path: &str, watch: bool) -> ZkResult<Stat>;
+
+    fn exists_w(&self, path: &str, watcher: Box<Watcher>) -> ZkResult<Stat>;
+
+    fn get_children(&self, path: &str, watch: bool) -> ZkResult<Vec<String>>;
+
+    fn get_children_w(&self, path: &str, watcher: Box<Watcher>) -> ZkResult<Vec<String>>;
+
+    fn get_data(&self, path: &str, watch: bool) -> ZkResult<(Vec<u8>, Stat)>;
+
+    fn get_data_w(&self, path: &str, watcher: Box<Watcher>) -> ZkResult<(Vec<u8>, Stat)>;
+
+    fn set_data(&self, path: &str, data: Vec<u8>, version: i32) -> ZkResult<Stat>;
+
+    fn get_acl(&self, path: &str) -> ZkResult<(Vec<Acl>, Stat)>;
+
+    fn set_acl(&self, path: &str, acl: Vec<Acl>, version: i32) -> ZkResult<Stat>;
+
+    fn add_auth(&self, scheme: &str, auth: Vec<u8>) -> ZkResult<()>;
+
+    fn close(&self);
+}
+
+// lets a boxed watcher go where the zookeeper crate wants a W: Watcher
+struct BoxedWatcher(Box<Watcher>);
+
+impl Watcher for BoxedWatcher {
+    fn handle(&self, e: &WatchedEvent) {
+        self.0.handle(e)
+    }
+}
+
+impl ZkClient for ZooKeeper {
+    fn create(&self, path: &str, data: Vec<u8>, acl: Vec<Acl>, mode: CreateMode) -> ZkResult<String> {
+        ZooKeeper::create(self, path, data, acl, mode)
+    }
+
+    fn delete(&self, path: &str, version: i32) -> ZkResult<()> {
+        ZooKeeper::delete(self, path, version)
+    }
+
+    fn exists(&self, path: &str, watch: bool) -> ZkResult<Stat> {
+        ZooKeeper::exists(self, path, watch)
+    }
+
+    fn exists_w(&self, path: &str, watcher: Box<Watcher>) -> ZkResult<Stat> {
+        ZooKeeper::exists_w(self, path, BoxedWatcher(watcher))
+    }
+
+    fn get_children(&self, path: &str, watch: bool) -> ZkResult<Vec<String>> {
+        ZooKeeper::get_children(self, path, watch)
+    }
+
+    fn get_children_w(&self, path: &str, watcher: Box<Watcher>) -> ZkResult<Vec<String>> {
+        ZooKeeper::get_children_w(self, path, BoxedWatcher(watcher))
+    }
+
+    fn get_data(&self, path: &str, watch: bool) -> ZkResult<(Vec<u8>, Stat)> {
+        ZooKeeper::get_data(self, path, watch)
+    }
+
+    fn get_data_w(&self, path: &str, watcher: Box<Watcher>) -> ZkResult<(Vec<u8>, Stat)> {
+        ZooKeeper::get_data_w(self, path, BoxedWatcher(watcher))
+    }
+
+    fn set_data(&self, path: &str, data: Vec<u8>, version: i32) -> ZkResult<Stat> {
+        ZooKeeper::set_data(self, path, data, version)
+    }
+
+    fn get_acl(&self, path: &str) -> ZkResult<(Vec<Acl>, Stat)> {
+        ZooKeeper::get_acl(self, path)
+    }
+
+    fn set_acl(&self, path: &str, acl: Vec<Acl>, version: i32) -> ZkResult<Stat> {
+        ZooKeeper::set_acl(self, path, acl, version)
+    }
+
+    fn add_auth(&self, scheme: &str, auth: Vec<u8>) -> ZkResult<()> {
+        ZooKeeper::add_auth(self, scheme, auth)
+    }
+
+    fn close(&self) {
+        let _ = ZooKeeper::close(self);
+    }
+}
+
+/// Wraps a client, logging every request (at debug) with its outcome and
+/// how long it took, and adding them up in metrics.
+pub struct LoggingClient {
+    inner: Box<ZkClient>,
+    metrics: Arc<Metrics>,
+}
+
+impl LoggingClient {
+    pub fn new(inner: Box<ZkClient>, metrics: Arc<Metrics>) -> LoggingClient {
+        LoggingClient { inner: inner, metrics: metrics }
+    }
+
+    fn logged<T, F: FnOnce() -> ZkResult<T>>(&self, op: &str, path: &str, f: F) -> ZkResult<T> {
+        let start = time::precise_time_ns();
+        let ret = f();
+        let ms = (time::precise_time_ns() - start) as f64 / 1e6;
+        match ret {
+            Ok(_) => debug!("{} {} -> ok ({:.3}ms)", op, path, ms),
+            Err(ref err) => debug!("{} {} -> {:?} ({:.3}ms)", op, path, err, ms),
+        }
+        self.metrics.record(op, ms, ret.as_ref().err().map(|err| format!("{:?}", err)));
+        ret
+    }
+}
+
+impl ZkClient for LoggingClient {
+    fn create(&self, path: &str, data: Vec<u8>, acl: Vec<Acl>, mode: CreateMode) -> ZkResult<String> {
+        self.logged("create", path, || self.inner.create(path, data, acl, mode))
+    }
+
+    fn delete(&self, path: &str, version: i32) -> ZkResult<()> {
+        self.logged("delete", path, || self.inner.delete(path, version))
+    }
+
+    fn exists(&self, path: &str, watch: bool) -> ZkResult<Stat> {
+        self.logged("exists", path, || self.inner.exists(path, watch))
+    }
+
+    fn exists_w(&self, path: &str, watcher: Box<Watcher>) -> ZkResult<Stat> {
+        self.logged("exists_w", path, || self.inner.exists_w(path, watcher))
+    }
+
+    fn get_children(&self, path: &str, watch: bool) -> ZkResult<Vec<String>> {
+        self.logged("get_children", path, || self.inner.get_children(path, watch))
+    }
+
+    fn get_children_w(&self, path: &str, watcher: Box<Watcher>) -> ZkResult<Vec<String>> {
+        self.logged("get_children_w", path, || self.inner.get_children_w(path, watcher))
+    }
+
+    fn get_data(&self, path: &str, watch: bool) -> ZkResult<(Vec<u8>, Stat)> {
+        self.logged("get_data", path, || self.inner.get_data(path, watch))
+    }
+
+    fn get_data_w(&self, path: &str, watcher: Box<Watcher>) -> ZkResult<(Vec<u8>, Stat)> {
+        self.logged("get_data_w", path, || self.inner.get_data_w(path, watcher))
+    }
+
+    fn set_data(&self, path: &str, data: Vec<u8>, version: i32) -> ZkResult<Stat> {
+        self.logged("set_data", path, || self.inner.set_data(path, data, version))
+    }
+
+    fn get_acl(&self, path: &str) -> ZkResult<(Vec<Acl>, Stat)> {
+        self.logged("get_acl", path, || self.inner.get_acl(path))
+    }
+
+    fn set_acl(&self, path: &str, acl: Vec<Acl>, version: i32) -> ZkResult<Stat> {
+        self.logged("set_acl", path, || self.inner.set_acl(path, acl, version))
+    }
+
+    fn add_auth(&self, scheme: &str, auth: Vec<u8>) -> ZkResult<()> {
+        self.logged("add_auth", scheme, || self.inner.add_auth(scheme, auth))
+    }
+
+    fn close(&self) {
+        debug!("close");
+        self.inner.close()
+    }
+}
+
+/// Wraps a client, retrying requests that failed with ConnectionLoss or
+/// OperationTimeout (the session may well outlive those) and giving up on
+/// them with OperationTimeout once past the deadline, as set for the
+/// command being run. A retried write may find it went through the first
+/// time, i.e.: create fails with NodeExists.
+///
+/// Requests with a watcher aren't retried, the watcher goes with the first.
+pub struct RetryingClient {
+    inner: Box<ZkClient>,
+    policy: Arc<RetryPolicy>,
+}
+
+/// How many times to retry, and until when (in precise_time_ns).
+pub struct RetryPolicy {
+    limits: Mutex<(usize, Option<u64>)>,
+}
+
+// the first retry waits this long, then twice as long each time up to a second
+const RETRY_BACKOFF_MS: u64 = 100;
+
+impl RetryPolicy {
+    pub fn new() -> RetryPolicy {
+        RetryPolicy { limits: Mutex::new((0, None)) }
+    }
+
+    /// The retries and the deadline (None for no deadline).
+    pub fn get(&self) -> (usize, Option<u64>) {
+        *self.limits.lock().unwrap()
+    }
+
+    pub fn set(&self, retries: usize, deadline: Option<u64>) {
+        *self.limits.lock().unwrap() = (retries, deadline);
+    }
+}
+
+impl RetryingClient {
+    pub fn new(inner: Box<ZkClient>, policy: Arc<RetryPolicy>) -> RetryingClient {
+        RetryingClient { inner: inner, policy: policy }
+    }
+
+    fn retried<T, F: FnMut() -> ZkResult<T>>(&self, mut f: F) -> ZkResult<T> {
+        let (retries, deadline) = self.policy.get();
+        let past = |deadline: Option<u64>| deadline.map_or(false, |deadline| time::precise_time_ns() >= deadline);
+        let mut backoff = RETRY_BACKOFF_MS;
+        let mut attempt = 0;
+        loop {
+            if past(deadline) {
+                return Err(ZkError::OperationTimeout);
+            }
+            match f() {
+                Err(ZkError::ConnectionLoss) | Err(ZkError::OperationTimeout) if attempt < retries => {
+                    attempt += 1;
+                    debug!("retrying ({}/{})", attempt, retries);
+                    thread::sleep(Duration::from_millis(backoff));
+                    backoff = (backoff * 2).min(1000);
+                },
+                ret => return ret,
+            }
+        }
+    }
+
+    // watchers can't be handed out twice, but there's still the deadline
+    fn once<T, F: FnOnce() -> ZkResult<T>>(&self, f: F) -> ZkResult<T> {
+        match self.policy.get().1 {
+            Some(deadline) if time::precise_time_ns() >= deadline => Err(ZkError::OperationTimeout),
+            _ => f(),
+        }
+    }
+}
+
+impl ZkClient for RetryingClient {
+    fn create(&self, path: &str, data: Vec<u8>, acl: Vec<Acl>, mode: CreateMode) -> ZkResult<String> {
+        self.retried(|| self.inner.create(path, data.clone(), acl.clone(), mode.clone()))
+    }
+
+    fn delete(&self, path: &str, version: i32) -> ZkResult<()> {
+        self.retried(|| self.inner.delete(path, version))
+    }
+
+    fn exists(&self, path: &str, watch: bool) -> ZkResult<Stat> {
+        self.retried(|| self.inner.exists(path, watch))
+    }
+
+    fn exists_w(&self, path: &str, watcher: Box<Watcher>) -> ZkResult<Stat> {
+        self.once(|| self.inner.exists_w(path, watcher))
+    }
+
+    fn get_children(&self, path: &str, watch: bool) -> ZkResult<Vec<String>> {
+        self.retried(|| self.inner.get_children(path, watch))
+    }
+
+    fn get_children_w(&self, path: &str, watcher: Box<Watcher>) -> ZkResult<Vec<String>> {
+        self.once(|| self.inner.get_children_w(path, watcher))
+    }
+
+    fn get_data(&self, path: &str, watch: bool) -> ZkResult<(Vec<u8>, Stat)> {
+        self.retried(|| self.inner.get_data(path, watch))
+    }
+
+    fn get_data_w(&self, path: &str, watcher: Box<Watcher>) -> ZkResult<(Vec<u8>, Stat)> {
+        self.once(|| self.inner.get_data_w(path, watcher))
+    }
+
+    fn set_data(&self, path: &str, data: Vec<u8>, version: i32) -> ZkResult<Stat> {
+        self.retried(|| self.inner.set_data(path, data.clone(), version))
+    }
+
+    fn get_acl(&self, path: &str) -> ZkResult<(Vec<Acl>, Stat)> {
+        self.retried(|| self.inner.get_acl(path))
+    }
+
+    fn set_acl(&self, path: &str, acl: Vec<Acl>, version: i32) -> ZkResult<Stat> {
+        self.retried(|| self.inner.set_acl(path, acl.clone(), version))
+    }
+
+    fn add_auth(&self, scheme: &str, auth: Vec<u8>) -> ZkResult<()> {
+        self.inner.add_auth(scheme, auth)
+    }
+
+    fn close(&self) {
+        self.inner.close()
+    }
+}
+
+/// Wraps a client so that, while enabled, writes are described (as command
+/// output) instead of sent. Reads still go through, so each write fails the
+/// way it would for real (i.e.: NoNode or BadVersion), and the writes
+/// pretended so far are taken into account (i.e.: creating /a then /a/b).
+pub struct DryRunClient {
+    inner: Box<ZkClient>,
+    state: Arc<DryRun>,
+}
+
+/// Whether writes are being pretended, and the ones pretended so far.
+pub struct DryRun {
+    enabled: AtomicBool,
+    // with the data they were given
+    created: Mutex<HashMap<String, Vec<u8>>>,
+    deleted: Mutex<HashSet<String>>,
+}
+
+impl DryRun {
+    pub fn new() -> DryRun {
+        DryRun {
+            enabled: AtomicBool::new(false),
+            created: Mutex::new(HashMap::new()),
+            deleted: Mutex::new(HashSet::new()),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled.load(Ordering::SeqCst)
+    }
+
+    /// Turning it on (or off) starts over from the real tree.
+    pub fn set(&self, enabled: bool) {
+        if self.enabled.swap(enabled, Ordering::SeqCst) != enabled {
+            self.created.lock().unwrap().clear();
+            self.deleted.lock().unwrap().clear();
+        }
+    }
+}
+
+impl DryRunClient {
+    pub fn new(inner: Box<ZkClient>, state: Arc<DryRun>) -> DryRunClient {
+        DryRunClient { inner: inner, state: state }
+    }
+
+    fn dry(&self) -> bool {
+        self.state.enabled()
+    }
+
+    // whether path exists, as far as this dry run goes
+    fn stat(&self, path: &str) -> ZkResult<Option<Stat>> {
+        if self.state.deleted.lock().unwrap().contains(path) {
+            return Err(ZkError::NoNode);
+        }
+        if self.state.created.lock().unwrap().contains_key(path) {
+            return Ok(None);
+        }
+        self.inner.exists(path, false).map(Some)
+    }
+
+    // path's data and stat as far as this dry run goes, None when that's
+    // the real tree's to say
+    fn pretended(&self, path: &str) -> Option<ZkResult<(Vec<u8>, Stat)>> {
+        if !self.dry() {
+            return None;
+        }
+        if self.state.deleted.lock().unwrap().contains(path) {
+            return Some(Err(ZkError::NoNode));
+        }
+        let data = match self.state.created.lock().unwrap().get(path) {
+            Some(data) => data.clone(),
+            None => return None,
+        };
+        // there's no real stat to go by, the root's stands in for one
+        let stat = self.inner.exists("/", false).and_then(|mut stat| {
+            stat.num_children = try!(self.get_children(path, false)).len() as i32;
+            stat.version = 0;
+            stat.cversion = 0;
+            stat.aversion = 0;
+            stat.ephemeral_owner = 0;
+            stat.data_length = data.len() as i32;
+            Ok(stat)
+        });
+        Some(stat.map(|stat| (data, stat)))
+    }
+}
+
+fn mode_name(mode: &CreateMode) -> &'static str {
+    match *mode {
+        CreateMode::Persistent => "persistent",
+        CreateMode::Ephemeral => "ephemeral",
+        CreateMode::PersistentSequential => "persistent sequential",
+        CreateMode::EphemeralSequential => "ephemeral sequential",
+    }
+}
+
+impl ZkClient for DryRunClient {
+    fn create(&self, path: &str, data: Vec<u8>, acl: Vec<Acl>, mode: CreateMode) -> ZkResult<String> {
+        if !self.dry() {
+            return self.inner.create(path, data, acl, mode);
+        }
+        if let Some(parent) = parent_path(path) {
+            try!(self.stat(parent));
+        }
+        let sequential = match mode {
+            CreateMode::PersistentSequential | CreateMode::EphemeralSequential => true,
+            _ => false
+        };
+        if !sequential {
+            match self.stat(path) {
+                Ok(_) => return Err(ZkError::NodeExists),
+                Err(ZkError::NoNode) => (),
+                Err(err) => return Err(err),
+            }
+        }
+
+        let acl: Vec<String> = acl.iter().map(format_acl).collect();
+        output::print(&*format!("[dry-run] would create {} ({} bytes, {}, acl {})",
+                                path, data.len(), mode_name(&mode), acl.join(",")));
+        self.state.deleted.lock().unwrap().remove(path);
+        self.state.created.lock().unwrap().insert(path.to_string(), data);
+        Ok(path.to_string())
+    }
+
+    fn delete(&self, path: &str, version: i32) -> ZkResult<()> {
+        if !self.dry() {
+            return self.inner.delete(path, version);
+        }
+        if let Some(stat) = try!(self.stat(path)) {
+            if version != -1 && stat.version != version {
+                return Err(ZkError::BadVersion);
+            }
+        }
+        let children = try!(self.get_children(path, false));
+        if !children.is_empty() {
+            return Err(ZkError::NotEmpty);
+        }
+
+        output::print(&*format!("[dry-run] would delete {}", path));
+        self.state.created.lock().unwrap().remove(path);
+        self.state.deleted.lock().unwrap().insert(path.to_string());
+        Ok(())
+    }
+
+    // like children, so i.e.: mv can check the copies it pretended
+    fn exists(&self, path: &str, watch: bool) -> ZkResult<Stat> {
+        match self.pretended(path) {
+            Some(node) => node.map(|(_, stat)| stat),
+            None => self.inner.exists(path, watch),
+        }
+    }
+
+    fn exists_w(&self, path: &str, watcher: Box<Watcher>) -> ZkResult<Stat> {
+        match self.pretended(path) {
+            Some(node) => node.map(|(_, stat)| stat),
+            None => self.inner.exists_w(path, watcher),
+        }
+    }
+
+    // children pretended to be created or deleted are accounted for, so
+    // i.e.: rmr sees its parents emptied
+    fn get_children(&self, path: &str, watch: bool) -> ZkResult<Vec<String>> {
+        if !self.dry() {
+            return self.inner.get_children(path, watch);
+        }
+        let mut children = match self.inner.get_children(path, watch) {
+            Ok(children) => children,
+            Err(ZkError::NoNode) if self.state.created.lock().unwrap().contains_key(path) => vec![],
+            Err(err) => return Err(err),
+        };
+        let deleted = self.state.deleted.lock().unwrap();
+        let prefix = if path == "/" { "/".to_string() } else { format!("{}/", path) };
+        children.retain(|child| !deleted.contains(&format!("{}{}", prefix, child)));
+        for created in self.state.created.lock().unwrap().keys() {
+            if parent_path(created) == Some(path) {
+                children.push(created[prefix.len()..].to_string());
+            }
+        }
+        Ok(children)
+    }
+
+    fn get_children_w(&self, path: &str, watcher: Box<Watcher>) -> ZkResult<Vec<String>> {
+        self.inner.get_children_w(path, watcher)
+    }
+
+    fn get_data(&self, path: &str, watch: bool) -> ZkResult<(Vec<u8>, Stat)> {
+        match self.pretended(path) {
+            Some(node) => node,
+            None => self.inner.get_data(path, watch),
+        }
+    }
+
+    fn get_data_w(&self, path: &str, watcher: Box<Watcher>) -> ZkResult<(Vec<u8>, Stat)> {
+        match self.pretended(path) {
+            Some(node) => node,
+            None => self.inner.get_data_w(path, watcher),
+        }
+    }
+
+    fn set_data(&self, path: &str, data: Vec<u8>, version: i32) -> ZkResult<Stat> {
+        if !self.dry() {
+            return self.inner.set_data(path, data, version);
+        }
+        let stat = match try!(self.stat(path)) {
+            Some(stat) => {
+                if version != -1 && stat.version != version {
+                    return Err(ZkError::BadVersion);
+                }
+                stat
+            },
+            // pretended, there's no real stat to go by
+            None => try!(self.inner.exists("/", false)),
+        };
+
+        output::print(&*format!("[dry-run] would set {} to {} bytes", path, data.len()));
+        let mut stat = stat;
+        stat.version += 1;
+        stat.data_length = data.len() as i32;
+        if let Some(pretended) = self.state.created.lock().unwrap().get_mut(path) {
+            *pretended = data;
+        }
+        Ok(stat)
+    }
+
+    fn get_acl(&self, path: &str) -> ZkResult<(Vec<Acl>, Stat)> {
+        self.inner.get_acl(path)
+    }
+
+    fn set_acl(&self, path: &str, acl: Vec<Acl>, version: i32) -> ZkResult<Stat> {
+        if !self.dry() {
+            return self.inner.set_acl(path, acl, version);
+        }
+        let stat = match try!(self.stat(path)) {
+            Some(stat) => {
+                if version != -1 && stat.aversion != version {
+                    return Err(ZkError::BadVersion);
+                }
+                stat
+            },
+            None => try!(self.inner.exists("/", false)),
+        };
+
+        let acl: Vec<String> = acl.iter().map(format_acl).collect();
+        output::print(&*format!("[dry-run] would set {}'s acl to {}", path, acl.join(",")));
+        let mut stat = stat;
+        stat.aversion += 1;
+        Ok(stat)
+    }
+
+    fn add_auth(&self, scheme: &str, auth: Vec<u8>) -> ZkResult<()> {
+        self.inner.add_auth(scheme, auth)
+    }
+
+    fn close(&self) {
+        self.inner.close()
+    }
+}
@@ -0,0 +1,172 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use zookeeper::Acl;
+
+use acl;
+
+
+/// The environment variable with the profile to use when none is given.
+pub const PROFILE_VAR: &'static str = "ZK_PROFILE";
+
+/// Settings from ~/.zkshellrc, an ini-like file:
+///
+/// ```text
+/// # comments start with # or ;
+/// hosts = localhost:2181
+/// session_timeout = 10
+///
+/// [section]
+/// key = value
+/// ```
+///
+/// Keys before the first section are the defaults, sections are profiles.
+pub struct Config {
+    sections: HashMap<String, HashMap<String, String>>,
+}
+
+impl Config {
+    pub fn empty() -> Config {
+        let mut sections = HashMap::new();
+        sections.insert(String::new(), HashMap::new());
+        Config { sections: sections }
+    }
+
+    /// ~/.zkshellrc
+    pub fn default_path() -> Option<PathBuf> {
+        env::home_dir().map(|home| home.join(".zkshellrc"))
+    }
+
+    pub fn load(path: &Path) -> io::Result<Config> {
+        let mut contents = String::new();
+        try!(File::open(path).and_then(|mut file| file.read_to_string(&mut contents)));
+        Config::parse(&*contents).map_err(|err| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), err))
+        })
+    }
+
+    pub fn parse(contents: &str) -> Result<Config, String> {
+        let mut config = Config::empty();
+        let mut section = String::new();
+
+        for (i, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with("#") || line.starts_with(";") {
+                continue;
+            }
+
+            if line.starts_with("[") && line.ends_with("]") {
+                section = line[1..line.len() - 1].trim().to_string();
+                config.sections.entry(section.clone()).or_insert(HashMap::new());
+                continue;
+            }
+
+            match line.find('=') {
+                Some(idx) => {
+                    let key = line[..idx].trim().to_string();
+                    let value = line[idx + 1..].trim().to_string();
+                    config.sections.get_mut(&section).unwrap().insert(key, value);
+                },
+                None => return Err(format!("line {}: expected key = value", i + 1)),
+            }
+        }
+        Ok(config)
+    }
+
+    /// A default (top-level) setting.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.section_get("", key)
+    }
+
+    pub fn section_get(&self, section: &str, key: &str) -> Option<&str> {
+        self.sections.get(section).and_then(|values| values.get(key)).map(|value| &**value)
+    }
+
+    pub fn has_section(&self, section: &str) -> bool {
+        self.sections.contains_key(section)
+    }
+
+    /// A boolean setting: true, yes, on or 1.
+    pub fn get_bool(&self, key: &str) -> bool {
+        self.get(key).map_or(false, is_true)
+    }
+
+    /// The sections' names, sorted.
+    pub fn profiles(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.sections.keys().filter(|name| !name.is_empty()).cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// The section called name, as a profile.
+    pub fn profile(&self, name: &str) -> Result<Profile, String> {
+        let values = match self.sections.get(name) {
+            Some(values) if !name.is_empty() => values,
+            _ => return Err(format!("no profile {}", name)),
+        };
+        for key in values.keys() {
+            // the client library has no TLS support
+            if key == "tls" || key.starts_with("tls_") || key.starts_with("ssl") {
+                return Err(format!("profile {}: {} can't be honored, this client can't connect over TLS", name, key));
+            }
+            if !PROFILE_KEYS.contains(&&**key) {
+                return Err(format!("profile {}: unknown setting {}", name, key));
+            }
+        }
+
+        let mut hosts = match values.get("hosts") {
+            Some(hosts) => hosts.clone(),
+            None => return Err(format!("profile {} has no hosts", name)),
+        };
+        if let Some(chroot) = values.get("chroot") {
+            if !chroot.starts_with('/') {
+                return Err(format!("profile {}: the chroot should be a path, got {}", name, chroot));
+            }
+            hosts.push_str(chroot.trim_right_matches('/'));
+        }
+        let auth = match values.get("auth").map(|auth| (auth, auth.find(':'))) {
+            Some((auth, Some(idx))) => Some((auth[..idx].to_string(), auth[idx + 1..].as_bytes().to_vec())),
+            Some((auth, None)) => return Err(format!("profile {}: bad auth {}, expected scheme:credential", name, auth)),
+            None => None,
+        };
+        let default_acl = match values.get("default_acl").map(|spec| acl::parse(spec)) {
+            Some(Ok(acl)) => Some(acl),
+            Some(Err(err)) => return Err(format!("profile {}: {}", name, err)),
+            None => None,
+        };
+        Ok(Profile {
+            hosts: hosts,
+            auth: auth,
+            read_only: values.get("read_only").map(|value| is_true(value)),
+            default_acl: default_acl,
+        })
+    }
+}
+
+const PROFILE_KEYS: [&'static str; 5] = ["hosts", "chroot", "auth", "read_only", "default_acl"];
+
+fn is_true(value: &str) -> bool {
+    ["true", "yes", "on", "1"].contains(&&*value.to_lowercase())
+}
+
+/// A named environment to connect to, from a section like:
+///
+/// ```text
+/// [prod]
+/// hosts = zk1:2181,zk2:2181
+/// chroot = /prod
+/// auth = digest:admin:secret
+/// read_only = yes
+/// default_acl = creator-all
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Profile {
+    /// With the chroot.
+    pub hosts: String,
+    pub auth: Option<(String, Vec<u8>)>,
+    pub read_only: Option<bool>,
+    pub default_acl: Option<Vec<Acl>>,
+}
@@ -0,0 +1,78 @@
+//! AES-256-GCM for values that should only be readable with the key.
+//! Encrypted values are laid out as:
+//!
+//! ```text
+//! ZKENC1 <12 byte nonce> <ciphertext> <16 byte tag>
+//! ```
+
+use std::fs::File;
+use std::io::Read;
+
+use aes_gcm::{self, Aes256Gcm, Nonce};
+use aes_gcm::aead::{Aead, KeyInit};
+
+
+pub const HEADER: &'static [u8] = b"ZKENC1";
+
+/// The environment variable the key can be given in, instead of the config.
+pub const KEY_VAR: &'static str = "ZKSHELL_ENCRYPTION_KEY";
+
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+
+pub type Key = [u8; 32];
+
+/// Parses a key given as 64 hex digits.
+pub fn parse_key(hex: &str) -> Result<Key, String> {
+    let hex = hex.trim();
+    if hex.len() != 64 || !hex.chars().all(|c| c.is_digit(16)) {
+        return Err("expected 64 hex digits (a 256 bit key)".to_string());
+    }
+    let mut key = [0u8; 32];
+    for i in 0..32 {
+        key[i] = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).unwrap();
+    }
+    Ok(key)
+}
+
+/// Whether data starts with the header encrypt writes.
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(HEADER)
+}
+
+/// Encrypts data under a random nonce, header included.
+pub fn encrypt(key: &Key, data: &[u8]) -> Result<Vec<u8>, String> {
+    let mut nonce = [0u8; NONCE_LEN];
+    let random = File::open("/dev/urandom").and_then(|mut urandom| urandom.read_exact(&mut nonce));
+    if let Err(err) = random {
+        return Err(format!("failed to get a random nonce: {}", err));
+    }
+
+    let mut out = HEADER.to_vec();
+    out.extend(nonce.iter().cloned());
+    out.extend(seal(key, &nonce, data));
+    Ok(out)
+}
+
+pub fn decrypt(key: &Key, data: &[u8]) -> Result<Vec<u8>, String> {
+    if !is_encrypted(data) || data.len() < HEADER.len() + NONCE_LEN + TAG_LEN {
+        return Err("not an encrypted value".to_string());
+    }
+    let mut nonce = [0u8; NONCE_LEN];
+    nonce.copy_from_slice(&data[HEADER.len()..HEADER.len() + NONCE_LEN]);
+    open(key, &nonce, &data[HEADER.len() + NONCE_LEN..]).ok_or("wrong key, or the value was tampered with".to_string())
+}
+
+/// AES-256-GCM without additional data: the ciphertext followed by the tag.
+pub fn seal(key: &Key, nonce: &[u8; NONCE_LEN], data: &[u8]) -> Vec<u8> {
+    cipher(key).encrypt(Nonce::from_slice(nonce), data).expect("values are nowhere near GCM's 64GB limit")
+}
+
+/// The inverse of seal, None if the tag doesn't match.
+pub fn open(key: &Key, nonce: &[u8; NONCE_LEN], sealed: &[u8]) -> Option<Vec<u8>> {
+    cipher(key).decrypt(Nonce::from_slice(nonce), sealed).ok()
+}
+
+fn cipher(key: &Key) -> Aes256Gcm {
+    Aes256Gcm::new(aes_gcm::Key::<Aes256Gcm>::from_slice(key))
+}
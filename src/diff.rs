@@ -0,0 +1,130 @@
+//! Line diffs (Myers' algorithm), printed as unified diffs.
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Edit {
+    /// A line both sides have: its index in old and in new.
+    Same(usize, usize),
+    /// A line only old has.
+    Delete(usize),
+    /// A line only new has.
+    Insert(usize),
+}
+
+/// The shortest edit script that turns old into new.
+pub fn edits(old: &[&str], new: &[&str]) -> Vec<Edit> {
+    let (n, m) = (old.len() as isize, new.len() as isize);
+    let max = n + m;
+    // v[k + offset]: the furthest x reached on diagonal k (x - y)
+    let offset = max + 1;
+    let mut v = vec![0isize; (2 * max + 3) as usize];
+    let mut trace = vec![];
+
+    'search: for d in 0..max + 1 {
+        trace.push(v.clone());
+        let mut k = -d;
+        while k <= d {
+            let i = (k + offset) as usize;
+            let mut x = if k == -d || (k != d && v[i - 1] < v[i + 1]) { v[i + 1] } else { v[i - 1] + 1 };
+            let mut y = x - k;
+            while x < n && y < m && old[x as usize] == new[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[i] = x;
+            if x >= n && y >= m {
+                break 'search;
+            }
+            k += 2;
+        }
+    }
+
+    // back from the end, one d at a time
+    let mut script = vec![];
+    let (mut x, mut y) = (n, m);
+    for d in (0..trace.len() as isize).rev() {
+        let v = &trace[d as usize];
+        let k = x - y;
+        let i = (k + offset) as usize;
+        let prev_k = if k == -d || (k != d && v[i - 1] < v[i + 1]) { k + 1 } else { k - 1 };
+        let prev_x = v[(prev_k + offset) as usize];
+        let prev_y = prev_x - prev_k;
+        while x > prev_x && y > prev_y {
+            x -= 1;
+            y -= 1;
+            script.push(Edit::Same(x as usize, y as usize));
+        }
+        if d > 0 {
+            if x == prev_x {
+                script.push(Edit::Insert((y - 1) as usize));
+            } else {
+                script.push(Edit::Delete((x - 1) as usize));
+            }
+        }
+        x = prev_x;
+        y = prev_y;
+    }
+    script.reverse();
+    script
+}
+
+/// The unified diff from old to new with context lines around each change,
+/// empty if they're the same. Lines are unterminated, as diff prints them.
+pub fn unified(old: &[&str], new: &[&str], old_name: &str, new_name: &str, context: usize) -> Vec<String> {
+    let script = edits(old, new);
+    let changes: Vec<usize> = (0..script.len()).filter(|&i| match script[i] {
+        Edit::Same(..) => false,
+        _ => true,
+    }).collect();
+    if changes.is_empty() {
+        return vec![];
+    }
+
+    // where each edit starts, in old and in new
+    let mut positions = Vec::with_capacity(script.len() + 1);
+    let (mut at_old, mut at_new) = (0, 0);
+    for edit in &script {
+        positions.push((at_old, at_new));
+        match *edit {
+            Edit::Same(..) => { at_old += 1; at_new += 1; },
+            Edit::Delete(_) => at_old += 1,
+            Edit::Insert(_) => at_new += 1,
+        }
+    }
+    positions.push((at_old, at_new));
+
+    let mut lines = vec![format!("--- {}", old_name), format!("+++ {}", new_name)];
+    let mut next = 0;
+    while next < changes.len() {
+        // changes closer than twice the context share a hunk
+        let start = changes[next].saturating_sub(context);
+        let mut last = changes[next];
+        next += 1;
+        while next < changes.len() && changes[next] <= last + 2 * context + 1 {
+            last = changes[next];
+            next += 1;
+        }
+        let end = (last + context + 1).min(script.len());
+
+        let (old_start, new_start) = positions[start];
+        let (old_end, new_end) = positions[end];
+        lines.push(format!("@@ -{} +{} @@", range(old_start, old_end - old_start), range(new_start, new_end - new_start)));
+        for edit in &script[start..end] {
+            lines.push(match *edit {
+                Edit::Same(i, _) => format!(" {}", old[i]),
+                Edit::Delete(i) => format!("-{}", old[i]),
+                Edit::Insert(j) => format!("+{}", new[j]),
+            });
+        }
+    }
+    lines
+}
+
+// i.e.: 3,4 for lines 3 to 6, as diff prints them (an empty range is the
+// line before it)
+fn range(start: usize, len: usize) -> String {
+    match len {
+        0 => format!("{},0", start),
+        1 => format!("{}", start + 1),
+        len => format!("{},{}", start + 1, len),
+    }
+}
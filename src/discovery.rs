@@ -0,0 +1,319 @@
+use std::fs::File;
+use std::io;
+use std::io::{Read, Write};
+use std::net::{IpAddr, SocketAddr, TcpStream, UdpSocket};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use serde_json::{self, Value};
+
+use http;
+
+
+const EXHIBITOR_LIST: &'static str = "/exhibitor/v1/cluster/list";
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Asks an Exhibitor instance (i.e.: http://exhibitor:8080) for the
+/// ensemble's current servers, returning them as a connect string.
+pub fn exhibitor(url: &str) -> io::Result<String> {
+    let url = match exhibitor_url(url) {
+        Some(url) => url,
+        None => return Err(invalid(format!("bad Exhibitor URL: {}", url))),
+    };
+
+    let (status, body) = try!(http::get(&*url));
+    if status != 200 {
+        return Err(invalid(format!("Exhibitor returned {}", status)));
+    }
+    let list = try!(serde_json::from_str(&*body).map_err(|err| invalid(err.to_string())));
+    exhibitor_hosts(&list).ok_or(invalid(format!("unexpected Exhibitor response: {}", body)))
+}
+
+/// What to GET for url's server list: url itself if it has a path, the
+/// cluster list endpoint if not. None unless it's an http(s) URL.
+pub fn exhibitor_url(url: &str) -> Option<String> {
+    let rest = if url.starts_with("http://") {
+        &url[7..]
+    } else if url.starts_with("https://") {
+        &url[8..]
+    } else {
+        return None;
+    };
+    match rest.find('/') {
+        _ if rest.is_empty() || rest.starts_with('/') => None,
+        Some(idx) if &rest[idx..] != "/" => Some(url.to_string()),
+        _ => Some(format!("{}{}", url.trim_right_matches('/'), EXHIBITOR_LIST)),
+    }
+}
+
+/// {"servers": ["a", "b"], "port": 2181} -> a:2181,b:2181
+pub fn exhibitor_hosts(list: &Value) -> Option<String> {
+    let port = match list["port"].as_u64() {
+        Some(port) => port,
+        None => return None,
+    };
+    let servers = match list["servers"].as_array() {
+        Some(servers) => servers,
+        None => return None,
+    };
+
+    let mut hosts = vec![];
+    for server in servers {
+        match server.as_str() {
+            Some(server) => hosts.push(format!("{}:{}", server, port)),
+            None => return None,
+        }
+    }
+    if hosts.is_empty() { None } else { Some(hosts.join(",")) }
+}
+
+/// Re-fetches the server list every interval, in the background, updating
+/// hosts when it changes. Failures are logged and the old list is kept.
+pub fn follow_exhibitor(url: &str, interval: Duration, hosts: Arc<Mutex<String>>) {
+    let url = url.to_string();
+    thread::spawn(move || {
+        loop {
+            thread::sleep(interval);
+            match exhibitor(&*url) {
+                Ok(current) => {
+                    let mut hosts = hosts.lock().unwrap();
+                    if *hosts != current {
+                        info!("Exhibitor's server list changed to {}", current);
+                        *hosts = current;
+                    }
+                },
+                Err(err) => warn!("refreshing the server list from {} failed: {}", url, err),
+            }
+        }
+    });
+}
+
+const SRV_PREFIX: &'static str = "srv:";
+const DNS_PORT: u16 = 53;
+const DNS_TIMEOUT: u64 = 5;
+const TYPE_SRV: u16 = 33;
+const CLASS_IN: u16 = 1;
+
+/// Resolves srv:_zookeeper._tcp.example.com[/chroot] into a connect string
+/// built from the SRV records, any other hosts string is returned as is.
+pub fn resolve_hosts(hosts: &str) -> io::Result<String> {
+    if !hosts.starts_with(SRV_PREFIX) {
+        return Ok(hosts.to_string());
+    }
+    let name = &hosts[SRV_PREFIX.len()..];
+    let (name, chroot) = match name.find('/') {
+        Some(idx) => (&name[..idx], &name[idx..]),
+        None => (name, ""),
+    };
+
+    let mut records = try!(srv_lookup(name));
+    if records.is_empty() {
+        return Err(invalid(format!("no SRV records for {}", name)));
+    }
+    // lower priorities first, heavier weights first within a priority
+    records.sort_by(|a, b| (a.priority, !a.weight).cmp(&(b.priority, !b.weight)));
+    let hosts: Vec<String> = records.iter().map(|r| format!("{}:{}", r.target, r.port)).collect();
+    Ok(format!("{}{}", hosts.join(","), chroot))
+}
+
+#[derive(Debug, PartialEq)]
+pub struct SrvRecord {
+    pub priority: u16,
+    pub weight: u16,
+    pub port: u16,
+    pub target: String,
+}
+
+// the nameservers in /etc/resolv.conf, in order
+fn nameservers() -> io::Result<Vec<SocketAddr>> {
+    let mut conf = String::new();
+    try!(File::open("/etc/resolv.conf").and_then(|mut file| file.read_to_string(&mut conf)));
+    let servers: Vec<SocketAddr> = conf.lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>())
+        .filter(|words| words.len() > 1 && words[0] == "nameserver")
+        .filter_map(|words| words[1].parse::<IpAddr>().ok())
+        .map(|ip| SocketAddr::new(ip, DNS_PORT))
+        .collect();
+    if servers.is_empty() {
+        return Err(invalid("no nameserver in /etc/resolv.conf".to_string()));
+    }
+    Ok(servers)
+}
+
+/// Asks the system's nameservers for name's SRV records.
+pub fn srv_lookup(name: &str) -> io::Result<Vec<SrvRecord>> {
+    srv_lookup_with(&try!(nameservers())[..], name)
+}
+
+/// Asks each of servers in turn for name's SRV records, until one answers.
+pub fn srv_lookup_with(servers: &[SocketAddr], name: &str) -> io::Result<Vec<SrvRecord>> {
+    let id = try!(query_id());
+    let query = try!(srv_query(id, name));
+    let mut failed = invalid("no nameservers to ask".to_string());
+    for server in servers {
+        match ask(server, id, &query[..]) {
+            Ok(records) => return Ok(records),
+            Err(err) => {
+                warn!("asking {} for {}'s SRV records failed: {}", server, name, err);
+                failed = err;
+            },
+        }
+    }
+    Err(failed)
+}
+
+// a random query id, so a reply to some other query can't pass for ours
+fn query_id() -> io::Result<u16> {
+    let mut id = [0u8; 2];
+    try!(File::open("/dev/urandom").and_then(|mut urandom| urandom.read_exact(&mut id)));
+    Ok((id[0] as u16) << 8 | id[1] as u16)
+}
+
+// sends query to server over UDP, again over TCP if the answer didn't fit
+fn ask(server: &SocketAddr, id: u16, query: &[u8]) -> io::Result<Vec<SrvRecord>> {
+    let socket = try!(UdpSocket::bind(if server.is_ipv6() { "[::]:0" } else { "0.0.0.0:0" }));
+    // a connected socket only takes datagrams from server
+    try!(socket.connect(server));
+    try!(socket.set_read_timeout(Some(Duration::from_secs(DNS_TIMEOUT))));
+    try!(socket.send(query));
+
+    let mut buf = [0; 4096];
+    loop {
+        let len = try!(socket.recv(&mut buf));
+        let reply = &buf[..len];
+        // forged replies have to guess the id
+        if try!(read_u16(reply, 0)) != id {
+            continue;
+        }
+        if is_truncated(reply) {
+            return ask_tcp(server, id, query);
+        }
+        return parse_srv_response(id, reply);
+    }
+}
+
+fn ask_tcp(server: &SocketAddr, id: u16, query: &[u8]) -> io::Result<Vec<SrvRecord>> {
+    let mut stream = try!(TcpStream::connect(server));
+    try!(stream.set_read_timeout(Some(Duration::from_secs(DNS_TIMEOUT))));
+    // TCP messages are preceded by their length
+    let mut msg = vec![];
+    push_u16(&mut msg, query.len() as u16);
+    msg.extend(query.iter().cloned());
+    try!(stream.write_all(&msg[..]));
+
+    let mut len = [0u8; 2];
+    try!(stream.read_exact(&mut len));
+    let mut reply = vec![0; (len[0] as usize) << 8 | len[1] as usize];
+    try!(stream.read_exact(&mut reply));
+    parse_srv_response(id, &reply[..])
+}
+
+fn push_u16(buf: &mut Vec<u8>, n: u16) {
+    buf.push((n >> 8) as u8);
+    buf.push(n as u8);
+}
+
+/// A DNS query (recursion desired) for name's SRV records, an error if name
+/// isn't a valid DNS name.
+pub fn srv_query(id: u16, name: &str) -> io::Result<Vec<u8>> {
+    let name = name.trim_right_matches('.');
+    if name.len() > 253 || name.split('.').any(|label| label.is_empty() || label.len() > 63) {
+        return Err(invalid(format!("bad DNS name: {}", name)));
+    }
+    let mut query = vec![];
+    push_u16(&mut query, id);
+    push_u16(&mut query, 0x0100);
+    push_u16(&mut query, 1); // questions
+    push_u16(&mut query, 0);
+    push_u16(&mut query, 0);
+    push_u16(&mut query, 0);
+    for label in name.split('.') {
+        query.push(label.len() as u8);
+        query.extend(label.as_bytes().iter().cloned());
+    }
+    query.push(0);
+    push_u16(&mut query, TYPE_SRV);
+    push_u16(&mut query, CLASS_IN);
+    Ok(query)
+}
+
+/// Whether the TC flag is set: the answer didn't fit, ask again over TCP.
+pub fn is_truncated(msg: &[u8]) -> bool {
+    msg.len() > 2 && msg[2] & 0x02 != 0
+}
+
+fn read_u16(msg: &[u8], pos: usize) -> io::Result<u16> {
+    if pos + 2 > msg.len() {
+        return Err(invalid("truncated DNS response".to_string()));
+    }
+    Ok(((msg[pos] as u16) << 8) | msg[pos + 1] as u16)
+}
+
+// reads a (possibly compressed) name at pos, returning it and where it ends
+fn read_name(msg: &[u8], pos: usize) -> io::Result<(String, usize)> {
+    let truncated = || invalid("truncated DNS response".to_string());
+    let mut labels = vec![];
+    let mut pos = pos;
+    let mut end = None;
+    // bounds the pointers followed, so a malicious loop can't hang us
+    for _ in 0..128 {
+        let len = *try!(msg.get(pos).ok_or(truncated())) as usize;
+        if len == 0 {
+            return Ok((labels.join("."), end.unwrap_or(pos + 1)));
+        }
+        if len & 0xc0 == 0xc0 {
+            let target = (try!(read_u16(msg, pos)) & 0x3fff) as usize;
+            end = end.or(Some(pos + 2));
+            pos = target;
+            continue;
+        }
+        let label = try!(msg.get(pos + 1..pos + 1 + len).ok_or(truncated()));
+        labels.push(String::from_utf8_lossy(label).into_owned());
+        pos += 1 + len;
+    }
+    Err(invalid("DNS name loops".to_string()))
+}
+
+/// The SRV records in the answer to query id.
+pub fn parse_srv_response(id: u16, msg: &[u8]) -> io::Result<Vec<SrvRecord>> {
+    if try!(read_u16(msg, 0)) != id {
+        return Err(invalid("DNS response for another query".to_string()));
+    }
+    let flags = try!(read_u16(msg, 2));
+    match flags & 0xf {
+        0 => (),
+        3 => return Ok(vec![]), // NXDOMAIN
+        rcode => return Err(invalid(format!("DNS error {}", rcode))),
+    }
+    let questions = try!(read_u16(msg, 4));
+    let answers = try!(read_u16(msg, 6));
+
+    let mut pos = 12;
+    for _ in 0..questions {
+        let (_, end) = try!(read_name(msg, pos));
+        pos = end + 4;
+    }
+
+    let mut records = vec![];
+    for _ in 0..answers {
+        let (_, end) = try!(read_name(msg, pos));
+        let rtype = try!(read_u16(msg, end));
+        let rdlength = try!(read_u16(msg, end + 8)) as usize;
+        let rdata = end + 10;
+        if rtype == TYPE_SRV {
+            let (target, _) = try!(read_name(msg, rdata + 6));
+            records.push(SrvRecord {
+                priority: try!(read_u16(msg, rdata)),
+                weight: try!(read_u16(msg, rdata + 2)),
+                port: try!(read_u16(msg, rdata + 4)),
+                target: target,
+            });
+        }
+        pos = rdata + rdlength;
+    }
+    Ok(records)
+}
@@ -0,0 +1,240 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{Write, stdout};
+use std::sync::{Arc, Condvar, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use ansi_term::Colour::{Green, Red, Yellow};
+use serde_json::Value;
+use time::{self, Tm};
+use zookeeper::{KeeperState, Watcher, WatchedEvent, WatchedEventType};
+
+
+#[derive(Clone)]
+pub struct Event {
+    pub time: Tm,
+    pub event_type: WatchedEventType,
+    pub keeper_state: KeeperState,
+    pub path: Option<String>,
+}
+
+impl Event {
+    pub fn new(e: &WatchedEvent) -> Event {
+        Event {
+            time: time::now(),
+            event_type: e.event_type,
+            keeper_state: e.keeper_state,
+            path: e.path.clone(),
+        }
+    }
+
+    /// The event as an object with time, type, path (null for session
+    /// events) and state.
+    pub fn to_json(&self) -> Value {
+        json!({
+            "time": self.time.rfc3339().to_string(),
+            "type": type_name(self.event_type),
+            "path": self.path,
+            "state": state_name(self.keeper_state),
+        })
+    }
+}
+
+/// time, type, path (- for session events) and state, i.e.:
+///
+/// ```text
+/// 2015-06-01T10:00:00+02:00 NodeDataChanged /config connected
+/// ```
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} {} {}", self.time.rfc3339(), type_name(self.event_type),
+               self.path.as_ref().map_or("-", |path| &*path), state_name(self.keeper_state))
+    }
+}
+
+/// A session state as shown to people, i.e.: in the prompt.
+pub fn state_name(state: KeeperState) -> &'static str {
+    match state {
+        KeeperState::SyncConnected => "connected",
+        KeeperState::Disconnected => "disconnected",
+        KeeperState::Expired => "expired",
+        KeeperState::ConnectedReadOnly => "read-only",
+        KeeperState::AuthFailed => "auth failed",
+        KeeperState::SaslAuthenticated => "authenticated",
+    }
+}
+
+/// An event type as ZooKeeper names it, Session for session events.
+pub fn type_name(event_type: WatchedEventType) -> &'static str {
+    match event_type {
+        WatchedEventType::None => "Session",
+        WatchedEventType::NodeCreated => "NodeCreated",
+        WatchedEventType::NodeDeleted => "NodeDeleted",
+        WatchedEventType::NodeDataChanged => "NodeDataChanged",
+        WatchedEventType::NodeChildrenChanged => "NodeChildrenChanged",
+    }
+}
+
+/// What going from one session state into another means for whoever is
+/// typing, None if it's not worth interrupting them for.
+pub fn transition(from: Option<KeeperState>, to: KeeperState) -> Option<&'static str> {
+    match (from, to) {
+        // connect covers the first one
+        (None, _) => None,
+        (Some(from), to) if from == to => None,
+        (_, KeeperState::Disconnected) => Some("Disconnected, trying to reconnect..."),
+        (_, KeeperState::SyncConnected) => Some("Reconnected, the session is still valid."),
+        (_, KeeperState::Expired) => Some("Session expired: ephemerals and watches are gone, run connect for a new one."),
+        (_, KeeperState::ConnectedReadOnly) => Some("Connected to a read-only server, writes will fail."),
+        (_, KeeperState::AuthFailed) => Some("Authentication failed."),
+        (_, KeeperState::SaslAuthenticated) => None,
+    }
+}
+
+fn notify(event: &Event, message: &str) {
+    let message = match event.keeper_state {
+        KeeperState::SyncConnected => Green.bold().paint(message),
+        KeeperState::ConnectedReadOnly => Yellow.bold().paint(message),
+        _ => Red.bold().paint(message),
+    };
+    // from the client's thread, so likely in the middle of a prompt
+    println!("\n[{}] {}", event.time.rfc3339(), message);
+}
+
+/// Called with every event, from the ZooKeeper client's thread.
+pub type Hook = Box<Fn(&Event) + Send + Sync>;
+
+/// A bounded buffer of the most recent watch events.
+pub struct EventLog {
+    events: Mutex<VecDeque<Event>>,
+    capacity: usize,
+    live: AtomicBool,
+    interactive: AtomicBool,
+    hooks: Mutex<Vec<Hook>>,
+    state: Mutex<Option<KeeperState>>,
+    state_changed: Condvar,
+}
+
+impl EventLog {
+    pub fn new(capacity: usize) -> EventLog {
+        EventLog {
+            events: Mutex::new(VecDeque::with_capacity(capacity)),
+            capacity: capacity,
+            live: AtomicBool::new(false),
+            interactive: AtomicBool::new(false),
+            hooks: Mutex::new(vec![]),
+            state: Mutex::new(None),
+            state_changed: Condvar::new(),
+        }
+    }
+
+    pub fn push(&self, event: Event) {
+        if self.is_live() {
+            println!("{}", event);
+        }
+        for hook in self.hooks.lock().unwrap().iter() {
+            hook(&event);
+        }
+        if event.event_type == WatchedEventType::None {
+            let mut state = self.state.lock().unwrap();
+            if let Some(message) = transition(*state, event.keeper_state) {
+                notify(&event, message);
+                if self.interactive.load(Ordering::SeqCst) {
+                    // the notice pushed the prompt up, here's one with the new state
+                    print!("({}) > ", state_name(event.keeper_state));
+                    let _ = stdout().flush();
+                }
+            }
+            *state = Some(event.keeper_state);
+            self.state_changed.notify_all();
+        }
+
+        let mut events = self.events.lock().unwrap();
+        if events.len() == self.capacity {
+            events.pop_front();
+        }
+        events.push_back(event);
+    }
+
+    /// The last count events, oldest first.
+    pub fn recent(&self, count: usize) -> Vec<Event> {
+        let events = self.events.lock().unwrap();
+        let skip = events.len() - count.min(events.len());
+        events.iter().skip(skip).cloned().collect()
+    }
+
+    pub fn clear(&self) {
+        self.events.lock().unwrap().clear();
+    }
+
+    /// The last session state reported, None before the first one.
+    pub fn state(&self) -> Option<KeeperState> {
+        *self.state.lock().unwrap()
+    }
+
+    /// Forgets the last session state, i.e.: before a new session.
+    pub fn reset_state(&self) {
+        *self.state.lock().unwrap() = None;
+    }
+
+    /// Waits (up to timeout) for the session to report SyncConnected.
+    pub fn wait_connected(&self, timeout: Duration) -> bool {
+        let deadline = time::precise_time_ns() + timeout.as_secs() * 1000000000;
+        let mut state = self.state.lock().unwrap();
+        while *state != Some(KeeperState::SyncConnected) {
+            let now = time::precise_time_ns();
+            if now >= deadline {
+                return false;
+            }
+            let left = deadline - now;
+            let wait = Duration::new(left / 1000000000, (left % 1000000000) as u32);
+            state = self.state_changed.wait_timeout(state, wait).unwrap().0;
+        }
+        true
+    }
+
+    pub fn add_hook(&self, hook: Hook) {
+        self.hooks.lock().unwrap().push(hook);
+    }
+
+    pub fn is_live(&self) -> bool {
+        self.live.load(Ordering::SeqCst)
+    }
+
+    /// Whether events are also printed as they arrive.
+    pub fn set_live(&self, live: bool) {
+        self.live.store(live, Ordering::SeqCst);
+    }
+
+    /// Whether someone's at a prompt, so session notices are followed by a
+    /// new one.
+    pub fn set_interactive(&self, interactive: bool) {
+        self.interactive.store(interactive, Ordering::SeqCst);
+    }
+
+    pub fn interactive(&self) -> bool {
+        self.interactive.load(Ordering::SeqCst)
+    }
+}
+
+/// The session watcher, it records every event in the log.
+pub struct LogWatcher {
+    log: Arc<EventLog>,
+}
+
+impl LogWatcher {
+    pub fn new(log: Arc<EventLog>) -> LogWatcher {
+        LogWatcher { log: log }
+    }
+}
+
+impl Watcher for LogWatcher {
+    fn handle(&self, e: &WatchedEvent) {
+        match e.event_type {
+            WatchedEventType::None => info!("session state {:?}", e.keeper_state),
+            event_type => debug!("watch event {:?} {}", event_type, e.path.as_ref().map_or("", |path| &*path)),
+        }
+        self.log.push(Event::new(e));
+    }
+}
@@ -0,0 +1,171 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+
+const DEFAULT_PORT: u16 = 2181;
+
+/// Sends a four letter word to host:port and returns the whole response.
+pub fn send(host: &str, cmd: &str) -> io::Result<String> {
+    let mut stream = try!(TcpStream::connect(host));
+    try!(stream.set_read_timeout(Some(Duration::from_secs(5))));
+    try!(stream.write_all(cmd.as_bytes()));
+
+    let mut response = String::new();
+    try!(stream.read_to_string(&mut response));
+    Ok(response)
+}
+
+/// Splits a connect string into host:port pairs, dropping the chroot.
+pub fn hosts(connect_string: &str) -> Vec<String> {
+    let hosts = match connect_string.find('/') {
+        Some(idx) => &connect_string[..idx],
+        None => connect_string,
+    };
+
+    hosts.split(',')
+        .map(|host| host.trim())
+        .filter(|host| !host.is_empty())
+        .map(|host| {
+            if host.contains(':') {
+                host.to_string()
+            } else {
+                format!("{}:{}", host, DEFAULT_PORT)
+            }
+        })
+        .collect()
+}
+
+pub struct Connection {
+    pub endpoint: String,
+    pub session_id: i64,
+    /// The negotiated session timeout, in ms.
+    pub timeout: Option<u32>,
+    /// The last zxid the server sent the client.
+    pub last_zxid: Option<i64>,
+}
+
+/// Parses the output of cons, which has one connection per line:
+///
+///   /10.0.0.1:39454[1](queued=0,recved=12,sent=12,sid=0x14f8e2c1b9b0001,...,to=30000,lzxid=0x1a,...)
+pub fn parse_cons(output: &str) -> Vec<Connection> {
+    let mut conns = vec![];
+
+    for line in output.lines().map(|line| line.trim()) {
+        if !line.starts_with("/") {
+            continue;
+        }
+
+        let endpoint = match line.find('[') {
+            Some(idx) => &line[1..idx],
+            None => continue,
+        };
+        let stats = match line.find('(') {
+            Some(idx) => line[idx + 1..].trim_right_matches(')'),
+            None => continue,
+        };
+        let stat = |name: &str| stats.split(',').filter_map(|pair| {
+            let mut fields = pair.splitn(2, '=');
+            match (fields.next(), fields.next()) {
+                (Some(key), Some(value)) if key == name => Some(value),
+                _ => None,
+            }
+        }).next();
+        let hex = |value: &str| if value.starts_with("0x") { u64::from_str_radix(&value[2..], 16).ok() } else { None };
+
+        if let Some(session_id) = stat("sid").and_then(&hex) {
+            conns.push(Connection {
+                endpoint: endpoint.to_string(),
+                session_id: session_id as i64,
+                timeout: stat("to").and_then(|to| to.parse().ok()),
+                // 0xffffffffffffffff until the client has seen any
+                last_zxid: stat("lzxid").and_then(&hex).map(|zxid| zxid as i64).and_then(|zxid| if zxid >= 0 { Some(zxid) } else { None }),
+            });
+        }
+    }
+
+    conns
+}
+
+/// Parses the ephemerals part of the output of dump, a line per session
+/// followed by a tab indented line per ephemeral znode it owns:
+///
+///   Sessions with Ephemerals (1):
+///   0x14f8e2c1b9b0001:
+///   \t/workers/w1
+pub fn parse_dump(output: &str) -> BTreeMap<i64, Vec<String>> {
+    let mut ephemerals = BTreeMap::new();
+    let mut current = None;
+    let section = output.find("Sessions with Ephemerals").map_or("", |idx| &output[idx..]);
+    for line in section.lines().skip(1) {
+        let session = line.trim().trim_right_matches(':');
+        if line.starts_with('\t') || line.starts_with(' ') {
+            if let Some(session) = current {
+                ephemerals.entry(session).or_insert(vec![]).push(line.trim().to_string());
+            }
+        } else if session.starts_with("0x") {
+            current = u64::from_str_radix(&session[2..], 16).ok().map(|session| session as i64);
+            if let Some(session) = current {
+                ephemerals.entry(session).or_insert(vec![]);
+            }
+        }
+    }
+    ephemerals
+}
+
+/// Parses the output of mntr, one tab separated key and value per line:
+///
+///   zk_avg_latency	0
+///   zk_server_state	leader
+pub fn parse_mntr(output: &str) -> HashMap<String, String> {
+    output.lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(2, '\t');
+            match (fields.next(), fields.next()) {
+                (Some(key), Some(value)) if !key.is_empty() => Some((key.to_string(), value.trim().to_string())),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Parses the output of conf, key=value lines (from 3.5 on, followed by
+/// a membership: line and the server.N ones), by key:
+///
+///   clientPort=2181
+///   dataDir=/var/lib/zookeeper/version-2
+pub fn parse_conf(output: &str) -> BTreeMap<String, String> {
+    output.lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(2, '=');
+            match (fields.next().map(|key| key.trim()), fields.next()) {
+                (Some(key), Some(value)) if !key.is_empty() => Some((key.to_string(), value.trim().to_string())),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Parses the output of wchp (or wchc), a line per path (session) followed
+/// by a tab indented line per session (path) watching it:
+///
+///   /config
+///   \t0x14d5bd9a8d50000
+///   \t0x14d5bd9a8d50001
+pub fn parse_watches(output: &str) -> BTreeMap<String, Vec<String>> {
+    let mut watches = BTreeMap::new();
+    let mut current: Option<String> = None;
+    for line in output.lines() {
+        if line.starts_with('\t') || line.starts_with(' ') {
+            if let Some(ref key) = current {
+                watches.entry(key.clone()).or_insert(vec![]).push(line.trim().to_string());
+            }
+        } else if !line.trim().is_empty() {
+            current = Some(line.trim().to_string());
+            watches.entry(line.trim().to_string()).or_insert(vec![]);
+        }
+    }
+    watches
+}
@@ -0,0 +1,41 @@
+//! gzip to keep large values under the server's 1MB limit, with a cap on
+//! what reading one back may grow to (a small value can inflate to GBs).
+
+use std::io::{Read, Write};
+
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+
+
+const MAGIC: [u8; 3] = [0x1f, 0x8b, 8];
+
+/// What a znode's value may decompress to, well past what compression can
+/// fit under the 1MB limit.
+pub const MAX_VALUE_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Whether data starts like a gzip member does.
+pub fn is_gzip(data: &[u8]) -> bool {
+    data.len() >= 18 && data[..3] == MAGIC
+}
+
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(vec![], Compression::default());
+    // writes to a Vec don't fail
+    encoder.write_all(data).and_then(|_| encoder.finish()).expect("gzip into memory")
+}
+
+/// What data decompresses to, an error if it's not gzip, is corrupt or
+/// comes to more than limit bytes.
+pub fn decompress(data: &[u8], limit: u64) -> Result<Vec<u8>, String> {
+    if !is_gzip(data) {
+        return Err("not gzip data".to_string());
+    }
+    let mut out = vec![];
+    // one byte more tells whether there's more than limit
+    match GzDecoder::new(data).take(limit + 1).read_to_end(&mut out) {
+        Ok(_) if out.len() as u64 > limit => Err(format!("decompresses to over {} bytes", limit)),
+        Ok(_) => Ok(out),
+        Err(err) => Err(err.to_string()),
+    }
+}
@@ -0,0 +1,111 @@
+use std::fmt;
+use std::net::Ipv6Addr;
+
+
+const DEFAULT_PORT: u16 = 2181;
+
+/// The environment variable with the hosts to use when none are given (and
+/// the one script plugins get the session's hosts in).
+pub const HOSTS_VAR: &'static str = "ZK_HOSTS";
+
+/// A parsed connect string, i.e.: `zk1:2181,[::1]:2182/chroot`.
+#[derive(Debug, PartialEq)]
+pub struct ConnectString {
+    /// (host, port) pairs, IPv6 addresses without their brackets
+    pub servers: Vec<(String, u16)>,
+    pub chroot: Option<String>,
+}
+
+impl ConnectString {
+    /// Parses hosts, allowing whitespace around them and leaving out ports
+    /// (2181 is assumed). IPv6 addresses go in brackets, i.e.: [::1]:2181.
+    pub fn parse(hosts: &str) -> Result<ConnectString, String> {
+        let hosts = hosts.trim();
+        // brackets don't hold slashes, so the first one starts the chroot
+        let (servers, chroot) = match hosts.find('/') {
+            Some(idx) => (&hosts[..idx], try!(parse_chroot(&hosts[idx..]))),
+            None => (hosts, None),
+        };
+        if servers.trim().is_empty() {
+            return Err("no hosts given".to_string());
+        }
+
+        let mut parsed = vec![];
+        for server in servers.split(',').map(|server| server.trim()) {
+            if server.is_empty() {
+                return Err(format!("empty host in {}", hosts));
+            }
+            parsed.push(try!(parse_server(server)));
+        }
+        Ok(ConnectString { servers: parsed, chroot: chroot })
+    }
+
+    /// Each server as host:port (or [address]:port), i.e.: to connect to.
+    pub fn addresses(&self) -> Vec<String> {
+        self.servers.iter().map(|&(ref host, port)| {
+            if host.contains(':') { format!("[{}]:{}", host, port) } else { format!("{}:{}", host, port) }
+        }).collect()
+    }
+
+    /// The servers as host:port, comma separated, without the chroot.
+    pub fn server_list(&self) -> String {
+        self.addresses().join(",")
+    }
+}
+
+impl fmt::Display for ConnectString {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", self.server_list(), self.chroot.as_ref().map_or("", |chroot| &**chroot))
+    }
+}
+
+fn parse_server(server: &str) -> Result<(String, u16), String> {
+    let (host, port) = if server.starts_with('[') {
+        let end = match server.find(']') {
+            Some(end) => end,
+            None => return Err(format!("unterminated [ in {}", server)),
+        };
+        let address = &server[1..end];
+        if address.parse::<Ipv6Addr>().is_err() {
+            return Err(format!("bad IPv6 address {}", address));
+        }
+        match &server[end + 1..] {
+            "" => (address, None),
+            rest if rest.starts_with(':') => (address, Some(&rest[1..])),
+            _ => return Err(format!("expected :port after ] in {}", server)),
+        }
+    } else {
+        match server.matches(':').count() {
+            0 => (server, None),
+            1 => {
+                let idx = server.find(':').unwrap();
+                (&server[..idx], Some(&server[idx + 1..]))
+            },
+            _ => return Err(format!("IPv6 addresses need brackets, i.e.: [{}]:{}", server, DEFAULT_PORT)),
+        }
+    };
+
+    let valid = !host.is_empty() && host.chars().all(|c| c.is_alphanumeric() || "-._:".contains(c));
+    if !valid {
+        return Err(format!("bad host in {}", server));
+    }
+    let port = match port.map(|port| port.parse::<u16>()) {
+        None => DEFAULT_PORT,
+        Some(Ok(port)) if port > 0 => port,
+        Some(_) => return Err(format!("bad port in {}", server)),
+    };
+    Ok((host.to_string(), port))
+}
+
+// None for /, which is the same as no chroot
+fn parse_chroot(chroot: &str) -> Result<Option<String>, String> {
+    if chroot == "/" {
+        return Ok(None);
+    }
+    let valid = !chroot.ends_with('/') && !chroot.contains("//") && !chroot.chars().any(|c| c.is_whitespace());
+    if valid {
+        Ok(Some(chroot.to_string()))
+    } else {
+        Err(format!("bad chroot {}", chroot))
+    }
+}
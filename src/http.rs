@@ -0,0 +1,54 @@
+use std::io;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+use ureq;
+
+
+/// GETs url, asking for JSON, returning the status code and the body; an
+/// error status is a response too (the AdminServer explains itself in it).
+pub fn get(url: &str) -> io::Result<(u16, String)> {
+    let request = ureq::get(url).timeout(Duration::from_secs(5)).set("Accept", "application/json");
+    let response = match request.call() {
+        Ok(response) | Err(ureq::Error::Status(_, response)) => response,
+        Err(err) => return Err(io::Error::new(io::ErrorKind::Other, err.to_string())),
+    };
+    let status = response.status();
+    response.into_string().map(|body| (status, body))
+}
+
+/// Reads a request off stream, returning its method and path (the headers
+/// and any body are ignored).
+pub fn read_request(stream: &mut TcpStream) -> io::Result<(String, String)> {
+    try!(stream.set_read_timeout(Some(Duration::from_secs(5))));
+    let mut request = vec![];
+    let mut buf = [0u8; 1024];
+    while !request.ends_with(b"\r\n\r\n") && !request.ends_with(b"\n\n") {
+        let read = try!(stream.read(&mut buf));
+        if read == 0 {
+            break;
+        }
+        request.extend_from_slice(&buf[..read]);
+    }
+
+    let request = String::from_utf8_lossy(&request).into_owned();
+    let mut fields = request.lines().next().unwrap_or("").split_whitespace();
+    match (fields.next(), fields.next()) {
+        (Some(method), Some(path)) => Ok((method.to_string(), path.to_string())),
+        _ => Err(io::Error::new(io::ErrorKind::InvalidData, "bad HTTP request")),
+    }
+}
+
+/// Writes a whole response and closes the connection (HTTP/1.0 again).
+pub fn respond(stream: &mut TcpStream, status: u16, content_type: &str, body: &str) -> io::Result<()> {
+    let reason = match status {
+        200 => "OK",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        _ => "Error",
+    };
+    let response = format!("HTTP/1.0 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                           status, reason, content_type, body.len(), body);
+    stream.write_all(response.as_bytes())
+}
@@ -0,0 +1,45 @@
+use std::process;
+use std::sync::atomic::{AtomicBool, AtomicUsize, ATOMIC_BOOL_INIT, ATOMIC_USIZE_INIT, Ordering};
+
+use ctrlc;
+
+
+// number of live guards, commands like loop nest other commands
+static ACTIVE: AtomicUsize = ATOMIC_USIZE_INIT;
+static INTERRUPTED: AtomicBool = ATOMIC_BOOL_INIT;
+
+/// Ctrl-C stops the current long-running command (if it holds a Guard),
+/// otherwise it exits the shell as usual.
+pub fn install() {
+    let ret = ctrlc::set_handler(|| {
+        if ACTIVE.load(Ordering::SeqCst) > 0 {
+            INTERRUPTED.store(true, Ordering::SeqCst);
+        } else {
+            process::exit(130);
+        }
+    });
+
+    if let Err(err) = ret {
+        println!("Failed to install the Ctrl-C handler: {:?}", err);
+    }
+}
+
+pub struct Guard;
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        ACTIVE.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Marks the start of an interruptible command, until the guard is dropped.
+pub fn guard() -> Guard {
+    if ACTIVE.fetch_add(1, Ordering::SeqCst) == 0 {
+        INTERRUPTED.store(false, Ordering::SeqCst);
+    }
+    Guard
+}
+
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
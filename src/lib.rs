@@ -0,0 +1,56 @@
+#![feature(duration)]
+
+//! The command engine behind the zk-shell-rs binary, for embedding it
+//! in other tools:
+//!
+//! ```no_run
+//! let mut shell = zk_shell_rs::Shell::new("localhost:2181");
+//! shell.run_once("ls /");
+//! ```
+//!
+//! Extra commands implement `shell::Command` and are added with
+//! `Shell::register`; `Shell::on_event` hooks into the watch events.
+
+extern crate aes_gcm;
+extern crate ansi_term;
+extern crate base64;
+extern crate ctrlc;
+extern crate flate2;
+extern crate getopts;
+#[macro_use]
+extern crate log;
+extern crate regex;
+extern crate serde;
+#[macro_use]
+extern crate serde_json;
+extern crate serde_yaml;
+extern crate sha1;
+extern crate time;
+extern crate ureq;
+extern crate zookeeper;
+
+pub mod acl;
+pub mod audit;
+pub mod backup;
+pub mod client;
+pub mod config;
+pub mod crypto;
+pub mod diff;
+pub mod discovery;
+pub mod events;
+pub mod flw;
+pub mod gzip;
+pub mod hosts;
+pub mod http;
+pub mod interrupt;
+pub mod logging;
+pub mod mem;
+pub mod metrics;
+pub mod prometheus;
+pub mod recipes;
+pub mod shell;
+pub mod traversal;
+pub mod util;
+pub mod watch;
+
+pub use shell::Shell;
@@ -0,0 +1,44 @@
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::sync::Mutex;
+
+use log::{self, Log, LogLevelFilter, LogMetadata, LogRecord};
+use time;
+
+
+/// Writes log records, one per line, to stderr or a file.
+pub struct Logger {
+    level: LogLevelFilter,
+    out: Mutex<Box<Write + Send>>,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &LogMetadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &LogRecord) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        if let Ok(mut out) = self.out.lock() {
+            let _ = writeln!(out, "{} {:<5} {}: {}",
+                             time::now().rfc3339(), record.level(), record.target(), record.args());
+        }
+    }
+}
+
+/// Installs the logger: records up to level go to path (appending) or, if
+/// there's no path, to stderr.
+pub fn init(level: LogLevelFilter, path: Option<&str>) -> io::Result<()> {
+    let out: Box<Write + Send> = match path {
+        Some(path) => Box::new(try!(OpenOptions::new().create(true).append(true).open(path))),
+        None => Box::new(io::stderr()),
+    };
+
+    let ret = log::set_logger(|max_level| {
+        max_level.set(level);
+        Box::new(Logger { level: level, out: Mutex::new(out) })
+    });
+    ret.map_err(|err| io::Error::new(io::ErrorKind::Other, err.to_string()))
+}
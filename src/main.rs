@@ -1,45 +1,339 @@
-#![feature(duration)]
-
-extern crate ansi_term;
 extern crate getopts;
-#[macro_use]
-extern crate lazy_static;
-extern crate zookeeper;
+extern crate log;
+extern crate zk_shell_rs;
 
 use std::env;
+use std::io;
+use std::path::Path;
+use std::process;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
-use getopts::Options;
-
-mod shell;
+use getopts::{Matches, Options, ParsingStyle};
+use log::LogLevelFilter;
 
-use shell::Shell;
+use zk_shell_rs::Shell;
+use zk_shell_rs::config::{Config, PROFILE_VAR};
+use zk_shell_rs::crypto;
+use zk_shell_rs::discovery;
+use zk_shell_rs::hosts::HOSTS_VAR;
+use zk_shell_rs::interrupt;
+use zk_shell_rs::logging;
+use zk_shell_rs::shell::{Format, Syntax, default_last_hosts_file};
+use zk_shell_rs::shell::completion::{self, Flag};
+use zk_shell_rs::shell::line;
+use zk_shell_rs::shell::plugins;
 
 
 fn usage(program: &str, opts: Options) {
-    let brief = format!("Usage: {} [options]", program);
+    let brief = format!("Usage: {} [options] [command [args...]]", program);
     print!("{}", opts.usage(&brief[..]));
 }
 
+// the flags, out of the usage getopts prints, i.e.:
+//
+//     -v, --verbose       log connection state changes
+//         --hosts HOSTS   hosts string
+fn flags(opts: &Options) -> Vec<Flag> {
+    let mut flags: Vec<Flag> = vec![];
+    for line in opts.usage("").lines() {
+        let line = line.trim();
+        let line = if line.starts_with('-') && !line.starts_with("--") {
+            line.splitn(2, ' ').nth(1).unwrap_or("").trim()
+        } else {
+            line
+        };
+        if !line.starts_with("--") {
+            // a description that didn't fit on its line
+            if let Some(flag) = flags.last_mut() {
+                if !line.is_empty() && !line.ends_with(':') {
+                    flag.desc = format!("{} {}", flag.desc, line);
+                }
+            }
+            continue;
+        }
+        let mut words = line.splitn(2, ' ');
+        let long = words.next().unwrap()[2..].to_string();
+        let rest = words.next().unwrap_or("").trim();
+        let hint = rest.split_whitespace().next().unwrap_or("");
+        let takes_value = !hint.is_empty() && hint.chars().all(|c| c.is_uppercase() || c == ':' || c == '_');
+        let desc = if takes_value { rest[hint.len()..].trim() } else { rest };
+        flags.push(Flag { long: long, takes_value: takes_value, desc: desc.to_string() });
+    }
+    flags
+}
+
+fn fail(msg: &str) -> ! {
+    println!("{}", msg);
+    process::exit(2);
+}
+
+// --config FILE, or ~/.zkshellrc if there's one
+fn load_config(matches: &Matches) -> Config {
+    let path = match matches.opt_str("config") {
+        Some(path) => Path::new(&*path).to_path_buf(),
+        None => match Config::default_path() {
+            Some(ref path) if path.exists() => path.clone(),
+            _ => return Config::empty()
+        }
+    };
+    match Config::load(&path) {
+        Ok(config) => config,
+        Err(err) => fail(&*format!("Failed to read {}: {}", path.display(), err))
+    }
+}
+
+// the command line wins over the config file, where keys use underscores
+fn setting(matches: &Matches, config: &Config, name: &str) -> Option<String> {
+    let key = name.replace("-", "_");
+    matches.opt_str(name).or(config.get(&*key).map(|value| value.to_string()))
+}
+
 fn main() {
     let args: Vec<String> = env::args().collect();
     let program = args[0].clone();
-    let mut hosts = "".to_string();
     let mut opts = Options::new();
 
-    opts.optopt("", "hosts", "hosts string", "HOSTS");
+    // anything after the first free argument belongs to the command
+    opts.parsing_style(ParsingStyle::StopAtFirstFree);
+    opts.optopt("", "hosts", "hosts string (default: $ZK_HOSTS)", "HOSTS");
+    opts.optopt("", "exhibitor", "get (and keep refreshing) the hosts from Exhibitor at URL", "URL");
+    opts.optopt("", "exhibitor-interval", "how often to refresh the hosts from Exhibitor (default 30)", "SECS");
+    opts.optopt("", "run-once", "run a command, exiting with its status", "CMD");
+    opts.optflag("", "run-from-stdin", "run the commands read from stdin, one per line");
+    opts.optopt("", "session-timeout", "session timeout to ask for, in seconds", "SECS");
+    // a command's --timeout is its command timeout, this one isn't
+    opts.optopt("", "timeout", "deprecated, use --session-timeout", "SECS");
+    opts.optopt("", "connect-timeout", "give up connecting after SECS, 0 doesn't wait", "SECS");
+    opts.optopt("", "retries", "retry requests that lost the connection N times (default 0), --retries N overrides it per command", "N");
+    opts.optopt("", "command-timeout", "fail a command's requests after SECS, --timeout SECS overrides it per command", "SECS");
+    opts.optmulti("", "auth", "add credentials, i.e.: digest:user:password", "SCHEME:CREDENTIAL");
+    opts.optflag("", "read-only", "refuse commands that write");
+    opts.optflag("", "dry-run", "print what commands would write instead of writing it");
+    opts.optflag("", "compress", "gzip the values set & create write");
+    opts.optflag("", "keep-scratch", "leave what fill & benchmark created in place on exit");
+    opts.optopt("", "max-buffer-size", "refuse to write values over BYTES (default 1048575, as jute.maxbuffer)", "BYTES");
+    opts.optflag("", "prefix-matching", "run commands given by an unambiguous prefix, i.e.: ex for exists");
+    opts.optopt("", "format", "output format for get, ls & exists: text or json", "FORMAT");
+    opts.optopt("", "validate", "refuse to set or create values that aren't well-formed json or yaml", "SYNTAX");
+    opts.optopt("", "config", "read settings from FILE instead of ~/.zkshellrc", "FILE");
+    opts.optopt("", "profile", "connect with the hosts, credentials & co. of a [NAME] config section (default: $ZK_PROFILE)",
+                "NAME");
+    opts.optflag("v", "verbose", "log connection state changes");
+    opts.optflag("", "debug", "also log every request and watch event");
+    opts.optopt("", "log-file", "log to FILE instead of stderr", "FILE");
+    opts.optopt("", "audit-log", "append every command run (and its status) to FILE", "FILE");
+    opts.optflag("", "version", "print the version and exit");
+    opts.optflag("h", "help", "print this help and exit");
 
     let matches = match opts.parse(&args[1..]) {
         Ok(m) => { m }
-        Err(_) => {
+        Err(err) => {
+            println!("{}", err);
             usage(&program[..], opts);
-            return;
+            process::exit(2);
         }
     };
 
-    if matches.opt_present("hosts") {
-        hosts = matches.opt_str("hosts").unwrap();
+    if matches.opt_present("help") {
+        usage(&program[..], opts);
+        return;
     }
 
+    if matches.opt_present("version") {
+        println!("zk-shell-rs {}", env!("CARGO_PKG_VERSION"));
+        return;
+    }
+
+    // zk-shell-rs completion bash, it needs no hosts and no config
+    if matches.free.get(0).map_or(false, |cmd| cmd == "completion") {
+        let shell_name = matches.free.get(1).map(|name| &**name).unwrap_or("");
+        let mut shell = Shell::new("");
+        if let Some(dir) = plugins::default_dir() {
+            shell.load_plugins(&dir);
+        }
+        let program = Path::new(&*program).file_name().and_then(|name| name.to_str()).unwrap_or("zk-shell-rs").to_string();
+        match completion::script(shell_name, &*program, &*flags(&opts), shell.registry()) {
+            Some(script) if matches.free.len() == 2 => print!("{}", script),
+            _ => fail(&*format!("Expected parameters: completion {}", completion::SHELLS.join("|"))),
+        }
+        return;
+    }
+
+    let config = load_config(&matches);
+
+    let level = if matches.opt_present("debug") {
+        LogLevelFilter::Debug
+    } else if matches.opt_present("verbose") {
+        LogLevelFilter::Info
+    } else {
+        LogLevelFilter::Warn
+    };
+    if let Err(err) = logging::init(level, matches.opt_str("log-file").as_ref().map(|path| &**path)) {
+        println!("Failed to set up logging: {}", err);
+        process::exit(1);
+    }
+
+    interrupt::install();
+
+    let exhibitor = setting(&matches, &config, "exhibitor");
+    let hosts = match exhibitor {
+        Some(ref url) => match discovery::exhibitor(&*url) {
+            Ok(hosts) => hosts,
+            Err(err) => fail(&*format!("Failed to get the hosts from {}: {}", url, err))
+        },
+        None => matches.opt_str("hosts").or(env::var(HOSTS_VAR).ok()).or(config.get("hosts").map(|hosts| hosts.to_string()))
+            .unwrap_or(String::new())
+    };
     let mut shell = Shell::new(&*hosts);
+    shell.set_last_hosts_file(default_last_hosts_file());
+
+    if let Some(ref url) = exhibitor {
+        let interval = match setting(&matches, &config, "exhibitor-interval").map(|secs| secs.parse::<u64>()) {
+            Some(Ok(secs)) if secs > 0 => secs,
+            Some(_) => fail("Bad value for --exhibitor-interval."),
+            None => 30
+        };
+        let followed = Arc::new(Mutex::new(hosts.clone()));
+        discovery::follow_exhibitor(&*url, Duration::from_secs(interval), followed.clone());
+        shell.follow_hosts(followed);
+    }
+
+    let deprecated_timeout = setting(&matches, &config, "timeout");
+    if deprecated_timeout.is_some() {
+        println!("--timeout (and timeout in the config) is deprecated, use --session-timeout.");
+    }
+    let session_timeout = setting(&matches, &config, "session-timeout").or(deprecated_timeout);
+    if let Some(timeout) = session_timeout {
+        match timeout.parse::<u64>() {
+            Ok(secs) if secs > 0 => shell.set_session_timeout(secs),
+            _ => fail(&*format!("Bad value for --session-timeout: {}", timeout))
+        }
+    }
+
+    if let Some(timeout) = setting(&matches, &config, "connect-timeout") {
+        match timeout.parse::<u64>() {
+            Ok(secs) => shell.set_connect_timeout(secs),
+            _ => fail(&*format!("Bad value for --connect-timeout: {}", timeout))
+        }
+    }
+
+    if let Some(retries) = setting(&matches, &config, "retries") {
+        match retries.parse::<usize>() {
+            Ok(retries) => shell.set_retries(retries),
+            _ => fail(&*format!("Bad value for --retries: {}", retries))
+        }
+    }
+
+    if let Some(timeout) = setting(&matches, &config, "command-timeout") {
+        match timeout.parse::<u64>() {
+            Ok(0) => shell.set_command_timeout(None),
+            Ok(secs) => shell.set_command_timeout(Some(secs)),
+            _ => fail(&*format!("Bad value for --command-timeout: {}", timeout))
+        }
+    }
+
+    if let Some(format) = setting(&matches, &config, "format") {
+        match Format::parse(&*format) {
+            Some(format) => shell.set_format(format),
+            None => fail(&*format!("Bad value for --format: {}, expected text or json.", format))
+        }
+    }
+
+    if let Some(syntax) = setting(&matches, &config, "validate") {
+        match Syntax::parse(&*syntax) {
+            Some(syntax) => shell.set_validate(Some(syntax)),
+            None => fail(&*format!("Bad value for --validate: {}, expected json or yaml.", syntax))
+        }
+    }
+
+    shell.set_read_only(matches.opt_present("read-only") || config.get_bool("read_only"));
+    // i.e.: protected = /zookeeper, /prod
+    if let Some(paths) = config.get("protected") {
+        shell.set_protected(paths.split(',').map(|path| path.trim().to_string()).filter(|path| !path.is_empty()).collect());
+    }
+    if let Some(path) = setting(&matches, &config, "audit-log") {
+        if let Err(err) = shell.set_audit_log(Path::new(&*path)) {
+            fail(&*format!("Failed to open the audit log {}: {}", path, err));
+        }
+    }
+    shell.set_dry_run(matches.opt_present("dry-run") || config.get_bool("dry_run"));
+    if let Some(size) = setting(&matches, &config, "max-buffer-size") {
+        match size.parse::<usize>() {
+            Ok(bytes) if bytes > 0 => shell.set_max_buffer_size(bytes),
+            _ => fail(&*format!("Bad value for --max-buffer-size: {}", size))
+        }
+    }
+    shell.set_compress(matches.opt_present("compress") || config.get_bool("compress"));
+    shell.set_keep_scratch(matches.opt_present("keep-scratch") || config.get_bool("keep_scratch"));
+    // keys are secrets, so not something to pass as an argument
+    if let Some(key) = env::var(crypto::KEY_VAR).ok().or(config.get("encryption_key").map(|key| key.to_string())) {
+        match crypto::parse_key(&*key) {
+            Ok(key) => shell.set_encryption_key(key),
+            Err(err) => fail(&*format!("Bad encryption key: {}", err))
+        }
+    }
+    shell.set_prefix_matching(matches.opt_present("prefix-matching") || config.get_bool("prefix_matching"));
+
+    for name in config.profiles() {
+        match config.profile(&*name) {
+            Ok(profile) => shell.add_profile(&*name, profile),
+            Err(err) => fail(&*format!("Bad profile in the config: {}", err))
+        }
+    }
+    // the command line, then the environment, then the config
+    let profile = match matches.opt_str("profile") {
+        Some(name) => Some(name),
+        None if matches.opt_present("hosts") || exhibitor.is_some() => None,
+        None => match env::var(PROFILE_VAR) {
+            Ok(name) => Some(name),
+            Err(_) if env::var(HOSTS_VAR).is_ok() => None,
+            Err(_) => config.get("profile").map(|name| name.to_string()),
+        },
+    };
+    if let Some(name) = profile {
+        if matches.opt_present("hosts") || exhibitor.is_some() {
+            fail("A profile has its own hosts, --profile doesn't go with --hosts or --exhibitor.");
+        }
+        if shell.use_profile(&*name).is_none() {
+            process::exit(2);
+        }
+        // the command line has the last word
+        if matches.opt_present("read-only") {
+            shell.set_read_only(true);
+        }
+    }
+
+    let mut auths = matches.opt_strs("auth");
+    if auths.is_empty() {
+        auths.extend(config.get("auth").map(|auth| auth.to_string()));
+    }
+    for auth in auths {
+        match auth.find(':') {
+            Some(idx) => { shell.add_auth(&auth[..idx], auth[idx + 1..].as_bytes()); },
+            None => fail(&*format!("Bad value for --auth: {}, expected scheme:credential.", auth))
+        }
+    }
+
+    if let Some(dir) = plugins::default_dir() {
+        shell.load_plugins(&dir);
+    }
+
+    if let Some(cmd) = matches.opt_str("run-once") {
+        process::exit(if shell.run_once(&*cmd) { 0 } else { 1 });
+    }
+
+    if matches.opt_present("run-from-stdin") {
+        let stdin = io::stdin();
+        process::exit(if shell.run_script(stdin.lock()) { 0 } else { 1 });
+    }
+
+    // zk-shell-rs get /path
+    if !matches.free.is_empty() {
+        let cmd: Vec<String> = matches.free.iter().map(|arg| line::quote(arg)).collect();
+        let cmd = cmd.join(" ");
+        process::exit(if shell.run_once(&*cmd) { 0 } else { 1 });
+    }
+
     shell.run();
 }
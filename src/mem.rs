@@ -0,0 +1,382 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+
+use time;
+use zookeeper::{Acl, CreateMode, KeeperState, Stat, Watcher, WatchedEvent, WatchedEventType, ZkError, ZkResult};
+
+use client::ZkClient;
+use util::parent_path;
+
+
+static SESSIONS: AtomicUsize = ATOMIC_USIZE_INIT;
+
+struct Node {
+    data: Vec<u8>,
+    acl: Vec<Acl>,
+    stat: Stat,
+}
+
+// the znodes (by path) and the pending one-shot watches
+struct Tree {
+    nodes: BTreeMap<String, Node>,
+    zxid: i64,
+    data_watches: HashMap<String, Vec<Box<Watcher>>>,
+    child_watches: HashMap<String, Vec<Box<Watcher>>>,
+}
+
+type Fired = Vec<(Box<Watcher>, WatchedEvent)>;
+
+impl Tree {
+    fn new() -> Tree {
+        let mut nodes = BTreeMap::new();
+        nodes.insert("/".to_string(), Node { data: vec![], acl: vec![], stat: new_stat(0, 0) });
+        Tree {
+            nodes: nodes,
+            zxid: 0,
+            data_watches: HashMap::new(),
+            child_watches: HashMap::new(),
+        }
+    }
+
+    fn children(&self, path: &str) -> Vec<String> {
+        let mut children = vec![];
+        for key in self.nodes.keys() {
+            if key != path && parent_path(key) == Some(path) {
+                children.push(key[key.rfind('/').unwrap() + 1..].to_string());
+            }
+        }
+        children
+    }
+
+    fn stat(&self, path: &str) -> ZkResult<Stat> {
+        match self.nodes.get(path) {
+            Some(node) => {
+                let mut stat = node.stat.clone();
+                stat.num_children = self.children(path).len() as i32;
+                Ok(stat)
+            },
+            None => Err(ZkError::NoNode)
+        }
+    }
+
+    // removes the watches on path of the given kind, pairing them with the event
+    fn fire(&mut self, fired: &mut Fired, path: &str, event_type: WatchedEventType, data: bool, children: bool) {
+        let mut watchers = vec![];
+        if data {
+            watchers.extend(self.data_watches.remove(path).unwrap_or(vec![]));
+        }
+        if children {
+            watchers.extend(self.child_watches.remove(path).unwrap_or(vec![]));
+        }
+        for watcher in watchers {
+            let event = WatchedEvent {
+                event_type: event_type,
+                keeper_state: KeeperState::SyncConnected,
+                path: Some(path.to_string()),
+            };
+            fired.push((watcher, event));
+        }
+    }
+
+    fn create(&mut self, fired: &mut Fired, path: &str, data: Vec<u8>, acl: Vec<Acl>, mode: CreateMode, session: i64) -> ZkResult<String> {
+        let parent = match parent_path(path) {
+            Some(parent) => parent.to_string(),
+            None => return Err(ZkError::NodeExists) // the root
+        };
+        let cversion = match self.nodes.get(&parent) {
+            Some(node) if node.stat.ephemeral_owner != 0 => return Err(ZkError::NoChildrenForEphemerals),
+            Some(node) => node.stat.cversion,
+            None => return Err(ZkError::NoNode)
+        };
+
+        let path = match mode {
+            CreateMode::PersistentSequential | CreateMode::EphemeralSequential => format!("{}{:010}", path, cversion),
+            _ => path.to_string()
+        };
+        if self.nodes.contains_key(&path) {
+            return Err(ZkError::NodeExists);
+        }
+
+        self.zxid += 1;
+        let mut stat = new_stat(self.zxid, now());
+        stat.data_length = data.len() as i32;
+        stat.ephemeral_owner = match mode {
+            CreateMode::Ephemeral | CreateMode::EphemeralSequential => session,
+            _ => 0
+        };
+        self.nodes.insert(path.clone(), Node { data: data, acl: acl, stat: stat });
+
+        let zxid = self.zxid;
+        if let Some(node) = self.nodes.get_mut(&parent) {
+            node.stat.cversion += 1;
+            node.stat.pzxid = zxid;
+        }
+
+        self.fire(fired, &*path, WatchedEventType::NodeCreated, true, false);
+        self.fire(fired, &*parent, WatchedEventType::NodeChildrenChanged, false, true);
+        Ok(path)
+    }
+
+    fn delete(&mut self, fired: &mut Fired, path: &str, version: i32) -> ZkResult<()> {
+        match self.nodes.get(path) {
+            Some(node) if version != -1 && node.stat.version != version => return Err(ZkError::BadVersion),
+            Some(_) => (),
+            None => return Err(ZkError::NoNode)
+        }
+        if path == "/" || !self.children(path).is_empty() {
+            return Err(ZkError::NotEmpty);
+        }
+
+        self.zxid += 1;
+        self.nodes.remove(path);
+
+        let zxid = self.zxid;
+        let parent = parent_path(path).unwrap_or("/").to_string();
+        if let Some(node) = self.nodes.get_mut(&parent) {
+            node.stat.cversion += 1;
+            node.stat.pzxid = zxid;
+        }
+
+        self.fire(fired, path, WatchedEventType::NodeDeleted, true, true);
+        self.fire(fired, &*parent, WatchedEventType::NodeChildrenChanged, false, true);
+        Ok(())
+    }
+
+    fn set_data(&mut self, fired: &mut Fired, path: &str, data: Vec<u8>, version: i32) -> ZkResult<Stat> {
+        let zxid = self.zxid + 1;
+        match self.nodes.get_mut(path) {
+            Some(node) => {
+                if version != -1 && node.stat.version != version {
+                    return Err(ZkError::BadVersion);
+                }
+                node.stat.data_length = data.len() as i32;
+                node.stat.version += 1;
+                node.stat.mzxid = zxid;
+                node.stat.mtime = now();
+                node.data = data;
+            },
+            None => return Err(ZkError::NoNode)
+        }
+        self.zxid = zxid;
+
+        self.fire(fired, path, WatchedEventType::NodeDataChanged, true, false);
+        self.stat(path)
+    }
+
+    // like the server, no watch fires
+    fn set_acl(&mut self, path: &str, acl: Vec<Acl>, version: i32) -> ZkResult<Stat> {
+        if acl.is_empty() {
+            return Err(ZkError::InvalidACL);
+        }
+        match self.nodes.get_mut(path) {
+            Some(node) => {
+                if version != -1 && node.stat.aversion != version {
+                    return Err(ZkError::BadVersion);
+                }
+                node.stat.aversion += 1;
+                node.acl = acl;
+            },
+            None => return Err(ZkError::NoNode)
+        }
+        self.zxid += 1;
+        self.stat(path)
+    }
+}
+
+fn new_stat(zxid: i64, time: i64) -> Stat {
+    Stat {
+        czxid: zxid,
+        mzxid: zxid,
+        ctime: time,
+        mtime: time,
+        version: 0,
+        cversion: 0,
+        aversion: 0,
+        ephemeral_owner: 0,
+        data_length: 0,
+        num_children: 0,
+        pzxid: zxid,
+    }
+}
+
+// ms since the epoch, like the server's
+fn now() -> i64 {
+    let now = time::get_time();
+    now.sec * 1000 + (now.nsec / 1000000) as i64
+}
+
+fn check_path(path: &str) -> ZkResult<()> {
+    if !path.starts_with("/") || (path.len() > 1 && path.ends_with("/")) || path.contains("//") {
+        Err(ZkError::BadArguments)
+    } else {
+        Ok(())
+    }
+}
+
+/// An in-memory ZooKeeper, for exercising commands without an ensemble.
+///
+/// Clones share the same session; `session` opens another one against the
+/// same tree (i.e.: to contend for a lock). Watches fire synchronously, from
+/// the thread making the change.
+#[derive(Clone)]
+pub struct MemClient {
+    tree: Arc<Mutex<Tree>>,
+    session_id: i64,
+    closed: Arc<AtomicBool>,
+    auths: Arc<Mutex<Vec<(String, Vec<u8>)>>>,
+    // requests left to fail with ConnectionLoss
+    failures: Arc<AtomicUsize>,
+}
+
+impl MemClient {
+    pub fn new() -> MemClient {
+        MemClient::with_tree(Arc::new(Mutex::new(Tree::new())))
+    }
+
+    fn with_tree(tree: Arc<Mutex<Tree>>) -> MemClient {
+        MemClient {
+            tree: tree,
+            session_id: SESSIONS.fetch_add(1, Ordering::SeqCst) as i64 + 1,
+            closed: Arc::new(AtomicBool::new(false)),
+            auths: Arc::new(Mutex::new(vec![])),
+            failures: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// A new session on the same tree.
+    pub fn session(&self) -> MemClient {
+        MemClient::with_tree(self.tree.clone())
+    }
+
+    pub fn session_id(&self) -> i64 {
+        self.session_id
+    }
+
+    /// The (scheme, credential) pairs added to this session.
+    pub fn auths(&self) -> Vec<(String, Vec<u8>)> {
+        self.auths.lock().unwrap().clone()
+    }
+
+    /// The ACL path has.
+    pub fn acl(&self, path: &str) -> Option<Vec<Acl>> {
+        self.tree.lock().unwrap().nodes.get(path).map(|node| node.acl.clone())
+    }
+
+    /// Fails the session's next count requests with ConnectionLoss, as if
+    /// the server had gone away (without the tree seeing them).
+    pub fn fail_next(&self, count: usize) {
+        self.failures.store(count, Ordering::SeqCst);
+    }
+
+    // runs op on the tree, then delivers whatever watches it fired
+    fn with<T, F>(&self, path: &str, op: F) -> ZkResult<T> where F: FnOnce(&mut Tree, &mut Fired) -> ZkResult<T> {
+        if self.closed.load(Ordering::SeqCst) {
+            return Err(ZkError::SessionExpired);
+        }
+        if self.failures.load(Ordering::SeqCst) > 0 {
+            self.failures.fetch_sub(1, Ordering::SeqCst);
+            return Err(ZkError::ConnectionLoss);
+        }
+        try!(check_path(path));
+
+        let mut fired = vec![];
+        let ret = op(&mut *self.tree.lock().unwrap(), &mut fired);
+        for (watcher, event) in fired {
+            watcher.handle(&event);
+        }
+        ret
+    }
+}
+
+impl ZkClient for MemClient {
+    fn create(&self, path: &str, data: Vec<u8>, acl: Vec<Acl>, mode: CreateMode) -> ZkResult<String> {
+        let session = self.session_id;
+        self.with(path, |tree, fired| tree.create(fired, path, data, acl, mode, session))
+    }
+
+    fn delete(&self, path: &str, version: i32) -> ZkResult<()> {
+        self.with(path, |tree, fired| tree.delete(fired, path, version))
+    }
+
+    fn exists(&self, path: &str, _: bool) -> ZkResult<Stat> {
+        self.with(path, |tree, _| tree.stat(path))
+    }
+
+    fn exists_w(&self, path: &str, watcher: Box<Watcher>) -> ZkResult<Stat> {
+        self.with(path, |tree, _| {
+            tree.data_watches.entry(path.to_string()).or_insert(vec![]).push(watcher);
+            tree.stat(path)
+        })
+    }
+
+    fn get_children(&self, path: &str, _: bool) -> ZkResult<Vec<String>> {
+        self.with(path, |tree, _| {
+            try!(tree.stat(path));
+            Ok(tree.children(path))
+        })
+    }
+
+    fn get_children_w(&self, path: &str, watcher: Box<Watcher>) -> ZkResult<Vec<String>> {
+        self.with(path, |tree, _| {
+            try!(tree.stat(path));
+            tree.child_watches.entry(path.to_string()).or_insert(vec![]).push(watcher);
+            Ok(tree.children(path))
+        })
+    }
+
+    fn get_data(&self, path: &str, _: bool) -> ZkResult<(Vec<u8>, Stat)> {
+        self.with(path, |tree, _| {
+            let stat = try!(tree.stat(path));
+            Ok((tree.nodes[path].data.clone(), stat))
+        })
+    }
+
+    fn get_data_w(&self, path: &str, watcher: Box<Watcher>) -> ZkResult<(Vec<u8>, Stat)> {
+        self.with(path, |tree, _| {
+            let stat = try!(tree.stat(path));
+            tree.data_watches.entry(path.to_string()).or_insert(vec![]).push(watcher);
+            Ok((tree.nodes[path].data.clone(), stat))
+        })
+    }
+
+    fn set_data(&self, path: &str, data: Vec<u8>, version: i32) -> ZkResult<Stat> {
+        self.with(path, |tree, fired| tree.set_data(fired, path, data, version))
+    }
+
+    fn get_acl(&self, path: &str) -> ZkResult<(Vec<Acl>, Stat)> {
+        self.with(path, |tree, _| {
+            let stat = try!(tree.stat(path));
+            Ok((tree.nodes[path].acl.clone(), stat))
+        })
+    }
+
+    fn set_acl(&self, path: &str, acl: Vec<Acl>, version: i32) -> ZkResult<Stat> {
+        self.with(path, |tree, _| tree.set_acl(path, acl, version))
+    }
+
+    // credentials aren't checked, there are no ACLs to enforce
+    fn add_auth(&self, scheme: &str, auth: Vec<u8>) -> ZkResult<()> {
+        if self.closed.load(Ordering::SeqCst) {
+            return Err(ZkError::SessionExpired);
+        }
+        self.auths.lock().unwrap().push((scheme.to_string(), auth));
+        Ok(())
+    }
+
+    /// Ends the session, dropping its ephemeral znodes.
+    fn close(&self) {
+        let session = self.session_id;
+        let _ = self.with("/", |tree, fired| {
+            let ephemerals: Vec<String> = tree.nodes.iter()
+                .filter(|&(_, node)| node.stat.ephemeral_owner == session)
+                .map(|(path, _)| path.clone())
+                .collect();
+            for path in ephemerals {
+                let _ = tree.delete(fired, &*path, -1);
+            }
+            Ok(())
+        });
+        self.closed.store(true, Ordering::SeqCst);
+    }
+}
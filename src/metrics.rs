@@ -0,0 +1,76 @@
+use std::cmp;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+
+/// What one kind of request did during the session.
+#[derive(Clone, Default)]
+pub struct OpStats {
+    pub count: u64,
+    /// how many failed with each error, i.e.: NoNode
+    pub errors: BTreeMap<String, u64>,
+    // in ms
+    latencies: Vec<f64>,
+}
+
+impl OpStats {
+    pub fn failed(&self) -> u64 {
+        self.errors.values().fold(0, |sum, count| sum + count)
+    }
+
+    /// The latency (in ms) p percent of the requests took at most, by
+    /// nearest rank, 0 if there were none.
+    pub fn percentile(&self, p: f64) -> f64 {
+        if self.latencies.is_empty() {
+            return 0.0;
+        }
+        let mut sorted = self.latencies.clone();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let rank = (p / 100.0 * sorted.len() as f64).ceil() as usize;
+        sorted[cmp::min(cmp::max(rank, 1), sorted.len()) - 1]
+    }
+}
+
+/// Counts and latencies of the requests made to the server, by operation.
+pub struct Metrics {
+    ops: Mutex<BTreeMap<String, OpStats>>,
+}
+
+impl Metrics {
+    pub fn new() -> Metrics {
+        Metrics { ops: Mutex::new(BTreeMap::new()) }
+    }
+
+    /// Records a request to the client's method, i.e.: get_data_w. Methods
+    /// are grouped by operation (see `operation`).
+    pub fn record(&self, method: &str, ms: f64, error: Option<String>) {
+        let mut ops = self.ops.lock().unwrap();
+        let stats = ops.entry(operation(method).to_string()).or_insert(OpStats::default());
+        stats.count += 1;
+        stats.latencies.push(ms);
+        if let Some(error) = error {
+            *stats.errors.entry(error).or_insert(0) += 1;
+        }
+    }
+
+    /// Each operation's stats, by name.
+    pub fn snapshot(&self) -> Vec<(String, OpStats)> {
+        self.ops.lock().unwrap().iter().map(|(op, stats)| (op.clone(), stats.clone())).collect()
+    }
+
+    pub fn reset(&self) {
+        self.ops.lock().unwrap().clear();
+    }
+}
+
+/// The operation a client method counts as: get, set, create, delete,
+/// list or exists, with and without watches alike.
+pub fn operation(method: &str) -> &str {
+    match method {
+        "get_data" | "get_data_w" => "get",
+        "set_data" => "set",
+        "get_children" | "get_children_w" => "list",
+        "exists" | "exists_w" => "exists",
+        method => method,
+    }
+}
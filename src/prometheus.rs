@@ -0,0 +1,63 @@
+//! mntr output in Prometheus' text exposition format, so a scraper can
+//! read it straight off the shell.
+
+use std::collections::{BTreeMap, HashMap};
+
+
+/// What a scrape gets back as its Content-Type.
+pub const CONTENT_TYPE: &'static str = "text/plain; version=0.0.4";
+
+/// The exposition of every server's mntr stats (None for the unreachable
+/// ones), labelled by server. Numeric stats keep their mntr names, the
+/// others (zk_server_state, zk_version) become a label on a 1, and zk_up
+/// says which servers answered.
+pub fn exposition(servers: &[(String, Option<HashMap<String, String>>)]) -> String {
+    // by metric: its samples, labels first
+    let mut metrics: BTreeMap<String, Vec<(String, String)>> = BTreeMap::new();
+    for &(ref server, ref stats) in servers {
+        let labels = format!("server=\"{}\"", escape(server));
+        metrics.entry("zk_up".to_string()).or_insert(vec![])
+            .push((labels.clone(), if stats.is_some() { "1" } else { "0" }.to_string()));
+        let stats = match *stats {
+            Some(ref stats) => stats,
+            None => continue,
+        };
+        for (key, value) in stats {
+            let name = metric_name(key);
+            let sample = if value.parse::<f64>().is_ok() {
+                (labels.clone(), value.clone())
+            } else {
+                // i.e.: zk_server_state -> state="leader"
+                let label = metric_name(key.trim_left_matches("zk_").trim_left_matches("server_"));
+                (format!("{},{}=\"{}\"", labels, label, escape(value)), "1".to_string())
+            };
+            metrics.entry(name).or_insert(vec![]).push(sample);
+        }
+    }
+
+    let mut text = String::new();
+    for (name, mut samples) in metrics {
+        samples.sort();
+        // mntr doesn't say which are counters
+        text.push_str(&*format!("# TYPE {} untyped\n", name));
+        for (labels, value) in samples {
+            text.push_str(&*format!("{}{{{}}} {}\n", name, labels, value));
+        }
+    }
+    text
+}
+
+// letters, digits and underscores, not starting with a digit
+fn metric_name(key: &str) -> String {
+    let valid = |c: char| (c >= 'a' && c <= 'z') || (c >= 'A' && c <= 'Z') || c.is_digit(10) || c == '_';
+    let name: String = key.chars().map(|c| if valid(c) { c } else { '_' }).collect();
+    if name.chars().next().map_or(true, |c| c.is_digit(10)) {
+        format!("_{}", name)
+    } else {
+        name
+    }
+}
+
+fn escape(value: &str) -> String {
+    value.replace("\\", "\\\\").replace("\"", "\\\"").replace("\n", "\\n")
+}
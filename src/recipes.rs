@@ -0,0 +1,161 @@
+use std::str;
+use std::sync::mpsc::channel;
+
+use zookeeper::{Acl, CreateMode, ZkError, ZkResult};
+
+use client::ZkClient;
+use util::{ensure_path, join_path};
+use watch::{ChannelWatcher, wait_event};
+
+
+const MAX_RETRIES: usize = 100;
+
+pub enum CounterError {
+    Zk(ZkError),
+    NotANumber,
+}
+
+// an empty znode counts as 0
+fn parse_counter(data: &[u8]) -> Result<i64, CounterError> {
+    match str::from_utf8(data).map(|data| data.trim()) {
+        Ok("") => Ok(0),
+        Ok(value) => value.parse::<i64>().map_err(|_| CounterError::NotANumber),
+        Err(_) => Err(CounterError::NotANumber),
+    }
+}
+
+/// Reads a counter, a znode holding a decimal number.
+pub fn counter_get(zk: &ZkClient, path: &str) -> Result<i64, CounterError> {
+    match zk.get_data(path, false) {
+        Ok((data, _)) => parse_counter(&data[..]),
+        Err(err) => Err(CounterError::Zk(err)),
+    }
+}
+
+/// Adds delta to a counter (created if needed) with a version-checked
+/// read-modify-write, retrying when someone else updated it in between.
+pub fn counter_add(zk: &ZkClient, path: &str, delta: i64, acl: &Vec<Acl>) -> Result<i64, CounterError> {
+    for _ in 0..MAX_RETRIES {
+        let (value, version) = match zk.get_data(path, false) {
+            Ok((data, stat)) => (try!(parse_counter(&data[..])), stat.version),
+            Err(ZkError::NoNode) => {
+                let data = delta.to_string().into_bytes();
+                match zk.create(path, data, acl.clone(), CreateMode::Persistent) {
+                    Ok(_) => return Ok(delta),
+                    Err(ZkError::NodeExists) => continue,
+                    Err(err) => return Err(CounterError::Zk(err)),
+                }
+            },
+            Err(err) => return Err(CounterError::Zk(err)),
+        };
+
+        let value = value + delta;
+        match zk.set_data(path, value.to_string().into_bytes(), version) {
+            Ok(_) => return Ok(value),
+            Err(ZkError::BadVersion) => continue,
+            Err(err) => return Err(CounterError::Zk(err)),
+        }
+    }
+
+    Err(CounterError::Zk(ZkError::BadVersion))
+}
+
+/// Rewrites path's data with change(current data), version checked, and
+/// retries up to retries times when someone else wrote it in between.
+/// Nothing is written (and it's Ok(false)) once change gives None.
+pub fn update<F>(zk: &ZkClient, path: &str, retries: usize, mut change: F) -> ZkResult<bool>
+    where F: FnMut(&[u8]) -> Option<Vec<u8>>
+{
+    for _ in 0..retries + 1 {
+        let (data, stat) = try!(zk.get_data(path, false));
+        let data = match change(&data[..]) {
+            Some(data) => data,
+            None => return Ok(false),
+        };
+        match zk.set_data(path, data, stat.version) {
+            Ok(_) => return Ok(true),
+            Err(ZkError::BadVersion) => continue,
+            Err(err) => return Err(err),
+        }
+    }
+
+    Err(ZkError::BadVersion)
+}
+
+/// The sequence number ZooKeeper appended to a sequential znode's name.
+pub fn sequence(name: &str) -> Option<u32> {
+    if name.len() < 10 {
+        return None;
+    }
+    name[name.len() - 10..].parse::<u32>().ok()
+}
+
+/// The sequential children of path (i.e.: lock contenders), in order.
+pub fn contenders(zk: &ZkClient, path: &str) -> ZkResult<Vec<String>> {
+    let mut children: Vec<_> = try!(zk.get_children(path, false))
+        .into_iter()
+        .filter(|child| sequence(child).is_some())
+        .collect();
+    children.sort_by(|a, b| sequence(a).cmp(&sequence(b)));
+    Ok(children)
+}
+
+/// Blocks until node is the first contender under path, watching only its
+/// predecessor. Returns false if interrupted.
+pub fn wait_first(zk: &ZkClient, path: &str, node: &str) -> ZkResult<bool> {
+    let name = &node[node.rfind('/').map_or(0, |idx| idx + 1)..];
+    let (tx, rx) = channel();
+
+    loop {
+        let contenders = try!(contenders(zk, path));
+        let idx = match contenders.iter().position(|contender| contender == name) {
+            Some(idx) => idx,
+            None => return Err(ZkError::NoNode), // our node is gone
+        };
+        if idx == 0 {
+            return Ok(true);
+        }
+
+        match zk.exists_w(&*join_path(path, &*contenders[idx - 1]), Box::new(ChannelWatcher::new(&tx))) {
+            Ok(_) => {
+                if wait_event(&rx).is_none() {
+                    return Ok(false);
+                }
+            },
+            Err(ZkError::NoNode) => continue,
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Adds us as a contender under path (i.e.: for a lock or an election),
+/// returns the znode created for us.
+pub fn enqueue(zk: &ZkClient, path: &str, prefix: &str, data: Vec<u8>, acl: &Vec<Acl>) -> ZkResult<String> {
+    try!(ensure_path(zk, path, acl));
+    zk.create(&*join_path(path, prefix), data, acl.clone(), CreateMode::EphemeralSequential)
+}
+
+/// Blocks until the children of path satisfy done, re-checking every time
+/// they change. Returns false if interrupted.
+pub fn wait_children<F>(zk: &ZkClient, path: &str, done: F) -> ZkResult<bool>
+    where F: Fn(&Vec<String>) -> bool {
+    let (tx, rx) = channel();
+
+    loop {
+        let children = try!(zk.get_children_w(path, Box::new(ChannelWatcher::new(&tx))));
+        if done(&children) {
+            return Ok(true);
+        }
+        if wait_event(&rx).is_none() {
+            return Ok(false);
+        }
+    }
+}
+
+/// The ready flag of a barrier, created once everyone has entered.
+pub const BARRIER_READY: &'static str = "ready";
+
+/// Participants of a barrier, i.e.: everything but the ready flag.
+pub fn barrier_participants(children: &Vec<String>) -> usize {
+    children.iter().filter(|child| *child != BARRIER_READY).count()
+}
@@ -0,0 +1,314 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::str;
+
+use ansi_term::Colour::White;
+use getopts::{Matches, Options};
+
+use super::{Shell, line};
+
+
+/// A command line option, i.e.: `-l, --long`. Options without a hint are
+/// flags, those with one take a value.
+pub struct Opt {
+    pub short: &'static str,
+    pub long: &'static str,
+    pub hint: &'static str,
+    pub desc: &'static str,
+}
+
+impl Opt {
+    pub fn flag(short: &'static str, long: &'static str, desc: &'static str) -> Opt {
+        Opt { short: short, long: long, hint: "", desc: desc }
+    }
+
+    pub fn opt(short: &'static str, long: &'static str, hint: &'static str, desc: &'static str) -> Opt {
+        Opt { short: short, long: long, hint: hint, desc: desc }
+    }
+
+    fn usage(&self) -> String {
+        let name = match self.short {
+            "" => format!("--{}", self.long),
+            short => format!("-{}, --{}", short, self.long)
+        };
+        match self.hint {
+            "" => format!("{:<24} {}", name, self.desc),
+            hint => format!("{:<24} {}", format!("{} {}", name, hint), self.desc)
+        }
+    }
+}
+
+/// A shell command. Implementations only declare their arguments, the
+/// shell validates them (and generates the help) before calling execute.
+pub trait Command {
+    fn name(&self) -> &str;
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn description(&self) -> &str;
+
+    fn synopsis(&self) -> &str;
+
+    fn options(&self) -> Vec<Opt> {
+        vec![]
+    }
+
+    /// At least one example invocation, one per line.
+    fn examples(&self) -> &str;
+
+    /// Bounds for the number of positional arguments, usize::MAX means
+    /// there's no upper bound.
+    fn min_args(&self) -> usize;
+
+    fn max_args(&self) -> usize;
+
+    /// Commands wrapping other command lines (i.e.: loop) get their
+    /// arguments untouched instead of having options parsed out of them.
+    fn raw_args(&self) -> bool {
+        false
+    }
+
+    /// Whether running with args would write to ZooKeeper, such commands
+    /// are refused in read-only mode.
+    fn mutates(&self, _: &Args) -> bool {
+        false
+    }
+
+    /// The path running with args would overwrite or delete, and whether
+    /// everything under it goes too. Protected paths are checked against it.
+    fn destroys<'a>(&self, _: &'a Args) -> Option<(&'a str, bool)> {
+        None
+    }
+
+    /// The paths running with args would write to, by default the one it
+    /// destroys. Writes into ZooKeeper's own subtree are checked against it.
+    fn writes<'a>(&self, args: &'a Args) -> Vec<&'a str> {
+        self.destroys(args).map(|(path, _)| path).into_iter().collect()
+    }
+
+    /// Whether --dry-run can keep running with args from writing, i.e.: not
+    /// when the writes go through something other than the shell's session.
+    /// Such commands are refused under --dry-run.
+    fn supports_dry_run(&self, _: &Args) -> bool {
+        true
+    }
+
+    /// Whether a - argument stands for a value typed (or pasted) over the
+    /// lines that follow the command, up to a lone `.`.
+    fn reads_value(&self) -> bool {
+        false
+    }
+
+    /// Whether the arguments are secrets (i.e.: credentials) that must not
+    /// end up in the audit log.
+    fn sensitive(&self) -> bool {
+        false
+    }
+
+    /// Runs the command, returning whether it succeeded.
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool;
+}
+
+/// A command's positional arguments and parsed options.
+pub struct Args {
+    free: Vec<String>,
+    matches: Option<Matches>,
+}
+
+impl Args {
+    /// Parses args for cmd, complaining (and returning None) if they are
+    /// not what it expects.
+    pub fn parse(cmd: &Command, args: &[&str]) -> Option<Args> {
+        let (free, matches) = if cmd.raw_args() {
+            (args.iter().map(|arg| arg.to_string()).collect(), None)
+        } else {
+            let mut opts = Options::new();
+            for opt in cmd.options() {
+                match opt.hint {
+                    "" => opts.optflag(opt.short, opt.long, opt.desc),
+                    hint => opts.optopt(opt.short, opt.long, opt.desc, hint),
+                };
+            }
+            match opts.parse(args) {
+                Ok(matches) => (matches.free.clone(), Some(matches)),
+                Err(err) => {
+                    println!("{}, expected parameters: {}", err, cmd.synopsis());
+                    return None;
+                }
+            }
+        };
+
+        if free.len() < cmd.min_args() || free.len() > cmd.max_args() {
+            println!("Wrong number of arguments, expected parameters: {}", cmd.synopsis());
+            return None;
+        }
+        Some(Args { free: free, matches: matches })
+    }
+
+    pub fn len(&self) -> usize {
+        self.free.len()
+    }
+
+    /// The i-th positional argument, which must be there.
+    pub fn get(&self, i: usize) -> &str {
+        &*self.free[i]
+    }
+
+    pub fn optional(&self, i: usize) -> Option<&str> {
+        self.free.get(i).map(|arg| &**arg)
+    }
+
+    /// The positional arguments from i onwards as a command line, quoted
+    /// as needed.
+    pub fn rest(&self, i: usize) -> String {
+        self.free[i..].iter().map(|arg| line::quote(arg)).collect::<Vec<String>>().join(" ")
+    }
+
+    pub fn all(&self) -> &[String] {
+        &self.free
+    }
+
+    pub fn flag(&self, name: &str) -> bool {
+        self.matches.as_ref().map_or(false, |matches| matches.opt_present(name))
+    }
+
+    pub fn value(&self, name: &str) -> Option<String> {
+        self.matches.as_ref().and_then(|matches| matches.opt_str(name))
+    }
+
+    /// An option's value parsed as T (or default if it wasn't given), None
+    /// after complaining if it can't be parsed.
+    pub fn parse_value<T: str::FromStr>(&self, name: &str, default: T) -> Option<T> {
+        match self.value(name) {
+            Some(value) => match value.parse::<T>() {
+                Ok(value) => Some(value),
+                Err(_) => {
+                    println!("Bad value for --{}: {}", name, value);
+                    None
+                }
+            },
+            None => Some(default)
+        }
+    }
+}
+
+/// The help for cmd, like a man page.
+pub fn help(cmd: &Command) -> String {
+    let mut help = format!("{}\n\t{} - {}\n\n{}\n\t{} {}\n",
+                           White.bold().paint("NAME"), cmd.name(), cmd.description(),
+                           White.bold().paint("SYNOPSIS"), cmd.name(), cmd.synopsis());
+    if !cmd.aliases().is_empty() {
+        help.push_str(&*format!("\n{}\n\t{}\n", White.bold().paint("ALIASES"), cmd.aliases().join(", ")));
+    }
+
+    let options = cmd.options();
+    if !options.is_empty() {
+        let options: Vec<String> = options.iter().map(|opt| opt.usage()).collect();
+        help.push_str(&*format!("\n{}\n\t{}\n", White.bold().paint("OPTIONS"), options.join("\n\t")));
+    }
+
+    let examples: Vec<&str> = cmd.examples().lines().collect();
+    help.push_str(&*format!("\n{}\n\t{}\n", White.bold().paint("EXAMPLES"), examples.join("\n\t")));
+    help
+}
+
+/// Whether cmd's name, aliases or description mention keyword (ignoring
+/// case), for apropos.
+pub fn matches_keyword(cmd: &Command, keyword: &str) -> bool {
+    let keyword = keyword.to_lowercase();
+    cmd.name().to_lowercase().contains(&*keyword) ||
+        cmd.aliases().iter().any(|alias| alias.to_lowercase().contains(&*keyword)) ||
+        cmd.description().to_lowercase().contains(&*keyword)
+}
+
+// Levenshtein, counting chars rather than bytes
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..b.len() + 1).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, &cb) in b.iter().enumerate() {
+            let cost = if ca == cb { 0 } else { 1 };
+            let best = (previous[j] + cost).min(previous[j + 1] + 1).min(current[j] + 1);
+            current.push(best);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// The known commands, by name and alias.
+pub struct Registry {
+    commands: Vec<Rc<Command>>,
+    names: HashMap<String, usize>,
+}
+
+impl Registry {
+    pub fn new() -> Registry {
+        Registry { commands: vec![], names: HashMap::new() }
+    }
+
+    /// Adds cmd, replacing any command previously registered under the
+    /// same name or alias.
+    pub fn register<C: Command + 'static>(&mut self, cmd: C) {
+        let idx = self.commands.len();
+        self.names.insert(cmd.name().to_string(), idx);
+        for alias in cmd.aliases() {
+            self.names.insert(alias.to_string(), idx);
+        }
+        self.commands.push(Rc::new(cmd));
+    }
+
+    pub fn get(&self, name: &str) -> Option<Rc<Command>> {
+        self.names.get(name).map(|&idx| self.commands[idx].clone())
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.names.contains_key(name)
+    }
+
+    /// Every command (reachable by its own name), sorted by name.
+    pub fn commands(&self) -> Vec<Rc<Command>> {
+        let mut commands: Vec<_> = self.commands.iter().enumerate()
+            .filter(|&(idx, cmd)| self.names.get(cmd.name()) == Some(&idx))
+            .map(|(_, cmd)| cmd.clone())
+            .collect();
+        commands.sort_by(|a, b| a.name().cmp(b.name()));
+        commands
+    }
+
+    /// The (primary names of the) commands with a name or alias starting
+    /// with prefix, sorted.
+    pub fn with_prefix(&self, prefix: &str) -> Vec<String> {
+        let mut found: Vec<usize> = self.names.iter()
+            .filter(|&(name, _)| name.starts_with(prefix))
+            .map(|(_, &idx)| idx)
+            .collect();
+        found.sort();
+        found.dedup();
+        let mut names: Vec<String> = found.into_iter().map(|idx| self.commands[idx].name().to_string()).collect();
+        names.sort();
+        names
+    }
+
+    /// The names (or aliases) within a couple of typos of name, closest
+    /// first.
+    pub fn suggest(&self, name: &str) -> Vec<String> {
+        let max = if name.chars().count() > 4 { 2 } else { 1 };
+        let mut close: Vec<(usize, &String)> = self.names.keys()
+            .map(|known| (edit_distance(name, known), known))
+            .filter(|&(distance, _)| distance <= max)
+            .collect();
+        close.sort();
+        close.into_iter().take(3).map(|(_, known)| known.clone()).collect()
+    }
+
+    /// Every name and alias, sorted (i.e.: for completion).
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<_> = self.names.keys().cloned().collect();
+        names.sort();
+        names
+    }
+}
@@ -0,0 +1,390 @@
+use std::thread;
+use std::time::Duration;
+use std::usize;
+
+use ansi_term::Colour::White;
+use time;
+
+use interrupt;
+use shell::{Args, Command, Shell, EVENTS_CAPACITY, timestamp};
+use shell::command;
+use shell::output;
+
+use super::{columns, valid_name};
+
+
+pub struct Loop;
+
+impl Command for Loop {
+    fn name(&self) -> &str { "loop" }
+    fn description(&self) -> &str { "Runs a command repeatedly, sleeping in between runs" }
+    fn synopsis(&self) -> &str { "<count|forever> <interval-secs> <command...>" }
+    fn examples(&self) -> &str { "loop 10 1 get /config\nloop forever 5 echo still here" }
+    fn min_args(&self) -> usize { 3 }
+    fn max_args(&self) -> usize { usize::MAX }
+    fn raw_args(&self) -> bool { true }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        let count = match args.get(0) {
+            "forever" => None,
+            n => match n.parse::<u64>() {
+                Ok(n) if n > 0 => Some(n),
+                _ => {
+                    out!("Bad count: {}, expected a positive number or forever.", n);
+                    return false;
+                }
+            }
+        };
+        let interval = match args.get(1).parse::<u64>() {
+            Ok(secs) => Duration::from_secs(secs),
+            Err(_) => {
+                out!("Bad interval: {}.", args.get(1));
+                return false;
+            }
+        };
+        let cmd = args.rest(2);
+
+        let _guard = interrupt::guard();
+        let mut runs = 0;
+        loop {
+            out!("{}", White.bold().paint(&*timestamp()));
+            let status = shell.execute(&cmd);
+
+            runs += 1;
+            if count.map_or(false, |count| runs >= count) || interrupt::interrupted() {
+                return status;
+            }
+            thread::sleep(interval);
+        }
+    }
+}
+
+pub struct Time;
+
+impl Command for Time {
+    fn name(&self) -> &str { "time" }
+    fn description(&self) -> &str { "Runs a command and reports how long it took" }
+    fn synopsis(&self) -> &str { "<command...>" }
+    fn examples(&self) -> &str { "time get /config\ntime loop 100 0 set /counter x" }
+    fn min_args(&self) -> usize { 1 }
+    fn max_args(&self) -> usize { usize::MAX }
+    fn raw_args(&self) -> bool { true }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        let cmd = args.rest(0);
+
+        let executed = shell.executed;
+        let start = time::precise_time_ns();
+        let status = shell.execute(&cmd);
+        let elapsed = time::precise_time_ns() - start;
+
+        let secs = elapsed as f64 / 1e9;
+        out!("{} {:.3}ms", White.bold().paint("real"), elapsed as f64 / 1e6);
+
+        // bulk commands (i.e.: loop) dispatch further commands, count those as ops
+        let ops = shell.executed.saturating_sub(executed + 1);
+        if ops > 0 && secs > 0.0 {
+            out!("{} {} ({:.2} ops/sec)", White.bold().paint("ops"), ops, ops as f64 / secs);
+        }
+
+        status
+    }
+}
+
+pub struct Stats;
+
+impl Command for Stats {
+    fn name(&self) -> &str { "stats" }
+    fn description(&self) -> &str { "Shows how many requests of each kind were made, how they fared and their latencies" }
+    fn synopsis(&self) -> &str { "[reset]" }
+    fn examples(&self) -> &str { "stats\nstats reset" }
+    fn min_args(&self) -> usize { 0 }
+    fn max_args(&self) -> usize { 1 }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        match args.optional(0) {
+            Some("reset") => {
+                shell.metrics.reset();
+                return true;
+            },
+            Some(other) => {
+                out!("Bad argument: {}, expected reset.", other);
+                return false;
+            },
+            None => (),
+        }
+
+        let ops = shell.metrics.snapshot();
+        if ops.is_empty() {
+            out!("No requests yet.");
+            return true;
+        }
+        let mut rows = vec![["op", "count", "errors", "p50 ms", "p90 ms", "p99 ms", "max ms"].iter().map(|s| s.to_string()).collect()];
+        for &(ref op, ref stats) in &ops {
+            let mut row = vec![op.clone(), stats.count.to_string(), stats.failed().to_string()];
+            row.extend([50.0, 90.0, 99.0, 100.0].iter().map(|&p| format!("{:.3}", stats.percentile(p))));
+            rows.push(row);
+        }
+        for line in columns(&rows) {
+            out!("{}", line);
+        }
+
+        // which errors, i.e.: get: NoNode 3
+        for &(ref op, ref stats) in ops.iter().filter(|&&(_, ref stats)| !stats.errors.is_empty()) {
+            let errors: Vec<String> = stats.errors.iter().map(|(err, count)| format!("{} {}", err, count)).collect();
+            out!("{}: {}", op, errors.join(", "));
+        }
+        true
+    }
+}
+
+pub struct Events;
+
+impl Events {
+    fn print(shell: &Shell, count: usize) {
+        for event in shell.events.recent(count) {
+            out!("{}", event);
+        }
+    }
+}
+
+impl Command for Events {
+    fn name(&self) -> &str { "events" }
+    fn description(&self) -> &str { "Lists recent watch events, or toggles printing them as they arrive" }
+    fn synopsis(&self) -> &str { "[count] | live <on|off> | clear" }
+    fn examples(&self) -> &str { "events 20\nevents live on" }
+    fn min_args(&self) -> usize { 0 }
+    fn max_args(&self) -> usize { 2 }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        match (args.len(), args.optional(0)) {
+            (0, _) => Events::print(shell, EVENTS_CAPACITY),
+            (1, Some("clear")) => shell.events.clear(),
+            (1, Some("live")) => out!("live {}", if shell.events.is_live() { "on" } else { "off" }),
+            (2, Some("live")) => match args.get(1) {
+                "on" => shell.events.set_live(true),
+                "off" => shell.events.set_live(false),
+                other => {
+                    out!("Bad value: {}, expected on or off.", other);
+                    return false;
+                }
+            },
+            (1, Some(count)) => match count.parse::<usize>() {
+                Ok(count) => Events::print(shell, count),
+                Err(_) => {
+                    out!("Bad count: {}.", count);
+                    return false;
+                }
+            },
+            _ => {
+                out!("Wrong arguments, expected parameters: {}", self.synopsis());
+                return false;
+            }
+        }
+        true
+    }
+}
+
+pub struct Help;
+
+impl Command for Help {
+    fn name(&self) -> &str { "help" }
+    fn aliases(&self) -> &'static [&'static str] { &["man"] }
+    fn description(&self) -> &str { "Lists the commands, or shows the full help for one" }
+    fn synopsis(&self) -> &str { "[cmd]" }
+    fn examples(&self) -> &str { "help ls\nhelp" }
+    fn min_args(&self) -> usize { 0 }
+    fn max_args(&self) -> usize { 1 }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        match args.optional(0) {
+            Some(name) => match shell.registry.get(name) {
+                Some(cmd) => {
+                    out!("{}", command::help(&*cmd));
+                    true
+                },
+                None => {
+                    out!("Unknown command: {}.", name);
+                    false
+                }
+            },
+            None => {
+                for cmd in shell.registry.commands() {
+                    out!("{} - {}", White.bold().paint(cmd.name()), cmd.synopsis());
+                }
+                true
+            }
+        }
+    }
+}
+
+pub struct Apropos;
+
+impl Command for Apropos {
+    fn name(&self) -> &str { "apropos" }
+    fn description(&self) -> &str { "Lists the commands whose name or description mention a keyword" }
+    fn synopsis(&self) -> &str { "<keyword>" }
+    fn examples(&self) -> &str { "apropos lock\napropos watch" }
+    fn min_args(&self) -> usize { 1 }
+    fn max_args(&self) -> usize { 1 }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        let found: Vec<_> = shell.registry.commands().into_iter()
+            .filter(|cmd| command::matches_keyword(&**cmd, args.get(0)))
+            .collect();
+        if found.is_empty() {
+            out!("Nothing about {}.", args.get(0));
+            return false;
+        }
+        for cmd in found {
+            out!("{} - {}", White.bold().paint(cmd.name()), cmd.description());
+        }
+        true
+    }
+}
+
+pub struct SetVar;
+
+impl Command for SetVar {
+    fn name(&self) -> &str { "set_var" }
+    fn description(&self) -> &str { "Sets a shell variable, later commands can use it as $name" }
+    fn synopsis(&self) -> &str { "<name> <value>" }
+    fn examples(&self) -> &str { "set_var env prod" }
+    fn min_args(&self) -> usize { 2 }
+    fn max_args(&self) -> usize { 2 }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        if !valid_name(args.get(0)) {
+            out!("Bad variable name: {}", args.get(0));
+            return false;
+        }
+        shell.set_variable(args.get(0), args.get(1));
+        true
+    }
+}
+
+pub struct Var;
+
+impl Command for Var {
+    fn name(&self) -> &str { "var" }
+    fn description(&self) -> &str { "Sets a shell variable to what a command prints" }
+    fn synopsis(&self) -> &str { "<name> = <command...>" }
+    fn examples(&self) -> &str { "var leader = get /election/leader" }
+    fn min_args(&self) -> usize { 3 }
+    fn max_args(&self) -> usize { usize::MAX }
+    fn raw_args(&self) -> bool { true }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        if args.get(1) != "=" {
+            out!("Wrong arguments, expected parameters: {}", self.synopsis());
+            return false;
+        }
+        let name = args.get(0);
+        if !valid_name(name) {
+            out!("Bad variable name: {}", name);
+            return false;
+        }
+
+        let cmd = args.rest(2);
+        let (status, captured) = output::capture(|| shell.execute(&*cmd));
+        if !status {
+            // the output probably explains why
+            for line in captured.lines() {
+                out!("{}", line);
+            }
+            return false;
+        }
+        shell.set_variable(name, captured.trim_right_matches('\n'));
+        true
+    }
+}
+
+pub struct Vars;
+
+impl Command for Vars {
+    fn name(&self) -> &str { "vars" }
+    fn description(&self) -> &str { "Lists the shell variables" }
+    fn synopsis(&self) -> &str { "" }
+    fn examples(&self) -> &str { "vars" }
+    fn min_args(&self) -> usize { 0 }
+    fn max_args(&self) -> usize { 0 }
+
+    fn execute(&self, shell: &mut Shell, _: &Args) -> bool {
+        let mut names: Vec<&String> = shell.variables.keys().collect();
+        names.sort();
+        for name in names {
+            out!("{}={}", name, shell.variables[name]);
+        }
+        true
+    }
+}
+
+pub struct Sleep;
+
+impl Command for Sleep {
+    fn name(&self) -> &str { "sleep" }
+    fn description(&self) -> &str { "Pauses for the given (possibly fractional) seconds" }
+    fn synopsis(&self) -> &str { "<secs>" }
+    fn examples(&self) -> &str { "sleep 0.5" }
+    fn min_args(&self) -> usize { 1 }
+    fn max_args(&self) -> usize { 1 }
+
+    fn execute(&self, _: &mut Shell, args: &Args) -> bool {
+        let secs = match args.get(0).parse::<f64>() {
+            Ok(secs) if secs >= 0.0 => secs,
+            _ => {
+                out!("Bad duration: {}", args.get(0));
+                return false;
+            }
+        };
+
+        // in small steps, so Ctrl-C gets a chance
+        let _guard = interrupt::guard();
+        let deadline = time::precise_time_ns() + (secs * 1e9) as u64;
+        loop {
+            let now = time::precise_time_ns();
+            if now >= deadline {
+                return true;
+            }
+            if interrupt::interrupted() {
+                return false;
+            }
+            thread::sleep(Duration::from_millis(((deadline - now) / 1000000).min(100).max(1)));
+        }
+    }
+}
+
+pub struct Exit;
+
+impl Command for Exit {
+    fn name(&self) -> &str { "exit" }
+    fn description(&self) -> &str {
+        "Leaves the shell, deleting what fill & benchmark left behind and closing the session"
+    }
+    fn synopsis(&self) -> &str { "" }
+    fn examples(&self) -> &str { "exit" }
+    fn min_args(&self) -> usize { 0 }
+    fn max_args(&self) -> usize { 0 }
+
+    fn execute(&self, shell: &mut Shell, _: &Args) -> bool {
+        shell.exiting = true;
+        true
+    }
+}
+
+pub struct Echo;
+
+impl Command for Echo {
+    fn name(&self) -> &str { "echo" }
+    fn description(&self) -> &str { "Prints its arguments" }
+    fn synopsis(&self) -> &str { "[text...]" }
+    fn examples(&self) -> &str { "echo leader is $leader" }
+    fn min_args(&self) -> usize { 0 }
+    fn max_args(&self) -> usize { usize::MAX }
+    fn raw_args(&self) -> bool { true }
+
+    fn execute(&self, _: &mut Shell, args: &Args) -> bool {
+        out!("{}", args.all().join(" "));
+        true
+    }
+}
@@ -0,0 +1,408 @@
+use ansi_term::Colour::Green;
+use zookeeper::{CreateMode, ZkError};
+
+use interrupt;
+use recipes;
+use shell::{Args, Command, Opt, Shell, report_counter_error, report_error};
+use util::{ensure_path, join_path, parent_path};
+
+
+pub struct CounterGet;
+
+impl Command for CounterGet {
+    fn name(&self) -> &str { "counter_get" }
+    fn description(&self) -> &str { "Gets the value of a shared counter" }
+    fn synopsis(&self) -> &str { "<path>" }
+    fn examples(&self) -> &str { "counter_get /counters/jobs" }
+    fn min_args(&self) -> usize { 1 }
+    fn max_args(&self) -> usize { 1 }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        let zk = fetch_zk!(shell.zk);
+        match recipes::counter_get(zk, args.get(0)) {
+            Ok(value) => {
+                out!("{}", value);
+                true
+            },
+            Err(err) => report_counter_error(err, args.get(0)),
+        }
+    }
+}
+
+// sign is 1 for counter_incr and -1 for counter_decr
+fn counter_add(shell: &mut Shell, args: &Args, sign: i64) -> bool {
+    let amount = match args.optional(1) {
+        Some(amount) => match amount.parse::<i64>() {
+            Ok(amount) => amount,
+            Err(_) => {
+                out!("Bad amount: {}.", amount);
+                return false;
+            }
+        },
+        None => 1
+    };
+
+    let zk = fetch_zk!(shell.zk);
+    match recipes::counter_add(zk, args.get(0), sign * amount, &shell.default_acl) {
+        Ok(value) => {
+            out!("{}", value);
+            true
+        },
+        Err(err) => report_counter_error(err, args.get(0)),
+    }
+}
+
+pub struct CounterIncr;
+
+impl Command for CounterIncr {
+    fn name(&self) -> &str { "counter_incr" }
+    fn description(&self) -> &str { "Increments a shared counter, creating it if needed" }
+    fn synopsis(&self) -> &str { "<path> [amount]" }
+    fn examples(&self) -> &str { "counter_incr /counters/jobs\ncounter_incr /counters/jobs 10" }
+    fn min_args(&self) -> usize { 1 }
+    fn max_args(&self) -> usize { 2 }
+    fn mutates(&self, _: &Args) -> bool { true }
+    fn writes<'a>(&self, args: &'a Args) -> Vec<&'a str> { vec![args.get(0)] }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        counter_add(shell, args, 1)
+    }
+}
+
+pub struct CounterDecr;
+
+impl Command for CounterDecr {
+    fn name(&self) -> &str { "counter_decr" }
+    fn description(&self) -> &str { "Decrements a shared counter, creating it if needed" }
+    fn synopsis(&self) -> &str { "<path> [amount]" }
+    fn examples(&self) -> &str { "counter_decr /counters/jobs 2" }
+    fn min_args(&self) -> usize { 1 }
+    fn max_args(&self) -> usize { 2 }
+    fn mutates(&self, _: &Args) -> bool { true }
+    fn writes<'a>(&self, args: &'a Args) -> Vec<&'a str> { vec![args.get(0)] }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        counter_add(shell, args, -1)
+    }
+}
+
+pub struct Lock;
+
+impl Command for Lock {
+    fn name(&self) -> &str { "lock" }
+    fn description(&self) -> &str {
+        "Acquires a lock (sequential-ephemeral recipe), waiting for it if needed"
+    }
+    fn synopsis(&self) -> &str { "<path> [data]" }
+    fn examples(&self) -> &str { "lock /locks/deploy me" }
+    fn min_args(&self) -> usize { 1 }
+    fn max_args(&self) -> usize { 2 }
+    fn mutates(&self, _: &Args) -> bool { true }
+    fn writes<'a>(&self, args: &'a Args) -> Vec<&'a str> { vec![args.get(0)] }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        let path = args.get(0);
+        if let Some(node) = shell.locks.get(path) {
+            out!("Already holding {} ({}).", path, node);
+            return false;
+        }
+
+        let zk = fetch_zk!(shell.zk);
+        let data = args.optional(1).map_or(vec![], |data| data.as_bytes().to_vec());
+        let node = match recipes::enqueue(zk, path, "lock-", data, &shell.default_acl) {
+            Ok(node) => node,
+            Err(err) => return report_error(err, path),
+        };
+
+        let _guard = interrupt::guard();
+        match recipes::wait_first(zk, path, &*node) {
+            Ok(true) => {
+                out!("Acquired {} ({}).", path, node);
+                shell.locks.insert(path.to_string(), node);
+                true
+            },
+            Ok(false) => {
+                let _ = zk.delete(&*node, -1);
+                false
+            },
+            Err(err) => {
+                let _ = zk.delete(&*node, -1);
+                report_error(err, path)
+            }
+        }
+    }
+}
+
+pub struct Unlock;
+
+impl Command for Unlock {
+    fn name(&self) -> &str { "unlock" }
+    fn description(&self) -> &str { "Releases a lock acquired with lock" }
+    fn synopsis(&self) -> &str { "<path>" }
+    fn examples(&self) -> &str { "unlock /locks/deploy" }
+    fn min_args(&self) -> usize { 1 }
+    fn max_args(&self) -> usize { 1 }
+    fn mutates(&self, _: &Args) -> bool { true }
+    fn writes<'a>(&self, args: &'a Args) -> Vec<&'a str> { vec![args.get(0)] }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        let path = args.get(0);
+        let node = match shell.locks.remove(path) {
+            Some(node) => node,
+            None => {
+                out!("Not holding {}.", path);
+                return false;
+            }
+        };
+
+        let zk = fetch_zk!(shell.zk);
+        match zk.delete(&*node, -1) {
+            Ok(()) | Err(ZkError::NoNode) => true,
+            Err(err) => report_error(err, &*node),
+        }
+    }
+}
+
+pub struct LockHolders;
+
+impl Command for LockHolders {
+    fn name(&self) -> &str { "lock_holders" }
+    fn description(&self) -> &str { "Shows the session holding a lock and those queued behind it" }
+    fn synopsis(&self) -> &str { "<path>" }
+    fn examples(&self) -> &str { "lock_holders /locks/deploy" }
+    fn min_args(&self) -> usize { 1 }
+    fn max_args(&self) -> usize { 1 }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        let zk = fetch_zk!(shell.zk);
+        let path = args.get(0);
+        let contenders = match recipes::contenders(zk, path) {
+            Ok(contenders) => contenders,
+            Err(err) => return report_error(err, path),
+        };
+
+        for (i, contender) in contenders.iter().enumerate() {
+            let node = join_path(path, contender);
+            if let Ok((data, stat)) = zk.get_data(&*node, false) {
+                out!("{:<7} {} 0x{:x} {}",
+                         if i == 0 { "holder" } else { "queued" },
+                         contender, stat.ephemeral_owner, String::from_utf8_lossy(&data[..]));
+            }
+        }
+        true
+    }
+}
+
+pub struct Election;
+
+impl Command for Election {
+    fn name(&self) -> &str { "election" }
+    fn description(&self) -> &str {
+        "Lists an election's candidates by sequence, optionally joining or leaving it"
+    }
+    fn synopsis(&self) -> &str { "<path> [--join [data]] [--leave]" }
+    fn examples(&self) -> &str { "election /election --join candidate-1\nelection /election --leave" }
+    fn min_args(&self) -> usize { 1 }
+    fn max_args(&self) -> usize { 2 }
+    fn mutates(&self, args: &Args) -> bool { args.flag("join") || args.flag("leave") }
+    fn writes<'a>(&self, args: &'a Args) -> Vec<&'a str> { vec![args.get(0)] }
+
+    fn options(&self) -> Vec<Opt> {
+        vec![
+            Opt::flag("j", "join", "become a candidate"),
+            Opt::flag("l", "leave", "stop being a candidate"),
+        ]
+    }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        let zk = fetch_zk!(shell.zk);
+        let path = args.get(0);
+
+        if args.flag("join") {
+            if shell.candidates.contains_key(path) {
+                out!("Already a candidate for {}.", path);
+                return false;
+            }
+            let data = args.optional(1).map_or(vec![], |data| data.as_bytes().to_vec());
+            match recipes::enqueue(zk, path, "n_", data, &shell.default_acl) {
+                Ok(node) => {
+                    shell.candidates.insert(path.to_string(), node);
+                },
+                Err(err) => return report_error(err, path),
+            }
+        } else if args.flag("leave") {
+            match shell.candidates.remove(path) {
+                Some(node) => match zk.delete(&*node, -1) {
+                    Ok(()) | Err(ZkError::NoNode) => (),
+                    Err(err) => return report_error(err, &*node),
+                },
+                None => {
+                    out!("Not a candidate for {}.", path);
+                    return false;
+                }
+            }
+        }
+
+        let contenders = match recipes::contenders(zk, path) {
+            Ok(contenders) => contenders,
+            Err(err) => return report_error(err, path),
+        };
+        let ours = shell.candidates.get(path).map(|node| &node[node.rfind('/').unwrap_or(0) + 1..]);
+
+        for (i, contender) in contenders.iter().enumerate() {
+            let node = join_path(path, contender);
+            if let Ok((data, stat)) = zk.get_data(&*node, false) {
+                let line = format!("{} 0x{:x} {}{}", contender, stat.ephemeral_owner,
+                                   String::from_utf8_lossy(&data[..]),
+                                   if ours == Some(&**contender) { " (us)" } else { "" });
+                if i == 0 {
+                    out!("{} {}", Green.bold().paint("leader"), line);
+                } else {
+                    out!("{:<6} {}", "", line);
+                }
+            }
+        }
+        true
+    }
+}
+
+pub struct BarrierCreate;
+
+impl Command for BarrierCreate {
+    fn name(&self) -> &str { "barrier_create" }
+    fn description(&self) -> &str { "Creates a double barrier for the given number of participants" }
+    fn synopsis(&self) -> &str { "<path> <participants>" }
+    fn examples(&self) -> &str { "barrier_create /barriers/start 3" }
+    fn min_args(&self) -> usize { 2 }
+    fn max_args(&self) -> usize { 2 }
+    fn mutates(&self, _: &Args) -> bool { true }
+    fn writes<'a>(&self, args: &'a Args) -> Vec<&'a str> { vec![args.get(0)] }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        let size = match args.get(1).parse::<usize>() {
+            Ok(size) if size > 0 => size,
+            _ => {
+                out!("Bad number of participants: {}.", args.get(1));
+                return false;
+            }
+        };
+
+        let zk = fetch_zk!(shell.zk);
+        let path = args.get(0);
+        if let Some(parent) = parent_path(path) {
+            if let Err(err) = ensure_path(zk, parent, &shell.default_acl) {
+                return report_error(err, parent);
+            }
+        }
+        let data = size.to_string().into_bytes();
+        match zk.create(path, data, shell.default_acl.clone(), CreateMode::Persistent) {
+            Ok(_) => true,
+            Err(err) => report_error(err, path),
+        }
+    }
+}
+
+// enters the barrier, waiting for everyone else to enter
+pub struct BarrierWait;
+
+impl Command for BarrierWait {
+    fn name(&self) -> &str { "barrier_wait" }
+    fn description(&self) -> &str { "Enters a barrier, waiting until every participant has entered" }
+    fn synopsis(&self) -> &str { "<path>" }
+    fn examples(&self) -> &str { "barrier_wait /barriers/start" }
+    fn min_args(&self) -> usize { 1 }
+    fn max_args(&self) -> usize { 1 }
+    fn mutates(&self, _: &Args) -> bool { true }
+    fn writes<'a>(&self, args: &'a Args) -> Vec<&'a str> { vec![args.get(0)] }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        let path = args.get(0);
+        if shell.barriers.contains_key(path) {
+            out!("Already entered {}.", path);
+            return false;
+        }
+
+        let zk = fetch_zk!(shell.zk);
+        let size = match recipes::counter_get(zk, path) {
+            Ok(size) => size as usize,
+            Err(err) => return report_counter_error(err, path),
+        };
+        let node = match zk.create(&*join_path(path, "p-"), vec![], shell.default_acl.clone(), CreateMode::EphemeralSequential) {
+            Ok(node) => node,
+            Err(err) => return report_error(err, path),
+        };
+
+        let _guard = interrupt::guard();
+        let ready = |children: &Vec<String>| {
+            children.iter().any(|child| child == recipes::BARRIER_READY) ||
+                recipes::barrier_participants(children) >= size
+        };
+        match recipes::wait_children(zk, path, ready) {
+            Ok(true) => {
+                let ready = join_path(path, recipes::BARRIER_READY);
+                let _ = zk.create(&*ready, vec![], shell.default_acl.clone(), CreateMode::Persistent);
+                shell.barriers.insert(path.to_string(), node);
+                true
+            },
+            Ok(false) => {
+                let _ = zk.delete(&*node, -1);
+                false
+            },
+            Err(err) => {
+                let _ = zk.delete(&*node, -1);
+                report_error(err, path)
+            }
+        }
+    }
+}
+
+// leaves the barrier, waiting for everyone else to leave; the last ones out
+// remove it
+pub struct BarrierRemove;
+
+impl Command for BarrierRemove {
+    fn name(&self) -> &str { "barrier_remove" }
+    fn description(&self) -> &str { "Leaves a barrier, waiting until every participant has left" }
+    fn synopsis(&self) -> &str { "<path>" }
+    fn examples(&self) -> &str { "barrier_remove /barriers/start" }
+    fn min_args(&self) -> usize { 1 }
+    fn max_args(&self) -> usize { 1 }
+    fn mutates(&self, _: &Args) -> bool { true }
+    fn writes<'a>(&self, args: &'a Args) -> Vec<&'a str> { vec![args.get(0)] }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        let zk = fetch_zk!(shell.zk);
+        let path = args.get(0);
+        let node = match shell.barriers.remove(path) {
+            Some(node) => node,
+            None => {
+                out!("Not in barrier {}.", path);
+                return false;
+            }
+        };
+        if let Err(err) = zk.delete(&*node, -1) {
+            return report_error(err, &*node);
+        }
+
+        // ready stays until everyone's out, or a participant still waiting
+        // to enter would miss it
+        let _guard = interrupt::guard();
+        let empty = |children: &Vec<String>| recipes::barrier_participants(children) == 0;
+        match recipes::wait_children(zk, path, empty) {
+            Ok(true) => (),
+            Ok(false) => return false,
+            Err(ZkError::NoNode) => return true, // someone else cleaned up
+            Err(err) => return report_error(err, path),
+        }
+
+        // whoever gets here first cleans up
+        match zk.delete(&*join_path(path, recipes::BARRIER_READY), -1) {
+            Ok(()) | Err(ZkError::NoNode) => (),
+            Err(err) => return report_error(err, path),
+        }
+        match zk.delete(path, -1) {
+            Ok(()) | Err(ZkError::NoNode) => true,
+            Err(err) => report_error(err, path),
+        }
+    }
+}
@@ -0,0 +1,185 @@
+use zookeeper::Stat;
+
+use client::ZkClient;
+use shell::{Args, Registry};
+use util::join_path;
+
+mod control;
+mod coordination;
+mod nodes;
+mod server;
+mod session;
+mod tree;
+mod watches;
+
+
+pub fn register_all(registry: &mut Registry) {
+    registry.register(nodes::Get);
+    registry.register(nodes::DumpData);
+    registry.register(nodes::MGet);
+    registry.register(nodes::Set);
+    registry.register(nodes::Append);
+    registry.register(nodes::GetToFile);
+    registry.register(nodes::SetFromFile);
+    registry.register(nodes::DiffFile);
+    registry.register(nodes::Ls);
+    registry.register(nodes::Create);
+    registry.register(nodes::Rm);
+    registry.register(nodes::Rmr);
+    registry.register(nodes::Mv);
+    registry.register(nodes::SetAcl);
+    registry.register(tree::Mirror);
+    registry.register(nodes::Touch);
+    registry.register(nodes::Exists);
+    registry.register(nodes::DecodeZxid);
+    registry.register(session::Connect);
+    registry.register(session::Disconnect);
+    registry.register(session::Reconnect);
+    registry.register(session::CurrentServer);
+    registry.register(session::SetTimeout);
+    registry.register(session::AddAuth);
+    registry.register(session::Whoami);
+    registry.register(session::CloseSession);
+    registry.register(control::Loop);
+    registry.register(control::Time);
+    registry.register(control::Events);
+    registry.register(control::Stats);
+    registry.register(control::Help);
+    registry.register(control::Apropos);
+    registry.register(control::SetVar);
+    registry.register(control::Var);
+    registry.register(control::Vars);
+    registry.register(control::Sleep);
+    registry.register(control::Echo);
+    registry.register(control::Exit);
+    registry.register(tree::Benchmark);
+    registry.register(tree::Fill);
+    registry.register(tree::CreateFromTemplate);
+    registry.register(tree::EphemeralEndpoints);
+    registry.register(tree::EphemeralsBySession);
+    registry.register(tree::Summary);
+    registry.register(tree::SizeHistogram);
+    registry.register(tree::Biggest);
+    registry.register(tree::Find);
+    registry.register(tree::Age);
+    registry.register(tree::ChildCount);
+    registry.register(tree::Backup);
+    registry.register(tree::Restore);
+    registry.register(server::Admin);
+    registry.register(server::Ping);
+    registry.register(server::Isro);
+    registry.register(server::Monitor);
+    registry.register(server::Mntr);
+    registry.register(server::ServerConfig);
+    registry.register(server::ServerWatches);
+    registry.register(server::Sessions);
+    registry.register(watches::Tail);
+    registry.register(watches::Record);
+    registry.register(watches::WaitExists);
+    registry.register(watches::WaitValue);
+    registry.register(watches::OnChange);
+    registry.register(watches::WatchChildren);
+    registry.register(watches::StressWatches);
+    registry.register(coordination::CounterGet);
+    registry.register(coordination::CounterIncr);
+    registry.register(coordination::CounterDecr);
+    registry.register(coordination::Lock);
+    registry.register(coordination::Unlock);
+    registry.register(coordination::LockHolders);
+    registry.register(coordination::Election);
+    registry.register(coordination::BarrierCreate);
+    registry.register(coordination::BarrierWait);
+    registry.register(coordination::BarrierRemove);
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum SortKey {
+    Name,
+    Mtime,
+    Size,
+    Children,
+}
+
+impl SortKey {
+    fn parse(key: &str) -> Option<SortKey> {
+        match key {
+            "name" => Some(SortKey::Name),
+            "mtime" => Some(SortKey::Mtime),
+            "size" => Some(SortKey::Size),
+            "children" => Some(SortKey::Children),
+            _ => None
+        }
+    }
+}
+
+// --sort & --reverse, None (after complaining) if the key is bad
+fn sort_opts(args: &Args) -> Option<(SortKey, bool)> {
+    let key = match args.value("sort") {
+        Some(key) => match SortKey::parse(&*key) {
+            Some(key) => key,
+            None => {
+                out!("Bad value for --sort: {}, expected name, mtime, size or children.", key);
+                return None;
+            }
+        },
+        None => SortKey::Name
+    };
+    Some((key, args.flag("reverse")))
+}
+
+// a child's name, its stat and how many children it has
+type Entry = (String, Stat, usize);
+
+fn child_entries(zk: &ZkClient, parent: &str, children: Vec<String>) -> Vec<Entry> {
+    children.into_iter().filter_map(|child| {
+        match zk.exists(&*join_path(parent, &*child), false) {
+            Ok(stat) => {
+                let count = stat.num_children as usize;
+                Some((child, stat, count))
+            },
+            Err(_) => None, // it's gone
+        }
+    }).collect()
+}
+
+fn sort_entries(entries: &mut Vec<Entry>, key: SortKey, reverse: bool) {
+    match key {
+        SortKey::Name => entries.sort_by(|a, b| a.0.cmp(&b.0)),
+        SortKey::Mtime => entries.sort_by(|a, b| a.1.mtime.cmp(&b.1.mtime)),
+        SortKey::Size => entries.sort_by(|a, b| a.1.data_length.cmp(&b.1.data_length)),
+        SortKey::Children => entries.sort_by(|a, b| a.2.cmp(&b.2)),
+    }
+    if reverse {
+        entries.reverse();
+    }
+}
+
+// whether name can be a shell variable's, $name has to make sense
+fn valid_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
+// i.e.: min/avg/max = 0.210/0.344/1.020 ms, samples mustn't be empty
+fn latencies(samples: &[f64]) -> String {
+    let min = samples.iter().cloned().fold(::std::f64::MAX, f64::min);
+    let max = samples.iter().cloned().fold(0.0, f64::max);
+    let avg = samples.iter().fold(0.0, |sum, ms| sum + ms) / samples.len() as f64;
+    format!("min/avg/max = {:.3}/{:.3}/{:.3} ms", min, avg, max)
+}
+
+// rows left aligned in columns as wide as their widest cell
+fn columns(rows: &[Vec<String>]) -> Vec<String> {
+    let mut widths: Vec<usize> = vec![];
+    for row in rows {
+        for (i, cell) in row.iter().enumerate() {
+            if i == widths.len() {
+                widths.push(0);
+            }
+            widths[i] = widths[i].max(cell.chars().count());
+        }
+    }
+    rows.iter().map(|row| {
+        let cells: Vec<String> = row.iter().enumerate().map(|(i, cell)| format!("{:<1$}", cell, widths[i])).collect();
+        cells.join("  ").trim_right().to_string()
+    }).collect()
+}
@@ -0,0 +1,1387 @@
+use std::fs::{self, File};
+use std::io::stdin;
+use std::io::stdout;
+use std::io::{Read, Write};
+use std::str;
+use std::sync::Arc;
+use std::thread;
+
+use ansi_term::Colour::{Blue, Cyan, Green, Purple, Red};
+use regex::Regex;
+use serde_json::{self, Value};
+use zookeeper::{CreateMode, Stat, ZkError, ZkResult};
+
+use acl;
+use client::ZkClient;
+use crypto;
+use diff;
+use gzip;
+use recipes;
+use shell::{Args, Command, Format, Opt, Shell, Syntax, confirm_unless_forced, format_time, output, report_error, stat_json};
+use traversal::{descendants, expand_glob, walk};
+use util::{data_summary, ensure_path, is_glob, join_path, parent_path, parse_zxid, split_zxid};
+
+use super::{Entry, SortKey, child_entries, sort_entries, sort_opts, valid_name};
+
+
+// like ls -l: dataLength, numChildren, version, ephemeral, mtime & name
+fn long_line(entry: &Entry) -> String {
+    let (ref child, ref stat, _) = *entry;
+    format!("{:>8} {:>6} {:>6} {} {} {}",
+            stat.data_length, stat.num_children, stat.version,
+            if stat.ephemeral_owner != 0 { "e" } else { "-" },
+            format_time(stat.mtime), child)
+}
+
+fn format_zxid(zxid: i64) -> String {
+    let (epoch, counter) = split_zxid(zxid);
+    format!("0x{:x} (epoch {}, counter {})", zxid, epoch, counter)
+}
+
+fn stat_lines(stat: &Stat) -> Vec<String> {
+    vec![
+        format!("czxid: {}", format_zxid(stat.czxid)),
+        format!("mzxid: {}", format_zxid(stat.mzxid)),
+        format!("pzxid: {}", format_zxid(stat.pzxid)),
+        format!("ctime: {}", format_time(stat.ctime)),
+        format!("mtime: {}", format_time(stat.mtime)),
+        format!("version: {}", stat.version),
+        format!("cversion: {}", stat.cversion),
+        format!("aversion: {}", stat.aversion),
+        format!("ephemeralOwner: 0x{:x}", stat.ephemeral_owner),
+        format!("dataLength: {}", stat.data_length),
+        format!("numChildren: {}", stat.num_children),
+    ]
+}
+
+// prints page_size items at a time, asking before moving on to the next page
+fn print_paged<T, F>(items: &[T], page_size: usize, render: F) where F: Fn(&[T]) -> Vec<String> {
+    for (i, page) in items.chunks(page_size).enumerate() {
+        if i > 0 {
+            let mut answer = String::new();
+            print!("--More-- ({}/{}, q to quit) ", i * page_size, items.len());
+            let _ = stdout().flush();
+            if stdin().read_line(&mut answer).is_err() || answer.trim() == "q" {
+                return;
+            }
+        }
+
+        for line in render(page) {
+            out!("{}", line);
+        }
+    }
+}
+
+// optional trailing arguments, i.e.: [watch] or [version]
+fn bool_arg(args: &Args, i: usize) -> bool {
+    args.optional(i).map_or(false, |arg| arg.to_lowercase() == "true")
+}
+
+fn version_arg(args: &Args, i: usize) -> i32 {
+    args.optional(i).and_then(|arg| arg.parse::<i32>().ok()).unwrap_or(-1)
+}
+
+// the value to write: validated and gzipped if asked to (or if the session
+// does), then encrypted with --encrypt. None after complaining, i.e.: if
+// it's malformed or too big
+fn encode(shell: &Shell, args: &Args, data: Vec<u8>) -> Option<Vec<u8>> {
+    if !valid(shell, args, &data[..]) {
+        return None;
+    }
+    let data = if shell.compress || args.flag("compress") { gzip::compress(&data[..]) } else { data };
+    let data = if args.flag("encrypt") {
+        match encryption_key(shell).map(|key| crypto::encrypt(key, &data[..])) {
+            Some(Ok(encrypted)) => encrypted,
+            Some(Err(err)) => {
+                out!("Failed to encrypt: {}", err);
+                return None;
+            },
+            None => return None,
+        }
+    } else {
+        data
+    };
+    if fits(shell, data.len()) { Some(data) } else { None }
+}
+
+// whether data is well-formed as per --validate (or the session's), empty
+// values always are: they're what znodes that only hold children have
+fn valid(shell: &Shell, args: &Args, data: &[u8]) -> bool {
+    let syntax = match args.value("validate") {
+        Some(ref syntax) if syntax == "none" => None,
+        Some(syntax) => match Syntax::parse(&*syntax) {
+            Some(syntax) => Some(syntax),
+            None => {
+                out!("Bad value for --validate: {}, expected json, yaml or none.", syntax);
+                return false;
+            }
+        },
+        None => shell.validate,
+    };
+    let syntax = match syntax {
+        Some(syntax) if !data.is_empty() => syntax,
+        _ => return true,
+    };
+    let ret = str::from_utf8(data).map_err(|_| "not UTF-8".to_string()).and_then(|text| syntax.check(text));
+    if let Err(err) = ret {
+        out!("Refusing to write malformed {}: {}", syntax.name(), err);
+        return false;
+    }
+    true
+}
+
+// whether a value of len bytes fits under --max-buffer-size, complains if not
+fn fits(shell: &Shell, len: usize) -> bool {
+    if len > shell.max_buffer_size {
+        out!("The value is {} bytes, over the {} byte limit: try --compress, or raise --max-buffer-size \
+              if the servers' jute.maxbuffer allows it.", len, shell.max_buffer_size);
+        return false;
+    }
+    true
+}
+
+// values this big may be what a server dropped the connection over
+const LARGE_VALUE: usize = 256 * 1024;
+
+// like report_error, but servers drop the connection rather than reject
+// values over their jute.maxbuffer, so say so
+fn report_write_error(error: ZkError, path: &str, len: usize) -> bool {
+    match error {
+        ZkError::ConnectionLoss if len >= LARGE_VALUE => {
+            out!("Lost the connection writing {} bytes to {}: servers drop it for values over their \
+                  jute.maxbuffer, try --compress.", len, path);
+            false
+        },
+        err => report_error(err, path),
+    }
+}
+
+fn encryption_key(shell: &Shell) -> Option<&crypto::Key> {
+    if shell.encryption_key.is_none() {
+        out!("No encryption key, set encryption_key in the config or ${}.", crypto::KEY_VAR);
+    }
+    shell.encryption_key.as_ref()
+}
+
+// the value as written, decompressed if it's gzipped
+fn decode(data: Vec<u8>) -> Vec<u8> {
+    if gzip::is_gzip(&data[..]) {
+        if let Ok(decompressed) = gzip::decompress(&data[..], gzip::MAX_VALUE_SIZE) {
+            return decompressed;
+        }
+    }
+    data
+}
+
+// objects & arrays indented, and in colours unless the output is being
+// captured; None for anything else
+fn pretty_json(data: &str) -> Option<String> {
+    match serde_json::from_str::<Value>(data) {
+        Ok(ref value) if value.is_object() || value.is_array() => {
+            Some(if output::capturing() { format!("{:#}", value) } else { highlighted(value, 0) })
+        },
+        _ => None,
+    }
+}
+
+// laid out like serde_json's pretty printing, with keys, strings, numbers &
+// literals in colours
+fn highlighted(value: &Value, indent: usize) -> String {
+    let pad = "  ".repeat(indent + 1);
+    match *value {
+        Value::Array(ref values) if !values.is_empty() => {
+            let items: Vec<String> = values.iter().map(|value| format!("{}{}", pad, highlighted(value, indent + 1))).collect();
+            format!("[\n{}\n{}]", items.join(",\n"), &pad[2..])
+        },
+        Value::Object(ref map) if !map.is_empty() => {
+            let items: Vec<String> = map.iter().map(|(key, value)| {
+                format!("{}{}: {}", pad, Blue.paint(&*Value::String(key.clone()).to_string()), highlighted(value, indent + 1))
+            }).collect();
+            format!("{{\n{}\n{}}}", items.join(",\n"), &pad[2..])
+        },
+        Value::String(_) => Green.paint(&*value.to_string()).to_string(),
+        Value::Number(_) => Cyan.paint(&*value.to_string()).to_string(),
+        Value::Null | Value::Bool(_) => Purple.paint(&*value.to_string()).to_string(),
+        // [] & {}
+        _ => value.to_string(),
+    }
+}
+
+pub struct Get;
+
+impl Command for Get {
+    fn name(&self) -> &str { "get" }
+    fn description(&self) -> &str { "Gets the znode's value, followed by its size and version" }
+    fn synopsis(&self) -> &str { "<path> [watch] [--decrypt] [--raw]" }
+    fn examples(&self) -> &str { "get /config\nget /config true\nget /secrets/db --decrypt\nget /config --raw" }
+    fn min_args(&self) -> usize { 1 }
+    fn max_args(&self) -> usize { 2 }
+
+    fn options(&self) -> Vec<Opt> {
+        vec![
+            Opt::flag("d", "decrypt", "decrypt the value with the encryption key"),
+            Opt::flag("r", "raw", "the exact bytes: not decompressed, JSON not pretty-printed, no size or version"),
+        ]
+    }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        let zk = fetch_zk!(shell.zk);
+        let path = args.get(0);
+        let raw = args.flag("raw");
+        let decode = |bytes| if raw { bytes } else { decode(bytes) };
+        let ret = match zk.get_data(path, bool_arg(args, 1)) {
+            Ok((bytes, _)) if crypto::is_encrypted(&bytes[..]) && !args.flag("decrypt") => {
+                out!("{} is encrypted, use get --decrypt.", path);
+                return false;
+            },
+            Ok((bytes, stat)) if args.flag("decrypt") => {
+                let key = match encryption_key(shell) {
+                    Some(key) => key,
+                    None => return false,
+                };
+                match crypto::decrypt(key, &bytes[..]) {
+                    Ok(bytes) => Ok((decode(bytes), stat)),
+                    Err(err) => {
+                        out!("Failed to decrypt {}: {}", path, err);
+                        return false;
+                    }
+                }
+            },
+            ret => ret.map(|(bytes, stat)| (decode(bytes), stat)),
+        };
+
+        match ret {
+            Ok((bytes, stat)) if shell.format == Format::Json => {
+                let data = String::from_utf8_lossy(&bytes[..]);
+                out!("{}", json!({"path": path, "data": data, "stat": stat_json(&stat)}));
+                true
+            },
+            Ok((bytes, stat)) => {
+                let data = String::from_utf8_lossy(&bytes[..]);
+                match pretty_json(&*data) {
+                    Some(pretty) if !raw => out!("{}", pretty),
+                    _ => out!("{}", data),
+                }
+                // so a value the terminal cuts short isn't taken for a short
+                // one; not when captured (i.e.: by var), that's the value
+                if !raw && !output::capturing() {
+                    out!("{}", data_summary(&bytes[..], stat.data_length, stat.version));
+                }
+                true
+            },
+            Err(err) => report_error(err, path),
+        }
+    }
+}
+
+const DUMP_SEPARATOR: &'static str = "--";
+
+pub struct DumpData;
+
+impl Command for DumpData {
+    fn name(&self) -> &str { "dump_data" }
+    fn description(&self) -> &str { "Prints the path, size and value of every znode under path (whose path contains pattern)" }
+    fn synopsis(&self) -> &str { "<path> [pattern] [--max-depth N]" }
+    fn examples(&self) -> &str { "dump_data /config\ndump_data /services endpoint --max-depth 2" }
+    fn min_args(&self) -> usize { 1 }
+    fn max_args(&self) -> usize { 2 }
+
+    fn options(&self) -> Vec<Opt> {
+        vec![Opt::opt("", "max-depth", "N", "how many levels to descend")]
+    }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        let max_depth = match args.value("max-depth").map(|depth| depth.parse::<usize>()) {
+            None => None,
+            Some(Ok(depth)) => Some(depth),
+            Some(Err(_)) => {
+                out!("Bad value for --max-depth: {}", args.value("max-depth").unwrap());
+                return false;
+            }
+        };
+        let zk = fetch_zk!(shell.zk);
+        let path = args.get(0);
+        let pattern = args.optional(1).unwrap_or("");
+        let json = shell.format == Format::Json;
+        let ret = walk(zk, path, max_depth, &mut |node, _| {
+            if !node.contains(pattern) {
+                return;
+            }
+            let (bytes, stat) = match zk.get_data(node, false) {
+                Ok(read) => read,
+                Err(_) => return, // it's gone
+            };
+            // dumping secrets in bulk is what --decrypt is there to prevent
+            let data = if crypto::is_encrypted(&bytes[..]) {
+                "(encrypted)".to_string()
+            } else {
+                String::from_utf8_lossy(&decode(bytes)[..]).into_owned()
+            };
+            if json {
+                out!("{}", json!({"path": node, "size": stat.data_length, "data": data}));
+                return;
+            }
+            out!("{} ({} bytes)", node, stat.data_length);
+            if !data.is_empty() {
+                out!("{}", pretty_json(&*data).unwrap_or(data));
+            }
+            out!("{}", DUMP_SEPARATOR);
+        });
+        match ret {
+            Ok(()) => true,
+            Err(err) => report_error(err, path),
+        }
+    }
+}
+
+// the data argument, - for the lines typed after the command
+fn data_arg(shell: &mut Shell, args: &Args) -> Option<Vec<u8>> {
+    match args.get(1) {
+        "-" => shell.take_value().map(|value| value.into_bytes()),
+        data => Some(data.as_bytes().to_vec()),
+    }
+}
+
+// how many of mget's reads are in flight at once
+const MGET_CONCURRENCY: usize = 8;
+
+pub struct MGet;
+
+impl Command for MGet {
+    fn name(&self) -> &str { "mget" }
+    fn description(&self) -> &str { "Gets the values of several znodes (or of those matching globs) at once" }
+    fn synopsis(&self) -> &str { "<path|pattern> [path|pattern...]" }
+    fn examples(&self) -> &str { "mget /config/db /config/cache\nmget '/services/*/endpoint'\nmget /config/host-[0-9]" }
+    fn min_args(&self) -> usize { 1 }
+    fn max_args(&self) -> usize { usize::MAX }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        let zk: Arc<ZkClient> = match shell.zk {
+            Some(ref zk) => zk.clone(),
+            None => {
+                out!("Not connected.");
+                return false;
+            }
+        };
+        let mut paths = vec![];
+        for i in 0..args.len() {
+            match expand_glob(&*zk, args.get(i)) {
+                Ok(ref expanded) if expanded.is_empty() => out!("Nothing matches {}.", args.get(i)),
+                Ok(expanded) => paths.extend(expanded),
+                Err(err) => return report_error(err, args.get(i)),
+            }
+        }
+        if paths.is_empty() {
+            return false;
+        }
+
+        // the reads go out together, a chunk per thread; the printing stays
+        // here since that's where the output is captured
+        let chunk = (paths.len() + MGET_CONCURRENCY - 1) / MGET_CONCURRENCY;
+        let workers: Vec<_> = paths.chunks(chunk).map(|chunk| {
+            let zk = zk.clone();
+            let chunk = chunk.to_vec();
+            thread::spawn(move || chunk.iter().map(|path| zk.get_data(path, false).map(|(bytes, _)| bytes)).collect::<Vec<_>>())
+        }).collect();
+        let mut values: Vec<ZkResult<Vec<u8>>> = vec![];
+        for (worker, chunk) in workers.into_iter().zip(paths.chunks(chunk)) {
+            match worker.join() {
+                Ok(read) => values.extend(read),
+                Err(_) => values.extend(chunk.iter().map(|_| Err(ZkError::SystemError))),
+            }
+        }
+
+        let mut status = true;
+        let mut json = vec![];
+        for (path, value) in paths.iter().zip(values) {
+            let data = match value {
+                Ok(ref bytes) if crypto::is_encrypted(&bytes[..]) => Some("(encrypted)".to_string()),
+                Ok(bytes) => Some(String::from_utf8_lossy(&decode(bytes)[..]).into_owned()),
+                Err(ZkError::NoNode) => {
+                    status = false;
+                    None
+                },
+                Err(err) => {
+                    report_error(err, path);
+                    status = false;
+                    continue;
+                }
+            };
+            if shell.format == Format::Json {
+                json.push(json!({"path": path, "data": data}));
+                continue;
+            }
+            match data {
+                None => out!("{}: does not exist", path),
+                Some(ref data) if data.contains('\n') => out!("{}:\n{}", path, data),
+                Some(data) => out!("{}: {}", path, data),
+            }
+        }
+        if shell.format == Format::Json {
+            out!("{}", Value::Array(json));
+        }
+        status
+    }
+}
+
+pub struct Set;
+
+impl Command for Set {
+    fn name(&self) -> &str { "set" }
+    fn description(&self) -> &str { "Sets the znode's value" }
+    fn synopsis(&self) -> &str { "<path> <data> [version] [--create] [--parents] | --cas <path> <expected> <new> [--retry N]" }
+    fn examples(&self) -> &str {
+        "set /config 'new value'\nset /config stale 3\nset /config -\nset /config - --validate yaml\nset /a/b/c v --create --parents\nset --cas /leader host1 host2 --retry 5"
+    }
+    fn min_args(&self) -> usize { 2 }
+    fn max_args(&self) -> usize { 3 }
+    fn mutates(&self, _: &Args) -> bool { true }
+    fn reads_value(&self) -> bool { true }
+    fn destroys<'a>(&self, args: &'a Args) -> Option<(&'a str, bool)> { Some((args.get(0), false)) }
+
+    fn options(&self) -> Vec<Opt> {
+        vec![
+            Opt::flag("c", "create", "create the znode if it doesn't exist"),
+            Opt::flag("p", "parents", "with --create, also create missing parents"),
+            Opt::flag("z", "compress", "gzip the value"),
+            Opt::opt("", "validate", "SYNTAX", "refuse the value unless it's well-formed json or yaml (none skips the session's check)"),
+            Opt::flag("e", "encrypt", "encrypt the value with the encryption key"),
+            Opt::flag("", "cas", "only set it if its value is <expected>"),
+            Opt::opt("", "retry", "N", "with --cas, retry up to N times when it's written in between (default 10)"),
+        ]
+    }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        if args.flag("cas") {
+            return compare_and_set(shell, args);
+        }
+        if args.flag("retry") {
+            out!("--retry only goes with --cas.");
+            return false;
+        }
+
+        let data = match data_arg(shell, args) {
+            Some(data) => data,
+            None => return false
+        };
+        let zk = fetch_zk!(shell.zk);
+        let path = args.get(0);
+        let data = match encode(shell, args, data) {
+            Some(data) => data,
+            None => return false
+        };
+        set_value(shell, zk, args, path, data, version_arg(args, 2))
+    }
+}
+
+// sets path to data, with --create (and --parents) creating it (and its
+// ancestors) if it doesn't exist
+fn set_value(shell: &Shell, zk: &ZkClient, args: &Args, path: &str, data: Vec<u8>, version: i32) -> bool {
+    let len = data.len();
+    // only keep a copy around if it may be needed
+    let (ret, data) = if args.flag("create") {
+        (zk.set_data(path, data.clone(), version), Some(data))
+    } else {
+        (zk.set_data(path, data, version), None)
+    };
+
+    match (ret, data) {
+        (Ok(_), _) => true,
+        (Err(ZkError::NoNode), Some(data)) => {
+            if args.flag("parents") {
+                if let Some(parent) = parent_path(path) {
+                    if let Err(err) = ensure_path(zk, parent, &shell.default_acl) {
+                        return report_error(err, parent);
+                    }
+                }
+            }
+            match zk.create(path, data, shell.default_acl.clone(), CreateMode::Persistent) {
+                Ok(_) => true,
+                Err(err) => report_write_error(err, path, len),
+            }
+        },
+        (Err(err), _) => report_write_error(err, path, len),
+    }
+}
+
+pub struct GetToFile;
+
+impl Command for GetToFile {
+    fn name(&self) -> &str { "get_to_file" }
+    fn description(&self) -> &str { "Writes the znode's value to a local file, byte for byte" }
+    fn synopsis(&self) -> &str { "<path> <file>" }
+    fn examples(&self) -> &str { "get_to_file /config/big big.json" }
+    fn min_args(&self) -> usize { 2 }
+    fn max_args(&self) -> usize { 2 }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        let zk = fetch_zk!(shell.zk);
+        let (path, file) = (args.get(0), args.get(1));
+        let data = match zk.get_data(path, false) {
+            Ok((data, _)) => data,
+            Err(err) => return report_error(err, path),
+        };
+        match File::create(file).and_then(|mut f| f.write_all(&data[..])) {
+            Ok(()) => {
+                out!("Wrote {} bytes to {}.", data.len(), file);
+                true
+            },
+            Err(err) => {
+                out!("Failed to write {}: {}", file, err);
+                false
+            }
+        }
+    }
+}
+
+pub struct SetFromFile;
+
+impl Command for SetFromFile {
+    fn name(&self) -> &str { "set_from_file" }
+    fn description(&self) -> &str { "Sets the znode's value to a local file's contents" }
+    fn synopsis(&self) -> &str { "<path> <file> [version] [--create] [--parents] [--compress] [--encrypt]" }
+    fn examples(&self) -> &str { "set_from_file /config/big big.json\nset_from_file /config/big big.json --create --compress" }
+    fn min_args(&self) -> usize { 2 }
+    fn max_args(&self) -> usize { 3 }
+    fn mutates(&self, _: &Args) -> bool { true }
+    fn destroys<'a>(&self, args: &'a Args) -> Option<(&'a str, bool)> { Some((args.get(0), false)) }
+
+    fn options(&self) -> Vec<Opt> {
+        vec![
+            Opt::flag("c", "create", "create the znode if it doesn't exist"),
+            Opt::flag("p", "parents", "with --create, also create missing parents"),
+            Opt::flag("z", "compress", "gzip the value"),
+            Opt::opt("", "validate", "SYNTAX", "refuse the value unless it's well-formed json or yaml (none skips the session's check)"),
+            Opt::flag("e", "encrypt", "encrypt the value with the encryption key"),
+        ]
+    }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        let file = args.get(1);
+        // refuse oversized files before reading them, unless compressing
+        // may bring them under the limit
+        let len = match fs::metadata(file) {
+            Ok(metadata) => metadata.len() as usize,
+            Err(err) => {
+                out!("Failed to read {}: {}", file, err);
+                return false;
+            }
+        };
+        if !shell.compress && !args.flag("compress") && !fits(shell, len) {
+            return false;
+        }
+
+        let mut data = Vec::with_capacity(len);
+        if let Err(err) = File::open(file).and_then(|mut f| f.read_to_end(&mut data)) {
+            out!("Failed to read {}: {}", file, err);
+            return false;
+        }
+        let data = match encode(shell, args, data) {
+            Some(data) => data,
+            None => return false
+        };
+
+        let zk = fetch_zk!(shell.zk);
+        set_value(shell, zk, args, args.get(0), data, version_arg(args, 2))
+    }
+}
+
+const CAS_RETRIES: usize = 10;
+
+// set --cas <path> <expected> <new>
+fn compare_and_set(shell: &mut Shell, args: &Args) -> bool {
+    if args.len() != 3 || args.flag("create") {
+        out!("Expected parameters: --cas <path> <expected> <new> [--retry N]");
+        return false;
+    }
+    let retries = match args.parse_value::<usize>("retry", CAS_RETRIES) {
+        Some(retries) => retries,
+        None => return false
+    };
+
+    let zk = fetch_zk!(shell.zk);
+    let new = match encode(shell, args, args.get(2).as_bytes().to_vec()) {
+        Some(new) => new,
+        None => return false
+    };
+    let (path, expected) = (args.get(0), args.get(1).as_bytes());
+    let mut current = vec![];
+    let ret = recipes::update(zk, path, retries, |data| {
+        current = decode(data.to_vec());
+        if current == expected { Some(new.clone()) } else { None }
+    });
+
+    match ret {
+        Ok(true) => true,
+        Ok(false) => {
+            out!("{} is {}, not {}; left as is.", path, String::from_utf8_lossy(&current[..]), args.get(1));
+            false
+        },
+        Err(ZkError::BadVersion) => {
+            out!("{} kept changing, gave up after {} retries.", path, retries);
+            false
+        },
+        Err(err) => report_error(err, path),
+    }
+}
+
+const DIFF_CONTEXT: usize = 3;
+
+pub struct DiffFile;
+
+impl Command for DiffFile {
+    fn name(&self) -> &str { "diff_file" }
+    fn description(&self) -> &str {
+        "Prints a unified diff from the znode's value to a local file's contents: what set_from_file would change"
+    }
+    fn synopsis(&self) -> &str { "<path> <local-file> [--context N] [--decrypt]" }
+    fn examples(&self) -> &str { "diff_file /config ./config.json\ndiff_file /config ./config.json --context 10" }
+    fn min_args(&self) -> usize { 2 }
+    fn max_args(&self) -> usize { 2 }
+
+    fn options(&self) -> Vec<Opt> {
+        vec![
+            Opt::opt("U", "context", "N", "lines of context around each change (default 3)"),
+            Opt::flag("d", "decrypt", "decrypt the value with the encryption key"),
+        ]
+    }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        let context = match args.parse_value::<usize>("context", DIFF_CONTEXT) {
+            Some(context) => context,
+            None => return false
+        };
+        let (path, file) = (args.get(0), args.get(1));
+        let mut local = vec![];
+        if let Err(err) = File::open(file).and_then(|mut f| f.read_to_end(&mut local)) {
+            out!("Failed to read {}: {}", file, err);
+            return false;
+        }
+
+        let zk = fetch_zk!(shell.zk);
+        let value = match zk.get_data(path, false) {
+            Ok((bytes, _)) if crypto::is_encrypted(&bytes[..]) && !args.flag("decrypt") => {
+                out!("{} is encrypted, use diff_file --decrypt.", path);
+                return false;
+            },
+            Ok((bytes, _)) if args.flag("decrypt") => {
+                let key = match encryption_key(shell) {
+                    Some(key) => key,
+                    None => return false,
+                };
+                match crypto::decrypt(key, &bytes[..]) {
+                    Ok(bytes) => decode(bytes),
+                    Err(err) => {
+                        out!("Failed to decrypt {}: {}", path, err);
+                        return false;
+                    }
+                }
+            },
+            Ok((bytes, _)) => decode(bytes),
+            Err(err) => return report_error(err, path),
+        };
+
+        let (old, new) = match (str::from_utf8(&value[..]), str::from_utf8(&local[..])) {
+            (Ok(old), Ok(new)) => (old, new),
+            _ => {
+                out!("{}", if value == local { "No differences." } else { "Binary values differ." });
+                return true;
+            }
+        };
+        let old: Vec<&str> = old.lines().collect();
+        let new: Vec<&str> = new.lines().collect();
+        let lines = diff::unified(&old[..], &new[..], path, file, context);
+        if lines.is_empty() {
+            out!("No differences.");
+        }
+        for (i, line) in lines.iter().enumerate() {
+            if output::capturing() || i < 2 {
+                out!("{}", line);
+            } else if line.starts_with('-') {
+                out!("{}", Red.paint(&**line));
+            } else if line.starts_with('+') {
+                out!("{}", Green.paint(&**line));
+            } else if line.starts_with('@') {
+                out!("{}", Cyan.paint(&**line));
+            } else {
+                out!("{}", line);
+            }
+        }
+        true
+    }
+}
+
+pub struct Append;
+
+impl Command for Append {
+    fn name(&self) -> &str { "append" }
+    fn description(&self) -> &str { "Appends to the znode's value" }
+    fn synopsis(&self) -> &str { "<path> <data> [--separator SEP | --newline]" }
+    fn examples(&self) -> &str { "append /hosts host4 --separator ,\nappend /ops/log 'restarted zk3' --newline" }
+    fn min_args(&self) -> usize { 2 }
+    fn max_args(&self) -> usize { 2 }
+    fn mutates(&self, _: &Args) -> bool { true }
+    fn destroys<'a>(&self, args: &'a Args) -> Option<(&'a str, bool)> { Some((args.get(0), false)) }
+
+    fn options(&self) -> Vec<Opt> {
+        vec![
+            Opt::opt("s", "separator", "SEP", "put SEP in between, unless the value is empty"),
+            Opt::flag("n", "newline", "like --separator, with a newline"),
+        ]
+    }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        let separator = match (args.value("separator"), args.flag("newline")) {
+            (Some(_), true) => {
+                out!("Either --separator or --newline, not both.");
+                return false;
+            },
+            (Some(separator), false) => separator,
+            (None, true) => "\n".to_string(),
+            (None, false) => String::new(),
+        };
+
+        let zk = fetch_zk!(shell.zk);
+        let path = args.get(0);
+        let ret = recipes::update(zk, path, CAS_RETRIES, |data| {
+            if crypto::is_encrypted(data) {
+                return None;
+            }
+            // gzipped values stay gzipped
+            let gzipped = gzip::is_gzip(data);
+            let mut data = decode(data.to_vec());
+            if !data.is_empty() {
+                data.extend(separator.as_bytes().iter().cloned());
+            }
+            data.extend(args.get(1).as_bytes().iter().cloned());
+            Some(if gzipped || shell.compress { gzip::compress(&data[..]) } else { data })
+        });
+
+        match ret {
+            Ok(true) => true,
+            Ok(false) => {
+                out!("{} is encrypted, it can't be appended to.", path);
+                false
+            },
+            Err(ZkError::BadVersion) => {
+                out!("{} kept changing, gave up after {} retries.", path, CAS_RETRIES);
+                false
+            },
+            Err(err) => report_error(err, path),
+        }
+    }
+}
+
+// ls' --match, --ephemeral-only & --persistent-only
+struct ChildFilter {
+    pattern: Option<Regex>,
+    ephemeral: Option<bool>,
+}
+
+impl ChildFilter {
+    // None (after complaining) if they don't make sense
+    fn parse(args: &Args) -> Option<ChildFilter> {
+        let pattern = match args.value("match").map(|pattern| Regex::new(&*pattern)) {
+            None => None,
+            Some(Ok(pattern)) => Some(pattern),
+            Some(Err(err)) => {
+                out!("Bad value for --match: {}", err);
+                return None;
+            }
+        };
+        let ephemeral = match (args.flag("ephemeral-only"), args.flag("persistent-only")) {
+            (true, true) => {
+                out!("--ephemeral-only and --persistent-only don't go together.");
+                return None;
+            },
+            (true, false) => Some(true),
+            (false, true) => Some(false),
+            (false, false) => None,
+        };
+        Some(ChildFilter { pattern: pattern, ephemeral: ephemeral })
+    }
+
+    // whether name (which is at path) is listed, gone znodes aren't
+    fn keeps(&self, zk: &ZkClient, name: &str, path: &str) -> bool {
+        if !self.pattern.as_ref().map_or(true, |pattern| pattern.is_match(name)) {
+            return false;
+        }
+        match self.ephemeral {
+            Some(ephemeral) => zk.exists(path, false).map_or(false, |stat| (stat.ephemeral_owner != 0) == ephemeral),
+            None => true,
+        }
+    }
+}
+
+pub struct Ls;
+
+impl Command for Ls {
+    fn name(&self) -> &str { "ls" }
+    fn description(&self) -> &str { "Lists a znode's children" }
+    fn synopsis(&self) -> &str {
+        "<path> [watch] [-l] [-R [--max-depth N]] [--sort name|mtime|size|children] [--reverse] [--page-size N] \
+         [--match REGEX] [--ephemeral-only | --persistent-only]"
+    }
+    fn examples(&self) -> &str {
+        "ls /\nls /services -l --sort mtime --reverse\nls /services -R --max-depth 2\nls /brokers/ids --match '^1[0-9]$'\nls /locks --ephemeral-only"
+    }
+    fn min_args(&self) -> usize { 1 }
+    fn max_args(&self) -> usize { 2 }
+
+    fn options(&self) -> Vec<Opt> {
+        vec![
+            Opt::flag("l", "long", "one child per line, with stat columns"),
+            Opt::opt("", "sort", "KEY", "sort children by name, mtime, size or children"),
+            Opt::flag("r", "reverse", "reverse the sort order"),
+            Opt::flag("R", "recursive", "list every descendant path, one per line"),
+            Opt::opt("", "max-depth", "N", "with -R, how many levels to descend"),
+            Opt::opt("", "page-size", "N", "print N children at a time, one per line"),
+            Opt::opt("m", "match", "REGEX", "only the children whose name (with -R, path) matches REGEX"),
+            Opt::flag("", "ephemeral-only", "only the ephemeral children"),
+            Opt::flag("", "persistent-only", "only the persistent children"),
+        ]
+    }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        let (key, reverse) = match sort_opts(args) {
+            Some(sort) => sort,
+            None => return false
+        };
+        let sorted = args.flag("sort") || reverse;
+        let page_size = match args.parse_value::<usize>("page-size", 0) {
+            Some(size) => size,
+            None => return false
+        };
+        let long = args.flag("long");
+        let filter = match ChildFilter::parse(args) {
+            Some(filter) => filter,
+            None => return false
+        };
+
+        let zk = fetch_zk!(shell.zk);
+        let path = args.get(0);
+
+        if args.flag("recursive") {
+            let max_depth = match args.value("max-depth").map(|depth| depth.parse::<usize>()) {
+                Some(Ok(depth)) => Some(depth),
+                Some(Err(_)) => {
+                    out!("Bad value for --max-depth.");
+                    return false;
+                },
+                None => None
+            };
+            let json = shell.format == Format::Json;
+            let mut nodes = vec![];
+            let ret = walk(zk, path, max_depth, &mut |node, depth| {
+                if depth == 0 || !filter.keeps(zk, node, node) {
+                    return;
+                }
+                if json {
+                    nodes.push(node.to_string());
+                } else {
+                    out!("{}", node);
+                }
+            });
+            return match ret {
+                Ok(()) => {
+                    if json {
+                        out!("{}", json!(nodes));
+                    }
+                    true
+                },
+                Err(err) => report_error(err, path),
+            };
+        }
+
+        // the children are read once, then filtered here
+        let ret = zk.get_children(path, bool_arg(args, 1)).map(|children| {
+            children.into_iter().filter(|child| filter.keeps(zk, child, &*join_path(path, child))).collect::<Vec<String>>()
+        });
+
+        match ret {
+            Ok(children) if shell.format == Format::Json => {
+                let mut entries = child_entries(zk, path, children);
+                sort_entries(&mut entries, key, reverse);
+                let children = entries.iter().map(|entry| {
+                    if long {
+                        json!({"name": entry.0, "stat": stat_json(&entry.1)})
+                    } else {
+                        json!(entry.0)
+                    }
+                }).collect();
+                out!("{}", Value::Array(children));
+                true
+            },
+            Ok(children) => {
+                if key != SortKey::Name {
+                    let mut entries = child_entries(zk, path, children);
+                    sort_entries(&mut entries, key, reverse);
+                    let render = |entries: &[Entry]| -> Vec<String> {
+                        entries.iter().map(|entry| if long { long_line(entry) } else { entry.0.clone() }).collect()
+                    };
+                    if page_size > 0 {
+                        print_paged(&entries, page_size, render);
+                    } else if long {
+                        print_paged(&entries, entries.len().max(1), render);
+                    } else {
+                        out!("{}", render(&entries).join(" "));
+                    }
+                    return true;
+                }
+
+                let mut children = children;
+                if sorted || long {
+                    children.sort();
+                    if reverse {
+                        children.reverse();
+                    }
+                }
+
+                // stats are only fetched for the page being shown
+                let render = |children: &[String]| -> Vec<String> {
+                    if long {
+                        child_entries(zk, path, children.to_vec()).iter().map(long_line).collect()
+                    } else {
+                        children.to_vec()
+                    }
+                };
+                if page_size > 0 {
+                    print_paged(&children, page_size, render);
+                } else if long {
+                    print_paged(&children, children.len().max(1), render);
+                } else {
+                    out!("{}", children.join(" "));
+                }
+                true
+            },
+            Err(err) => report_error(err, path),
+        }
+    }
+}
+
+pub struct Create;
+
+impl Command for Create {
+    fn name(&self) -> &str { "create" }
+    fn description(&self) -> &str { "Creates a znode with the given value, printing its path (with the sequence number)" }
+    fn synopsis(&self) -> &str { "<path> <data> [-e] [-s] [--acl SPEC] [--var NAME]" }
+    fn examples(&self) -> &str {
+        "create /config value\ncreate /config -\ncreate /workers/worker- '' -e -s --var me\ncreate /secret x --acl digest:admin:pw:cdrwa,read-only"
+    }
+    fn min_args(&self) -> usize { 2 }
+    fn max_args(&self) -> usize { 2 }
+    fn mutates(&self, _: &Args) -> bool { true }
+    fn writes<'a>(&self, args: &'a Args) -> Vec<&'a str> { vec![args.get(0)] }
+    fn reads_value(&self) -> bool { true }
+
+    fn options(&self) -> Vec<Opt> {
+        vec![
+            Opt::flag("e", "ephemeral", "deleted when the session ends"),
+            Opt::flag("s", "sequential", "with a sequence number appended to the name"),
+            Opt::opt("", "acl", "SPEC", "instead of the default ACL, i.e.: creator-all or ip:10.0.0.0/8:r"),
+            Opt::opt("", "var", "NAME", "also keep the created path in the shell variable NAME"),
+            Opt::flag("z", "compress", "gzip the value"),
+            Opt::opt("", "validate", "SYNTAX", "refuse the value unless it's well-formed json or yaml (none skips the session's check)"),
+            Opt::flag("", "encrypt", "encrypt the value with the encryption key"),
+        ]
+    }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        let var = args.value("var");
+        if let Some(ref name) = var {
+            if !valid_name(name) {
+                out!("Bad variable name: {}", name);
+                return false;
+            }
+        }
+        let mode = match (args.flag("ephemeral"), args.flag("sequential")) {
+            (false, false) => CreateMode::Persistent,
+            (false, true) => CreateMode::PersistentSequential,
+            (true, false) => CreateMode::Ephemeral,
+            (true, true) => CreateMode::EphemeralSequential,
+        };
+        let acl = match args.value("acl") {
+            Some(spec) => match acl::parse(&*spec) {
+                Ok(acl) => acl,
+                Err(err) => {
+                    out!("{}", err);
+                    return false;
+                }
+            },
+            None => shell.default_acl.clone()
+        };
+
+        let data = match data_arg(shell, args) {
+            Some(data) => data,
+            None => return false
+        };
+        let path = args.get(0);
+        let data = match encode(shell, args, data) {
+            Some(data) => data,
+            None => return false
+        };
+
+        let len = data.len();
+        let created = match fetch_zk!(shell.zk).create(path, data, acl, mode) {
+            Ok(created) => created,
+            Err(err) => return report_write_error(err, path, len),
+        };
+        out!("{}", created);
+        if let Some(name) = var {
+            shell.set_variable(&*name, &*created);
+        }
+        true
+    }
+}
+
+pub struct Rm;
+
+impl Command for Rm {
+    fn name(&self) -> &str { "rm" }
+    fn description(&self) -> &str { "Delete a znode, or every znode a glob matches" }
+    fn synopsis(&self) -> &str { "<path|glob> [version] [--force]" }
+    fn examples(&self) -> &str { "rm /config\nrm /config 3\nrm '/locks/lock-*'\nrm '/locks/lock-*' --force" }
+    fn min_args(&self) -> usize { 1 }
+    fn max_args(&self) -> usize { 2 }
+    fn mutates(&self, _: &Args) -> bool { true }
+
+    // a glob may match anything under its first wildcard's parent
+    fn destroys<'a>(&self, args: &'a Args) -> Option<(&'a str, bool)> {
+        let path = args.get(0);
+        match path.find(|c| "*?[".contains(c)).map(|idx| path[..idx].rfind('/').unwrap_or(0)) {
+            Some(0) => Some(("/", true)),
+            Some(end) => Some((&path[..end], true)),
+            None => Some((path, false)),
+        }
+    }
+
+    fn options(&self) -> Vec<Opt> {
+        vec![Opt::flag("f", "force", "don't ask before deleting what a glob matches")]
+    }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        let zk = fetch_zk!(shell.zk);
+        let path = args.get(0);
+        if !is_glob(path) {
+            return match zk.delete(path, version_arg(args, 1)) {
+                Ok(()) => true,
+                Err(err) => report_error(err, path),
+            };
+        }
+
+        if args.len() > 1 {
+            out!("A version can't go with a glob, each znode has its own.");
+            return false;
+        }
+        let paths = match expand_glob(zk, path) {
+            Ok(paths) => paths,
+            Err(err) => return report_error(err, path),
+        };
+        if paths.is_empty() {
+            out!("Nothing matches {}.", path);
+            return false;
+        }
+        if !confirm_unless_forced(args, &*format!("This will delete {} znode(s) matching {}.", paths.len(), path)) {
+            return false;
+        }
+
+        for node in &paths {
+            match zk.delete(node, -1) {
+                // a component of the glob that was taken as is, or gone since
+                Ok(()) | Err(ZkError::NoNode) => (),
+                Err(err) => return report_error(err, node),
+            }
+        }
+        true
+    }
+}
+
+pub struct Rmr;
+
+impl Command for Rmr {
+    fn name(&self) -> &str { "rmr" }
+    fn description(&self) -> &str { "Recursively deletes a znode and its descendants" }
+    fn synopsis(&self) -> &str { "<path> [--force]" }
+    fn examples(&self) -> &str { "rmr /old-app\nrmr /old-app --force" }
+    fn min_args(&self) -> usize { 1 }
+    fn max_args(&self) -> usize { 1 }
+    fn mutates(&self, _: &Args) -> bool { true }
+    fn destroys<'a>(&self, args: &'a Args) -> Option<(&'a str, bool)> { Some((args.get(0), true)) }
+
+    fn options(&self) -> Vec<Opt> {
+        vec![Opt::flag("f", "force", "don't ask for confirmation")]
+    }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        let zk = fetch_zk!(shell.zk);
+        let path = args.get(0);
+        let paths = match descendants(zk, path) {
+            Ok(paths) => paths,
+            Err(err) => return report_error(err, path),
+        };
+
+        if !confirm_unless_forced(args, &*format!("This will delete {} znode(s) under {}.", paths.len(), path)) {
+            return false;
+        }
+
+        // children go before their parents
+        for path in paths.iter().rev() {
+            match zk.delete(path, -1) {
+                Ok(()) | Err(ZkError::NoNode) => (),
+                Err(err) => return report_error(err, path),
+            }
+        }
+        true
+    }
+}
+
+pub struct Mv;
+
+impl Command for Mv {
+    fn name(&self) -> &str { "mv" }
+    fn description(&self) -> &str { "Moves (renames) a znode, or a whole subtree with --recursive" }
+    fn synopsis(&self) -> &str { "<src> <dst> [--recursive]" }
+    fn examples(&self) -> &str { "mv /config /config.old\nmv /services/a /archive/a --recursive" }
+    fn min_args(&self) -> usize { 2 }
+    fn max_args(&self) -> usize { 2 }
+    fn mutates(&self, _: &Args) -> bool { true }
+    fn destroys<'a>(&self, args: &'a Args) -> Option<(&'a str, bool)> { Some((args.get(0), args.flag("recursive"))) }
+    fn writes<'a>(&self, args: &'a Args) -> Vec<&'a str> { vec![args.get(0), args.get(1)] }
+
+    fn options(&self) -> Vec<Opt> {
+        vec![Opt::flag("r", "recursive", "move the znode's children too")]
+    }
+
+    // the client library has no multi, so this is copy, verify, then
+    // delete (each source only if it hasn't changed since it was read)
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        let zk = fetch_zk!(shell.zk);
+        let (src, dst) = (args.get(0), args.get(1));
+        if src == "/" || dst == src || dst.starts_with(&*format!("{}/", src)) {
+            out!("Can't move {} to {}.", src, dst);
+            return false;
+        }
+
+        let paths = if args.flag("recursive") {
+            match descendants(zk, src) {
+                Ok(paths) => paths,
+                Err(err) => return report_error(err, src),
+            }
+        } else {
+            vec![src.to_string()]
+        };
+
+        // (path, data, acl, version), read before anything is written
+        let mut nodes = vec![];
+        for path in &paths {
+            let (data, stat) = match zk.get_data(path, false) {
+                Ok(node) => node,
+                Err(err) => return report_error(err, path),
+            };
+            let acl = match zk.get_acl(path) {
+                Ok((acl, _)) => acl,
+                Err(err) => return report_error(err, path),
+            };
+            if stat.ephemeral_owner != 0 {
+                out!("{} is ephemeral, its copy would belong to this session.", path);
+                return false;
+            }
+            if stat.num_children > 0 && !args.flag("recursive") {
+                out!("{} has children, use --recursive to move them too.", path);
+                return false;
+            }
+            nodes.push((path.clone(), data, acl, stat.version));
+        }
+
+        let target = |path: &str| format!("{}{}", dst, &path[src.len()..]);
+        let mut created = vec![];
+        let undo = |created: &Vec<String>| for path in created.iter().rev() {
+            let _ = zk.delete(path, -1);
+        };
+        for &(ref path, ref data, ref acl, _) in &nodes {
+            let copy = target(path);
+            match zk.create(&*copy, data.clone(), acl.clone(), CreateMode::Persistent) {
+                Ok(_) => created.push(copy),
+                Err(err) => {
+                    undo(&created);
+                    return report_error(err, &*copy);
+                }
+            }
+        }
+        for &(ref path, ref data, _, _) in &nodes {
+            match zk.get_data(&*target(path), false) {
+                Ok((ref copied, _)) if copied == data => (),
+                _ => {
+                    out!("The copy of {} doesn't match, leaving {} as it was.", path, src);
+                    undo(&created);
+                    return false;
+                }
+            }
+        }
+
+        // children before their parents
+        for &(ref path, _, _, version) in nodes.iter().rev() {
+            match zk.delete(path, version) {
+                Ok(()) => (),
+                Err(ZkError::BadVersion) | Err(ZkError::NotEmpty) => {
+                    out!("{} changed while moving it, it's been copied to {} but not deleted.", path, target(path));
+                    return false;
+                },
+                Err(err) => return report_error(err, path),
+            }
+        }
+        true
+    }
+}
+
+pub struct SetAcl;
+
+impl Command for SetAcl {
+    fn name(&self) -> &str { "setacl" }
+    fn description(&self) -> &str { "Replaces the znode's ACL, or its whole subtree's with --recursive" }
+    fn synopsis(&self) -> &str { "<path> <acl> [--recursive] [--force]" }
+    fn examples(&self) -> &str { "setacl /config read-only\nsetacl /app digest:admin:pw:cdrwa,world:anyone:r --recursive" }
+    fn min_args(&self) -> usize { 2 }
+    fn max_args(&self) -> usize { 2 }
+    fn mutates(&self, _: &Args) -> bool { true }
+    fn writes<'a>(&self, args: &'a Args) -> Vec<&'a str> { vec![args.get(0)] }
+
+    fn options(&self) -> Vec<Opt> {
+        vec![
+            Opt::flag("r", "recursive", "the znode's descendants too"),
+            Opt::flag("f", "force", "don't ask before changing a whole subtree"),
+        ]
+    }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        let zk = fetch_zk!(shell.zk);
+        let path = args.get(0);
+        // same entries as create --acl and default_acl take
+        let acl = match acl::parse(args.get(1)) {
+            Ok(acl) => acl,
+            Err(err) => {
+                out!("{}", err);
+                return false;
+            }
+        };
+        let paths = if args.flag("recursive") {
+            let paths = match descendants(zk, path) {
+                Ok(paths) => paths,
+                Err(err) => return report_error(err, path),
+            };
+            if !confirm_unless_forced(args, &*format!("This will set the ACL of {} znode(s) under {}.", paths.len(), path)) {
+                return false;
+            }
+            paths
+        } else {
+            vec![path.to_string()]
+        };
+
+        for node in &paths {
+            match zk.set_acl(node, acl.clone(), -1) {
+                Ok(_) => (),
+                // a descendant deleted since it was listed
+                Err(ZkError::NoNode) if node != path => (),
+                Err(err) => return report_error(err, node),
+            }
+        }
+        true
+    }
+}
+
+pub struct Touch;
+
+impl Command for Touch {
+    fn name(&self) -> &str { "touch" }
+    fn description(&self) -> &str { "Creates an empty znode, or bumps its mtime and version if it exists" }
+    fn synopsis(&self) -> &str { "<path>" }
+    fn examples(&self) -> &str { "touch /heartbeat" }
+    fn min_args(&self) -> usize { 1 }
+    fn max_args(&self) -> usize { 1 }
+    fn mutates(&self, _: &Args) -> bool { true }
+    fn writes<'a>(&self, args: &'a Args) -> Vec<&'a str> { vec![args.get(0)] }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        let zk = fetch_zk!(shell.zk);
+        let path = args.get(0);
+
+        // rewriting the same data bumps mtime & version; if someone else
+        // wrote in between (BadVersion) it has been bumped anyway
+        match zk.get_data(path, false) {
+            Ok((data, stat)) => match zk.set_data(path, data, stat.version) {
+                Ok(_) | Err(ZkError::BadVersion) => true,
+                Err(err) => report_error(err, path),
+            },
+            Err(ZkError::NoNode) => {
+                match zk.create(path, vec![], shell.default_acl.clone(), CreateMode::Persistent) {
+                    Ok(_) | Err(ZkError::NodeExists) => true,
+                    Err(err) => report_error(err, path),
+                }
+            },
+            Err(err) => report_error(err, path),
+        }
+    }
+}
+
+pub struct Exists;
+
+impl Command for Exists {
+    fn name(&self) -> &str { "exists" }
+    fn aliases(&self) -> &'static [&'static str] { &["stat"] }
+    fn description(&self) -> &str { "Gets the znode's stat information" }
+    fn synopsis(&self) -> &str { "<path> [watch] [-q]" }
+    fn examples(&self) -> &str { "exists /config\nstat /config\nexists /config -q && echo there" }
+    fn min_args(&self) -> usize { 1 }
+    fn max_args(&self) -> usize { 2 }
+
+    fn options(&self) -> Vec<Opt> {
+        vec![Opt::flag("q", "quiet", "print nothing but errors (i.e.: not connected), the status says whether it exists")]
+    }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        let zk = fetch_zk!(shell.zk);
+        let path = args.get(0);
+        let ret = zk.exists(path, bool_arg(args, 1));
+
+        match ret {
+            Ok(_) if args.flag("quiet") => true,
+            // an outage isn't an answer, report_error says what went wrong
+            Err(ZkError::NoNode) if args.flag("quiet") => false,
+            Ok(stat) if shell.format == Format::Json => {
+                out!("{}", stat_json(&stat));
+                true
+            },
+            Ok(stat) => {
+                for line in stat_lines(&stat) {
+                    out!("{}", line);
+                }
+                true
+            },
+            Err(err) => report_error(err, path),
+        }
+    }
+}
+
+pub struct DecodeZxid;
+
+impl Command for DecodeZxid {
+    fn name(&self) -> &str { "decode_zxid" }
+    fn description(&self) -> &str { "Splits a zxid (hex or decimal) into its epoch and counter" }
+    fn synopsis(&self) -> &str { "<zxid>" }
+    fn examples(&self) -> &str { "decode_zxid 0x300000a2f\ndecode_zxid 12884904495" }
+    fn min_args(&self) -> usize { 1 }
+    fn max_args(&self) -> usize { 1 }
+
+    fn execute(&self, _: &mut Shell, args: &Args) -> bool {
+        match parse_zxid(args.get(0)) {
+            Some(zxid) => {
+                out!("{}", format_zxid(zxid));
+                true
+            },
+            None => {
+                out!("Bad zxid: {}", args.get(0));
+                false
+            }
+        }
+    }
+}
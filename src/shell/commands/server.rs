@@ -0,0 +1,681 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io;
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+use ansi_term::Colour::Yellow;
+use serde_json::{self, Value};
+use time;
+
+use flw;
+use hosts::ConnectString;
+use http;
+use interrupt;
+use prometheus;
+use shell::{Args, Command, Opt, Shell, output, timestamp};
+
+use super::{columns, latencies};
+
+
+const ADMIN_PORT: u16 = 8080;
+
+pub struct Admin;
+
+impl Command for Admin {
+    fn name(&self) -> &str { "admin" }
+    fn description(&self) -> &str { "Runs an AdminServer command (ZooKeeper 3.5+), i.e.: stat, monitor or conf" }
+    fn synopsis(&self) -> &str { "<host[:port]> <command>" }
+    fn examples(&self) -> &str { "admin localhost stat\nadmin zk1:8081 monitor" }
+    fn min_args(&self) -> usize { 2 }
+    fn max_args(&self) -> usize { 2 }
+
+    fn execute(&self, _: &mut Shell, args: &Args) -> bool {
+        let host = if args.get(0).contains(':') {
+            args.get(0).to_string()
+        } else {
+            format!("{}:{}", args.get(0), ADMIN_PORT)
+        };
+        let url = format!("http://{}/commands/{}", host, args.get(1));
+
+        let (status, body) = match http::get(&*url) {
+            Ok(response) => response,
+            Err(err) => {
+                out!("Failed to reach {}: {}", host, err);
+                return false;
+            }
+        };
+
+        // errors come back as JSON too, with an "error" field
+        match serde_json::from_str::<Value>(&*body) {
+            Ok(value) => out!("{:#}", value),
+            Err(_) => out!("{}", body),
+        }
+        status == 200
+    }
+}
+
+const PINGS: usize = 4;
+
+// what's being pinged
+enum Target {
+    Session,
+    Servers(Vec<String>),
+}
+
+pub struct Ping;
+
+impl Command for Ping {
+    fn name(&self) -> &str { "ping" }
+    fn description(&self) -> &str { "Measures round trip latency to a server, every server or through the session" }
+    fn synopsis(&self) -> &str { "[host[:port] | --all] [count]" }
+    fn examples(&self) -> &str { "ping\nping 10\nping zk2:2181\nping --all 10" }
+    fn min_args(&self) -> usize { 0 }
+    fn max_args(&self) -> usize { 2 }
+
+    fn options(&self) -> Vec<Opt> {
+        vec![Opt::flag("a", "all", "ping every server in the connect string (with ruok)")]
+    }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        let is_count = |arg: &str| arg.parse::<usize>().is_ok();
+        let (target, count) = match (args.flag("all"), args.optional(0)) {
+            (true, _) if args.len() > 1 => {
+                out!("Expected parameters: {}", self.synopsis());
+                return false;
+            },
+            (true, count) => match ConnectString::parse(&*shell.current_hosts()) {
+                Ok(hosts) => (Target::Servers(hosts.addresses()), count),
+                Err(err) => {
+                    out!("No servers to ping: {}", err);
+                    return false;
+                }
+            },
+            (false, Some(count)) if args.len() == 1 && is_count(count) => (Target::Session, Some(count)),
+            (false, Some(host)) => match ConnectString::parse(host) {
+                Ok(ref server) if server.servers.len() == 1 && server.chroot.is_none() => {
+                    (Target::Servers(vec![server.to_string()]), args.optional(1))
+                },
+                _ => {
+                    out!("Bad server: {}", host);
+                    return false;
+                }
+            },
+            (false, None) => (Target::Session, None),
+        };
+        let count = match count.map(|count| count.parse::<usize>()) {
+            None => PINGS,
+            Some(Ok(count)) if count > 0 => count,
+            Some(_) => {
+                out!("Bad count: {}", count.unwrap());
+                return false;
+            }
+        };
+
+        let _guard = interrupt::guard();
+        match target {
+            Target::Session => {
+                let zk = fetch_zk!(shell.zk);
+                report("session", &*ping(count, &mut || zk.exists("/", false).is_ok()))
+            },
+            Target::Servers(servers) => {
+                let mut ok = true;
+                for server in servers {
+                    // each ruok is a new connection, so that's included too
+                    let samples = ping(count, &mut || flw::send(&*server, "ruok").map_or(false, |reply| reply == "imok"));
+                    ok = report(&*server, &*samples) && ok;
+                }
+                ok
+            },
+        }
+    }
+}
+
+// the round trip times in ms, None for the failed ones
+fn ping<F>(count: usize, f: &mut F) -> Vec<Option<f64>> where F: FnMut() -> bool {
+    let mut samples = vec![];
+    for _ in 0..count {
+        if interrupt::interrupted() {
+            break;
+        }
+        let start = time::precise_time_ns();
+        let ok = f();
+        let elapsed = (time::precise_time_ns() - start) as f64 / 1000000.0;
+        samples.push(if ok { Some(elapsed) } else { None });
+    }
+    samples
+}
+
+fn report(name: &str, samples: &[Option<f64>]) -> bool {
+    let ok: Vec<f64> = samples.iter().filter_map(|&sample| sample).collect();
+    if ok.is_empty() {
+        out!("{}: no replies ({} sent)", name, samples.len());
+        return false;
+    }
+    out!("{}: {}/{} replies, {}", name, ok.len(), samples.len(), latencies(&*ok));
+    true
+}
+
+pub struct Isro;
+
+impl Command for Isro {
+    fn name(&self) -> &str { "isro" }
+    fn description(&self) -> &str { "Shows which servers are serving read-only, i.e.: cut off from the quorum" }
+    fn synopsis(&self) -> &str { "[host[:port][,...] | --all]" }
+    fn examples(&self) -> &str { "isro\nisro zk3:2181" }
+    fn min_args(&self) -> usize { 0 }
+    fn max_args(&self) -> usize { 1 }
+
+    fn options(&self) -> Vec<Opt> {
+        vec![Opt::flag("a", "all", "every server in the connect string (the default)")]
+    }
+
+    // fails unless every server answered and none of them is read-only
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        let servers = match servers(self, shell, args) {
+            Some(servers) => servers,
+            None => return false,
+        };
+
+        let mut read_only = vec![];
+        let mut read_write = 0;
+        for server in &servers {
+            match flw::send(&*server, "isro").map(|reply| reply.trim().to_string()) {
+                Ok(ref reply) if reply == "ro" => {
+                    out!("{}: {}", server, attention("read-only"));
+                    read_only.push(server.clone());
+                },
+                Ok(ref reply) if reply == "rw" => {
+                    out!("{}: read-write", server);
+                    read_write += 1;
+                },
+                // i.e.: isro isn't whitelisted
+                Ok(reply) => out!("{}: unexpected reply {:?}", server, reply),
+                Err(err) => out!("{}: unreachable ({})", server, err),
+            }
+        }
+
+        if !read_only.is_empty() && read_write > 0 {
+            out!("\n{}", attention(&*format!("Partitioned: {} of {} servers are read-only ({}).",
+                                               read_only.len(), servers.len(), read_only.join(", "))));
+        } else if !read_only.is_empty() {
+            out!("\n{}", attention("Every server that answered is read-only, there's no quorum."));
+        }
+        read_only.is_empty() && read_write == servers.len()
+    }
+}
+
+// in yellow, unless it's going somewhere other than the terminal
+fn attention(text: &str) -> String {
+    if output::capturing() {
+        text.to_string()
+    } else {
+        Yellow.bold().paint(text).to_string()
+    }
+}
+
+// mntr keys, alongside their column headers
+const MONITORED: [(&'static str, &'static str); 7] = [
+    ("zk_avg_latency", "AVG LAT"),
+    ("zk_max_latency", "MAX LAT"),
+    ("zk_outstanding_requests", "OUTSTANDING"),
+    ("zk_znode_count", "ZNODES"),
+    ("zk_watch_count", "WATCHES"),
+    ("zk_num_alive_connections", "CONNS"),
+    ("zk_packets_received", "RECEIVED"),
+];
+
+pub struct Monitor;
+
+impl Command for Monitor {
+    fn name(&self) -> &str { "monitor" }
+    fn description(&self) -> &str { "Shows mntr stats for every server, refreshed every interval secs (like top)" }
+    fn synopsis(&self) -> &str { "[interval]" }
+    fn examples(&self) -> &str { "monitor\nmonitor 5\nmonitor 1 --count 10" }
+    fn min_args(&self) -> usize { 0 }
+    fn max_args(&self) -> usize { 1 }
+
+    fn options(&self) -> Vec<Opt> {
+        vec![Opt::opt("n", "count", "N", "stop after N refreshes")]
+    }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        let interval = match args.optional(0).map(|secs| secs.parse::<u64>()) {
+            None => 2,
+            Some(Ok(secs)) if secs > 0 => secs,
+            Some(_) => {
+                out!("Bad interval: {}", args.get(0));
+                return false;
+            }
+        };
+        let count = match args.value("count").map(|count| count.parse::<u64>()) {
+            None => None,
+            Some(Ok(count)) => Some(count),
+            Some(Err(_)) => {
+                out!("Bad value for --count: {}", args.value("count").unwrap());
+                return false;
+            }
+        };
+        let servers = match ConnectString::parse(&*shell.current_hosts()) {
+            Ok(hosts) => hosts.addresses(),
+            Err(err) => {
+                out!("No servers to monitor: {}", err);
+                return false;
+            }
+        };
+
+        let _guard = interrupt::guard();
+        let mut refreshes = 0;
+        loop {
+            let mut table = vec![];
+            let mut header = vec!["SERVER".to_string(), "STATE".to_string()];
+            header.extend(MONITORED.iter().map(|&(_, title)| title.to_string()));
+            table.push(header);
+            for server in &servers {
+                let mut row = vec![server.clone()];
+                match flw::send(&*server, "mntr") {
+                    Ok(output) => {
+                        let stats = flw::parse_mntr(&*output);
+                        let get = |key: &str| stats.get(key).cloned().unwrap_or("-".to_string());
+                        row.push(get("zk_server_state"));
+                        row.extend(MONITORED.iter().map(|&(key, _)| get(key)));
+                    },
+                    // i.e.: down, or mntr isn't whitelisted
+                    Err(err) => row.push(format!("unreachable ({})", err)),
+                }
+                table.push(row);
+            }
+
+            // clears the screen, unless it's going somewhere else
+            if !output::capturing() {
+                print!("\x1b[2J\x1b[H");
+            }
+            out!("{}  (every {}s, Ctrl-C to stop)\n", timestamp(), interval);
+            for line in columns(&table) {
+                out!("{}", line);
+            }
+
+            refreshes += 1;
+            if count.map_or(false, |count| refreshes >= count) {
+                return true;
+            }
+            // sleeps in small steps, so Ctrl-C doesn't wait for the interval
+            for _ in 0..interval * 10 {
+                if interrupt::interrupted() {
+                    return true;
+                }
+                thread::sleep(Duration::from_millis(100));
+            }
+        }
+    }
+}
+
+pub struct Mntr;
+
+impl Command for Mntr {
+    fn name(&self) -> &str { "mntr" }
+    fn description(&self) -> &str { "Shows every server's mntr stats, or serves them to Prometheus" }
+    fn synopsis(&self) -> &str { "[host[:port][,...] | --all] [--prometheus] [--serve-metrics PORT]" }
+    fn examples(&self) -> &str { "mntr\nmntr zk1:2181 --prometheus\nmntr --all --serve-metrics 9141" }
+    fn min_args(&self) -> usize { 0 }
+    fn max_args(&self) -> usize { 1 }
+
+    fn options(&self) -> Vec<Opt> {
+        vec![
+            Opt::flag("a", "all", "every server in the connect string (the default)"),
+            Opt::flag("p", "prometheus", "in Prometheus' exposition format"),
+            Opt::opt("", "serve-metrics", "PORT", "answer scrapes on PORT's /metrics until Ctrl-C, asking the servers each time"),
+        ]
+    }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        let port = match args.value("serve-metrics").map(|port| port.parse::<u16>()) {
+            None => None,
+            Some(Ok(port)) => Some(port),
+            Some(Err(_)) => {
+                out!("Bad value for --serve-metrics: {}", args.value("serve-metrics").unwrap());
+                return false;
+            }
+        };
+        let servers = match servers(self, shell, args) {
+            Some(servers) => servers,
+            None => return false,
+        };
+        if let Some(port) = port {
+            return serve_metrics(port, &servers);
+        }
+
+        if args.flag("prometheus") {
+            let stats = scrape(&servers);
+            let answered = stats.iter().all(|&(_, ref stats)| stats.is_some());
+            out!("{}", prometheus::exposition(&*stats).trim_right());
+            return answered;
+        }
+
+        let mut answered = true;
+        for server in &servers {
+            match flw::send(&*server, "mntr") {
+                Ok(output) => {
+                    if servers.len() > 1 {
+                        out!("{}:", server);
+                    }
+                    out!("{}", output.trim_right());
+                },
+                Err(err) => {
+                    out!("{}: unreachable ({})", server, err);
+                    answered = false;
+                },
+            }
+        }
+        answered
+    }
+}
+
+// every server's mntr stats, None for the ones that didn't answer
+fn scrape(servers: &[String]) -> Vec<(String, Option<HashMap<String, String>>)> {
+    servers.iter().map(|server| {
+        (server.clone(), flw::send(&*server, "mntr").ok().map(|output| flw::parse_mntr(&*output)))
+    }).collect()
+}
+
+fn serve_metrics(port: u16, servers: &[String]) -> bool {
+    let listener = match TcpListener::bind(("0.0.0.0", port)) {
+        Ok(listener) => listener,
+        Err(err) => {
+            out!("Can't listen on port {}: {}", port, err);
+            return false;
+        }
+    };
+    // so Ctrl-C isn't stuck behind accept
+    if let Err(err) = listener.set_nonblocking(true) {
+        out!("Can't listen on port {}: {}", port, err);
+        return false;
+    }
+    let address = listener.local_addr().map(|addr| addr.to_string()).unwrap_or(format!("0.0.0.0:{}", port));
+    out!("Serving the metrics of {} on http://{}/metrics (Ctrl-C to stop)", servers.join(", "), address);
+
+    let _guard = interrupt::guard();
+    while !interrupt::interrupted() {
+        match listener.accept() {
+            Ok((mut stream, _)) => {
+                // a scraper that went away isn't a reason to stop
+                if let Err(err) = answer_scrape(&mut stream, servers) {
+                    info!("failed to answer a scrape: {}", err);
+                }
+            },
+            Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => thread::sleep(Duration::from_millis(100)),
+            Err(err) => {
+                out!("Failed to accept a connection: {}", err);
+                return false;
+            },
+        }
+    }
+    true
+}
+
+fn answer_scrape(stream: &mut TcpStream, servers: &[String]) -> io::Result<()> {
+    try!(stream.set_nonblocking(false));
+    let (method, path) = try!(http::read_request(stream));
+    let path = path.split('?').next().unwrap_or("").to_string();
+    if method != "GET" {
+        return http::respond(stream, 405, "text/plain", "Only GET is supported.\n");
+    }
+    if path != "/metrics" && path != "/" {
+        return http::respond(stream, 404, "text/plain", "Try /metrics.\n");
+    }
+    http::respond(stream, 200, prometheus::CONTENT_TYPE, &*prometheus::exposition(&*scrape(servers)))
+}
+
+// the servers in the first argument, or in the connect string with --all
+// or without it, None (after complaining) if there's none
+fn servers(cmd: &Command, shell: &Shell, args: &Args) -> Option<Vec<String>> {
+    if args.flag("all") && args.len() > 0 {
+        out!("Expected parameters: {}", cmd.synopsis());
+        return None;
+    }
+    let hosts = args.optional(0).map(|hosts| hosts.to_string()).unwrap_or(shell.current_hosts());
+    match ConnectString::parse(&*hosts) {
+        Ok(hosts) => Some(hosts.addresses()),
+        Err(err) => {
+            out!("No servers to ask: {}", err);
+            None
+        }
+    }
+}
+
+const DYNAMIC_CONFIG: &'static str = "/zookeeper/config";
+
+// settings that are meant to differ from server to server
+const PER_SERVER_SETTINGS: [&'static str; 3] = ["serverId", "clientPortAddress", "secureClientPortAddress"];
+
+pub struct ServerConfig;
+
+impl Command for ServerConfig {
+    fn name(&self) -> &str { "server_config" }
+    fn description(&self) -> &str { "Compares the servers' configurations (from conf), marking the settings that differ" }
+    fn synopsis(&self) -> &str { "[host[:port][,...] | --all] [--diff]" }
+    fn examples(&self) -> &str { "server_config\nserver_config zk1,zk2 --diff\nserver_config zk3:2181" }
+    fn min_args(&self) -> usize { 0 }
+    fn max_args(&self) -> usize { 1 }
+
+    fn options(&self) -> Vec<Opt> {
+        vec![
+            Opt::flag("a", "all", "every server in the connect string (the default)"),
+            Opt::flag("d", "diff", "only the settings that differ"),
+        ]
+    }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        let servers = match servers(self, shell, args) {
+            Some(servers) => servers,
+            None => return false,
+        };
+
+        let mut configs = vec![];
+        for server in servers {
+            match flw::send(&*server, "conf") {
+                Ok(output) => configs.push((server, flw::parse_conf(&*output))),
+                // i.e.: down, or conf isn't whitelisted
+                Err(err) => out!("{}: unreachable ({})", server, err),
+            }
+        }
+        if configs.is_empty() {
+            return false;
+        }
+
+        let mut keys = BTreeSet::new();
+        for &(_, ref config) in &configs {
+            keys.extend(config.keys().cloned());
+        }
+        let mut header = vec!["  SETTING".to_string()];
+        header.extend(configs.iter().map(|&(ref server, _)| server.clone()));
+        let mut table = vec![header];
+        let mut differing = 0;
+        for key in keys {
+            let values: Vec<String> = configs.iter()
+                .map(|&(_, ref config)| config.get(&key).cloned().unwrap_or("-".to_string()))
+                .collect();
+            let differs = values.iter().any(|value| *value != values[0]) && !PER_SERVER_SETTINGS.contains(&&*key);
+            if differs {
+                differing += 1;
+            } else if args.flag("diff") {
+                continue;
+            }
+            let mut row = vec![format!("{} {}", if differs { "*" } else { " " }, key)];
+            row.extend(values);
+            table.push(row);
+        }
+
+        for line in columns(&table) {
+            if line.starts_with('*') && !output::capturing() {
+                out!("{}", Yellow.bold().paint(&*line));
+            } else {
+                out!("{}", line);
+            }
+        }
+        if configs.len() > 1 {
+            match differing {
+                0 => out!("\nThe servers agree."),
+                1 => out!("\n1 setting differs."),
+                n => out!("\n{} settings differ.", n),
+            }
+        }
+        // 3.5+ keeps the ensemble's membership in a znode too
+        if let Some(ref zk) = shell.zk {
+            if let Ok((data, stat)) = zk.get_data(DYNAMIC_CONFIG, false) {
+                out!("\n{} (version {}):", DYNAMIC_CONFIG, stat.version);
+                for line in String::from_utf8_lossy(&*data).lines().filter(|line| !line.is_empty()) {
+                    out!("  {}", line);
+                }
+            }
+        }
+        true
+    }
+}
+
+pub struct ServerWatches;
+
+impl Command for ServerWatches {
+    fn name(&self) -> &str { "server_watches" }
+    fn description(&self) -> &str { "Counts the watches the servers hold, by path and by session (from wchp)" }
+    fn synopsis(&self) -> &str { "[host[:port][,...] | --all] [--top N]" }
+    fn examples(&self) -> &str { "server_watches\nserver_watches zk1 --top 20" }
+    fn min_args(&self) -> usize { 0 }
+    fn max_args(&self) -> usize { 1 }
+
+    fn options(&self) -> Vec<Opt> {
+        vec![
+            Opt::flag("a", "all", "every server in the connect string (the default)"),
+            Opt::opt("n", "top", "N", "how many paths and sessions to show (default 10, 0 for all)"),
+        ]
+    }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        let top = match args.parse_value::<usize>("top", 10) {
+            Some(top) => top,
+            None => return false,
+        };
+        let servers = match servers(self, shell, args) {
+            Some(servers) => servers,
+            None => return false,
+        };
+
+        // sessions only watch through the server they're connected to, so
+        // adding up the servers' counts doesn't count anything twice
+        let mut by_path: BTreeMap<String, usize> = BTreeMap::new();
+        let mut by_session: BTreeMap<String, usize> = BTreeMap::new();
+        let mut answered = 0;
+        for server in servers {
+            match flw::send(&*server, "wchp") {
+                Ok(output) => {
+                    answered += 1;
+                    for (path, sessions) in flw::parse_watches(&*output) {
+                        *by_path.entry(path).or_insert(0) += sessions.len();
+                        for session in sessions {
+                            *by_session.entry(session).or_insert(0) += 1;
+                        }
+                    }
+                },
+                Err(err) => out!("{}: unreachable ({})", server, err),
+            }
+        }
+        if answered == 0 {
+            return false;
+        }
+
+        let total = by_path.values().fold(0, |total, count| total + count);
+        out!("{} watches on {} paths, from {} sessions.", total, by_path.len(), by_session.len());
+        for &(title, ref counts) in &[("PATH", by_path), ("SESSION", by_session)] {
+            let mut counts: Vec<(&String, &usize)> = counts.iter().collect();
+            // stable, so ties stay in key order
+            counts.sort_by(|a, b| b.1.cmp(a.1));
+            if top > 0 {
+                counts.truncate(top);
+            }
+            let mut table = vec![vec![title.to_string(), "WATCHES".to_string()]];
+            table.extend(counts.into_iter().map(|(key, count)| vec![key.clone(), count.to_string()]));
+            out!("");
+            for line in columns(&table) {
+                out!("{}", line);
+            }
+        }
+        true
+    }
+}
+
+pub struct Sessions;
+
+impl Command for Sessions {
+    fn name(&self) -> &str { "sessions" }
+    fn description(&self) -> &str { "Lists the ensemble's sessions, from every server's cons and dump" }
+    fn synopsis(&self) -> &str { "[host[:port][,...] | --all]" }
+    fn examples(&self) -> &str { "sessions\nsessions zk1:2181,zk2:2181" }
+    fn min_args(&self) -> usize { 0 }
+    fn max_args(&self) -> usize { 1 }
+
+    fn options(&self) -> Vec<Opt> {
+        vec![Opt::flag("a", "all", "every server in the connect string (the default)")]
+    }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        let servers = match servers(self, shell, args) {
+            Some(servers) => servers,
+            None => return false,
+        };
+
+        // by session: (client, server, timeout, last zxid) and ephemerals
+        let mut sessions: BTreeMap<i64, Option<(String, String, Option<u32>, Option<i64>)>> = BTreeMap::new();
+        let mut ephemerals: BTreeMap<i64, BTreeSet<String>> = BTreeMap::new();
+        let mut answered = 0;
+        for server in &servers {
+            match flw::send(&*server, "cons") {
+                Ok(output) => {
+                    answered += 1;
+                    for conn in flw::parse_cons(&*output) {
+                        let info = (conn.endpoint, server.clone(), conn.timeout, conn.last_zxid);
+                        sessions.insert(conn.session_id, Some(info));
+                    }
+                },
+                Err(err) => {
+                    out!("{}: unreachable ({})", server, err);
+                    continue;
+                }
+            }
+            // only the leader knows about every session's ephemerals
+            if let Ok(output) = flw::send(&*server, "dump") {
+                for (session, paths) in flw::parse_dump(&*output) {
+                    sessions.entry(session).or_insert(None);
+                    ephemerals.entry(session).or_insert(BTreeSet::new()).extend(paths);
+                }
+            }
+        }
+        if answered == 0 {
+            return false;
+        }
+
+        let mut table = vec![
+            ["SESSION", "CLIENT", "SERVER", "TIMEOUT", "LAST ZXID", "EPHEMERALS"].iter().map(|title| title.to_string()).collect(),
+        ];
+        for (session, info) in &sessions {
+            let owned = ephemerals.get(session).map_or(0, |paths| paths.len());
+            let mut row = vec![format!("0x{:x}", session)];
+            match *info {
+                Some((ref client, ref server, timeout, last_zxid)) => {
+                    row.push(client.clone());
+                    row.push(server.clone());
+                    row.push(timeout.map_or("-".to_string(), |timeout| format!("{}ms", timeout)));
+                    row.push(last_zxid.map_or("-".to_string(), |zxid| format!("0x{:x}", zxid)));
+                },
+                // not connected right now, but not expired yet either
+                None => row.extend(vec!["-".to_string(); 4]),
+            }
+            row.push(owned.to_string());
+            table.push(row);
+        }
+        for line in columns(&table) {
+            out!("{}", line);
+        }
+        out!("\n{} sessions, {} with ephemerals.", sessions.len(), ephemerals.values().filter(|paths| !paths.is_empty()).count());
+        true
+    }
+}
@@ -0,0 +1,309 @@
+use ansi_term::Colour::Red;
+use zookeeper::ZkError;
+
+use hosts::ConnectString;
+use shell::{Args, Command, LAST_PROFILE_PREFIX, Opt, Shell, confirm, output, report_error};
+use traversal::walk_stats;
+use util::parse_session_id;
+
+
+pub struct Connect;
+
+impl Command for Connect {
+    fn name(&self) -> &str { "connect" }
+    fn description(&self) -> &str {
+        "Connects to one of the given hosts (or the current ones, or the last session's), creating a session"
+    }
+    fn synopsis(&self) -> &str { "[hosts | srv:name | --profile NAME]" }
+    fn examples(&self) -> &str {
+        "connect localhost:2181\nconnect zk1:2181,zk2:2181/chroot\nconnect srv:_zookeeper._tcp.example.com\nconnect --profile prod"
+    }
+    fn min_args(&self) -> usize { 0 }
+    fn max_args(&self) -> usize { 1 }
+
+    fn options(&self) -> Vec<Opt> {
+        vec![Opt::opt("p", "profile", "NAME", "the hosts, credentials & co. of a profile in the config")]
+    }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        let hosts = match (args.optional(0), args.value("profile")) {
+            (Some(_), Some(_)) => {
+                out!("Either hosts or --profile, not both.");
+                return false;
+            },
+            (None, Some(name)) => match shell.use_profile(&*name) {
+                Some(hosts) => hosts,
+                None => return false
+            },
+            (Some(hosts), None) => hosts.to_string(),
+            (None, None) => match (shell.current_hosts(), shell.last_hosts()) {
+                (ref current, Some(ref last)) if current.is_empty() && last.starts_with(LAST_PROFILE_PREFIX) => {
+                    match shell.use_profile(&last[LAST_PROFILE_PREFIX.len()..]) {
+                        Some(hosts) => hosts,
+                        None => return false
+                    }
+                },
+                (ref current, Some(last)) if current.is_empty() => last,
+                (current, _) => current,
+            }
+        };
+        if hosts.is_empty() {
+            out!("No hosts to connect to.");
+            return false;
+        }
+        shell.close();
+        shell.connect_to(&*hosts)
+    }
+}
+
+pub struct Disconnect;
+
+impl Command for Disconnect {
+    fn name(&self) -> &str { "disconnect" }
+    fn description(&self) -> &str { "Disconnects from the server (closing the session)" }
+    fn synopsis(&self) -> &str { "" }
+    fn examples(&self) -> &str { "disconnect" }
+    fn min_args(&self) -> usize { 0 }
+    fn max_args(&self) -> usize { 0 }
+
+    fn execute(&self, shell: &mut Shell, _: &Args) -> bool {
+        if shell.zk.is_none() {
+            out!("Not connected.");
+            return false;
+        }
+        shell.close();
+        true
+    }
+}
+
+pub struct Reconnect;
+
+impl Command for Reconnect {
+    fn name(&self) -> &str { "reconnect" }
+    fn description(&self) -> &str { "Drops the session and starts a new one, optionally with just the given server" }
+    fn synopsis(&self) -> &str { "[host[:port]]" }
+    fn examples(&self) -> &str { "reconnect\nreconnect zk2:2181" }
+    fn min_args(&self) -> usize { 0 }
+    fn max_args(&self) -> usize { 1 }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        let hosts = shell.current_hosts();
+        if hosts.is_empty() {
+            out!("No hosts to connect to.");
+            return false;
+        }
+        let hosts = match args.optional(0) {
+            // the chroot stays, so paths mean the same
+            Some(server) => match (ConnectString::parse(server), ConnectString::parse(&*hosts)) {
+                (Ok(ref pinned), _) if pinned.servers.len() != 1 || pinned.chroot.is_some() => {
+                    out!("Expected a single server, i.e.: zk2:2181");
+                    return false;
+                },
+                (Ok(pinned), Ok(current)) => ConnectString { servers: pinned.servers, chroot: current.chroot }.to_string(),
+                (Err(err), _) => {
+                    out!("Bad server {}: {}", server, err);
+                    return false;
+                },
+                // srv: names and the like, the chroot can't be told apart
+                (Ok(pinned), Err(_)) => pinned.to_string(),
+            },
+            None => hosts,
+        };
+        shell.close();
+        shell.connect_to(&*hosts)
+    }
+}
+
+pub struct CurrentServer;
+
+impl Command for CurrentServer {
+    fn name(&self) -> &str { "current_server" }
+    fn description(&self) -> &str { "Shows the server the session is attached to" }
+    fn synopsis(&self) -> &str { "" }
+    fn examples(&self) -> &str { "current_server" }
+    fn min_args(&self) -> usize { 0 }
+    fn max_args(&self) -> usize { 0 }
+
+    // the client library doesn't say which server it picked, so that's
+    // only known for sure when there's just one to pick from
+    fn execute(&self, shell: &mut Shell, _: &Args) -> bool {
+        if shell.zk.is_none() {
+            out!("Not connected.");
+            return false;
+        }
+        let hosts = match ConnectString::parse(&*shell.hosts) {
+            Ok(hosts) => hosts,
+            Err(_) => {
+                out!("Connected through {}", shell.hosts);
+                return true;
+            }
+        };
+        if hosts.servers.len() > 1 {
+            out!("One of {} (the client doesn't tell which, reconnect <host> to pick one).", hosts.server_list());
+        } else {
+            out!("{}", hosts.server_list());
+        }
+        true
+    }
+}
+
+pub struct SetTimeout;
+
+impl Command for SetTimeout {
+    fn name(&self) -> &str { "set_timeout" }
+    fn description(&self) -> &str { "Sets the session (and connect) timeout used by the next connect" }
+    fn synopsis(&self) -> &str { "[session secs] [connect secs]" }
+    fn examples(&self) -> &str { "set_timeout\nset_timeout 30\nset_timeout 30 5" }
+    fn min_args(&self) -> usize { 0 }
+    fn max_args(&self) -> usize { 2 }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        if args.len() == 0 {
+            out!("session timeout: {}s, connect timeout: {}s", shell.session_timeout, shell.connect_timeout);
+            return true;
+        }
+
+        let session = match args.get(0).parse::<u64>() {
+            Ok(secs) if secs > 0 => secs,
+            _ => {
+                out!("Bad session timeout: {}", args.get(0));
+                return false;
+            }
+        };
+        let connect = match args.optional(1).map(|secs| secs.parse::<u64>()) {
+            Some(Ok(secs)) => secs,
+            Some(Err(_)) => {
+                out!("Bad connect timeout: {}", args.get(1));
+                return false;
+            },
+            None => shell.connect_timeout
+        };
+        shell.set_session_timeout(session);
+        shell.set_connect_timeout(connect);
+        if shell.zk.is_some() {
+            out!("The new timeout applies from the next connect.");
+        }
+        true
+    }
+}
+
+pub struct AddAuth;
+
+impl Command for AddAuth {
+    fn name(&self) -> &str { "add_auth" }
+    fn description(&self) -> &str { "Adds credentials to the session (and to later ones)" }
+    fn synopsis(&self) -> &str { "<scheme> <credential>" }
+    fn examples(&self) -> &str { "add_auth digest user:password" }
+    fn min_args(&self) -> usize { 2 }
+    fn max_args(&self) -> usize { 2 }
+    fn sensitive(&self) -> bool { true }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        shell.add_auth(args.get(0), args.get(1).as_bytes())
+    }
+}
+
+pub struct Whoami;
+
+impl Command for Whoami {
+    fn name(&self) -> &str { "whoami" }
+    fn description(&self) -> &str { "Lists the credentials added to the session" }
+    fn synopsis(&self) -> &str { "" }
+    fn examples(&self) -> &str { "whoami" }
+    fn min_args(&self) -> usize { 0 }
+    fn max_args(&self) -> usize { 0 }
+
+    // the client library has no whoAmI request (ZooKeeper 3.6+), so this is
+    // what the shell sent with add_auth rather than what the server accepted
+    fn execute(&self, shell: &mut Shell, _: &Args) -> bool {
+        if shell.zk.is_none() {
+            out!("Not connected.");
+            return false;
+        }
+        if shell.auths.is_empty() {
+            out!("No credentials added.");
+            return true;
+        }
+        for &(ref scheme, ref auth) in &shell.auths {
+            let auth = String::from_utf8_lossy(auth);
+            match &**scheme {
+                // don't echo passwords
+                "digest" => out!("digest: {}", auth.split(':').next().unwrap_or("")),
+                scheme => out!("{}: {}", scheme, auth),
+            }
+        }
+        true
+    }
+}
+
+pub struct CloseSession;
+
+impl Command for CloseSession {
+    fn name(&self) -> &str { "close_session" }
+    fn description(&self) -> &str { "Evicts a stuck client by deleting the ephemeral znodes (i.e.: locks) its session owns" }
+    fn synopsis(&self) -> &str { "<session-id> [path] [--force]" }
+    fn examples(&self) -> &str { "close_session 0x14f8e2c1b9b0001\nclose_session 0x14f8e2c1b9b0001 /locks" }
+    fn min_args(&self) -> usize { 1 }
+    fn max_args(&self) -> usize { 2 }
+    fn mutates(&self, _: &Args) -> bool { true }
+    fn destroys<'a>(&self, args: &'a Args) -> Option<(&'a str, bool)> { Some((args.optional(1).unwrap_or("/"), true)) }
+
+    fn options(&self) -> Vec<Opt> {
+        vec![Opt::flag("f", "force", "don't ask for confirmation")]
+    }
+
+    // closing the session itself takes its password, which only its client
+    // has (and the client library can't take over a session anyway), and
+    // no admin command expires sessions. So the session lives on until it
+    // times out, but whatever it was holding is released.
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        let session = match parse_session_id(args.get(0)) {
+            Some(session) if session != 0 => session,
+            _ => {
+                out!("Bad session id: {}, expected i.e.: 0x14f8e2c1b9b0001", args.get(0));
+                return false;
+            }
+        };
+
+        let zk = fetch_zk!(shell.zk);
+        let path = args.optional(1).unwrap_or("/");
+        let mut owned = vec![];
+        let ret = walk_stats(zk, path, &mut |node, _, stat| {
+            if stat.ephemeral_owner == session {
+                owned.push(node.to_string());
+            }
+        });
+        if let Err(err) = ret {
+            return report_error(err, path);
+        }
+        if owned.is_empty() {
+            out!("0x{:x} owns no ephemeral znodes under {}.", session, path);
+            return true;
+        }
+
+        if !args.flag("force") {
+            let warning = format!("This deletes the {} ephemeral znode(s) owned by 0x{:x}, taking away its locks, \
+                                   leadership and registrations while it may still be running:", owned.len(), session);
+            if output::capturing() {
+                out!("{}", warning);
+            } else {
+                out!("{}", Red.bold().paint(&*warning));
+            }
+            for node in &owned {
+                out!("  {}", node);
+            }
+            if !confirm("Evict it?") {
+                return false;
+            }
+        }
+
+        for node in &owned {
+            match zk.delete(node, -1) {
+                Ok(()) | Err(ZkError::NoNode) => (),
+                Err(err) => return report_error(err, node),
+            }
+        }
+        out!("Deleted {} ephemeral znode(s) owned by 0x{:x}, the session itself lasts until it times out.", owned.len(), session);
+        true
+    }
+}
@@ -0,0 +1,1119 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::thread;
+use std::time::Duration;
+
+use time;
+use zookeeper::{Acl, CreateMode, Stat, ZkError, ZkResult, ZooKeeper};
+
+use backup::{self, Manifest};
+use client::{DryRunClient, ZkClient};
+use flw;
+use hosts::ConnectString;
+use interrupt;
+use shell::{Args, Command, Opt, QuietWatcher, Shell, confirm, confirm_unless_forced, format_time, report_error, timestamp};
+use traversal::{count_descendants, descendants, walk, walk_stats};
+use util::{SYSTEM_TREE, ensure_path, fill_template, format_age, in_system_tree, join_path, parent_path, parse_session_id,
+           parse_time, random_uuid};
+
+use super::{child_entries, columns, sort_entries, sort_opts};
+
+
+const BENCH_OPS: [&'static str; 4] = ["create", "set", "get", "delete"];
+
+fn timed<T, F: FnMut() -> ZkResult<T>>(samples: &mut Vec<u64>, mut op: F) -> ZkResult<T> {
+    let start = time::precise_time_ns();
+    let ret = op();
+    samples.push(time::precise_time_ns() - start);
+    ret
+}
+
+// runs every op against count znodes, returns the latencies (ns) per op
+fn bench_run(zk: &ZkClient, paths: &Vec<String>, data: &Vec<u8>, acl: &Vec<Acl>) -> ZkResult<Vec<Vec<u64>>> {
+    let mut samples = vec![Vec::with_capacity(paths.len()); BENCH_OPS.len()];
+
+    for path in paths {
+        if let Err(err) = timed(&mut samples[0], || zk.create(path, data.clone(), acl.clone(), CreateMode::Persistent)) {
+            bench_cleanup(zk, paths);
+            return Err(err);
+        }
+    }
+    for path in paths {
+        if let Err(err) = timed(&mut samples[1], || zk.set_data(path, data.clone(), -1)) {
+            bench_cleanup(zk, paths);
+            return Err(err);
+        }
+    }
+    for path in paths {
+        if let Err(err) = timed(&mut samples[2], || zk.get_data(path, false)) {
+            bench_cleanup(zk, paths);
+            return Err(err);
+        }
+    }
+    for path in paths {
+        try!(timed(&mut samples[3], || zk.delete(path, -1)));
+    }
+
+    Ok(samples)
+}
+
+fn bench_cleanup(zk: &ZkClient, paths: &Vec<String>) {
+    for path in paths {
+        let _ = zk.delete(path, -1);
+    }
+}
+
+fn percentile(sorted: &Vec<u64>, p: f64) -> u64 {
+    let idx = (p * sorted.len() as f64).ceil() as usize;
+    sorted[if idx == 0 { 0 } else { idx - 1 }]
+}
+
+fn as_ms(ns: u64) -> f64 {
+    ns as f64 / 1e6
+}
+
+// can depth levels with fanout children each hold count leaves?
+fn fill_fits(fanout: usize, depth: u32, count: usize) -> bool {
+    let mut leaves = 1;
+    for _ in 0..depth {
+        if leaves >= count {
+            break;
+        }
+        leaves *= fanout;
+    }
+    leaves >= count
+}
+
+pub struct Benchmark;
+
+impl Command for Benchmark {
+    fn name(&self) -> &str { "benchmark" }
+    fn description(&self) -> &str { "Measures create/set/get/delete latencies under a scratch path" }
+    fn synopsis(&self) -> &str { "<path> <count> [--data-size N] [--concurrency C]" }
+    fn examples(&self) -> &str { "benchmark /bench 1000 --data-size 128 --concurrency 4" }
+    fn min_args(&self) -> usize { 2 }
+    fn max_args(&self) -> usize { 2 }
+    fn mutates(&self, _: &Args) -> bool { true }
+    fn writes<'a>(&self, args: &'a Args) -> Vec<&'a str> { vec![args.get(0)] }
+
+    fn options(&self) -> Vec<Opt> {
+        vec![
+            Opt::opt("", "data-size", "N", "bytes of data per znode"),
+            Opt::opt("", "concurrency", "C", "number of concurrent sessions"),
+        ]
+    }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        let count = match args.get(1).parse::<usize>() {
+            Ok(count) => count,
+            Err(_) => {
+                out!("Bad count: {}.", args.get(1));
+                return false;
+            }
+        };
+        let data_size = match args.parse_value("data-size", 64) {
+            Some(size) => size,
+            None => return false
+        };
+        let concurrency = match args.parse_value::<usize>("concurrency", 1) {
+            Some(0) => 1,
+            Some(concurrency) => concurrency,
+            None => return false
+        };
+
+        let zk = fetch_zk!(shell.zk);
+        let path = args.get(0);
+        let data = vec![b'x'; data_size];
+
+        // the scratch path is removed afterwards only if we created it
+        let created = match zk.exists(path, false) {
+            Ok(_) => false,
+            Err(ZkError::NoNode) => match zk.create(path, vec![], shell.default_acl.clone(), CreateMode::Persistent) {
+                Ok(_) => true,
+                Err(err) => return report_error(err, path)
+            },
+            Err(err) => return report_error(err, path)
+        };
+
+        out!("Running {} ops x {} znodes with {} session(s)...", BENCH_OPS.len(), count, concurrency);
+
+        let start = time::precise_time_ns();
+        let results = if concurrency == 1 {
+            let paths: Vec<String> = (0..count).map(|i| join_path(path, &format!("bench-{}", i))).collect();
+            vec![bench_run(zk, &paths, &data, &shell.default_acl)]
+        } else {
+            let timeout = Duration::from_secs(shell.session_timeout);
+            let workers: Vec<_> = (0..concurrency).map(|w| {
+                // wrapped like the shell's own, so --dry-run and the credentials hold
+                let zk = ZooKeeper::connect(&*shell.hosts, timeout, QuietWatcher).map(|zk| shell.stack(zk));
+                let paths: Vec<String> = (0..count).map(|i| join_path(path, &format!("bench-{}-{}", w, i))).collect();
+                let data = data.clone();
+                let acl = shell.default_acl.clone();
+                thread::spawn(move || {
+                    let zk = try!(zk);
+                    let ret = bench_run(&zk, &paths, &data, &acl);
+                    zk.close();
+                    ret
+                })
+            }).collect();
+            workers.into_iter().map(|w| w.join().unwrap_or(Err(ZkError::SystemError))).collect()
+        };
+        let elapsed = time::precise_time_ns() - start;
+
+        let mut samples = vec![vec![]; BENCH_OPS.len()];
+        let mut status = true;
+        for result in results {
+            match result {
+                Ok(worker_samples) => {
+                    for (op, s) in worker_samples.into_iter().enumerate() {
+                        samples[op].extend(s);
+                    }
+                },
+                Err(err) => {
+                    out!("Worker failed: {:?}", err);
+                    status = false;
+                }
+            }
+        }
+
+        // a failed worker may leave znodes behind, exit removes them
+        if created && zk.delete(path, -1).is_err() {
+            shell.scratch.push(path.to_string());
+        }
+
+        out!("{:<8} {:>10} {:>10} {:>10} {:>10}", "op (ms)", "min", "avg", "p95", "p99");
+        for (op, mut s) in samples.into_iter().enumerate() {
+            if s.is_empty() {
+                continue;
+            }
+            s.sort();
+            let avg = s.iter().fold(0, |sum, ns| sum + ns) / s.len() as u64;
+            out!("{:<8} {:>10.3} {:>10.3} {:>10.3} {:>10.3}",
+                     BENCH_OPS[op], as_ms(s[0]), as_ms(avg), as_ms(percentile(&s, 0.95)), as_ms(percentile(&s, 0.99)));
+        }
+        out!("total {:.3}s", elapsed as f64 / 1e9);
+
+        status
+    }
+}
+
+pub struct Fill;
+
+impl Command for Fill {
+    fn name(&self) -> &str { "fill" }
+    fn description(&self) -> &str { "Populates a subtree with synthetic znodes" }
+    fn synopsis(&self) -> &str { "<path> <num-nodes> [--data-size N] [--depth D]" }
+    fn examples(&self) -> &str { "fill /test 1000 --depth 3 --data-size 16" }
+    fn min_args(&self) -> usize { 2 }
+    fn max_args(&self) -> usize { 2 }
+    fn mutates(&self, _: &Args) -> bool { true }
+    fn writes<'a>(&self, args: &'a Args) -> Vec<&'a str> { vec![args.get(0)] }
+
+    fn options(&self) -> Vec<Opt> {
+        vec![
+            Opt::opt("", "data-size", "N", "bytes of data per znode"),
+            Opt::opt("", "depth", "D", "levels of znodes below path"),
+        ]
+    }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        let count = match args.get(1).parse::<usize>() {
+            Ok(count) => count,
+            Err(_) => {
+                out!("Bad number of nodes: {}.", args.get(1));
+                return false;
+            }
+        };
+        let data_size = match args.parse_value::<usize>("data-size", 0) {
+            Some(size) => size,
+            None => return false
+        };
+        let depth = match args.parse_value::<u32>("depth", 1) {
+            Some(0) => 1,
+            Some(depth) => depth,
+            None => return false
+        };
+
+        let zk = fetch_zk!(shell.zk);
+        let path = args.get(0);
+        let data: Vec<u8> = (0..data_size).map(|i| b'a' + (i % 26) as u8).collect();
+
+        if let Err(err) = ensure_path(zk, path, &shell.default_acl) {
+            return report_error(err, path);
+        }
+
+        // spread the leaves evenly: each level has (at most) fanout children
+        let mut fanout = 1;
+        while !fill_fits(fanout, depth, count) {
+            fanout += 1;
+        }
+
+        let mut created = 0;
+        // the top level ones, exit deletes them (and what's under them)
+        let mut roots = vec![];
+        let mut prev: Vec<usize> = vec![];
+        for i in 0..count {
+            let mut digits = vec![0; depth as usize];
+            let mut n = i;
+            for level in (0..depth as usize).rev() {
+                digits[level] = n % fanout;
+                n /= fanout;
+            }
+
+            let mut node = path.to_string();
+            for level in 0..depth as usize {
+                node = join_path(&*node, &format!("fill-{}", digits[level]));
+                let leaf = level + 1 == depth as usize;
+                if !leaf && prev.len() > level && prev[..level + 1] == digits[..level + 1] {
+                    continue;
+                }
+
+                let ret = zk.create(&*node, if leaf { data.clone() } else { vec![] },
+                                    shell.default_acl.clone(), CreateMode::Persistent);
+                match ret {
+                    Ok(_) => created += 1,
+                    Err(err) => {
+                        shell.scratch.extend(roots);
+                        return report_error(err, &*node);
+                    }
+                }
+                if level == 0 {
+                    roots.push(node.clone());
+                }
+            }
+            prev = digits;
+        }
+
+        shell.scratch.extend(roots);
+        out!("Created {} znodes under {}.", created, path);
+        true
+    }
+}
+
+pub struct CreateFromTemplate;
+
+impl Command for CreateFromTemplate {
+    fn name(&self) -> &str { "create_from_template" }
+    fn description(&self) -> &str {
+        "Creates count znodes from a template file: the path on the first line, the data on the rest"
+    }
+    fn synopsis(&self) -> &str { "<template-file> <count> [--start N]" }
+    fn examples(&self) -> &str {
+        "create_from_template worker.tpl 100\ncreate_from_template worker.tpl 50 --start 100"
+    }
+    fn min_args(&self) -> usize { 2 }
+    fn max_args(&self) -> usize { 2 }
+    fn mutates(&self, _: &Args) -> bool { true }
+
+    fn options(&self) -> Vec<Opt> {
+        vec![Opt::opt("", "start", "N", "the first value of {{i}} (default 0)")]
+    }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        let file = args.get(0);
+        let count = match args.get(1).parse::<usize>() {
+            Ok(count) => count,
+            Err(_) => {
+                out!("Bad count: {}.", args.get(1));
+                return false;
+            }
+        };
+        let start = match args.parse_value::<usize>("start", 0) {
+            Some(start) => start,
+            None => return false
+        };
+
+        let mut template = String::new();
+        if let Err(err) = File::open(file).and_then(|mut f| f.read_to_string(&mut template)) {
+            out!("Failed to read {}: {}", file, err);
+            return false;
+        }
+        let (path, data) = match template.find('\n') {
+            Some(idx) => (template[..idx].trim(), template[idx + 1..].trim_right_matches('\n')),
+            None => (template.trim(), ""),
+        };
+        if !path.starts_with('/') {
+            out!("The template's first line should be a path, got: {}", path);
+            return false;
+        }
+        // or every znode would get the same path
+        if count > 1 && !path.contains("{{i}}") && !path.contains("{{uuid}}") {
+            out!("The path needs {{{{i}}}} or {{{{uuid}}}} to create more than one znode: {}", path);
+            return false;
+        }
+
+        let zk = fetch_zk!(shell.zk);
+        let _guard = interrupt::guard();
+        let mut created = 0;
+        for i in start..start + count {
+            if interrupt::interrupted() {
+                break;
+            }
+            // one per znode, shared by its path and its data
+            let uuid = match random_uuid() {
+                Ok(uuid) => uuid,
+                Err(err) => {
+                    out!("Failed to generate a UUID: {}", err);
+                    return false;
+                }
+            };
+            let node = fill_template(path, i, &*uuid);
+            let value = fill_template(data, i, &*uuid).into_bytes();
+            let mut ret = zk.create(&*node, value.clone(), shell.default_acl.clone(), CreateMode::Persistent);
+            if let Err(ZkError::NoNode) = ret {
+                if let Err(err) = ensure_path(zk, parent_path(&*node).unwrap_or("/"), &shell.default_acl) {
+                    return report_error(err, &*node);
+                }
+                ret = zk.create(&*node, value, shell.default_acl.clone(), CreateMode::Persistent);
+            }
+            match ret {
+                Ok(_) => created += 1,
+                Err(err) => {
+                    out!("Created {} znodes before failing.", created);
+                    return report_error(err, &*node);
+                }
+            }
+        }
+
+        out!("Created {} znodes.", created);
+        created == count
+    }
+}
+
+pub struct EphemeralEndpoints;
+
+impl Command for EphemeralEndpoints {
+    fn name(&self) -> &str { "ephemeral_endpoints" }
+    fn description(&self) -> &str { "Shows the client endpoint owning each ephemeral znode under path" }
+    fn synopsis(&self) -> &str { "<path> [hosts]" }
+    fn examples(&self) -> &str { "ephemeral_endpoints /workers\nephemeral_endpoints /workers zk1:2181,zk2:2181" }
+    fn min_args(&self) -> usize { 1 }
+    fn max_args(&self) -> usize { 2 }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        let hosts = flw::hosts(args.optional(1).unwrap_or(&*shell.hosts));
+
+        let zk = fetch_zk!(shell.zk);
+        let path = args.get(0);
+        let mut ephemerals = vec![];
+        let ret = walk(zk, path, None, &mut |path, _| {
+            if let Ok(stat) = zk.exists(path, false) {
+                if stat.ephemeral_owner != 0 {
+                    ephemerals.push((path.to_string(), stat.ephemeral_owner));
+                }
+            }
+        });
+        if let Err(err) = ret {
+            return report_error(err, path);
+        }
+
+        let mut endpoints = HashMap::new();
+        for host in hosts {
+            match flw::send(&*host, "cons") {
+                Ok(output) => {
+                    for conn in flw::parse_cons(&*output) {
+                        endpoints.insert(conn.session_id, conn.endpoint);
+                    }
+                },
+                Err(err) => out!("Failed to get cons from {}: {}", host, err),
+            }
+        }
+
+        for (path, owner) in ephemerals {
+            let endpoint = endpoints.get(&owner).map_or("unknown", |endpoint| &*endpoint);
+            out!("{} 0x{:x} {}", path, owner, endpoint);
+        }
+        true
+    }
+}
+
+pub struct Summary;
+
+impl Command for Summary {
+    fn name(&self) -> &str { "summary" }
+    fn description(&self) -> &str { "Reports counts, sizes and extremes for a subtree" }
+    fn synopsis(&self) -> &str { "<path>" }
+    fn examples(&self) -> &str { "summary /services" }
+    fn min_args(&self) -> usize { 1 }
+    fn max_args(&self) -> usize { 1 }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        let zk = fetch_zk!(shell.zk);
+        let path = args.get(0);
+        let mut descendants = 0;
+        let mut bytes: i64 = 0;
+        let mut ephemerals = 0;
+        let mut deepest = 0;
+        let mut largest = (path.to_string(), -1);
+        let mut newest = (path.to_string(), -1);
+
+        let ret = walk(zk, path, None, &mut |node, depth| {
+            let stat = match zk.exists(node, false) {
+                Ok(stat) => stat,
+                Err(_) => return,
+            };
+
+            if depth > 0 {
+                descendants += 1;
+            }
+            if depth > deepest {
+                deepest = depth;
+            }
+            if stat.ephemeral_owner != 0 {
+                ephemerals += 1;
+            }
+            bytes += stat.data_length as i64;
+            if stat.data_length > largest.1 {
+                largest = (node.to_string(), stat.data_length);
+            }
+            if stat.mtime > newest.1 {
+                newest = (node.to_string(), stat.mtime);
+            }
+        });
+        if let Err(err) = ret {
+            return report_error(err, path);
+        }
+
+        out!("{:<14} {}", "descendants", descendants);
+        out!("{:<14} {}", "data bytes", bytes);
+        out!("{:<14} {}", "ephemerals", ephemerals);
+        out!("{:<14} {}", "deepest level", deepest);
+        out!("{:<14} {} ({} bytes)", "largest", largest.0, largest.1);
+        out!("{:<14} {} ({})", "last modified", newest.0, format_time(newest.1));
+        true
+    }
+}
+
+pub struct ChildCount;
+
+impl Command for ChildCount {
+    fn name(&self) -> &str { "child_count" }
+    fn description(&self) -> &str { "Prints the number of descendants of each child" }
+    fn synopsis(&self) -> &str { "<path> [--total] [--sort name|mtime|size|children] [--reverse]" }
+    fn examples(&self) -> &str { "child_count /services --sort children --reverse\nchild_count /services --total" }
+    fn min_args(&self) -> usize { 1 }
+    fn max_args(&self) -> usize { 1 }
+
+    fn options(&self) -> Vec<Opt> {
+        vec![
+            Opt::opt("", "sort", "KEY", "sort children by name, mtime, size or descendants"),
+            Opt::flag("r", "reverse", "reverse the sort order"),
+            Opt::flag("t", "total", "just print the number of descendants of path"),
+        ]
+    }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        let (key, reverse) = match sort_opts(args) {
+            Some(sort) => sort,
+            None => return false
+        };
+
+        let zk = fetch_zk!(shell.zk);
+        let path = args.get(0);
+
+        if args.flag("total") {
+            return match count_descendants(zk, path) {
+                Ok(count) => {
+                    out!("{}", count);
+                    true
+                },
+                Err(err) => report_error(err, path),
+            };
+        }
+
+        let children = match zk.get_children(path, false) {
+            Ok(children) => children,
+            Err(err) => return report_error(err, path),
+        };
+
+        // count every descendant rather than just the direct children
+        let mut entries = child_entries(zk, path, children);
+        for entry in entries.iter_mut() {
+            entry.2 = count_descendants(zk, &*join_path(path, &*entry.0)).unwrap_or(0);
+        }
+
+        sort_entries(&mut entries, key, reverse);
+        for (child, _, count) in entries {
+            out!("{}: {}", join_path(path, &*child), count);
+        }
+        true
+    }
+}
+
+// i.e.: 512, 4K or 1M
+fn human_size(bytes: i64) -> String {
+    match bytes {
+        b if b >= 1 << 20 && b % (1 << 20) == 0 => format!("{}M", b >> 20),
+        b if b >= 1 << 10 && b % (1 << 10) == 0 => format!("{}K", b >> 10),
+        b => b.to_string(),
+    }
+}
+
+// the upper bounds of the histogram buckets, up to jute.maxbuffer's 1M
+// default; there's one more for anything bigger
+const SIZE_BUCKETS: [i64; 9] = [0, 64, 256, 1 << 10, 4 << 10, 16 << 10, 64 << 10, 256 << 10, 1 << 20];
+
+pub struct SizeHistogram;
+
+impl Command for SizeHistogram {
+    fn name(&self) -> &str { "size_histogram" }
+    fn description(&self) -> &str { "Shows how the znodes in a subtree spread over data sizes" }
+    fn synopsis(&self) -> &str { "<path>" }
+    fn examples(&self) -> &str { "size_histogram /\nsize_histogram /services" }
+    fn min_args(&self) -> usize { 1 }
+    fn max_args(&self) -> usize { 1 }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        let zk = fetch_zk!(shell.zk);
+        let path = args.get(0);
+        // (znodes, bytes) per bucket
+        let mut buckets = vec![(0, 0i64); SIZE_BUCKETS.len() + 1];
+        let ret = walk_stats(zk, path, &mut |_, _, stat| {
+            let size = stat.data_length as i64;
+            let bucket = SIZE_BUCKETS.iter().position(|&bound| size <= bound).unwrap_or(SIZE_BUCKETS.len());
+            buckets[bucket].0 += 1;
+            buckets[bucket].1 += size;
+        });
+        if let Err(err) = ret {
+            return report_error(err, path);
+        }
+
+        let total = buckets.iter().fold(0, |total, &(_, bytes)| total + bytes);
+        let mut rows = vec![vec!["SIZE".to_string(), "ZNODES".to_string(), "BYTES".to_string(), "CUMULATIVE".to_string()]];
+        let mut cumulative = 0;
+        for (i, &(znodes, bytes)) in buckets.iter().enumerate() {
+            let label = match i {
+                0 => "0".to_string(),
+                i if i == SIZE_BUCKETS.len() => format!("> {}", human_size(SIZE_BUCKETS[i - 1])),
+                i => format!("<= {}", human_size(SIZE_BUCKETS[i])),
+            };
+            cumulative += bytes;
+            let share = if total > 0 { cumulative * 100 / total } else { 100 };
+            rows.push(vec![label, znodes.to_string(), bytes.to_string(), format!("{} ({}%)", cumulative, share)]);
+        }
+        for line in columns(&rows) {
+            out!("{}", line);
+        }
+        true
+    }
+}
+
+pub struct Biggest;
+
+impl Command for Biggest {
+    fn name(&self) -> &str { "biggest" }
+    fn description(&self) -> &str { "Lists the largest znodes in a subtree, by data length and by children" }
+    fn synopsis(&self) -> &str { "<path> [count]" }
+    fn examples(&self) -> &str { "biggest /\nbiggest /services 20" }
+    fn min_args(&self) -> usize { 1 }
+    fn max_args(&self) -> usize { 2 }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        let count = match args.optional(1).map(|count| count.parse::<usize>()) {
+            None => 10,
+            Some(Ok(count)) if count > 0 => count,
+            Some(_) => {
+                out!("Bad count: {}", args.get(1));
+                return false;
+            }
+        };
+
+        let zk = fetch_zk!(shell.zk);
+        let path = args.get(0);
+        let mut nodes = vec![];
+        let ret = walk_stats(zk, path, &mut |node, _, stat| {
+            nodes.push((node.to_string(), stat.data_length, stat.num_children));
+        });
+        if let Err(err) = ret {
+            return report_error(err, path);
+        }
+
+        // ties go by path, so the output is stable
+        nodes.sort_by(|a, b| (b.1, &a.0).cmp(&(a.1, &b.0)));
+        out!("By data length:");
+        for &(ref node, bytes, _) in nodes.iter().take(count) {
+            out!("{:>10} {}", bytes, node);
+        }
+
+        nodes.sort_by(|a, b| (b.2, &a.0).cmp(&(a.2, &b.0)));
+        out!("\nBy children:");
+        for &(ref node, _, children) in nodes.iter().take(count) {
+            out!("{:>10} {}", children, node);
+        }
+        true
+    }
+}
+
+pub struct EphemeralsBySession;
+
+impl Command for EphemeralsBySession {
+    fn name(&self) -> &str { "ephemerals_by_session" }
+    fn description(&self) -> &str { "Lists the ephemeral znodes (under path) owned by a session" }
+    fn synopsis(&self) -> &str { "<session-id> [path]" }
+    fn examples(&self) -> &str { "ephemerals_by_session 0x14f8e2c1b9b0001\nephemerals_by_session 0x14f8e2c1b9b0001 /workers" }
+    fn min_args(&self) -> usize { 1 }
+    fn max_args(&self) -> usize { 2 }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        let session = match parse_session_id(args.get(0)) {
+            Some(session) if session != 0 => session,
+            _ => {
+                out!("Bad session id: {}, expected i.e.: 0x14f8e2c1b9b0001", args.get(0));
+                return false;
+            }
+        };
+
+        let zk = fetch_zk!(shell.zk);
+        let path = args.optional(1).unwrap_or("/");
+        let mut owned = 0;
+        let ret = walk_stats(zk, path, &mut |node, _, stat| {
+            if stat.ephemeral_owner == session {
+                out!("{}", node);
+                owned += 1;
+            }
+        });
+        if let Err(err) = ret {
+            return report_error(err, path);
+        }
+        out!("{} ephemeral znode(s) owned by 0x{:x}.", owned, session);
+        true
+    }
+}
+
+pub struct Find;
+
+impl Command for Find {
+    fn name(&self) -> &str { "find" }
+    fn description(&self) -> &str { "Lists the znodes under path whose path contains pattern, optionally by time" }
+    fn synopsis(&self) -> &str { "<path> [pattern] [--include-system]" }
+    fn examples(&self) -> &str {
+        "find /services worker\nfind / --mtime-after -10m\nfind /config --ctime-after 2015-06-01 --mtime-before 2015-06-02T12:00Z"
+    }
+    fn min_args(&self) -> usize { 1 }
+    fn max_args(&self) -> usize { 2 }
+
+    fn options(&self) -> Vec<Opt> {
+        vec![
+            Opt::opt("", "mtime-after", "TIME", "modified after TIME, i.e.: -5m, -2h or 2015-06-01T10:00:00Z"),
+            Opt::opt("", "mtime-before", "TIME", "modified before TIME"),
+            Opt::opt("", "ctime-after", "TIME", "created after TIME"),
+            Opt::opt("", "ctime-before", "TIME", "created before TIME"),
+            Opt::flag("", "include-system", "don't leave out /zookeeper"),
+        ]
+    }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        let now = time::get_time();
+        let now_ms = now.sec * 1000 + (now.nsec / 1000000) as i64;
+        let mut bounds = vec![];
+        for name in &["mtime-after", "mtime-before", "ctime-after", "ctime-before"] {
+            if let Some(spec) = args.value(name) {
+                match parse_time(&*spec, now_ms) {
+                    Ok(ms) => bounds.push((*name, ms)),
+                    Err(err) => {
+                        out!("{}", err);
+                        return false;
+                    }
+                }
+            }
+        }
+        let within = |stat: &Stat| bounds.iter().all(|&(name, ms)| match name {
+            "mtime-after" => stat.mtime > ms,
+            "mtime-before" => stat.mtime < ms,
+            "ctime-after" => stat.ctime > ms,
+            _ => stat.ctime < ms,
+        });
+
+        let zk = fetch_zk!(shell.zk);
+        let path = args.get(0);
+        let pattern = args.optional(1).unwrap_or("");
+        let system = args.flag("include-system") || in_system_tree(path);
+        let ret = walk_stats(zk, path, &mut |node, _, stat| {
+            if node.contains(pattern) && within(stat) && (system || !in_system_tree(node)) {
+                out!("{}", node);
+            }
+        });
+        match ret {
+            Ok(()) => true,
+            Err(err) => report_error(err, path),
+        }
+    }
+}
+
+pub struct Age;
+
+impl Command for Age {
+    fn name(&self) -> &str { "age" }
+    fn description(&self) -> &str {
+        "Shows how long ago the znode and its children (or all its descendants) were modified and created, stalest first"
+    }
+    fn synopsis(&self) -> &str { "<path> [--recursive] [--include-system]" }
+    fn examples(&self) -> &str { "age /locks\nage /services --recursive" }
+    fn min_args(&self) -> usize { 1 }
+    fn max_args(&self) -> usize { 1 }
+
+    fn options(&self) -> Vec<Opt> {
+        vec![
+            Opt::flag("R", "recursive", "every descendant, not just the children"),
+            Opt::flag("", "include-system", "don't leave out /zookeeper"),
+        ]
+    }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        let zk = fetch_zk!(shell.zk);
+        let path = args.get(0);
+        let max_depth = if args.flag("recursive") { None } else { Some(1) };
+        let system = args.flag("include-system") || in_system_tree(path);
+        let mut ages = vec![];
+        let ret = walk(zk, path, max_depth, &mut |node, _| {
+            if system || !in_system_tree(node) {
+                if let Ok(stat) = zk.exists(node, false) {
+                    ages.push((stat.mtime, stat.ctime, node.to_string()));
+                }
+            }
+        });
+        if let Err(err) = ret {
+            return report_error(err, path);
+        }
+
+        let now = time::get_time();
+        let now_ms = now.sec * 1000 + (now.nsec / 1000000) as i64;
+        ages.sort();
+        out!("{:<10} {:<10} {}", "modified", "created", "path");
+        for (mtime, ctime, node) in ages {
+            out!("{:<10} {:<10} {}", format_age(now_ms - mtime), format_age(now_ms - ctime), node);
+        }
+        true
+    }
+}
+
+pub struct Backup;
+
+impl Command for Backup {
+    fn name(&self) -> &str { "backup" }
+    fn description(&self) -> &str { "Saves the tree's znodes (paths, data and ACLs) to a local archive, for restore" }
+    fn synopsis(&self) -> &str { "<file> [path] [--include-system]" }
+    fn examples(&self) -> &str { "backup tree.zkb\nbackup services.zkb /services" }
+    fn min_args(&self) -> usize { 1 }
+    fn max_args(&self) -> usize { 2 }
+
+    fn options(&self) -> Vec<Opt> {
+        vec![Opt::flag("", "include-system", "don't leave out /zookeeper (quotas and config aren't for restoring)")]
+    }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        let zk = fetch_zk!(shell.zk);
+        let (file, path) = (args.get(0), args.optional(1).unwrap_or("/"));
+        let system = args.flag("include-system") || in_system_tree(path);
+        let exclude = if system { vec![] } else { vec![SYSTEM_TREE] };
+        let znodes = match backup::collect(zk, path, &exclude[..]) {
+            Ok(znodes) => znodes,
+            Err(err) => return report_error(err, path),
+        };
+
+        let archive = backup::encode(&Manifest::new(path, znodes.len()), &znodes[..]);
+        match File::create(file).and_then(|mut f| f.write_all(&archive[..])) {
+            Ok(()) => {
+                out!("Saved {} znodes to {} ({} bytes).", znodes.len(), file, archive.len());
+                true
+            },
+            Err(err) => {
+                out!("Failed to write {}: {}", file, err);
+                false
+            }
+        }
+    }
+}
+
+pub struct Restore;
+
+impl Command for Restore {
+    fn name(&self) -> &str { "restore" }
+    fn description(&self) -> &str {
+        "Creates the znodes saved by backup, leaving the ones that exist alone; it shows what it'll do and asks first"
+    }
+    fn synopsis(&self) -> &str { "<file> [--overwrite] [--yes]" }
+    fn examples(&self) -> &str { "restore tree.zkb\nrestore services.zkb --overwrite --yes" }
+    fn min_args(&self) -> usize { 1 }
+    fn max_args(&self) -> usize { 1 }
+    fn mutates(&self, _: &Args) -> bool { true }
+
+    fn options(&self) -> Vec<Opt> {
+        vec![
+            Opt::flag("o", "overwrite", "set the data of the znodes that exist (their ACLs are kept)"),
+            Opt::flag("y", "yes", "don't ask for confirmation"),
+        ]
+    }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        let file = args.get(0);
+        let mut archive = vec![];
+        if let Err(err) = File::open(file).and_then(|mut f| f.read_to_end(&mut archive)) {
+            out!("Failed to read {}: {}", file, err);
+            return false;
+        }
+        let (manifest, znodes) = match backup::decode(&archive[..]) {
+            Ok(backup) => backup,
+            Err(err) => {
+                out!("Failed to read {}: {}", file, err);
+                return false;
+            }
+        };
+        out!("Restoring {} znodes under {}, saved {}.", manifest.znodes, manifest.root, manifest.created);
+
+        let zk = fetch_zk!(shell.zk);
+        // the root's ancestors weren't saved
+        if let Some(parent) = parent_path(&*manifest.root) {
+            if let Err(err) = ensure_path(zk, parent, shell.default_acl()) {
+                return report_error(err, parent);
+            }
+        }
+
+        let preview = match backup::preview(zk, &znodes[..], args.flag("overwrite")) {
+            Ok(preview) => preview,
+            Err(err) => return report_error(err, &*manifest.root),
+        };
+        out!("To create:    {} ({} bytes)", preview.created, preview.created_bytes);
+        out!("To overwrite: {} ({} bytes)", preview.updated, preview.updated_bytes);
+        if args.flag("overwrite") {
+            out!("Unchanged:    {}", preview.unchanged);
+        } else {
+            out!("To skip:      {} (already there, see --overwrite)", preview.skipped);
+        }
+        out!("Nothing gets deleted.");
+        if !args.flag("yes") && (preview.created > 0 || preview.updated > 0) && !confirm("Continue?") {
+            return false;
+        }
+
+        match backup::restore(zk, &znodes[..], args.flag("overwrite")) {
+            Ok(restored) => {
+                out!("Created {}, overwrote {}, skipped {} (already there).", restored.created, restored.updated, restored.skipped);
+                true
+            },
+            Err(err) => report_error(err, &*manifest.root),
+        }
+    }
+}
+
+// what a mirror pass is to change under dst, worked out before anything
+// is written so it can be shown (and confirmed) first
+#[derive(Default)]
+struct MirrorPlan {
+    // (path, data, acl)
+    creates: Vec<(String, Vec<u8>, Vec<Acl>)>,
+    // (path, data)
+    updates: Vec<(String, Vec<u8>)>,
+    // children before their parents
+    deletes: Vec<String>,
+}
+
+impl MirrorPlan {
+    fn is_empty(&self) -> bool {
+        self.creates.is_empty() && self.updates.is_empty() && self.deletes.is_empty()
+    }
+}
+
+// what a mirror pass changed under dst
+#[derive(Default)]
+struct Synced {
+    created: usize,
+    updated: usize,
+    deleted: usize,
+}
+
+// what makes dst_root a copy of src_root: creating what's missing (with
+// src's ACLs), setting what differs and deleting what src doesn't have,
+// ephemerals and (unless system) ZooKeeper's own znodes aside. Fails with
+// the path it failed on.
+fn mirror_plan(src: &ZkClient, src_root: &str, dst: &ZkClient, dst_root: &str, system: bool)
+               -> Result<MirrorPlan, (ZkError, String)> {
+    let relative = |root: &str, path: &str| match root {
+        _ if path == root => String::new(),
+        "/" => path.to_string(),
+        _ => path[root.len()..].to_string(),
+    };
+    let target = |rel: &str| if rel.is_empty() { dst_root.to_string() } else { join_path(dst_root, &rel[1..]) };
+    let mut plan = MirrorPlan::default();
+
+    let paths = try!(descendants(src, src_root).map_err(|err| (err, src_root.to_string())));
+    let mut kept = HashSet::new();
+    for path in paths.into_iter().filter(|path| system || !in_system_tree(path)) {
+        let (data, stat) = match src.get_data(&*path, false) {
+            Ok(read) => read,
+            Err(ZkError::NoNode) => continue,
+            Err(err) => return Err((err, path)),
+        };
+        let rel = relative(src_root, &*path);
+        let to = target(&*rel);
+        kept.insert(rel);
+        if stat.ephemeral_owner != 0 {
+            continue;
+        }
+        match dst.get_data(&*to, false) {
+            Ok((ref current, _)) if *current == data => (),
+            Ok(_) => plan.updates.push((to, data)),
+            Err(ZkError::NoNode) => {
+                let acl = try!(src.get_acl(&*path).map_err(|err| (err, path.clone()))).0;
+                plan.creates.push((to, data, acl));
+            },
+            Err(err) => return Err((err, to)),
+        }
+    }
+
+    let existing = try!(descendants(dst, dst_root).map_err(|err| (err, dst_root.to_string())));
+    plan.deletes = existing.into_iter().rev()
+        .filter(|path| (system || !in_system_tree(path)) && !kept.contains(&relative(dst_root, path)))
+        .collect();
+    Ok(plan)
+}
+
+// carries out a mirror plan, creates in the order they were found (so
+// parents go first)
+fn mirror(dst: &ZkClient, plan: MirrorPlan) -> Result<Synced, (ZkError, String)> {
+    let mut synced = Synced::default();
+    for (path, data) in plan.updates {
+        try!(dst.set_data(&*path, data, -1).map_err(|err| (err, path.clone())));
+        synced.updated += 1;
+    }
+    for (path, data, acl) in plan.creates {
+        try!(dst.create(&*path, data, acl, CreateMode::Persistent).map_err(|err| (err, path.clone())));
+        synced.created += 1;
+    }
+    for path in plan.deletes {
+        match dst.delete(&*path, -1) {
+            Ok(()) => synced.deleted += 1,
+            Err(ZkError::NoNode) => (),
+            Err(err) => return Err((err, path)),
+        }
+    }
+    Ok(synced)
+}
+
+// a mirror side, either a path in the session or zk://<hosts>/<path> in
+// another ensemble (then with its own session)
+fn mirror_side(shell: &Shell, spec: &str) -> Result<(Option<DryRunClient>, String), String> {
+    if !spec.starts_with("zk://") {
+        return Ok((None, spec.to_string()));
+    }
+    let hosts = try!(ConnectString::parse(&spec[5..]));
+    let path = hosts.chroot.clone().unwrap_or("/".to_string());
+    let timeout = Duration::from_secs(shell.session_timeout);
+    match ZooKeeper::connect(&*hosts.server_list(), timeout, QuietWatcher) {
+        Ok(zk) => Ok((Some(shell.stack(zk)), path)),
+        Err(err) => Err(format!("failed to connect to {}: {:?}", hosts.server_list(), err)),
+    }
+}
+
+// a mirror side's path, without connecting anywhere
+fn mirror_path(spec: &str) -> &str {
+    if !spec.starts_with("zk://") {
+        return spec;
+    }
+    spec[5..].find('/').map_or("/", |idx| &spec[5 + idx..])
+}
+
+pub struct Mirror;
+
+impl Command for Mirror {
+    fn name(&self) -> &str { "mirror" }
+    fn description(&self) -> &str { "Makes dst an exact copy of src, once or (with --interval) until Ctrl-C" }
+    fn synopsis(&self) -> &str { "<src> <dst> [--interval SECS] [--include-system] [--force]" }
+    fn examples(&self) -> &str {
+        "mirror /config /config-copy\nmirror /config zk://dr1:2181,dr2:2181/config --interval 30"
+    }
+    fn min_args(&self) -> usize { 2 }
+    fn max_args(&self) -> usize { 2 }
+    fn mutates(&self, _: &Args) -> bool { true }
+    fn destroys<'a>(&self, args: &'a Args) -> Option<(&'a str, bool)> {
+        // another ensemble's are protected like this one's, a mirror is
+        // most likely laid out the same
+        Some((mirror_path(args.get(1)), true))
+    }
+    // the dry run's pretend writes aren't kept per ensemble
+    fn supports_dry_run(&self, args: &Args) -> bool {
+        !args.get(0).starts_with("zk://") && !args.get(1).starts_with("zk://")
+    }
+
+    fn options(&self) -> Vec<Opt> {
+        vec![
+            Opt::opt("i", "interval", "SECS", "keep mirroring, checking for changes every SECS seconds"),
+            Opt::flag("", "include-system", "don't leave out /zookeeper"),
+            Opt::flag("f", "force", "don't ask before changing dst, allow / as dst"),
+        ]
+    }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        let interval = match args.parse_value::<u64>("interval", 0) {
+            Some(interval) => interval,
+            None => return false,
+        };
+        if mirror_path(args.get(1)) == "/" && !args.flag("force") {
+            out!("Mirroring to / replaces the whole tree, add --force to go ahead.");
+            return false;
+        }
+        let zk = fetch_zk!(shell.zk);
+        let (src, dst) = match (mirror_side(shell, args.get(0)), mirror_side(shell, args.get(1))) {
+            (Ok(src), Ok(dst)) => (src, dst),
+            (Err(err), _) | (_, Err(err)) => {
+                out!("Bad mirror side: {}", err);
+                return false;
+            }
+        };
+        // mirroring into itself would never end
+        let within = |a: &str, b: &str| a == b || b == "/" || a.starts_with(&*format!("{}/", b));
+        if src.0.is_none() && dst.0.is_none() && (within(&*src.1, &*dst.1) || within(&*dst.1, &*src.1)) {
+            out!("Can't mirror {} to {}, one is within the other.", src.1, dst.1);
+            return false;
+        }
+        let src_zk: &ZkClient = src.0.as_ref().map_or(zk, |zk| zk as &ZkClient);
+        let dst_zk: &ZkClient = dst.0.as_ref().map_or(zk, |zk| zk as &ZkClient);
+
+        // the client predates persistent watches, so changes are found by
+        // comparing the trees each time
+        let _guard = interrupt::guard();
+        let mut ok = true;
+        // only the first pass asks, the later ones follow src unattended
+        let mut ask = true;
+        loop {
+            let plan = mirror_plan(src_zk, &*src.1, dst_zk, &*dst.1, args.flag("include-system"));
+            if let (true, &Ok(ref plan)) = (ask, &plan) {
+                let summary = format!("This will create {}, update {} and delete {} znode(s) under {}.",
+                                      plan.creates.len(), plan.updates.len(), plan.deletes.len(), dst.1);
+                if !plan.is_empty() && !confirm_unless_forced(args, &*summary) {
+                    ok = false;
+                    break;
+                }
+                ask = false;
+            }
+            match plan.and_then(|plan| mirror(dst_zk, plan)) {
+                Ok(ref synced) if interval > 0 && synced.created + synced.updated + synced.deleted == 0 => (),
+                Ok(synced) => {
+                    let when = if interval > 0 { format!("{} ", timestamp()) } else { String::new() };
+                    out!("{}Created {}, updated {}, deleted {}.", when, synced.created, synced.updated, synced.deleted);
+                },
+                Err((err, path)) => ok = report_error(err, &*path),
+            }
+            if interval == 0 {
+                break;
+            }
+            // sleeps in small steps, so Ctrl-C doesn't wait for the interval
+            for _ in 0..interval * 10 {
+                if interrupt::interrupted() {
+                    break;
+                }
+                thread::sleep(Duration::from_millis(100));
+            }
+            if interrupt::interrupted() {
+                break;
+            }
+        }
+        if let Some(ref zk) = src.0 {
+            zk.close();
+        }
+        if let Some(ref zk) = dst.0 {
+            zk.close();
+        }
+        ok
+    }
+}
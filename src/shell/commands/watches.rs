@@ -0,0 +1,627 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::process;
+use std::sync::Mutex;
+use std::sync::mpsc::{Sender, channel};
+use std::thread;
+use std::time::Duration;
+use std::usize;
+
+use ansi_term::Colour::White;
+use serde_json::Value;
+use time;
+use zookeeper::{CreateMode, KeeperState, Stat, Watcher, WatchedEvent, WatchedEventType, ZkError, ZkResult};
+
+use events::{self, Event};
+use interrupt;
+use shell::{Args, Command, Opt, Shell, report_error, stat_json, timestamp};
+use util::join_path;
+use watch::{ChannelWatcher, wait_event, wait_event_until};
+
+use super::latencies;
+
+
+// the stat fields that changed, i.e.: version 3 -> 4
+fn stat_delta(old: &Stat, new: &Stat) -> Vec<String> {
+    let mut delta = vec![];
+    macro_rules! field {
+        ($name:expr, $field:ident) => (
+            if old.$field != new.$field {
+                delta.push(format!("{} {} -> {}", $name, old.$field, new.$field));
+            })
+    }
+    field!("version", version);
+    field!("dataLength", data_length);
+    field!("numChildren", num_children);
+    field!("mzxid", mzxid);
+    field!("mtime", mtime);
+    delta
+}
+
+fn session_state(shell: &Shell) -> KeeperState {
+    shell.events.state().unwrap_or(KeeperState::SyncConnected)
+}
+
+// an event for path as of now, in the session's current state
+fn event(shell: &Shell, event_type: WatchedEventType, path: &str) -> Event {
+    Event { time: time::now(), event_type: event_type, keeper_state: session_state(shell), path: Some(path.to_string()) }
+}
+
+// what tail --json prints & record writes: the event's fields (with a null
+// type for the first read or a sample), the value (null while there's no
+// znode) & maybe the stat
+fn change_json(shell: &Shell, fired: Option<WatchedEventType>, path: &str,
+               node: Option<(&[u8], &Stat)>, with_stat: bool) -> Value {
+    let mut fields = json!({
+        "time": timestamp(),
+        "type": fired.map(events::type_name),
+        "path": path,
+        "state": events::state_name(session_state(shell)),
+        "data": node.map(|(data, _)| String::from_utf8_lossy(data).into_owned()),
+    });
+    if let Some((_, stat)) = node {
+        if with_stat {
+            fields["stat"] = stat_json(stat);
+        }
+    }
+    fields
+}
+
+pub struct Tail;
+
+impl Command for Tail {
+    fn name(&self) -> &str { "tail" }
+    fn description(&self) -> &str { "Prints a znode's value every time it changes, until interrupted" }
+    fn synopsis(&self) -> &str { "<path> [--stat] [--json]" }
+    fn examples(&self) -> &str { "tail /config\ntail /config --stat\ntail /config --json" }
+    fn min_args(&self) -> usize { 1 }
+    fn max_args(&self) -> usize { 1 }
+
+    fn options(&self) -> Vec<Opt> {
+        vec![
+            Opt::flag("", "stat", "show the stat fields that changed"),
+            Opt::flag("", "json", "one JSON object per change, with the event, the value and (with --stat) the stat"),
+        ]
+    }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        let zk = fetch_zk!(shell.zk);
+        let path = args.get(0);
+        let (tx, rx) = channel();
+        let _guard = interrupt::guard();
+        let mut prev: Option<Stat> = None;
+        // what fired the last watch, None before the first one
+        let mut fired = None;
+
+        loop {
+            match zk.get_data_w(path, Box::new(ChannelWatcher::new(&tx))) {
+                Ok((bytes, stat)) if args.flag("json") => {
+                    out!("{}", change_json(shell, fired, path, Some((&bytes[..], &stat)), args.flag("stat")));
+                    prev = Some(stat);
+                },
+                Err(ZkError::NoNode) if args.flag("json") => {
+                    out!("{}", change_json(shell, fired, path, None, false));
+                    match zk.exists_w(path, Box::new(ChannelWatcher::new(&tx))) {
+                        Ok(_) => continue, // created in between
+                        Err(ZkError::NoNode) => prev = None,
+                        Err(err) => return report_error(err, path),
+                    }
+                },
+                Ok((bytes, stat)) => {
+                    out!("{} {}", White.bold().paint(&*timestamp()), String::from_utf8_lossy(&bytes[..]));
+                    if args.flag("stat") {
+                        if let Some(ref prev) = prev {
+                            out!("  {}", stat_delta(prev, &stat).join(", "));
+                        }
+                    }
+                    prev = Some(stat);
+                },
+                Err(ZkError::NoNode) => {
+                    out!("{} Path {} does not exist, waiting for it.", White.bold().paint(&*timestamp()), path);
+                    match zk.exists_w(path, Box::new(ChannelWatcher::new(&tx))) {
+                        Ok(_) => continue, // created in between
+                        Err(ZkError::NoNode) => prev = None,
+                        Err(err) => return report_error(err, path),
+                    }
+                },
+                Err(err) => return report_error(err, path),
+            }
+
+            fired = match wait_event(&rx) {
+                Some(event_type) => Some(event_type),
+                None => return true,
+            };
+        }
+    }
+}
+
+pub struct Record;
+
+impl Command for Record {
+    fn name(&self) -> &str { "record" }
+    fn description(&self) -> &str {
+        "Appends a znode's value and stat to a local file (a JSON object per line) every interval seconds, \
+         or on every change with watch, until interrupted"
+    }
+    fn synopsis(&self) -> &str { "<path> <interval|watch> <output-file>" }
+    fn examples(&self) -> &str { "record /config 5 config.jsonl\nrecord /leader watch leader.jsonl" }
+    fn min_args(&self) -> usize { 3 }
+    fn max_args(&self) -> usize { 3 }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        let interval = match args.get(1) {
+            "watch" => None,
+            secs => match secs.parse::<u64>() {
+                Ok(secs) if secs > 0 => Some(secs),
+                _ => {
+                    out!("Bad interval: {}, expected seconds or watch.", secs);
+                    return false;
+                }
+            },
+        };
+        let file = args.get(2);
+        let mut history = match OpenOptions::new().create(true).append(true).open(file) {
+            Ok(history) => history,
+            Err(err) => {
+                out!("Failed to open {}: {}", file, err);
+                return false;
+            }
+        };
+
+        let zk = fetch_zk!(shell.zk);
+        let path = args.get(0);
+        let (tx, rx) = channel();
+        let _guard = interrupt::guard();
+        // what fired the last watch, samples have no event
+        let mut fired = None;
+        let mut samples = 0;
+        out!("Recording {} to {}, until interrupted.", path, file);
+
+        loop {
+            let read = match interval {
+                Some(_) => zk.get_data(path, false),
+                None => zk.get_data_w(path, Box::new(ChannelWatcher::new(&tx))),
+            };
+            let entry = match read {
+                Ok((bytes, stat)) => Some(change_json(shell, fired, path, Some((&bytes[..], &stat)), true)),
+                Err(ZkError::NoNode) => {
+                    if interval.is_none() {
+                        match zk.exists_w(path, Box::new(ChannelWatcher::new(&tx))) {
+                            Ok(_) => continue, // created in between
+                            Err(ZkError::NoNode) => (),
+                            Err(err) => return report_error(err, path),
+                        }
+                    }
+                    Some(change_json(shell, fired, path, None, false))
+                },
+                // incidents are when this matters, so sampling carries on
+                Err(err) if interval.is_some() => {
+                    out!("{} Failed to read {}: {:?}", White.bold().paint(&*timestamp()), path, err);
+                    None
+                },
+                Err(err) => return report_error(err, path),
+            };
+            if let Some(entry) = entry {
+                if let Err(err) = writeln!(history, "{}", entry) {
+                    out!("Failed to write to {}: {}", file, err);
+                    return false;
+                }
+                samples += 1;
+            }
+
+            match interval {
+                Some(secs) => {
+                    // sleeps in small steps, so Ctrl-C doesn't wait for the interval
+                    for _ in 0..secs * 10 {
+                        if interrupt::interrupted() {
+                            break;
+                        }
+                        thread::sleep(Duration::from_millis(100));
+                    }
+                    if interrupt::interrupted() {
+                        break;
+                    }
+                },
+                None => fired = match wait_event(&rx) {
+                    Some(event_type) => Some(event_type),
+                    None => break,
+                },
+            }
+        }
+
+        out!("Recorded {} entries in {}.", samples, file);
+        true
+    }
+}
+
+// the deadline for the [timeout] (in seconds) at args[i], Some(None) if
+// there's none and None if it's bad
+fn wait_deadline(args: &Args, i: usize) -> Option<Option<u64>> {
+    match args.optional(i).map(|secs| secs.parse::<f64>()) {
+        None => Some(None),
+        Some(Ok(secs)) if secs >= 0.0 => Some(Some(time::precise_time_ns() + (secs * 1e9) as u64)),
+        _ => {
+            out!("Bad timeout: {}", args.get(i));
+            None
+        }
+    }
+}
+
+// arms a watch with check (re-arming it after every event) until it says
+// it's done, then true; false if it fails, times out or is interrupted
+fn wait_until(path: &str, deadline: Option<u64>, what: &str,
+              check: &mut FnMut(&Sender<WatchedEventType>) -> ZkResult<bool>) -> bool {
+    let (tx, rx) = channel();
+    let _guard = interrupt::guard();
+    loop {
+        match check(&tx) {
+            Ok(true) => return true,
+            Ok(false) => (),
+            Err(err) => return report_error(err, path),
+        }
+        if wait_event_until(&rx, deadline).is_none() {
+            if !interrupt::interrupted() {
+                out!("Timed out waiting for {} {}.", path, what);
+            }
+            return false;
+        }
+    }
+}
+
+pub struct WaitExists;
+
+impl Command for WaitExists {
+    fn name(&self) -> &str { "wait_exists" }
+    fn description(&self) -> &str { "Blocks until the znode exists, or until timeout seconds go by (then it fails)" }
+    fn synopsis(&self) -> &str { "<path> [timeout]" }
+    fn examples(&self) -> &str { "wait_exists /services/api/ready\nwait_exists /locks/deploy 30" }
+    fn min_args(&self) -> usize { 1 }
+    fn max_args(&self) -> usize { 2 }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        let deadline = match wait_deadline(args, 1) {
+            Some(deadline) => deadline,
+            None => return false,
+        };
+        let zk = fetch_zk!(shell.zk);
+        let path = args.get(0);
+        wait_until(path, deadline, "to exist", &mut |tx| match zk.exists_w(path, Box::new(ChannelWatcher::new(tx))) {
+            Ok(_) => Ok(true),
+            Err(ZkError::NoNode) => Ok(false),
+            Err(err) => Err(err),
+        })
+    }
+}
+
+pub struct WaitValue;
+
+impl Command for WaitValue {
+    fn name(&self) -> &str { "wait_value" }
+    fn description(&self) -> &str {
+        "Blocks until the znode's value is expected (byte for byte), or until timeout seconds go by (then it fails)"
+    }
+    fn synopsis(&self) -> &str { "<path> <expected> [timeout]" }
+    fn examples(&self) -> &str { "wait_value /deploy/state done\nwait_value /config/version 42 60" }
+    fn min_args(&self) -> usize { 2 }
+    fn max_args(&self) -> usize { 3 }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        let deadline = match wait_deadline(args, 2) {
+            Some(deadline) => deadline,
+            None => return false,
+        };
+        let zk = fetch_zk!(shell.zk);
+        let path = args.get(0);
+        let expected = args.get(1).as_bytes();
+        let what = format!("to be {}", args.get(1));
+        wait_until(path, deadline, &*what, &mut |tx| loop {
+            match zk.get_data_w(path, Box::new(ChannelWatcher::new(tx))) {
+                Ok((bytes, _)) => return Ok(&bytes[..] == expected),
+                // a watch on its creation, then
+                Err(ZkError::NoNode) => match zk.exists_w(path, Box::new(ChannelWatcher::new(tx))) {
+                    Ok(_) => continue, // it was just created
+                    Err(ZkError::NoNode) => return Ok(false),
+                    Err(err) => return Err(err),
+                },
+                Err(err) => return Err(err),
+            }
+        })
+    }
+}
+
+pub struct OnChange;
+
+impl Command for OnChange {
+    fn name(&self) -> &str { "on_change" }
+    fn description(&self) -> &str {
+        "Runs a local command (with ZK_PATH and ZK_EVENT_TYPE set) whenever a znode changes"
+    }
+    fn synopsis(&self) -> &str { "<path> [--json] -- <local command>" }
+    fn examples(&self) -> &str {
+        "on_change /config -- echo '$ZK_PATH changed: $ZK_EVENT_TYPE'\non_change /config --json -- ./reload.sh"
+    }
+    fn min_args(&self) -> usize { 3 }
+    fn max_args(&self) -> usize { usize::MAX }
+    fn raw_args(&self) -> bool { true }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        // raw arguments, so --json is spotted by hand
+        let json = args.get(1) == "--json";
+        let sep = if json { 2 } else { 1 };
+        if args.optional(sep) != Some("--") || args.len() <= sep + 1 {
+            out!("Wrong arguments, expected parameters: {}", self.synopsis());
+            return false;
+        }
+
+        let zk = fetch_zk!(shell.zk);
+        let path = args.get(0);
+        let cmd = args.rest(sep + 1);
+        let (tx, rx) = channel();
+        let _guard = interrupt::guard();
+
+        // arms the data & children watches, returns whether the node exists
+        let arm = |children: bool| -> ZkResult<bool> {
+            match zk.get_data_w(path, Box::new(ChannelWatcher::new(&tx))) {
+                Ok(_) => {
+                    if children {
+                        try!(zk.get_children_w(path, Box::new(ChannelWatcher::new(&tx))));
+                    }
+                    Ok(true)
+                },
+                Err(ZkError::NoNode) => match zk.exists_w(path, Box::new(ChannelWatcher::new(&tx))) {
+                    Ok(_) => Ok(true),
+                    Err(ZkError::NoNode) => Ok(false),
+                    Err(err) => Err(err),
+                },
+                Err(err) => Err(err),
+            }
+        };
+
+        let mut exists = match arm(true) {
+            Ok(exists) => exists,
+            Err(err) => return report_error(err, path),
+        };
+
+        // a deletion fires both the data and the children watch
+        let mut skip_deleted = false;
+        loop {
+            let event_type = match wait_event(&rx) {
+                Some(event_type) => event_type,
+                None => return true,
+            };
+
+            if event_type == WatchedEventType::NodeDeleted && skip_deleted {
+                skip_deleted = false;
+                continue;
+            }
+
+            let event = event(shell, event_type, path);
+            if json {
+                out!("{}", event.to_json());
+            } else {
+                out!("{}", event);
+            }
+            let status = process::Command::new("sh")
+                .arg("-c")
+                .arg(&cmd)
+                .env("ZK_PATH", path)
+                .env("ZK_EVENT_TYPE", events::type_name(event_type))
+                .status();
+            match status {
+                Ok(ref status) if status.success() => (),
+                Ok(status) => out!("Command exited with {}", status),
+                Err(err) => out!("Failed to run command: {}", err),
+            }
+
+            let ret = match event_type {
+                WatchedEventType::NodeChildrenChanged => zk.get_children_w(path, Box::new(ChannelWatcher::new(&tx))).map(|_| true),
+                WatchedEventType::NodeDataChanged => arm(false),
+                WatchedEventType::NodeDeleted => {
+                    skip_deleted = exists;
+                    arm(false)
+                },
+                _ => arm(true),
+            };
+            exists = match ret {
+                Ok(exists) => exists,
+                Err(err) => return report_error(err, path),
+            };
+        }
+    }
+}
+
+// the members that joined & left between two child sets
+fn membership_delta(old: &BTreeSet<String>, new: &BTreeSet<String>) -> (Vec<String>, Vec<String>) {
+    (new.difference(old).cloned().collect(), old.difference(new).cloned().collect())
+}
+
+pub struct WatchChildren;
+
+impl Command for WatchChildren {
+    fn name(&self) -> &str { "watch_children" }
+    fn description(&self) -> &str {
+        "Prints the children added to and removed from a znode every time they change, until interrupted"
+    }
+    fn synopsis(&self) -> &str { "<path> [--json]" }
+    fn examples(&self) -> &str { "watch_children /services/api\nwatch_children /services/api --json" }
+    fn min_args(&self) -> usize { 1 }
+    fn max_args(&self) -> usize { 1 }
+
+    fn options(&self) -> Vec<Opt> {
+        vec![Opt::flag("", "json", "one JSON object per change, with the members added and removed")]
+    }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        let zk = fetch_zk!(shell.zk);
+        let path = args.get(0);
+        let json = args.flag("json");
+        let (tx, rx) = channel();
+        let _guard = interrupt::guard();
+        // None before the first read, the empty set while there's no znode
+        let mut members: Option<BTreeSet<String>> = None;
+
+        loop {
+            let current = match zk.get_children_w(path, Box::new(ChannelWatcher::new(&tx))) {
+                Ok(children) => children.into_iter().collect(),
+                Err(ZkError::NoNode) => {
+                    match zk.exists_w(path, Box::new(ChannelWatcher::new(&tx))) {
+                        Ok(_) => continue, // created in between
+                        Err(ZkError::NoNode) => (),
+                        Err(err) => return report_error(err, path),
+                    }
+                    if !json && members.as_ref().map_or(true, |members| !members.is_empty()) {
+                        out!("{} Path {} does not exist, waiting for it.", White.bold().paint(&*timestamp()), path);
+                    }
+                    BTreeSet::new()
+                },
+                Err(err) => return report_error(err, path),
+            };
+
+            let (added, removed) = membership_delta(members.as_ref().unwrap_or(&BTreeSet::new()), &current);
+            if json {
+                if members.is_none() || !added.is_empty() || !removed.is_empty() {
+                    out!("{}", json!({
+                        "time": timestamp(),
+                        "path": path,
+                        "added": added,
+                        "removed": removed,
+                        "children": current.len(),
+                    }));
+                }
+            } else if members.is_none() {
+                let names: Vec<&str> = current.iter().map(|name| &**name).collect();
+                out!("{} {} children: {}", White.bold().paint(&*timestamp()), current.len(), names.join(", "));
+            } else {
+                for name in &added {
+                    out!("{} + {}", White.bold().paint(&*timestamp()), name);
+                }
+                for name in &removed {
+                    out!("{} - {}", White.bold().paint(&*timestamp()), name);
+                }
+            }
+            members = Some(current);
+
+            // data changes fire the exists watch too, re-reading is harmless
+            if wait_event(&rx).is_none() {
+                return true;
+            }
+        }
+    }
+}
+
+// sends the path of each event and when it arrived (in ns)
+struct ArrivalWatcher {
+    tx: Mutex<Sender<(String, u64)>>,
+}
+
+impl Watcher for ArrivalWatcher {
+    fn handle(&self, e: &WatchedEvent) {
+        let now = time::precise_time_ns();
+        if let (Some(ref path), Ok(tx)) = (e.path.as_ref(), self.tx.lock()) {
+            let _ = tx.send((path.to_string(), now));
+        }
+    }
+}
+
+const STRESS_PREFIX: &'static str = "stress-watches-";
+
+pub struct StressWatches;
+
+impl Command for StressWatches {
+    fn name(&self) -> &str { "stress_watches" }
+    fn description(&self) -> &str {
+        "Watches count temporary znodes under path (data & children), then times registration and delivery"
+    }
+    fn synopsis(&self) -> &str { "<path> <count>" }
+    fn examples(&self) -> &str { "stress_watches /tmp 1000\nstress_watches /tmp 10000 --wait 30" }
+    fn min_args(&self) -> usize { 2 }
+    fn max_args(&self) -> usize { 2 }
+    fn mutates(&self, _: &Args) -> bool { true }
+    fn writes<'a>(&self, args: &'a Args) -> Vec<&'a str> { vec![args.get(0)] }
+
+    fn options(&self) -> Vec<Opt> {
+        vec![Opt::opt("w", "wait", "SECS", "how long to wait for the events after the writes (default 10)")]
+    }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        let count = match args.get(1).parse::<usize>() {
+            Ok(count) if count > 0 => count,
+            _ => {
+                out!("Bad count: {}", args.get(1));
+                return false;
+            }
+        };
+        let wait = match args.parse_value("wait", 10) {
+            Some(wait) => Duration::from_secs(wait),
+            None => return false
+        };
+
+        let zk = fetch_zk!(shell.zk);
+        let path = args.get(0);
+        let _guard = interrupt::guard();
+
+        // ephemeral, so they don't outlive the session if this goes wrong
+        let mut nodes = vec![];
+        let prefix = join_path(path, STRESS_PREFIX);
+        for _ in 0..count {
+            match zk.create(&*prefix, vec![], shell.default_acl.clone(), CreateMode::EphemeralSequential) {
+                Ok(node) => nodes.push(node),
+                Err(err) => {
+                    for node in &nodes {
+                        let _ = zk.delete(node, -1);
+                    }
+                    return report_error(err, path);
+                }
+            }
+        }
+
+        let (tx, rx) = channel();
+        let watcher = || Box::new(ArrivalWatcher { tx: Mutex::new(tx.clone()) });
+        let mut registrations = vec![];
+        for node in &nodes {
+            let start = time::precise_time_ns();
+            let ok = zk.get_data_w(node, watcher()).is_ok() && zk.get_children_w(node, watcher()).is_ok();
+            if ok {
+                registrations.push((time::precise_time_ns() - start) as f64 / 2000000.0);
+            }
+        }
+        if registrations.is_empty() {
+            out!("Failed to register any watches under {}.", path);
+            for node in &nodes {
+                let _ = zk.delete(node, -1);
+            }
+            return false;
+        }
+        out!("Registered {} watches on {} znodes, {} per watch.",
+             registrations.len() * 2, nodes.len(), latencies(&*registrations));
+
+        // deleting fires both watches on each node, and cleans up
+        let mut written = HashMap::new();
+        for node in &nodes {
+            written.insert(node.clone(), time::precise_time_ns());
+            if let Err(err) = zk.delete(node, -1) {
+                out!("Failed to delete {}: {:?}", node, err);
+            }
+        }
+
+        let expected = registrations.len() * 2;
+        let deadline = time::precise_time_ns() + wait.as_secs() * 1000000000;
+        let mut delivered = vec![];
+        while delivered.len() < expected && time::precise_time_ns() < deadline && !interrupt::interrupted() {
+            match rx.try_recv() {
+                Ok((node, arrived)) => if let Some(&at) = written.get(&node) {
+                    delivered.push(arrived.saturating_sub(at) as f64 / 1000000.0);
+                },
+                Err(_) => thread::sleep(Duration::from_millis(10)),
+            }
+        }
+
+        if delivered.is_empty() {
+            out!("None of the {} events arrived.", expected);
+            return false;
+        }
+        out!("Delivered {}/{} events, {} after the write.", delivered.len(), expected, latencies(&*delivered));
+        delivered.len() == expected
+    }
+}
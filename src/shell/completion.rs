@@ -0,0 +1,226 @@
+//! Completion scripts for bash, zsh & fish, covering the binary's flags and
+//! the commands it runs directly (i.e.: zk-shell-rs get /path), generated
+//! from the registry so they know about every command.
+
+use std::rc::Rc;
+
+use super::{COMMAND_FLAGS, Command, Registry};
+
+
+/// One of the binary's own flags.
+pub struct Flag {
+    pub long: String,
+    pub takes_value: bool,
+    pub desc: String,
+}
+
+/// The shells there's a script for.
+pub const SHELLS: [&'static str; 3] = ["bash", "zsh", "fish"];
+
+// the command that prints the scripts, it's the binary's and not the shell's
+const COMPLETION: (&'static str, &'static str) = ("completion", "Prints a completion script for bash, zsh or fish");
+
+/// The completion script for shell (bash, zsh or fish), None for others.
+pub fn script(shell: &str, program: &str, flags: &[Flag], registry: &Registry) -> Option<String> {
+    let commands = registry.commands();
+    match shell {
+        "bash" => Some(bash(program, flags, &*commands, registry)),
+        "zsh" => Some(zsh(program, flags, &*commands, registry)),
+        "fish" => Some(fish(program, flags, &*commands, registry)),
+        _ => None,
+    }
+}
+
+// i.e.: zk-shell-rs -> _zk_shell_rs
+fn function_name(program: &str) -> String {
+    let name: String = program.chars().map(|c| if c.is_alphanumeric() { c } else { '_' }).collect();
+    format!("_{}", name)
+}
+
+// the command's name and whatever aliases still lead to it
+fn names(cmd: &Command, registry: &Registry) -> Vec<String> {
+    let mut names = vec![cmd.name().to_string()];
+    names.extend(cmd.aliases().iter()
+                 .filter(|alias| registry.get(alias).map_or(false, |found| found.name() == cmd.name()))
+                 .map(|alias| alias.to_string()));
+    names
+}
+
+// what can follow the command: its options, then the ones every command takes
+fn command_options(cmd: &Command) -> Vec<String> {
+    let mut options = vec![];
+    for opt in cmd.options() {
+        if !opt.short.is_empty() {
+            options.push(format!("-{}", opt.short));
+        }
+        options.push(format!("--{}", opt.long));
+    }
+    options.extend(COMMAND_FLAGS.iter().map(|flag| flag.to_string()));
+    options
+}
+
+fn value_flags(flags: &[Flag]) -> String {
+    let flags: Vec<String> = flags.iter().filter(|flag| flag.takes_value).map(|flag| format!("--{}", flag.long)).collect();
+    flags.join("|")
+}
+
+fn bash(program: &str, flags: &[Flag], commands: &[Rc<Command>], registry: &Registry) -> String {
+    let function = function_name(program);
+    let all_flags: Vec<String> = flags.iter().map(|flag| format!("--{}", flag.long)).collect();
+    let mut all_commands: Vec<String> = registry.names();
+    all_commands.push(COMPLETION.0.to_string());
+
+    let mut lines = vec![
+        format!("# bash completion for {}, i.e.: source <({} completion bash)", program, program),
+        format!("{}() {{", function),
+        "    local cur=\"${COMP_WORDS[COMP_CWORD]}\" prev=\"${COMP_WORDS[COMP_CWORD-1]}\"".to_string(),
+        "    case \"$prev\" in".to_string(),
+        // the value's up to the user (or to the files, with -o default)
+        format!("        {}) return ;;", value_flags(flags)),
+        "    esac".to_string(),
+        "".to_string(),
+        "    # the command is the first word that isn't a flag (or a flag's value)".to_string(),
+        "    local i cmd=\"\"".to_string(),
+        "    for ((i = 1; i < COMP_CWORD; i++)); do".to_string(),
+        "        case \"${COMP_WORDS[i]}\" in".to_string(),
+        format!("            {}) ((i++)) ;;", value_flags(flags)),
+        "            -*) ;;".to_string(),
+        "            *) cmd=\"${COMP_WORDS[i]}\"; break ;;".to_string(),
+        "        esac".to_string(),
+        "    done".to_string(),
+        "".to_string(),
+        "    if [[ -z \"$cmd\" ]]; then".to_string(),
+        "        if [[ \"$cur\" == -* ]]; then".to_string(),
+        format!("            COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))", all_flags.join(" ")),
+        "        else".to_string(),
+        format!("            COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))", all_commands.join(" ")),
+        "        fi".to_string(),
+        "        return".to_string(),
+        "    fi".to_string(),
+        "    case \"$cmd\" in".to_string(),
+        format!("        {}) COMPREPLY=($(compgen -W \"{}\" -- \"$cur\")) ;;", COMPLETION.0, SHELLS.join(" ")),
+    ];
+    for cmd in commands {
+        lines.push(format!("        {}) [[ \"$cur\" == -* ]] && COMPREPLY=($(compgen -W \"{}\" -- \"$cur\")) ;;",
+                           names(&**cmd, registry).join("|"), command_options(&**cmd).join(" ")));
+    }
+    lines.push("    esac".to_string());
+    lines.push("}".to_string());
+    lines.push(format!("complete -o default -F {} {}", function, program));
+    lines.join("\n") + "\n"
+}
+
+// inside single quotes
+fn zsh_quote(text: &str) -> String {
+    format!("'{}'", text.replace("'", "'\\''"))
+}
+
+fn zsh(program: &str, flags: &[Flag], commands: &[Rc<Command>], registry: &Registry) -> String {
+    let function = function_name(program);
+    let mut lines = vec![
+        format!("#compdef {}", program),
+        format!("# zsh completion for {}, i.e.: source <({} completion zsh)", program, program),
+        format!("{}() {{", function),
+        "    local i cmd".to_string(),
+        "    local -a described".to_string(),
+        "    # the command is the first word that isn't a flag (or a flag's value)".to_string(),
+        "    for ((i = 2; i < CURRENT; i++)); do".to_string(),
+        "        case \"$words[i]\" in".to_string(),
+        format!("            {}) ((i++)) ;;", value_flags(flags)),
+        "            -*) ;;".to_string(),
+        "            *) cmd=\"$words[i]\"; break ;;".to_string(),
+        "        esac".to_string(),
+        "    done".to_string(),
+        "".to_string(),
+        "    if [[ -z \"$cmd\" ]]; then".to_string(),
+        "        if [[ \"$PREFIX\" == -* ]]; then".to_string(),
+        "            described=(".to_string(),
+    ];
+    for flag in flags {
+        lines.push(format!("                {}", zsh_quote(&*format!("--{}:{}", flag.long, flag.desc))));
+    }
+    lines.push("            )".to_string());
+    lines.push("            _describe 'flag' described".to_string());
+    lines.push("        else".to_string());
+    lines.push("            described=(".to_string());
+    for cmd in commands {
+        for name in names(&**cmd, registry) {
+            lines.push(format!("                {}", zsh_quote(&*format!("{}:{}", name, cmd.description()))));
+        }
+    }
+    lines.push(format!("                {}", zsh_quote(&*format!("{}:{}", COMPLETION.0, COMPLETION.1))));
+    lines.push("            )".to_string());
+    lines.push("            _describe 'command' described".to_string());
+    lines.push("        fi".to_string());
+    lines.push("        return".to_string());
+    lines.push("    fi".to_string());
+    lines.push("    case \"$cmd\" in".to_string());
+    lines.push(format!("        {}) compadd -- {} ;;", COMPLETION.0, SHELLS.join(" ")));
+    for cmd in commands {
+        lines.push(format!("        {}) [[ \"$PREFIX\" == -* ]] && compadd -- {} ;;",
+                           names(&**cmd, registry).join("|"), command_options(&**cmd).join(" ")));
+    }
+    lines.push("    esac".to_string());
+    lines.push("}".to_string());
+    lines.push(format!("compdef {} {}", function, program));
+    lines.join("\n") + "\n"
+}
+
+fn fish_quote(text: &str) -> String {
+    format!("'{}'", text.replace("\\", "\\\\").replace("'", "\\'"))
+}
+
+fn fish(program: &str, flags: &[Flag], commands: &[Rc<Command>], registry: &Registry) -> String {
+    let function = function_name(program);
+    let value_flags: Vec<String> = flags.iter().filter(|flag| flag.takes_value).map(|flag| format!("--{}", flag.long)).collect();
+    let mut lines = vec![
+        format!("# fish completion for {}, i.e.: {} completion fish | source", program, program),
+        "# the command is the first word that isn't a flag (or a flag's value)".to_string(),
+        format!("function {}_command", function),
+        "    set -l words (commandline -opc)".to_string(),
+        "    set -e words[1]".to_string(),
+        "    while set -q words[1]".to_string(),
+        "        switch $words[1]".to_string(),
+        format!("            case {}", value_flags.join(" ")),
+        "                set -e words[1]".to_string(),
+        "            case '-*'".to_string(),
+        "            case '*'".to_string(),
+        "                echo $words[1]".to_string(),
+        "                return".to_string(),
+        "        end".to_string(),
+        "        set -e words[1]".to_string(),
+        "    end".to_string(),
+        "end".to_string(),
+        format!("function {}_using", function),
+        format!("    set -l cmd ({}_command)", function),
+        "    test \"$cmd\" = \"$argv[1]\"".to_string(),
+        "end".to_string(),
+        "".to_string(),
+    ];
+    let before = format!("complete -c {} -n '{}_using \"\"'", program, function);
+    for flag in flags {
+        lines.push(format!("{} -l {}{} -d {}", before, flag.long, if flag.takes_value { " -r" } else { "" }, fish_quote(&*flag.desc)));
+    }
+    for cmd in commands {
+        for name in names(&**cmd, registry) {
+            lines.push(format!("{} -f -a {} -d {}", before, name, fish_quote(cmd.description())));
+        }
+    }
+    lines.push(format!("{} -f -a {} -d {}", before, COMPLETION.0, fish_quote(COMPLETION.1)));
+
+    lines.push(format!("complete -c {} -n '{}_using {}' -f -a {}", program, function, COMPLETION.0, fish_quote(&*SHELLS.join(" "))));
+    for flag in COMMAND_FLAGS.iter() {
+        lines.push(format!("complete -c {} -n 'not {}_using \"\"' -l {}", program, function, &flag[2..]));
+    }
+    for cmd in commands {
+        for name in names(&**cmd, registry) {
+            let using = format!("complete -c {} -n '{}_using {}'", program, function, name);
+            for opt in cmd.options() {
+                let short = if opt.short.is_empty() { String::new() } else { format!(" -s {}", opt.short) };
+                let value = if opt.hint.is_empty() { "" } else { " -r" };
+                lines.push(format!("{}{} -l {}{} -d {}", using, short, opt.long, value, fish_quote(opt.desc)));
+            }
+        }
+    }
+    lines.join("\n") + "\n"
+}
@@ -0,0 +1,254 @@
+/// Splits a command line into arguments, much like sh does:
+///
+/// - 'single quotes' keep everything as is
+/// - "double quotes" keep whitespace but expand variables
+/// - $NAME and ${NAME} are replaced by lookup(NAME), it's an error if
+///   there's no such variable
+/// - a backslash escapes the next character (i.e.: \$ or \ )
+/// - an unquoted # at the start of a word comments out the rest of the line
+pub fn tokenize<F>(line: &str, lookup: &F) -> Result<Vec<String>, String> where F: Fn(&str) -> Option<String> {
+    let chars: Vec<char> = line.chars().collect();
+    let mut tokens = vec![];
+    let mut current = String::new();
+    // a word was started, even if it's empty (i.e.: '')
+    let mut in_word = false;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        i += 1;
+        match c {
+            c if c.is_whitespace() => {
+                if in_word {
+                    tokens.push(current.clone());
+                    current.clear();
+                    in_word = false;
+                }
+            },
+            '#' if !in_word => break,
+            '\'' => {
+                in_word = true;
+                match chars[i..].iter().position(|&c| c == '\'') {
+                    Some(end) => {
+                        current.extend(chars[i..i + end].iter().cloned());
+                        i += end + 1;
+                    },
+                    None => return Err("Unterminated quote.".to_string()),
+                }
+            },
+            '"' => {
+                in_word = true;
+                loop {
+                    match chars.get(i).cloned() {
+                        Some('"') => {
+                            i += 1;
+                            break;
+                        },
+                        Some('\\') if i + 1 < chars.len() && "$\"\\".contains(chars[i + 1]) => {
+                            current.push(chars[i + 1]);
+                            i += 2;
+                        },
+                        Some('$') => i = try!(expand(&chars, i + 1, lookup, &mut current)),
+                        Some(c) => {
+                            current.push(c);
+                            i += 1;
+                        },
+                        None => return Err("Unterminated quote.".to_string()),
+                    }
+                }
+            },
+            '\\' => {
+                in_word = true;
+                if i < chars.len() {
+                    current.push(chars[i]);
+                    i += 1;
+                }
+            },
+            '$' => {
+                in_word = true;
+                i = try!(expand(&chars, i, lookup, &mut current));
+            },
+            c => {
+                in_word = true;
+                current.push(c);
+            },
+        }
+    }
+
+    if in_word {
+        tokens.push(current);
+    }
+    Ok(tokens)
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+// expands the variable whose name starts at i (just past the $), returning
+// where it ends; a $ that isn't followed by a name is kept
+fn expand<F>(chars: &[char], i: usize, lookup: &F, out: &mut String) -> Result<usize, String>
+    where F: Fn(&str) -> Option<String> {
+    let (name, end) = if chars.get(i) == Some(&'{') {
+        match chars[i..].iter().position(|&c| c == '}') {
+            Some(len) => (chars[i + 1..i + len].iter().cloned().collect::<String>(), i + len + 1),
+            None => return Err("Unterminated ${.".to_string()),
+        }
+    } else {
+        let len = chars[i..].iter().take_while(|&&c| is_name_char(c)).count();
+        (chars[i..i + len].iter().cloned().collect::<String>(), i + len)
+    };
+
+    if name.is_empty() {
+        out.push('$');
+        return Ok(end);
+    }
+    match lookup(&*name) {
+        Some(value) => {
+            out.push_str(&*value);
+            Ok(end)
+        },
+        None => Err(format!("Undefined variable: {}", name)),
+    }
+}
+
+/// Quotes arg so tokenize gives it back untouched, i.e.: when a command
+/// line is rebuilt from arguments.
+pub fn quote(arg: &str) -> String {
+    let plain = !arg.is_empty() && arg.chars().all(|c| is_name_char(c) || "/-.,:=@+%".contains(c));
+    if plain {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace("'", "'\\''"))
+    }
+}
+
+// the chars of line, each paired with whether it's outside quotes (and
+// not escaped or commented out), which is where && or then mean something
+fn unquoted(line: &str) -> Vec<(char, bool)> {
+    let mut chars = vec![];
+    let mut quote = None;
+    let mut escaped = false;
+    let mut comment = false;
+    let mut word_start = true;
+
+    for c in line.chars() {
+        let plain = !comment && !escaped && quote.is_none();
+        match c {
+            _ if comment => (),
+            _ if escaped => escaped = false,
+            '\\' if quote != Some('\'') => escaped = true,
+            '\'' | '"' if quote == Some(c) => quote = None,
+            '\'' | '"' if quote.is_none() => quote = Some(c),
+            '#' if quote.is_none() && word_start => comment = true,
+            _ => (),
+        }
+        let special = plain && !"\\'\"#".contains(c);
+        word_start = c.is_whitespace();
+        chars.push((c, special));
+    }
+    chars
+}
+
+/// How a command is chained to the one before it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Chain {
+    /// runs if the one before succeeded
+    And,
+    /// runs if the one before failed
+    Or,
+}
+
+/// Splits line at the unquoted && and ||, i.e.: `a && b || c` is a,
+/// [(And, b), (Or, c)]. The pieces aren't tokenized yet.
+pub fn split_chain(line: &str) -> (String, Vec<(Chain, String)>) {
+    let chars = unquoted(line);
+    let mut first = None;
+    let mut rest = vec![];
+    let mut current = String::new();
+    let mut op = None;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let (c, special) = chars[i];
+        let doubled = special && i + 1 < chars.len() && chars[i + 1] == (c, true);
+        match c {
+            '&' | '|' if doubled => {
+                let piece = current.trim().to_string();
+                match op {
+                    None => first = Some(piece),
+                    Some(op) => rest.push((op, piece)),
+                }
+                op = Some(if c == '&' { Chain::And } else { Chain::Or });
+                current.clear();
+                i += 2;
+                continue;
+            },
+            c => current.push(c),
+        }
+        i += 1;
+    }
+
+    let piece = current.trim().to_string();
+    match op {
+        None => (piece, rest),
+        Some(op) => {
+            rest.push((op, piece));
+            (first.unwrap_or(String::new()), rest)
+        }
+    }
+}
+
+/// Splits `if <cond> then <cmd> [else <cmd>]` into its three commands,
+/// None if line isn't an if.
+pub fn split_if(line: &str) -> Result<Option<(String, String, Option<String>)>, String> {
+    let chars = unquoted(line);
+    // the unquoted words, as (start, end) char offsets
+    let mut words = vec![];
+    let mut start = None;
+    for (i, &(c, special)) in chars.iter().enumerate() {
+        match (special && c.is_whitespace(), start) {
+            (true, Some(s)) => {
+                words.push((s, i));
+                start = None;
+            },
+            (false, None) => start = Some(i),
+            _ => (),
+        }
+    }
+    if let Some(s) = start {
+        words.push((s, chars.len()));
+    }
+
+    let text = |start: usize, end: usize| -> String {
+        chars[start..end].iter().map(|&(c, _)| c).collect::<String>().trim().to_string()
+    };
+    let is = |word: &(usize, usize), keyword: &str| text(word.0, word.1) == keyword;
+
+    match words.first() {
+        Some(word) if is(word, "if") => (),
+        _ => return Ok(None),
+    }
+    let then = match words.iter().position(|word| is(word, "then")) {
+        Some(then) => then,
+        None => return Err("Expected: if <command> then <command> [else <command>]".to_string()),
+    };
+    let otherwise = words.iter().skip(then).position(|word| is(word, "else")).map(|i| i + then);
+
+    let cond = text(words[0].1, words[then].0);
+    let body_end = otherwise.map_or(chars.len(), |i| words[i].0);
+    let body = text(words[then].1, body_end);
+    if cond.is_empty() || body.is_empty() {
+        return Err("Expected: if <command> then <command> [else <command>]".to_string());
+    }
+    let alternative = match otherwise {
+        Some(i) => match text(words[i].1, chars.len()) {
+            ref alternative if alternative.is_empty() => {
+                return Err("Expected a command after else.".to_string());
+            },
+            alternative => Some(alternative),
+        },
+        None => None,
+    };
+    Ok(Some((cond, body, alternative)))
+}
@@ -0,0 +1,1091 @@
+use std::cmp;
+use std::collections::HashMap;
+use std::env;
+use std::fs::{self, File};
+use std::io;
+use std::io::BufRead;
+use std::io::Read;
+use std::io::stdin;
+use std::io::stdout;
+use std::io::Write;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::Deserialize;
+use serde_json::{self, Value};
+use serde_yaml;
+use time;
+use zookeeper::{Acl, KeeperState, Stat, Watcher, WatchedEvent, ZkError, ZooKeeper};
+use zookeeper::acls;
+
+use audit::AuditLog;
+use client::{DryRun, DryRunClient, LoggingClient, RetryPolicy, RetryingClient, ZkClient};
+use config::Profile;
+use crypto;
+use discovery;
+use events::{self, Event, EventLog, LogWatcher};
+use hosts::ConnectString;
+use metrics::Metrics;
+use recipes::CounterError;
+use traversal::descendants;
+use util::{SYSTEM_TREE, in_system_tree};
+
+use self::line::Chain;
+
+pub use self::command::{Args, Command, Opt, Registry};
+pub use self::plugins::ScriptCommand;
+
+
+// command output, which can be captured (see output::capture)
+macro_rules! out {
+    ($($arg:tt)*) => (::shell::output::print(&*format!($($arg)*)))
+}
+
+// are we connected?
+macro_rules! fetch_zk {
+    ($e:expr) => (
+        match $e {
+            Some(ref __zk) => &**__zk,
+            _ => {
+                out!("Not connected.");
+                return false;
+            }
+        })
+}
+
+pub mod command;
+mod commands;
+pub mod completion;
+pub mod line;
+pub mod output;
+pub mod plugins;
+
+pub struct Shell {
+    hosts: String,
+    followed_hosts: Option<Arc<Mutex<String>>>,
+    zk: Option<Arc<ZkClient>>,
+    session_timeout: u64,
+    connect_timeout: u64,
+    default_acl: Vec<Acl>,
+    executed: u64,
+    events: Arc<EventLog>,
+    registry: Registry,
+    variables: HashMap<String, String>,
+    auths: Vec<(String, Vec<u8>)>,
+    read_only: bool,
+    audit: Option<AuditLog>,
+    dry_run: Arc<DryRun>,
+    // between commands, it holds the default retries
+    retry: Arc<RetryPolicy>,
+    command_timeout: Option<u64>,
+    metrics: Arc<Metrics>,
+    compress: bool,
+    max_buffer_size: usize,
+    encryption_key: Option<crypto::Key>,
+    prefix_matching: bool,
+    protected: Vec<String>,
+    format: Format,
+    validate: Option<Syntax>,
+    locks: HashMap<String, String>,
+    candidates: HashMap<String, String>,
+    barriers: HashMap<String, String>,
+    // what the lines after the command being run said, for its - argument
+    value: Option<String>,
+    // sessions with single servers, for --server
+    servers: HashMap<String, Arc<ZkClient>>,
+    profiles: HashMap<String, Profile>,
+    // the one last used, the last hosts file names it rather than its hosts
+    profile: Option<String>,
+    // what fill & benchmark left behind, deleted (with their subtrees) on exit
+    scratch: Vec<String>,
+    keep_scratch: bool,
+    // exit was run
+    exiting: bool,
+    // where the hosts of the last session are kept, for connect without any
+    last_hosts_file: Option<PathBuf>,
+}
+
+/// Prints a friendly version of error, always returns false so commands
+/// can return it as their status.
+pub fn report_error(error: ZkError, path: &str) -> bool {
+    match error {
+        ZkError::NoNode => out!("Path {} does not exist.", path),
+        ZkError::NotEmpty => out!("Path {} is not empty.", path),
+        ZkError::OperationTimeout => out!("Timed out on {}.", path),
+        unknown => out!("Unknown error: {:?}", unknown),
+    }
+    false
+}
+
+fn report_counter_error(error: CounterError, path: &str) -> bool {
+    match error {
+        CounterError::Zk(err) => report_error(err, path),
+        CounterError::NotANumber => {
+            out!("Path {} doesn't hold a number.", path);
+            false
+        }
+    }
+}
+
+fn timestamp() -> String {
+    time::now().rfc3339().to_string()
+}
+
+// znode times are in ms since the epoch
+fn format_time(ms: i64) -> String {
+    let spec = time::Timespec::new(ms / 1000, ((ms % 1000) * 1000000) as i32);
+    time::at(spec).rfc3339().to_string()
+}
+
+// the lines up to a lone . (or the end of input), as one value
+fn read_value<I: Iterator<Item = String>>(lines: I, prompt: bool) -> String {
+    let continuation = || if prompt {
+        print!("... ");
+        let _ = stdout().flush();
+    };
+    let mut value = vec![];
+    continuation();
+    for line in lines {
+        if line.trim_right() == "." {
+            break;
+        }
+        value.push(line.trim_right_matches(|c| c == '\r' || c == '\n').to_string());
+        continuation();
+    }
+    value.join("\n")
+}
+
+fn confirm(question: &str) -> bool {
+    let mut answer = String::new();
+
+    print!("{} [y/N] ", question);
+    let _ = stdout().flush();
+
+    match stdin().read_line(&mut answer) {
+        Ok(_) => ["y", "yes"].contains(&&*answer.trim().to_lowercase()),
+        Err(_) => false
+    }
+}
+
+// how the commands that change znodes in bulk (rmr, mirror, setacl
+// --recursive, rm with a glob) go ahead: saying how many first and asking,
+// unless given --force
+fn confirm_unless_forced(args: &Args, summary: &str) -> bool {
+    if args.flag("force") {
+        return true;
+    }
+    out!("{}", summary);
+    confirm("Continue?")
+}
+
+/// Where the hosts of the last session are kept by default:
+/// ~/.zk_shell_rs/last_hosts
+pub fn default_last_hosts_file() -> Option<PathBuf> {
+    env::home_dir().map(|home| home.join(".zk_shell_rs").join("last_hosts"))
+}
+
+const EVENTS_CAPACITY: usize = 100;
+
+const OVERRIDE_FLAG: &'static str = "--i-know-what-i-am-doing";
+
+// jute.maxbuffer's default, a bit under 1MB
+const MAX_BUFFER_SIZE: usize = 0xfffff;
+
+const DRY_RUN_FLAG: &'static str = "--dry-run";
+
+// what the last hosts file holds, instead of hosts, after connect --profile
+const LAST_PROFILE_PREFIX: &'static str = "profile:";
+
+const SERVER_FLAG: &'static str = "--server";
+
+const TIMEOUT_FLAG: &'static str = "--timeout";
+
+const RETRIES_FLAG: &'static str = "--retries";
+
+/// The flags any command takes, the shell handles them.
+pub const COMMAND_FLAGS: [&'static str; 5] = [OVERRIDE_FLAG, DRY_RUN_FLAG, SERVER_FLAG, TIMEOUT_FLAG, RETRIES_FLAG];
+
+// for auxiliary sessions (i.e.: --server, benchmark workers), their events
+// aren't the shell's
+struct QuietWatcher;
+
+impl Watcher for QuietWatcher {
+    fn handle(&self, _: &WatchedEvent) {}
+}
+
+/// How commands print what they read.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Text,
+    Json,
+}
+
+impl Format {
+    pub fn parse(format: &str) -> Option<Format> {
+        match format {
+            "text" => Some(Format::Text),
+            "json" => Some(Format::Json),
+            _ => None
+        }
+    }
+}
+
+/// What set & create check values against before writing them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Syntax {
+    Json,
+    Yaml,
+}
+
+impl Syntax {
+    pub fn parse(syntax: &str) -> Option<Syntax> {
+        match syntax {
+            "json" => Some(Syntax::Json),
+            "yaml" => Some(Syntax::Yaml),
+            _ => None
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Syntax::Json => "JSON",
+            Syntax::Yaml => "YAML",
+        }
+    }
+
+    /// Ok if text is a well-formed document, otherwise what's wrong with it.
+    pub fn check(&self, text: &str) -> Result<(), String> {
+        match *self {
+            Syntax::Json => serde_json::from_str::<Value>(text).map(|_| ()).map_err(|err| err.to_string()),
+            Syntax::Yaml => {
+                // a stream may hold several documents
+                for document in serde_yaml::Deserializer::from_str(text) {
+                    try!(serde_yaml::Value::deserialize(document).map_err(|err| err.to_string()));
+                }
+                Ok(())
+            },
+        }
+    }
+}
+
+fn stat_json(stat: &Stat) -> Value {
+    json!({
+        "czxid": stat.czxid,
+        "mzxid": stat.mzxid,
+        "pzxid": stat.pzxid,
+        "ctime": stat.ctime,
+        "mtime": stat.mtime,
+        "version": stat.version,
+        "cversion": stat.cversion,
+        "aversion": stat.aversion,
+        "ephemeralOwner": stat.ephemeral_owner,
+        "dataLength": stat.data_length,
+        "numChildren": stat.num_children,
+    })
+}
+
+impl Shell {
+    pub fn new(hosts: &str) -> Shell {
+        let mut registry = Registry::new();
+        commands::register_all(&mut registry);
+
+        Shell {
+            hosts: hosts.to_string(),
+            followed_hosts: None,
+            zk: None,
+            session_timeout: 5,
+            connect_timeout: 10,
+            default_acl: acls::OPEN_ACL_UNSAFE.clone(),
+            executed: 0,
+            events: Arc::new(EventLog::new(EVENTS_CAPACITY)),
+            registry: registry,
+            variables: HashMap::new(),
+            auths: vec![],
+            read_only: false,
+            audit: None,
+            dry_run: Arc::new(DryRun::new()),
+            retry: Arc::new(RetryPolicy::new()),
+            command_timeout: None,
+            metrics: Arc::new(Metrics::new()),
+            compress: false,
+            max_buffer_size: MAX_BUFFER_SIZE,
+            encryption_key: None,
+            prefix_matching: false,
+            protected: vec![],
+            format: Format::Text,
+            validate: None,
+            locks: HashMap::new(),
+            candidates: HashMap::new(),
+            barriers: HashMap::new(),
+            value: None,
+            servers: HashMap::new(),
+            profiles: HashMap::new(),
+            profile: None,
+            scratch: vec![],
+            keep_scratch: false,
+            exiting: false,
+            last_hosts_file: None,
+        }
+    }
+
+    pub fn run(&mut self) {
+        self.connect_initial();
+        self.events.set_interactive(true);
+
+        loop {
+            let mut line = String::new();
+
+            print!("{} ", self.prompt());
+            let _ = stdout().flush();
+
+            let read = match stdin().read_line(&mut line) {
+                Ok(read) => read,
+                Err(err) => {
+                    // e.g. a line that isn't UTF-8, skip it
+                    println!("Failed to read line: {}", err);
+                    continue;
+                }
+            };
+            // Ctrl-D
+            if read == 0 {
+                println!("");
+                break;
+            }
+
+            // the client keeps the session alive while this waits for
+            // input, but an expired one stays expired
+            if self.zk.is_some() && self.events.state() == Some(KeeperState::Expired) {
+                if confirm("The session expired, start a new one?") {
+                    let hosts = self.current_hosts();
+                    self.close();
+                    self.connect_to(&*hosts);
+                }
+            }
+
+            if self.reads_value(&line) {
+                let stdin = stdin();
+                let lines = stdin.lock().lines().take_while(|line| line.is_ok()).map(|line| line.unwrap());
+                self.value = Some(read_value(lines, true));
+            }
+            self.execute(&line);
+            self.value = None;
+            if self.exiting {
+                break;
+            }
+        }
+
+        self.shutdown();
+    }
+
+    // i.e.: (connected) >, so a dropped session doesn't go unnoticed
+    fn prompt(&self) -> String {
+        match (&self.zk, self.events.state()) {
+            (&None, _) => "(not connected) >".to_string(),
+            (&Some(_), Some(state)) => format!("({}) >", events::state_name(state)),
+            (&Some(_), None) => ">".to_string(),
+        }
+    }
+
+    /// A shell already connected through client, i.e.: a `mem::MemClient`.
+    pub fn with_client(client: Box<ZkClient>) -> Shell {
+        let mut shell = Shell::new("");
+        let client = LoggingClient::new(client, shell.metrics.clone());
+        let client = RetryingClient::new(Box::new(client), shell.retry.clone());
+        shell.zk = Some(Arc::new(DryRunClient::new(Box::new(client), shell.dry_run.clone())));
+        shell
+    }
+
+    /// Runs a single command, returning whether it succeeded.
+    pub fn run_once(&mut self, line: &str) -> bool {
+        self.connect_initial();
+        let status = self.execute(line);
+
+        // the caller is about to exit, don't leave the session (or the
+        // scratch znodes) around
+        self.shutdown();
+        status
+    }
+
+    /// Runs every line from input (i.e.: stdin), returning whether all of
+    /// them succeeded.
+    pub fn run_script<R: BufRead>(&mut self, input: R) -> bool {
+        self.connect_initial();
+        let mut status = true;
+        let mut lines = input.lines();
+        while let Some(line) = lines.next() {
+            match line {
+                Ok(line) => {
+                    if self.reads_value(&line) {
+                        let value = lines.by_ref().take_while(|line| line.is_ok()).map(|line| line.unwrap());
+                        self.value = Some(read_value(value, false));
+                    }
+                    status = self.execute(&*line) && status;
+                    self.value = None;
+                    if self.exiting {
+                        break;
+                    }
+                },
+                Err(err) => {
+                    println!("Failed to read the script: {}", err);
+                    status = false;
+                    break;
+                }
+            }
+        }
+
+        self.shutdown();
+        status
+    }
+
+    // whether line's (first) command takes a - value, to be read next
+    fn reads_value(&self, line: &str) -> bool {
+        let (first, _) = line::split_chain(line);
+        match line::tokenize(&*first, &|name| self.variable(name)) {
+            Ok(tokens) => tokens.first().and_then(|name| self.registry.get(name)).map_or(false, |cmd| {
+                cmd.reads_value() && tokens.iter().skip(1).any(|token| token == "-")
+            }),
+            Err(_) => false,
+        }
+    }
+
+    /// The value read for a - argument, None (after complaining) if there
+    /// was none to read, i.e.: for a command that didn't come from a prompt
+    /// or a script.
+    fn take_value(&mut self) -> Option<String> {
+        let value = self.value.take();
+        if value.is_none() {
+            out!("There's nothing to read the value for - from, pass it instead.");
+        }
+        value
+    }
+
+    /// Adds a command, replacing any built-in one with the same name.
+    pub fn register<C: Command + 'static>(&mut self, cmd: C) {
+        self.registry.register(cmd);
+    }
+
+    /// Registers the script plugins found in dir, returning how many.
+    pub fn load_plugins(&mut self, dir: &Path) -> usize {
+        plugins::load(&mut self.registry, dir)
+    }
+
+    /// Calls hook with every watch event (and session state change) from
+    /// now on.
+    pub fn on_event<F: Fn(&Event) + Send + Sync + 'static>(&self, hook: F) {
+        self.events.add_hook(Box::new(hook));
+    }
+
+    /// The session timeout asked for on the next connect.
+    pub fn set_session_timeout(&mut self, secs: u64) {
+        self.session_timeout = secs;
+    }
+
+    /// How long connecting waits for the session to be established, 0 means
+    /// don't wait.
+    pub fn set_connect_timeout(&mut self, secs: u64) {
+        self.connect_timeout = secs;
+    }
+
+    /// How many times a command retries requests that failed with
+    /// ConnectionLoss (or timed out), unless it's given --retries N.
+    pub fn set_retries(&mut self, retries: usize) {
+        self.retry.set(retries, None);
+    }
+
+    /// How long a command gets before its requests fail, unless it's given
+    /// --timeout SECS. None means as long as it takes.
+    pub fn set_command_timeout(&mut self, secs: Option<u64>) {
+        self.command_timeout = secs;
+    }
+
+    /// Adds credentials (i.e.: digest user:password) to the session, and to
+    /// every session created from now on.
+    pub fn add_auth(&mut self, scheme: &str, auth: &[u8]) -> bool {
+        self.auths.push((scheme.to_string(), auth.to_vec()));
+        match self.zk {
+            Some(ref zk) => match zk.add_auth(scheme, auth.to_vec()) {
+                Ok(()) => true,
+                Err(err) => report_error(err, scheme),
+            },
+            None => true
+        }
+    }
+
+    /// In read-only mode commands that would write are refused.
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+    }
+
+    /// Appends every command run from now on to the audit log at path.
+    pub fn set_audit_log(&mut self, path: &Path) -> io::Result<()> {
+        self.audit = Some(try!(AuditLog::open(path)));
+        Ok(())
+    }
+
+    /// In dry-run mode commands describe the writes they would make instead
+    /// of making them, --dry-run does the same for a single command.
+    pub fn set_dry_run(&mut self, dry_run: bool) {
+        self.dry_run.set(dry_run);
+    }
+
+    /// Whether set & create gzip values (get always decompresses them), so
+    /// values near the 1MB limit still fit.
+    pub fn set_compress(&mut self, compress: bool) {
+        self.compress = compress;
+    }
+
+    /// The key get --decrypt & set --encrypt use.
+    pub fn set_encryption_key(&mut self, key: crypto::Key) {
+        self.encryption_key = Some(key);
+    }
+
+    /// The biggest value (in bytes) writes will send, as big as the
+    /// servers' jute.maxbuffer: they drop the connection for bigger ones.
+    pub fn set_max_buffer_size(&mut self, bytes: usize) {
+        self.max_buffer_size = bytes;
+    }
+
+    /// Paths (and their subtrees) that rm, rmr, set & co. refuse to touch
+    /// unless given --i-know-what-i-am-doing.
+    pub fn set_protected(&mut self, paths: Vec<String>) {
+        self.protected = paths.into_iter().map(|path| {
+            if path.len() > 1 { path.trim_right_matches('/').to_string() } else { path }
+        }).collect();
+    }
+
+    // the protected path that destroying path (recursively or not) would hit
+    fn protected_by(&self, path: &str, recursive: bool) -> Option<&str> {
+        let under = |path: &str, prefix: &str| {
+            prefix == "/" || path == prefix || (path.starts_with(prefix) && path[prefix.len()..].starts_with("/"))
+        };
+        self.protected.iter()
+            .find(|prefix| under(path, prefix) || (recursive && under(prefix, path)))
+            .map(|prefix| &**prefix)
+    }
+
+    /// Whether an unambiguous prefix (i.e.: ex for exists) runs the command.
+    pub fn set_prefix_matching(&mut self, enabled: bool) {
+        self.prefix_matching = enabled;
+    }
+
+    pub fn set_format(&mut self, format: Format) {
+        self.format = format;
+    }
+
+    /// Whether set & create refuse values that aren't well-formed JSON or
+    /// YAML, --validate does the same for a single command.
+    pub fn set_validate(&mut self, syntax: Option<Syntax>) {
+        self.validate = syntax;
+    }
+
+    /// Takes the hosts from (a background-refreshed) hosts whenever a new
+    /// session is created without giving them, i.e.: from Exhibitor.
+    pub fn follow_hosts(&mut self, hosts: Arc<Mutex<String>>) {
+        self.followed_hosts = Some(hosts);
+    }
+
+    // the hosts to connect to when none are given
+    fn current_hosts(&self) -> String {
+        match self.followed_hosts {
+            Some(ref hosts) => hosts.lock().unwrap().clone(),
+            None => self.hosts.clone()
+        }
+    }
+
+    /// A shell variable, falling back to the environment.
+    pub fn variable(&self, name: &str) -> Option<String> {
+        self.variables.get(name).cloned().or(env::var(name).ok())
+    }
+
+    pub fn set_variable(&mut self, name: &str, value: &str) {
+        self.variables.insert(name.to_string(), value.to_string());
+    }
+
+    pub fn registry(&self) -> &Registry {
+        &self.registry
+    }
+
+    /// The current session, if connected.
+    pub fn zk(&self) -> Option<&ZkClient> {
+        self.zk.as_ref().map(|zk| &**zk)
+    }
+
+    pub fn hosts(&self) -> &str {
+        &*self.hosts
+    }
+
+    pub fn default_acl(&self) -> &Vec<Acl> {
+        &self.default_acl
+    }
+
+    /// The ACL create & co. give new znodes.
+    pub fn set_default_acl(&mut self, acl: Vec<Acl>) {
+        self.default_acl = acl;
+    }
+
+    /// Makes profile available to --profile & connect --profile.
+    pub fn add_profile(&mut self, name: &str, profile: Profile) {
+        self.profiles.insert(name.to_string(), profile);
+    }
+
+    /// Switches to the profile's hosts (for the next connection) and
+    /// credentials, plus its read-only flag & default ACL if it has them.
+    /// Returns the hosts, None (after complaining) if there's no such profile.
+    pub fn use_profile(&mut self, name: &str) -> Option<String> {
+        let profile = match self.profiles.get(name) {
+            Some(profile) => profile.clone(),
+            None => {
+                let mut names: Vec<&str> = self.profiles.keys().map(|name| &**name).collect();
+                names.sort();
+                match names.len() {
+                    0 => out!("No profile {}, there are none in the config.", name),
+                    _ => out!("No profile {}, expected one of: {}", name, names.join(", ")),
+                }
+                return None;
+            }
+        };
+        // one environment's credentials aren't for another's servers
+        self.auths = profile.auth.into_iter().collect();
+        if let Some(read_only) = profile.read_only {
+            self.read_only = read_only;
+        }
+        if let Some(acl) = profile.default_acl {
+            self.default_acl = acl;
+        }
+        self.hosts = profile.hosts.clone();
+        self.profile = Some(name.to_string());
+        Some(profile.hosts)
+    }
+
+    fn connect_initial(&mut self) {
+        let hosts = self.current_hosts();
+        if !hosts.is_empty() {
+            self.connect_to(&hosts);
+        }
+    }
+
+    fn connect_to(&mut self, hosts: &str) -> bool {
+        let given = hosts.to_string();
+        let hosts = match discovery::resolve_hosts(hosts) {
+            Ok(hosts) => hosts,
+            Err(err) => {
+                println!("Failed to resolve {}: {}", hosts, err);
+                return false;
+            }
+        };
+        let hosts = match ConnectString::parse(&*hosts) {
+            Ok(parsed) => parsed.to_string(),
+            Err(err) => {
+                println!("Bad hosts {}: {}", hosts, err);
+                return false;
+            }
+        };
+        let hosts = &*hosts;
+        println!("Connecting to {}...", hosts);
+        self.hosts = hosts.to_string();
+        let timeout = Duration::from_secs(self.session_timeout);
+        info!("connecting to {} (session timeout {}s)", hosts, self.session_timeout);
+        self.events.reset_state();
+        let result = ZooKeeper::connect(hosts, timeout, LogWatcher::new(self.events.clone()));
+        match result {
+            Ok(zk) => {
+                if self.connect_timeout > 0 && !self.events.wait_connected(Duration::from_secs(self.connect_timeout)) {
+                    warn!("no session with {} after {}s", hosts, self.connect_timeout);
+                    println!("Timed out connecting to {} after {}s.", hosts, self.connect_timeout);
+                    let _ = zk.close();
+                    return false;
+                }
+                // the server may clamp the timeout, but the client doesn't
+                // tell what it negotiated
+                println!("Connected (requested session timeout {}s).", self.session_timeout);
+                self.zk = Some(Arc::new(self.stack(zk)));
+                self.save_last_hosts(&*given);
+                true
+            },
+            Err(error) => {
+                warn!("connecting to {} failed: {:?}", hosts, error);
+                println!("{:?}", error);
+                false
+            }
+        }
+    }
+
+    // wraps a new session the way every session is: logged, retried,
+    // under --dry-run and with the shell's credentials
+    fn stack(&self, zk: ZooKeeper) -> DryRunClient {
+        let zk = LoggingClient::new(Box::new(zk), self.metrics.clone());
+        for &(ref scheme, ref auth) in &self.auths {
+            if let Err(err) = zk.add_auth(scheme, auth.clone()) {
+                println!("Failed to add {} credentials: {:?}", scheme, err);
+            }
+        }
+        let zk = RetryingClient::new(Box::new(zk), self.retry.clone());
+        DryRunClient::new(Box::new(zk), self.dry_run.clone())
+    }
+
+    /// Where the hosts of every session that connects are kept, so a later
+    /// shell's connect (without hosts) goes back to them; None for nowhere.
+    pub fn set_last_hosts_file(&mut self, path: Option<PathBuf>) {
+        self.last_hosts_file = path;
+    }
+
+    /// The hosts the last session connected to, as kept in the last hosts
+    /// file (see set_last_hosts_file); profile:NAME if they were a
+    /// profile's, whose credentials go with them.
+    pub fn last_hosts(&self) -> Option<String> {
+        let path = match self.last_hosts_file {
+            Some(ref path) => path,
+            None => return None,
+        };
+        let mut hosts = String::new();
+        match File::open(path).and_then(|mut f| f.read_to_string(&mut hosts)) {
+            Ok(_) if !hosts.trim().is_empty() => Some(hosts.trim().to_string()),
+            _ => None,
+        }
+    }
+
+    // as given, so srv: names get resolved again; a profile's hosts by the
+    // profile's name, or connecting to them again would leave out its auth
+    fn save_last_hosts(&self, hosts: &str) {
+        let hosts = match self.profile {
+            Some(ref name) if self.profiles.get(name).map_or(false, |profile| profile.hosts == hosts) => {
+                format!("{}{}", LAST_PROFILE_PREFIX, name)
+            },
+            _ => hosts.to_string(),
+        };
+        if let Some(ref path) = self.last_hosts_file {
+            let saved = path.parent().map_or(Ok(()), |dir| fs::create_dir_all(dir))
+                .and_then(|_| File::create(path))
+                .and_then(|mut f| f.write_all(format!("{}\n", hosts).as_bytes()));
+            if let Err(err) = saved {
+                warn!("failed to save the hosts to {}: {}", path.display(), err);
+            }
+        }
+    }
+
+    /// Whether exit leaves what fill & benchmark created in place.
+    pub fn set_keep_scratch(&mut self, keep: bool) {
+        self.keep_scratch = keep;
+    }
+
+    /// What the interactive shell does on exit (or Ctrl-D): deletes the
+    /// scratch znodes fill & benchmark left, unless told to keep them, then
+    /// closes the session so the ephemerals behind locks, elections and
+    /// barriers go right away instead of once it times out.
+    pub fn shutdown(&mut self) {
+        if !self.keep_scratch && !self.scratch.is_empty() {
+            if let Some(ref zk) = self.zk {
+                let mut removed = 0;
+                for root in self.scratch.iter().rev() {
+                    // children before their parents, and they may be gone by now
+                    let paths = descendants(&**zk, root).unwrap_or(vec![]);
+                    removed += paths.iter().rev().filter(|path| zk.delete(path, -1).is_ok()).count();
+                }
+                out!("Removed {} scratch znodes.", removed);
+            }
+        }
+        self.scratch.clear();
+        self.close();
+    }
+
+    // closes the session, forgetting whatever recipes were tied to it
+    fn close(&mut self) {
+        if let Some(ref zk) = self.zk {
+            info!("closing the session to {}", self.hosts);
+            zk.close();
+        }
+        self.zk = None;
+        for (_, zk) in self.servers.drain() {
+            zk.close();
+        }
+        self.locks.clear();
+        self.candidates.clear();
+        self.barriers.clear();
+    }
+
+    // runs cmd through a session with just server (and the session's
+    // chroot), i.e.: to compare what the members of an ensemble return
+    fn run_on_server(&mut self, cmd: &Command, args: &Args, server: &str) -> bool {
+        let hosts = match (ConnectString::parse(server), ConnectString::parse(&*self.current_hosts())) {
+            (Ok(ref parsed), current) if parsed.servers.len() == 1 && parsed.chroot.is_none() => {
+                let chroot = current.ok().and_then(|current| current.chroot).unwrap_or(String::new());
+                format!("{}{}", parsed, chroot)
+            },
+            _ => {
+                println!("Bad server: {}", server);
+                return false;
+            }
+        };
+        if !self.servers.contains_key(&hosts) {
+            let timeout = Duration::from_secs(self.session_timeout);
+            let zk = match ZooKeeper::connect(&*hosts, timeout, QuietWatcher) {
+                Ok(zk) => LoggingClient::new(Box::new(zk), self.metrics.clone()),
+                Err(err) => {
+                    println!("Failed to connect to {}: {:?}", hosts, err);
+                    return false;
+                }
+            };
+            for &(ref scheme, ref auth) in &self.auths {
+                if let Err(err) = zk.add_auth(scheme, auth.clone()) {
+                    println!("Failed to add {} credentials: {:?}", scheme, err);
+                }
+            }
+            self.servers.insert(hosts.clone(), Arc::new(zk));
+        }
+
+        // the command only knows about self.zk, so it's swapped in for it
+        let session = self.servers.remove(&hosts);
+        let main = mem::replace(&mut self.zk, session);
+        let status = cmd.execute(self, args);
+        if let Some(session) = mem::replace(&mut self.zk, main) {
+            self.servers.insert(hosts, session);
+        }
+        status
+    }
+
+    /// Parses and runs a command line, returning whether it succeeded.
+    ///
+    /// Commands can be chained with && and ||, or guarded with
+    /// `if <command> then <command> [else <command>]`, where the condition's
+    /// output is dropped. Arguments can be quoted and refer to shell (or
+    /// else environment) variables, see `line::tokenize`.
+    pub fn execute(&mut self, line: &str) -> bool {
+        let (first, rest) = line::split_chain(line);
+        if !rest.is_empty() && (first.is_empty() || rest.iter().any(|&(_, ref cmd)| cmd.is_empty())) {
+            println!("Expected a command on both sides of && and ||.");
+            return false;
+        }
+
+        let mut status = self.execute_if(&*first);
+        for (chain, cmd) in rest {
+            match chain {
+                Chain::And if status => status = self.execute_if(&*cmd),
+                Chain::Or if !status => status = self.execute_if(&*cmd),
+                _ => (),
+            }
+        }
+        status
+    }
+
+    fn execute_if(&mut self, line: &str) -> bool {
+        match line::split_if(line) {
+            Ok(Some((cond, body, otherwise))) => {
+                let (holds, _) = output::capture(|| self.execute_command(&*cond));
+                if holds {
+                    self.execute_command(&*body)
+                } else {
+                    otherwise.map_or(true, |otherwise| self.execute_command(&*otherwise))
+                }
+            },
+            Ok(None) => self.execute_command(line),
+            Err(err) => {
+                println!("{}", err);
+                false
+            }
+        }
+    }
+
+    // the command called name, or the only one it's a prefix of (if that's
+    // enabled), complaining with suggestions when there's none
+    fn find_command(&self, name: &str) -> Option<Rc<Command>> {
+        if let Some(cmd) = self.registry.get(name) {
+            return Some(cmd);
+        }
+
+        let candidates = if self.prefix_matching { self.registry.with_prefix(name) } else { vec![] };
+        if candidates.len() == 1 {
+            return self.registry.get(&*candidates[0]);
+        }
+
+        if candidates.len() > 1 {
+            println!("Ambiguous command: {} ({}).", name, candidates.join(", "));
+        } else {
+            let suggestions = self.registry.suggest(name);
+            if suggestions.is_empty() {
+                println!("Unknown command: {}", name);
+            } else {
+                println!("Unknown command: {}, did you mean {}?", name, suggestions.join(" or "));
+            }
+        }
+        None
+    }
+
+    fn execute_command(&mut self, line: &str) -> bool {
+        let tokens = match line::tokenize(line, &|name| self.variable(name)) {
+            Ok(tokens) => tokens,
+            Err(err) => {
+                println!("{}", err);
+                return false;
+            }
+        };
+        let pieces: Vec<&str> = tokens.iter().map(|token| &**token).collect();
+
+        if pieces.len() == 0 {
+            return true;
+        }
+
+        self.executed += 1;
+
+        let cmd = match self.find_command(pieces[0]) {
+            Some(cmd) => cmd,
+            None => return false
+        };
+        let status = self.run_command(&*cmd, &pieces);
+
+        let hosts = self.current_hosts();
+        if let Some(ref mut audit) = self.audit {
+            let line = if cmd.sensitive() {
+                format!("{} (arguments not logged)", cmd.name())
+            } else {
+                pieces.iter().map(|piece| line::quote(piece)).collect::<Vec<String>>().join(" ")
+            };
+            audit.record(&*hosts, &*line, status);
+        }
+        status
+    }
+
+    fn run_command(&mut self, cmd: &Command, pieces: &[&str]) -> bool {
+        // global flags, so commands don't have to declare them. They end at
+        // a --, and commands wrapping a command line (i.e.: loop) only take
+        // them before their first argument, the rest is the wrapped line's.
+        let end = if cmd.raw_args() {
+            leading_flags_end(pieces)
+        } else {
+            pieces.iter().position(|&piece| piece == "--").unwrap_or(pieces.len())
+        };
+        let (flagged, untouched) = pieces.split_at(end);
+        let overridden = flagged.contains(&OVERRIDE_FLAG);
+        let dry_run = flagged.contains(&DRY_RUN_FLAG);
+        let mut pieces: Vec<&str> = flagged.iter().cloned()
+            .filter(|&piece| piece != OVERRIDE_FLAG && piece != DRY_RUN_FLAG)
+            .collect();
+        let server = match take_value(&mut pieces, SERVER_FLAG, "a host[:port]") {
+            Ok(server) => server,
+            Err(_) => return false,
+        };
+        let retries = match take_value(&mut pieces, RETRIES_FLAG, "a count").map(|n| n.map(|n| n.parse::<usize>())) {
+            Ok(Some(Ok(retries))) => Some(retries),
+            Ok(Some(Err(_))) => {
+                println!("{} needs a count.", RETRIES_FLAG);
+                return false;
+            },
+            Ok(None) => None,
+            Err(_) => return false,
+        };
+        let timeout = match take_value(&mut pieces, TIMEOUT_FLAG, "a number of seconds").map(|secs| secs.map(|secs| secs.parse::<u64>())) {
+            Ok(Some(Ok(secs))) if secs > 0 => Some(secs),
+            Ok(Some(_)) => {
+                println!("{} needs a number of seconds.", TIMEOUT_FLAG);
+                return false;
+            },
+            Ok(None) => None,
+            Err(_) => return false,
+        };
+
+        pieces.extend_from_slice(untouched);
+
+        let args = match Args::parse(cmd, &pieces[1..]) {
+            Some(args) => args,
+            None => return false
+        };
+        let was_dry_run = self.dry_run.enabled();
+        if (dry_run || was_dry_run) && cmd.mutates(&args) && !cmd.supports_dry_run(&args) {
+            println!("{} can't be dry run, it would write for real.", cmd.name());
+            return false;
+        }
+        let simulated = (dry_run || was_dry_run) && cmd.supports_dry_run(&args);
+        // nothing gets written, so there's nothing to refuse
+        if self.read_only && cmd.mutates(&args) && !simulated {
+            println!("{} is not allowed in read-only mode.", cmd.name());
+            return false;
+        }
+        // the session landed on a server cut off from the quorum
+        if cmd.mutates(&args) && !simulated && self.zk.is_some()
+            && self.events.state() == Some(KeeperState::ConnectedReadOnly) {
+            println!("Warning: the server is read-only, {} will most likely fail (isro shows which are).", cmd.name());
+        }
+        if let Some((path, recursive)) = cmd.destroys(&args) {
+            if let Some(prefix) = self.protected_by(path, recursive) {
+                if !overridden {
+                    println!("Refusing to {} {}, {} is protected (add {} to go ahead).",
+                             cmd.name(), path, prefix, OVERRIDE_FLAG);
+                    return false;
+                }
+            }
+        }
+        // ZooKeeper's own znodes, i.e.: quotas, take a yes
+        if cmd.mutates(&args) && !overridden && !simulated {
+            if let Some(path) = cmd.writes(&args).into_iter().find(|path| in_system_tree(path)) {
+                // a script's next line isn't an answer
+                if !self.events.interactive() {
+                    println!("Refusing to {} {}, it's in {}, ZooKeeper's own subtree (add {} to go ahead).",
+                             cmd.name(), path, SYSTEM_TREE, OVERRIDE_FLAG);
+                    return false;
+                }
+                let question = format!("{} is in {}, ZooKeeper's own subtree. Go ahead with {}?", path, SYSTEM_TREE, cmd.name());
+                if !confirm(&*question) {
+                    return false;
+                }
+            }
+        }
+
+        if let Some(server) = server {
+            if cmd.mutates(&args) {
+                println!("{} is only for reading, {} writes.", SERVER_FLAG, cmd.name());
+                return false;
+            }
+            return self.run_on_server(cmd, &args, &*server);
+        }
+
+        // a command run by another (i.e.: loop) keeps to the outer one's
+        // limits, unless it has its own
+        let outer = self.retry.get();
+        let deadline = |secs: u64| time::precise_time_ns() + secs * 1000000000;
+        let deadline = timeout.map(&deadline).or(outer.1).or(self.command_timeout.map(&deadline));
+        self.retry.set(retries.unwrap_or(outer.0), deadline);
+        let status = if !dry_run {
+            cmd.execute(self, &args)
+        } else {
+            self.set_dry_run(true);
+            let status = cmd.execute(self, &args);
+            self.set_dry_run(was_dry_run);
+            status
+        };
+        self.retry.set(outer.0, outer.1);
+        status
+    }
+}
+
+// where the global flags right after the command name (pieces[0]) end
+fn leading_flags_end(pieces: &[&str]) -> usize {
+    let mut end = 1;
+    while end < pieces.len() {
+        match pieces[end] {
+            OVERRIDE_FLAG | DRY_RUN_FLAG => end += 1,
+            // a missing value is complained about later
+            SERVER_FLAG | TIMEOUT_FLAG | RETRIES_FLAG => end = cmp::min(end + 2, pieces.len()),
+            _ => break,
+        }
+    }
+    end
+}
+
+// takes flag and the value after it out of pieces, Err (after complaining)
+// if there's no value
+fn take_value(pieces: &mut Vec<&str>, flag: &str, what: &str) -> Result<Option<String>, ()> {
+    match pieces.iter().position(|&piece| piece == flag) {
+        Some(i) if i + 1 < pieces.len() => {
+            let value = pieces[i + 1].to_string();
+            pieces.drain(i..i + 2);
+            Ok(Some(value))
+        },
+        Some(_) => {
+            println!("{} needs {}.", flag, what);
+            Err(())
+        },
+        None => Ok(None),
+    }
+}
@@ -0,0 +1,37 @@
+use std::cell::RefCell;
+
+
+// what's being captured, innermost last
+thread_local!(static CAPTURES: RefCell<Vec<String>> = RefCell::new(vec![]));
+
+/// Prints a line of command output, or adds it to the current capture.
+pub fn print(line: &str) {
+    let captured = CAPTURES.with(|captures| {
+        match captures.borrow_mut().last_mut() {
+            Some(capture) => {
+                capture.push_str(line);
+                capture.push('\n');
+                true
+            },
+            None => false
+        }
+    });
+    if !captured {
+        println!("{}", line);
+    }
+}
+
+/// Whether output is being captured, i.e.: so a child process' output is
+/// read rather than inherited.
+pub fn capturing() -> bool {
+    CAPTURES.with(|captures| !captures.borrow().is_empty())
+}
+
+/// Runs f, returning what it returned along with what it printed through
+/// `print`.
+pub fn capture<T, F: FnOnce() -> T>(f: F) -> (T, String) {
+    CAPTURES.with(|captures| captures.borrow_mut().push(String::new()));
+    let ret = f();
+    let captured = CAPTURES.with(|captures| captures.borrow_mut().pop().unwrap_or(String::new()));
+    (ret, captured)
+}
@@ -0,0 +1,135 @@
+use std::env;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::usize;
+
+use hosts::HOSTS_VAR;
+
+use super::{Args, Command, Registry, Shell, output};
+
+
+/// Where script plugins are looked up by default: ~/.zk_shell_rs/plugins
+pub fn default_dir() -> Option<PathBuf> {
+    env::home_dir().map(|home| home.join(".zk_shell_rs").join("plugins"))
+}
+
+/// A command backed by an executable script, named after the file. The
+/// script gets the command's arguments as is, plus ZK_HOSTS in its
+/// environment; its exit status is the command's status.
+///
+/// A `# description: ...` line near the top is used for the help.
+pub struct ScriptCommand {
+    name: String,
+    path: PathBuf,
+    description: String,
+}
+
+impl ScriptCommand {
+    pub fn new(path: &Path) -> Option<ScriptCommand> {
+        let name = match path.file_stem().and_then(|stem| stem.to_str()) {
+            Some(name) => name.to_string(),
+            None => return None
+        };
+        Some(ScriptCommand {
+            name: name,
+            path: path.to_path_buf(),
+            description: description(path).unwrap_or("Script plugin".to_string()),
+        })
+    }
+}
+
+// looks at the first few lines only
+fn description(path: &Path) -> Option<String> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(_) => return None
+    };
+    for line in BufReader::new(file).lines().take(10) {
+        if let Ok(line) = line {
+            if line.starts_with("# description:") {
+                return Some(line["# description:".len()..].trim().to_string());
+            }
+        }
+    }
+    None
+}
+
+impl Command for ScriptCommand {
+    fn name(&self) -> &str { &*self.name }
+    fn description(&self) -> &str { &*self.description }
+    fn synopsis(&self) -> &str { "[args...]" }
+    fn examples(&self) -> &str { &*self.name }
+    fn min_args(&self) -> usize { 0 }
+    fn max_args(&self) -> usize { usize::MAX }
+    // scripts might write anything
+    fn mutates(&self, _: &Args) -> bool { true }
+    // and with their own sessions
+    fn supports_dry_run(&self, _: &Args) -> bool { false }
+    // there's no telling where, so ZooKeeper's own subtree is the script's
+    // business
+    fn writes<'a>(&self, _: &'a Args) -> Vec<&'a str> { vec![] }
+    fn raw_args(&self) -> bool { true }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        let mut script = process::Command::new(&self.path);
+        script.args(args.all()).env(HOSTS_VAR, &*shell.hosts);
+
+        // keep what it prints when that's being captured (i.e.: by var)
+        let status = if output::capturing() {
+            script.stderr(process::Stdio::inherit()).output().map(|run| {
+                for line in String::from_utf8_lossy(&run.stdout).lines() {
+                    out!("{}", line);
+                }
+                run.status
+            })
+        } else {
+            script.status()
+        };
+        match status {
+            Ok(ref status) if status.success() => true,
+            Ok(status) => {
+                out!("{} exited with {}", self.name, status);
+                false
+            },
+            Err(err) => {
+                out!("Failed to run {}: {}", self.path.display(), err);
+                false
+            }
+        }
+    }
+}
+
+/// Registers every executable in dir as a command, returning how many
+/// were loaded. Scripts never shadow a command that's already there.
+pub fn load(registry: &mut Registry, dir: &Path) -> usize {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return 0 // no plugins
+    };
+
+    let mut paths: Vec<PathBuf> = entries.filter_map(|entry| entry.ok()).map(|entry| entry.path()).collect();
+    paths.sort();
+
+    let mut loaded = 0;
+    for path in paths {
+        let executable = fs::metadata(&path)
+            .map(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+            .unwrap_or(false);
+        if !executable {
+            continue;
+        }
+
+        if let Some(cmd) = ScriptCommand::new(&path) {
+            if registry.contains(cmd.name()) {
+                println!("Skipping plugin {}: {} is already a command.", path.display(), cmd.name());
+                continue;
+            }
+            registry.register(cmd);
+            loaded += 1;
+        }
+    }
+    loaded
+}
@@ -0,0 +1,93 @@
+use zookeeper::{Stat, ZkError, ZkResult};
+
+use client::ZkClient;
+use util::{glob_match, is_glob, join_path};
+
+
+/// Walks the subtree rooted at path, parents before their children, calling
+/// f with each path and its depth (path itself is at depth 0). Nodes that
+/// vanish while walking are skipped.
+pub fn walk<F>(zk: &ZkClient, path: &str, max_depth: Option<usize>, f: &mut F) -> ZkResult<()>
+    where F: FnMut(&str, usize) {
+    let mut pending = vec![(path.to_string(), 0)];
+
+    while let Some((current, depth)) = pending.pop() {
+        let mut children = match zk.get_children(&*current, false) {
+            Ok(children) => children,
+            Err(ZkError::NoNode) if depth > 0 => continue,
+            Err(err) => return Err(err),
+        };
+
+        f(&*current, depth);
+
+        if max_depth.map_or(false, |max| depth >= max) {
+            continue;
+        }
+
+        // reversed, so they are popped in order
+        children.sort();
+        for child in children.iter().rev() {
+            pending.push((join_path(&*current, child), depth + 1));
+        }
+    }
+
+    Ok(())
+}
+
+/// Like walk, but f also gets each node's stat. Nodes that vanish before
+/// their stat is read are skipped.
+pub fn walk_stats<F>(zk: &ZkClient, path: &str, f: &mut F) -> ZkResult<()>
+    where F: FnMut(&str, usize, &Stat) {
+    walk(zk, path, None, &mut |node, depth| {
+        if let Ok(stat) = zk.exists(node, false) {
+            f(node, depth, &stat);
+        }
+    })
+}
+
+/// Returns path and all of its descendants, parents before their children.
+pub fn descendants(zk: &ZkClient, path: &str) -> ZkResult<Vec<String>> {
+    let mut paths = vec![];
+    try!(walk(zk, path, None, &mut |path, _| paths.push(path.to_string())));
+    Ok(paths)
+}
+
+/// How many descendants path has (not counting itself).
+///
+/// ZooKeeper 3.6 answers this server-side (getAllChildrenNumber), but the
+/// client library predates it, so the subtree is walked.
+pub fn count_descendants(zk: &ZkClient, path: &str) -> ZkResult<usize> {
+    let mut count = 0;
+    try!(walk(zk, path, None, &mut |_, depth| if depth > 0 { count += 1 }));
+    Ok(count)
+}
+
+/// The paths matching pattern, where each component can be a glob (see
+/// `util::glob_match`), sorted. One without wildcards is returned as is,
+/// whether it exists or not.
+pub fn expand_glob(zk: &ZkClient, pattern: &str) -> ZkResult<Vec<String>> {
+    if !is_glob(pattern) {
+        return Ok(vec![pattern.to_string()]);
+    }
+    let mut paths = vec!["/".to_string()];
+    for component in pattern.split('/').filter(|component| !component.is_empty()) {
+        let mut next = vec![];
+        for path in &paths {
+            if !is_glob(component) {
+                next.push(join_path(path, component));
+                continue;
+            }
+            let mut children = match zk.get_children(&*path, false) {
+                Ok(children) => children,
+                // it's gone, or it was only there in the pattern
+                Err(ZkError::NoNode) => continue,
+                Err(err) => return Err(err),
+            };
+            children.sort();
+            next.extend(children.iter().filter(|child| glob_match(component, child)).map(|child| join_path(path, child)));
+        }
+        paths = next;
+    }
+    Ok(paths)
+}
+
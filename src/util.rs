@@ -0,0 +1,240 @@
+use std::fs::File;
+use std::io::{self, Read};
+use std::str;
+
+use zookeeper::{Acl, CreateMode, ZkError, ZkResult};
+use zookeeper::perms;
+
+use client::ZkClient;
+
+
+/// ZooKeeper's own znodes: quotas and (3.5+) the dynamic config.
+pub const SYSTEM_TREE: &'static str = "/zookeeper";
+
+pub fn in_system_tree(path: &str) -> bool {
+    path == SYSTEM_TREE || (path.starts_with(SYSTEM_TREE) && path[SYSTEM_TREE.len()..].starts_with('/'))
+}
+
+/// Joins a parent path and a child name, taking care of the root.
+pub fn join_path(parent: &str, child: &str) -> String {
+    if parent.ends_with("/") {
+        format!("{}{}", parent, child)
+    } else {
+        format!("{}/{}", parent, child)
+    }
+}
+
+/// The parent of path, None for the root.
+pub fn parent_path(path: &str) -> Option<&str> {
+    match path.trim_right_matches('/').rfind('/') {
+        Some(0) if path.len() > 1 => Some("/"),
+        Some(idx) => Some(&path[..idx]),
+        None => None,
+    }
+}
+
+/// Creates path and any missing ancestors, like `mkdir -p`.
+pub fn ensure_path(zk: &ZkClient, path: &str, acl: &Vec<Acl>) -> ZkResult<()> {
+    let mut current = String::new();
+    for name in path.split('/').filter(|name| !name.is_empty()) {
+        current = join_path(if current.is_empty() { "/" } else { &*current }, name);
+        match zk.create(&*current, vec![], acl.clone(), CreateMode::Persistent) {
+            Ok(_) | Err(ZkError::NodeExists) => (),
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(())
+}
+
+/// A zxid's (epoch, counter): the high 32 bits count leader elections, the
+/// low ones the transactions within that epoch.
+pub fn split_zxid(zxid: i64) -> (i64, i64) {
+    (zxid >> 32, zxid & 0xffffffff)
+}
+
+/// Parses a zxid given in hex (0x...) or decimal.
+pub fn parse_zxid(zxid: &str) -> Option<i64> {
+    if zxid.starts_with("0x") || zxid.starts_with("0X") {
+        i64::from_str_radix(&zxid[2..], 16).ok()
+    } else {
+        zxid.parse::<i64>().ok()
+    }
+}
+
+/// Parses a session id given in hex (0x..., as servers log them) or
+/// decimal. Ids from servers with an id over 127 don't fit in an i64 as
+/// is, so they wrap, the way ephemeralOwner holds them.
+pub fn parse_session_id(id: &str) -> Option<i64> {
+    if id.starts_with("0x") || id.starts_with("0X") {
+        u64::from_str_radix(&id[2..], 16).ok().map(|id| id as i64)
+    } else {
+        id.parse::<i64>().ok().or(id.parse::<u64>().ok().map(|id| id as i64))
+    }
+}
+
+/// An ACL entry as scheme:id:perms, i.e.: world:anyone:cdrwa
+pub fn format_acl(acl: &Acl) -> String {
+    let letters = [(perms::CREATE, 'c'), (perms::DELETE, 'd'), (perms::READ, 'r'),
+                   (perms::WRITE, 'w'), (perms::ADMIN, 'a')];
+    let granted: String = letters.iter().filter(|&&(perm, _)| acl.perms & perm != 0).map(|&(_, c)| c).collect();
+    format!("{}:{}:{}", acl.scheme, acl.id, granted)
+}
+
+/// Parses a point in time as ms since the epoch (like znode times), given
+/// either relative to now_ms (i.e.: -90s, -5m, -2h or -1d) or as an ISO
+/// timestamp (i.e.: 2015-06-01, 2015-06-01T10:00 or
+/// 2015-06-01T10:00:00+02:00, UTC unless there's an offset).
+pub fn parse_time(spec: &str, now_ms: i64) -> Result<i64, String> {
+    let bad = || format!("Bad time: {}, expected i.e.: -10m or 2015-06-01T10:00:00Z", spec);
+
+    if spec.starts_with('-') {
+        if spec.len() < 3 {
+            return Err(bad());
+        }
+        let unit = spec.chars().last().unwrap();
+        let amount = &spec[1..spec.len() - unit.len_utf8()];
+        let unit_ms = match unit {
+            's' => 1000,
+            'm' => 60 * 1000,
+            'h' => 60 * 60 * 1000,
+            'd' => 24 * 60 * 60 * 1000,
+            _ => return Err(bad()),
+        };
+        return amount.parse::<i64>().map(|amount| now_ms - amount * unit_ms).map_err(|_| bad());
+    }
+
+    // the date, the time of day and the offset from UTC
+    let (date, rest) = match spec.find('T') {
+        Some(idx) => (&spec[..idx], &spec[idx + 1..]),
+        None => (spec, ""),
+    };
+    let (clock, offset) = match rest.find(|c| c == 'Z' || c == '+' || c == '-') {
+        Some(idx) => (&rest[..idx], &rest[idx..]),
+        None => (rest, ""),
+    };
+    let numbers = |s: &str, sep: char| -> Option<Vec<i64>> {
+        s.split(sep).map(|n| n.parse::<i64>().ok()).collect()
+    };
+
+    let date = try!(numbers(date, '-').ok_or_else(bad));
+    let clock = if clock.is_empty() { vec![0, 0] } else { try!(numbers(clock, ':').ok_or_else(bad)) };
+    let offset_minutes = match offset {
+        "" | "Z" => 0,
+        offset => match numbers(&offset[1..], ':') {
+            Some(ref hm) if hm.len() == 2 => (hm[0] * 60 + hm[1]) * if offset.starts_with('-') { -1 } else { 1 },
+            _ => return Err(bad()),
+        },
+    };
+    if date.len() != 3 || clock.len() < 2 || clock.len() > 3 {
+        return Err(bad());
+    }
+    let (year, month, day) = (date[0], date[1], date[2]);
+    let (hour, minute, second) = (clock[0], clock[1], *clock.get(2).unwrap_or(&0));
+    if month < 1 || month > 12 || day < 1 || day > 31 || hour > 23 || minute > 59 || second > 60 {
+        return Err(bad());
+    }
+
+    // days since the epoch, from Howard Hinnant's days_from_civil
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = (if y >= 0 { y } else { y - 399 }) / 400;
+    let yoe = y - era * 400;
+    let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146097 + doe - 719468;
+
+    let seconds = days * 86400 + hour * 3600 + minute * 60 + second - offset_minutes * 60;
+    Ok(seconds * 1000)
+}
+
+/// A random (version 4) UUID, i.e.: 1b4e28ba-2fa1-4d2b-883f-0016d3cca427.
+pub fn random_uuid() -> io::Result<String> {
+    let mut bytes = [0u8; 16];
+    try!(File::open("/dev/urandom").and_then(|mut urandom| urandom.read_exact(&mut bytes)));
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: Vec<String> = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    Ok(format!("{}-{}-{}-{}-{}", hex[0..4].concat(), hex[4..6].concat(), hex[6..8].concat(),
+               hex[8..10].concat(), hex[10..16].concat()))
+}
+
+/// Expands the {{i}} and {{uuid}} placeholders in template.
+pub fn fill_template(template: &str, i: usize, uuid: &str) -> String {
+    template.replace("{{i}}", &*i.to_string()).replace("{{uuid}}", uuid)
+}
+
+/// Whether name has glob wildcards (*, ? or [...]) in it.
+pub fn is_glob(name: &str) -> bool {
+    name.contains('*') || name.contains('?') || name.contains('[')
+}
+
+/// Whether name matches pattern, a glob where * is anything, ? a character
+/// and [abc] (or [a-c], or [!abc]) one of a set.
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    glob_from(&*pattern, &*name)
+}
+
+fn glob_from(pattern: &[char], name: &[char]) -> bool {
+    match pattern.first() {
+        None => name.is_empty(),
+        Some(&'*') => (0..name.len() + 1).any(|skip| glob_from(&pattern[1..], &name[skip..])),
+        Some(&'?') => !name.is_empty() && glob_from(&pattern[1..], &name[1..]),
+        Some(&'[') if pattern.iter().skip(2).any(|&c| c == ']') => {
+            let negated = pattern[1] == '!';
+            let start = if negated { 2 } else { 1 };
+            // a ] right after the [ is part of the set
+            let end = start + 1 + pattern[start + 1..].iter().position(|&c| c == ']').unwrap_or(0);
+            let set = &pattern[start..end];
+            let in_set = |c: char| {
+                let mut i = 0;
+                while i < set.len() {
+                    if i + 2 < set.len() && set[i + 1] == '-' {
+                        if c >= set[i] && c <= set[i + 2] {
+                            return true;
+                        }
+                        i += 3;
+                    } else {
+                        if c == set[i] {
+                            return true;
+                        }
+                        i += 1;
+                    }
+                }
+                false
+            };
+            !name.is_empty() && in_set(name[0]) != negated && glob_from(&pattern[end + 1..], &name[1..])
+        },
+        Some(&c) => name.first() == Some(&c) && glob_from(&pattern[1..], &name[1..]),
+    }
+}
+
+/// What goes under a value that's shown: how many bytes it has (and how many
+/// are stored, if it was decompressed) and its version, and whether the
+/// bytes that aren't UTF-8 were replaced to show it.
+pub fn data_summary(data: &[u8], stored: i32, version: i32) -> String {
+    let mut summary = if data.len() == 1 { "1 byte".to_string() } else { format!("{} bytes", data.len()) };
+    if stored as usize != data.len() {
+        summary.push_str(&*format!(" ({} stored)", stored));
+    }
+    summary.push_str(&*format!(", version {}", version));
+    if str::from_utf8(data).is_err() {
+        summary.push_str(", not UTF-8: invalid bytes are shown as \u{fffd}");
+    }
+    format!("[{}]", summary)
+}
+
+/// A duration in ms by its two largest units, i.e.: 3d 4h, 2h 0m, 5m 12s or
+/// 42s.
+pub fn format_age(ms: i64) -> String {
+    let secs = if ms > 0 { ms / 1000 } else { 0 };
+    let units = [("d", 24 * 60 * 60), ("h", 60 * 60), ("m", 60), ("s", 1)];
+    for (i, &(unit, unit_secs)) in units.iter().enumerate() {
+        if secs >= unit_secs && i + 1 < units.len() {
+            let (next, next_secs) = units[i + 1];
+            return format!("{}{} {}{}", secs / unit_secs, unit, secs % unit_secs / next_secs, next);
+        }
+    }
+    format!("{}s", secs)
+}
+
@@ -0,0 +1,60 @@
+use std::sync::Mutex;
+use std::sync::mpsc::{Receiver, Sender, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+use time;
+use zookeeper::{Watcher, WatchedEvent, WatchedEventType};
+
+use interrupt;
+
+
+/// Forwards the event types of a one-shot watch to a channel.
+pub struct ChannelWatcher {
+    tx: Mutex<Sender<WatchedEventType>>,
+}
+
+impl ChannelWatcher {
+    pub fn new(tx: &Sender<WatchedEventType>) -> ChannelWatcher {
+        ChannelWatcher { tx: Mutex::new(tx.clone()) }
+    }
+}
+
+impl Watcher for ChannelWatcher {
+    fn handle(&self, e: &WatchedEvent) {
+        debug!("watch event {:?} {}", e.event_type, e.path.as_ref().map_or("", |path| &*path));
+        if let Ok(tx) = self.tx.lock() {
+            let _ = tx.send(e.event_type);
+        }
+    }
+}
+
+/// Blocks until a znode event arrives, returns None if interrupted.
+/// Session events (type None) are skipped.
+pub fn wait_event(rx: &Receiver<WatchedEventType>) -> Option<WatchedEventType> {
+    wait_event_until(rx, None)
+}
+
+/// Like wait_event, but it also gives up (with None) once past deadline
+/// (in precise_time_ns terms).
+pub fn wait_event_until(rx: &Receiver<WatchedEventType>, deadline: Option<u64>) -> Option<WatchedEventType> {
+    loop {
+        match rx.try_recv() {
+            Ok(WatchedEventType::None) => continue,
+            Ok(event_type) => return Some(event_type),
+            Err(TryRecvError::Empty) => {
+                if interrupt::interrupted() {
+                    return None;
+                }
+                let now = time::precise_time_ns();
+                let left = match deadline {
+                    Some(deadline) if now >= deadline => return None,
+                    Some(deadline) => (deadline - now) / 1000000,
+                    None => 100,
+                };
+                thread::sleep(Duration::from_millis(left.min(100).max(1)));
+            },
+            Err(TryRecvError::Disconnected) => return None,
+        }
+    }
+}
@@ -0,0 +1,1470 @@
+extern crate zk_shell_rs;
+extern crate zookeeper;
+
+use std::env;
+use std::fs::{self, File};
+use std::io::{Cursor, Read, Write};
+use std::net::TcpListener;
+use std::process;
+use std::thread;
+use std::time::Duration;
+
+use zookeeper::ZkError;
+
+use zk_shell_rs::Shell;
+use zk_shell_rs::backup;
+use zk_shell_rs::client::ZkClient;
+use zk_shell_rs::config::Config;
+use zk_shell_rs::crypto;
+use zk_shell_rs::mem::MemClient;
+use zk_shell_rs::shell::{Args, Command, Format, Syntax};
+use zk_shell_rs::shell::command;
+use zk_shell_rs::shell::completion::{self, Flag};
+use zk_shell_rs::shell::output;
+use zk_shell_rs::util::format_acl;
+
+
+// a shell on a fresh tree, plus another session to look at the tree with
+fn shell() -> (Shell, MemClient) {
+    let zk = MemClient::new();
+    let inspector = zk.session();
+    (Shell::with_client(Box::new(zk)), inspector)
+}
+
+fn data(zk: &MemClient, path: &str) -> String {
+    String::from_utf8(zk.get_data(path, false).unwrap().0).unwrap()
+}
+
+#[test]
+fn create_get_set() {
+    let (mut shell, zk) = shell();
+    assert!(shell.execute("create /a hello"));
+    assert!(shell.execute("get /a"));
+    assert!(shell.execute("set /a bye"));
+    assert_eq!(data(&zk, "/a"), "bye");
+
+    assert!(!shell.execute("set /a stale 0"));
+    assert!(shell.execute("set /a fresh 1"));
+    assert_eq!(data(&zk, "/a"), "fresh");
+}
+
+#[test]
+fn set_can_create_parents() {
+    let (mut shell, zk) = shell();
+    assert!(!shell.execute("set /a/b/c v"));
+    assert!(!shell.execute("set /a/b/c v --create"));
+    assert!(shell.execute("set /a/b/c v --create --parents"));
+    assert_eq!(data(&zk, "/a/b/c"), "v");
+}
+
+#[test]
+fn compare_and_set() {
+    let (mut shell, zk) = shell();
+    assert!(shell.execute("create /leader host1"));
+    assert!(!shell.execute("set --cas /leader host2 host3"));
+    assert_eq!(data(&zk, "/leader"), "host1");
+    assert!(shell.execute("set --cas /leader host1 host2 --retry 5"));
+    assert_eq!(data(&zk, "/leader"), "host2");
+
+    assert!(!shell.execute("set --cas /leader host2"));
+    assert!(!shell.execute("set --cas /leader host2 host3 --retry x"));
+    assert!(!shell.execute("set /leader host3 --retry 5"));
+    assert!(!shell.execute("set --cas /missing a b"));
+    assert_eq!(data(&zk, "/leader"), "host2");
+}
+
+#[test]
+fn append() {
+    let (mut shell, zk) = shell();
+    assert!(shell.execute("create /hosts ''"));
+    assert!(shell.execute("append /hosts a --separator ,"));
+    assert!(shell.execute("append /hosts b --separator ,"));
+    assert_eq!(data(&zk, "/hosts"), "a,b");
+
+    assert!(shell.execute("create /log started"));
+    assert!(shell.execute("append /log stopped --newline"));
+    assert!(shell.execute("append /log ."));
+    assert_eq!(data(&zk, "/log"), "started\nstopped.");
+
+    assert!(!shell.execute("append /log x --newline --separator ,"));
+    assert!(!shell.execute("append /missing x"));
+}
+
+#[test]
+fn compressed_values() {
+    let (mut shell, zk) = shell();
+    assert!(shell.execute("create /big aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa --compress"));
+    let stored = zk.get_data("/big", false).unwrap().0;
+    assert_eq!(&stored[..2], &[0x1f, 0x8b]);
+    let (_, printed) = output::capture(|| shell.execute("get /big"));
+    assert_eq!(printed, "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa\n");
+
+    assert!(shell.execute("append /big b"));
+    let (_, printed) = output::capture(|| shell.execute("get /big"));
+    assert_eq!(printed, "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaab\n");
+
+    shell.set_compress(true);
+    assert!(shell.execute("set /plain x --create"));
+    assert!(zk.get_data("/plain", false).unwrap().0.len() > 1);
+    assert!(shell.execute("set --cas /plain x y"));
+    let (_, printed) = output::capture(|| shell.execute("get /plain"));
+    assert_eq!(printed, "y\n");
+}
+
+#[test]
+fn encrypted_values() {
+    let (mut shell, zk) = shell();
+    assert!(!shell.execute("create /secret hunter2 --encrypt"));
+    assert!(zk.exists("/secret", false).is_err());
+
+    shell.set_encryption_key(crypto::parse_key("000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f").unwrap());
+    assert!(shell.execute("create /secret hunter2 --encrypt --compress"));
+    assert!(!zk.get_data("/secret", false).unwrap().0.windows(7).any(|w| w == b"hunter2"));
+    let (ok, printed) = output::capture(|| shell.execute("get /secret"));
+    assert!(!ok);
+    assert_eq!(printed, "/secret is encrypted, use get --decrypt.\n");
+    let (_, printed) = output::capture(|| shell.execute("get /secret --decrypt"));
+    assert_eq!(printed, "hunter2\n");
+
+    assert!(shell.execute("set /secret swordfish --encrypt"));
+    let (_, printed) = output::capture(|| shell.execute("get /secret --decrypt"));
+    assert_eq!(printed, "swordfish\n");
+    assert!(!shell.execute("append /secret x"));
+
+    assert!(shell.execute("create /plain x"));
+    assert!(!shell.execute("get /plain --decrypt"));
+}
+
+#[test]
+fn get_pretty_prints_json() {
+    let (mut shell, _) = shell();
+    assert!(shell.execute("create /json '{\"hosts\": [\"zk1\", \"zk2\"], \"port\": 2181, \"tls\": false}'"));
+    let (_, printed) = output::capture(|| shell.execute("get /json"));
+    assert_eq!(printed, "{\n  \"hosts\": [\n    \"zk1\",\n    \"zk2\"\n  ],\n  \"port\": 2181,\n  \"tls\": false\n}\n");
+    let (_, printed) = output::capture(|| shell.execute("get /json --raw"));
+    assert_eq!(printed, "{\"hosts\": [\"zk1\", \"zk2\"], \"port\": 2181, \"tls\": false}\n");
+
+    assert!(shell.execute("create /number 42 && create /text '{not json'"));
+    let (_, printed) = output::capture(|| shell.execute("get /number"));
+    assert_eq!(printed, "42\n");
+    let (_, printed) = output::capture(|| shell.execute("get /text"));
+    assert_eq!(printed, "{not json\n");
+
+    assert!(shell.execute("create /zipped abc --compress"));
+    let (_, printed) = output::capture(|| shell.execute("get /zipped --raw"));
+    assert!(printed != "abc\n");
+}
+
+#[test]
+fn stats() {
+    let (mut shell, _) = shell();
+    assert!(shell.execute("stats reset"));
+    let (_, printed) = output::capture(|| shell.execute("stats"));
+    assert_eq!(printed, "No requests yet.\n");
+
+    assert!(shell.execute("create /a x && set /a y && get /a && ls /"));
+    assert!(!shell.execute("get /missing"));
+    let (_, printed) = output::capture(|| shell.execute("stats"));
+    let lines: Vec<&str> = printed.lines().collect();
+    assert!(lines[0].starts_with("op "));
+    let row = |op: &str| lines.iter().find(|line| line.starts_with(&*format!("{} ", op))).unwrap()
+        .split_whitespace().take(3).map(|s| s.to_string()).collect::<Vec<String>>();
+    assert_eq!(row("get"), vec!["get", "2", "1"]);
+    assert_eq!(row("create"), vec!["create", "1", "0"]);
+    assert_eq!(row("set"), vec!["set", "1", "0"]);
+    assert_eq!(row("list"), vec!["list", "1", "0"]);
+    assert_eq!(lines.last(), Some(&"get: NoNode 1"));
+
+    assert!(!shell.execute("stats bogus"));
+    assert!(shell.execute("stats reset"));
+    let (_, printed) = output::capture(|| shell.execute("stats"));
+    assert_eq!(printed, "No requests yet.\n");
+}
+
+#[test]
+fn values_to_and_from_files() {
+    let (mut shell, zk) = shell();
+    let file = env::temp_dir().join(format!("zk-shell-value-{}", process::id()));
+    let file = file.to_str().unwrap();
+    let big: Vec<u8> = (0..300000).map(|i| (i % 251) as u8).collect();
+    assert!(shell.execute("create /big ''"));
+    zk.set_data("/big", big.clone(), -1).unwrap();
+
+    assert!(shell.execute(&*format!("get_to_file /big {}", file)));
+    let mut written = vec![];
+    File::open(file).unwrap().read_to_end(&mut written).unwrap();
+    assert!(written == big);
+
+    assert!(shell.execute(&*format!("set_from_file /copy {} --create", file)));
+    assert!(zk.get_data("/copy", false).unwrap().0 == big);
+    assert!(!shell.execute(&*format!("set_from_file /other {}", file)));
+    assert!(!shell.execute("set_from_file /copy /does/not/exist"));
+
+    shell.set_max_buffer_size(1000);
+    assert!(!shell.execute(&*format!("set_from_file /copy {}", file)));
+    File::create(file).unwrap().write_all(&[b'a'; 5000]).unwrap();
+    assert!(!shell.execute(&*format!("set_from_file /copy {}", file)));
+    assert!(shell.execute(&*format!("set_from_file /copy {} --compress", file)));
+    let (_, printed) = output::capture(|| shell.execute("get /copy"));
+    assert_eq!(printed.len(), 5001);
+    fs::remove_file(file).unwrap();
+
+    shell.set_max_buffer_size(10);
+    assert!(!shell.execute("create /small 'more than ten bytes'"));
+    assert!(zk.exists("/small", false).is_err());
+}
+
+#[test]
+fn diff_file() {
+    let (mut shell, zk) = shell();
+    let file = env::temp_dir().join(format!("zk-shell-diff-{}", process::id()));
+    let file = file.to_str().unwrap();
+    output::capture(|| shell.execute("create /cfg ''"));
+    zk.set_data("/cfg", b"a=1\nb=2\nc=3".to_vec(), -1).unwrap();
+    File::create(file).unwrap().write_all(b"a=1\nb=20\nc=3\n").unwrap();
+
+    let (ok, out) = output::capture(|| shell.execute(&*format!("diff_file /cfg {}", file)));
+    assert!(ok);
+    assert_eq!(out, format!("--- /cfg\n+++ {}\n@@ -1,3 +1,3 @@\n a=1\n-b=2\n+b=20\n c=3\n", file));
+    assert!(shell.execute(&*format!("set_from_file /cfg {}", file)));
+    assert_eq!(output::capture(|| shell.execute(&*format!("diff_file /cfg {} -U 0", file))).1, "No differences.\n");
+    fs::remove_file(file).unwrap();
+    assert!(!shell.execute(&*format!("diff_file /cfg {}", file)));
+}
+
+#[test]
+fn backup_and_restore() {
+    let (mut shell, zk) = shell();
+    let file = env::temp_dir().join(format!("zk-shell-backup-{}", process::id()));
+    let file = file.to_str().unwrap();
+    assert!(shell.execute("create /b x && create /b/config 'a=1' && create /b/locked x --acl read-only"));
+    assert!(shell.execute("create /b/session x -e"));
+    assert!(shell.execute("create /zookeeper '' --i-know-what-i-am-doing && create /zookeeper/quota '' --i-know-what-i-am-doing"));
+    zk.set_data("/b/config", vec![0, 159, 146, 150], -1).unwrap();
+    let locked = zk.acl("/b/locked");
+    assert!(locked != Some(shell.default_acl().clone()));
+
+    assert!(shell.execute(&*format!("backup {}", file)));
+    assert!(shell.execute("rmr /b --force"));
+    let (ok, out) = output::capture(|| shell.execute(&*format!("restore {} --yes", file)));
+    assert!(ok);
+    assert_eq!(out.lines().skip(1).collect::<Vec<_>>(), vec![
+        "To create:    3 (6 bytes)",
+        "To overwrite: 0 (0 bytes)",
+        "To skip:      0 (already there, see --overwrite)",
+        "Nothing gets deleted.",
+        "Created 3, overwrote 0, skipped 0 (already there).",
+    ]);
+    assert_eq!(zk.get_data("/b/config", false).unwrap().0, vec![0, 159, 146, 150]);
+    assert_eq!(zk.acl("/b/locked"), locked);
+    assert!(zk.exists("/b/session", false).is_err());
+
+    assert!(shell.execute("set /b/config changed"));
+    // nothing to do, nothing to confirm
+    let (_, out) = output::capture(|| shell.execute(&*format!("restore {}", file)));
+    assert_eq!(out.lines().last(), Some("Created 0, overwrote 0, skipped 3 (already there)."));
+    assert_eq!(zk.get_data("/b/config", false).unwrap().0, b"changed".to_vec());
+    let (_, out) = output::capture(|| shell.execute(&*format!("restore {} --overwrite -y", file)));
+    assert_eq!(&out.lines().collect::<Vec<_>>()[1..4], &["To create:    0 (0 bytes)", "To overwrite: 1 (4 bytes)", "Unchanged:    2"]);
+    assert_eq!(zk.get_data("/b/config", false).unwrap().0, vec![0, 159, 146, 150]);
+
+    // a subtree, restored into a tree that lacks its parents
+    assert!(shell.execute(&*format!("backup {} /b/config", file)));
+    assert!(shell.execute("rmr /b --force"));
+    assert!(shell.execute(&*format!("restore {} --yes", file)));
+    assert!(zk.exists("/b/config", false).is_ok());
+    assert!(zk.get_children("/b", false).unwrap() == vec!["config".to_string()]);
+
+    File::create(file).unwrap().write_all(b"not a backup").unwrap();
+    assert!(!shell.execute(&*format!("restore {}", file)));
+    fs::remove_file(file).unwrap();
+    assert!(!shell.execute(&*format!("restore {}", file)));
+    assert!(!shell.execute(&*format!("backup {} /missing", file)));
+}
+
+#[test]
+fn create_from_template() {
+    let (mut shell, zk) = shell();
+    let file = env::temp_dir().join(format!("zk-shell-template-{}", process::id()));
+    let file = file.to_str().unwrap();
+    File::create(file).unwrap().write_all(b"/seed/worker-{{i}}\n{\"id\": \"{{uuid}}\",\n \"n\": {{i}}}\n").unwrap();
+
+    let (ok, out) = output::capture(|| shell.execute(&*format!("create_from_template {} 3 --start 5", file)));
+    assert!(ok);
+    assert_eq!(out, "Created 3 znodes.\n");
+    assert_eq!(zk.get_children("/seed", false).unwrap().len(), 3);
+    let value = data(&zk, "/seed/worker-7");
+    assert!(value.starts_with("{\"id\": \"") && value.ends_with("\",\n \"n\": 7}"));
+    assert_eq!(value.len(), "{\"id\": \"\",\n \"n\": 7}".len() + 36);
+    assert!(value != data(&zk, "/seed/worker-6").replace("6}", "7}"));
+
+    // existing znodes stop it, a constant path can only make one
+    assert!(!shell.execute(&*format!("create_from_template {} 1 --start 6", file)));
+    File::create(file).unwrap().write_all(b"/seed/single").unwrap();
+    assert!(!shell.execute(&*format!("create_from_template {} 2", file)));
+    assert!(shell.execute(&*format!("create_from_template {} 1", file)));
+    assert_eq!(data(&zk, "/seed/single"), "");
+    fs::remove_file(file).unwrap();
+    assert!(!shell.execute(&*format!("create_from_template {} 1", file)));
+}
+
+#[test]
+fn mirror() {
+    let (mut shell, zk) = shell();
+    assert!(shell.execute("create /src v1 && create /src/a x && create /src/a/b y && create /src/c z --acl read-only"));
+    assert!(shell.execute("create /src/session x -e"));
+    assert!(shell.execute("create /dst old && create /dst/stale x && create /dst/stale/child x && create /dst/a x"));
+
+    let (ok, out) = output::capture(|| shell.execute("mirror /src /dst --force"));
+    assert!(ok);
+    assert_eq!(out, "Created 2, updated 1, deleted 2.\n");
+    assert_eq!(zk.get_data("/dst", false).unwrap().0, b"v1".to_vec());
+    assert_eq!(zk.get_data("/dst/a/b", false).unwrap().0, b"y".to_vec());
+    assert_eq!(zk.acl("/dst/c"), zk.acl("/src/c"));
+    assert!(zk.exists("/dst/stale", false).is_err());
+    assert!(zk.exists("/dst/session", false).is_err());
+
+    assert!(shell.execute("set /src/a/b changed && rm /src/c"));
+    let (_, out) = output::capture(|| shell.execute("mirror /src /dst --force"));
+    assert_eq!(out, "Created 0, updated 1, deleted 1.\n");
+    let (_, out) = output::capture(|| shell.execute("mirror /src /dst --force"));
+    assert_eq!(out, "Created 0, updated 0, deleted 0.\n");
+
+    assert!(!shell.execute("mirror /src /src/copy"));
+    assert!(!shell.execute("mirror /dst /"));
+    assert!(shell.execute("create /empty x"));
+    let (ok, out) = output::capture(|| shell.execute("mirror /empty zk://127.0.0.1:1"));
+    assert!(!ok);
+    assert_eq!(out, "Mirroring to / replaces the whole tree, add --force to go ahead.\n");
+    assert!(!shell.execute("mirror /src /dst --interval soon"));
+    assert!(!shell.execute("mirror /src zk://"));
+    assert!(!shell.execute("mirror /missing /dst"));
+}
+
+#[test]
+fn create_modes() {
+    let (mut shell, zk) = shell();
+    assert!(shell.execute("create /q x"));
+    assert!(shell.execute("create /q/n- x -e -s"));
+    let stat = zk.exists("/q/n-0000000000", false).unwrap();
+    assert!(stat.ephemeral_owner != 0);
+    let (ok, out) = output::capture(|| shell.execute("create /q/n- x --sequential --var made"));
+    assert!(ok);
+    assert_eq!(out, "/q/n-0000000001\n");
+    assert_eq!(shell.variable("made"), Some("/q/n-0000000001".to_string()));
+    assert_eq!(zk.exists("/q/n-0000000001", false).unwrap().ephemeral_owner, 0);
+    assert!(!shell.execute("create /q/bad x --var 'not a name'"));
+    // the client library predates container and TTL znodes
+    assert!(!shell.execute("create /q/c '' -c"));
+    assert!(!shell.execute("create /q/t x --ttl 60000"));
+    assert!(!shell.execute("create /q/old x true"));
+    assert!(zk.get_children("/q", false).unwrap().len() == 2);
+    assert_eq!(zk.acl("/q"), Some(shell.default_acl().clone()));
+
+    assert!(!shell.execute("create /r x --acl world:anyone:rz"));
+    assert!(zk.exists("/r", false).is_err());
+    assert!(shell.execute("create /r x --acl read-only"));
+    assert_eq!(zk.acl("/r").unwrap()[0].perms, zookeeper::perms::READ);
+}
+
+#[test]
+fn setacl() {
+    let (mut shell, zk) = shell();
+    assert!(shell.execute("create /a x && create /a/b x && create /a/b/c x"));
+    assert!(shell.execute("setacl /a read-only"));
+    assert_eq!(zk.acl("/a").unwrap()[0].perms, zookeeper::perms::READ);
+    assert_eq!(zk.exists("/a", false).unwrap().aversion, 1);
+    assert_eq!(zk.acl("/a/b"), Some(shell.default_acl().clone()));
+
+    // there's no one to say yes
+    let (ok, out) = output::capture(|| shell.execute("setacl /a open --recursive"));
+    assert!(!ok);
+    assert_eq!(out, "This will set the ACL of 3 znode(s) under /a.\n");
+    assert_eq!(zk.acl("/a/b"), Some(shell.default_acl().clone()));
+    assert!(shell.execute("setacl /a digest:bob::cdrwa,world:anyone:r --recursive --force"));
+    for path in ["/a", "/a/b", "/a/b/c"].iter() {
+        let acl: Vec<String> = zk.acl(path).unwrap().iter().map(format_acl).collect();
+        assert_eq!(acl, vec!["digest:bob:tK1fpNO1hB0F++FhHpSq0Fa4tv0=:cdrwa", "world:anyone:r"]);
+    }
+
+    let (ok, out) = output::capture(|| shell.execute("setacl /a world:anyone:rz"));
+    assert!(!ok);
+    assert_eq!(out, "Bad ACL entry 'world:anyone:rz': unknown perm z, expected letters out of cdrwa\n");
+    assert!(!output::capture(|| shell.execute("setacl /missing open")).0);
+
+    let (ok, out) = output::capture(|| shell.execute("setacl /a/b open --dry-run"));
+    assert!(ok);
+    assert_eq!(out, "[dry-run] would set /a/b's acl to world:anyone:cdrwa\n");
+    assert_eq!(zk.exists("/a/b", false).unwrap().aversion, 1);
+}
+
+#[test]
+fn rm_and_rmr() {
+    let (mut shell, zk) = shell();
+    assert!(shell.execute("fill /f 20 --depth 2"));
+    assert!(!shell.execute("rm /f"));
+    assert!(!shell.execute("rm /missing"));
+    let (ok, out) = output::capture(|| shell.execute("rmr /f"));
+    assert!(!ok);
+    assert_eq!(out, "This will delete 25 znode(s) under /f.\n");
+    assert!(shell.execute("rmr /f --force"));
+    assert_eq!(zk.exists("/f", false), Err(ZkError::NoNode));
+}
+
+#[test]
+fn rm_globs() {
+    let (mut shell, zk) = shell();
+    assert!(shell.execute("create /l x && create /l/lock-1 x && create /l/lock-2 x && create /l/keep x"));
+    let (ok, out) = output::capture(|| shell.execute("rm '/l/lock-*'"));
+    assert!(!ok);
+    assert_eq!(out, "This will delete 2 znode(s) matching /l/lock-*.\n");
+    assert_eq!(zk.get_children("/l", false).unwrap().len(), 3);
+
+    assert!(shell.execute("rm '/l/lock-*' --force"));
+    assert_eq!(zk.get_children("/l", false).unwrap(), vec!["keep".to_string()]);
+    let (ok, out) = output::capture(|| shell.execute("rm '/l/lock-*' --force"));
+    assert!(!ok);
+    assert_eq!(out, "Nothing matches /l/lock-*.\n");
+    assert!(!output::capture(|| shell.execute("rm '/l/*' 0 --force")).0);
+
+    // a glob reaches anything under its parent
+    shell.set_protected(vec!["/l/keep".to_string()]);
+    assert!(!output::capture(|| shell.execute("rm '/l/k*' --force")).0);
+    assert!(zk.exists("/l/keep", false).is_ok());
+}
+
+#[test]
+fn fill_spreads_the_leaves() {
+    let (mut shell, zk) = shell();
+    assert!(shell.execute("fill /f 9 --depth 2 --data-size 3"));
+    assert_eq!(zk.get_children("/f", false).unwrap().len(), 3);
+    assert_eq!(data(&zk, "/f/fill-2/fill-2"), "abc");
+    assert!(!shell.execute("fill /f nine"));
+}
+
+#[test]
+fn exit_removes_scratch_znodes() {
+    let (mut shell, zk) = shell();
+    assert!(shell.execute("create /keep x"));
+    assert!(shell.execute("fill /f 9 --depth 2"));
+    assert!(shell.execute("fill /keep 2"));
+    let (_, out) = output::capture(|| shell.shutdown());
+    assert_eq!(out, "Removed 14 scratch znodes.\n");
+    assert!(zk.exists("/f", false).is_ok());
+    assert!(zk.exists("/f/fill-0", false).is_err());
+    assert!(zk.exists("/keep", false).is_ok());
+    assert!(zk.exists("/keep/fill-0", false).is_err());
+
+    let (mut shell, zk) = self::shell();
+    shell.set_keep_scratch(true);
+    assert!(shell.execute("fill /f 2"));
+    shell.shutdown();
+    assert!(zk.exists("/f/fill-1", false).is_ok());
+
+    // nothing after exit runs
+    let (mut shell, zk) = self::shell();
+    assert!(shell.run_script(Cursor::new("create /a x\nexit\ncreate /b y\n")));
+    assert!(zk.exists("/a", false).is_ok());
+    assert!(zk.exists("/b", false).is_err());
+
+    // nor after a script or a single command, however they end
+    let (mut shell, zk) = self::shell();
+    assert!(shell.run_script(Cursor::new("fill /s 3\nexit\n")));
+    assert!(zk.exists("/s/fill-0", false).is_err());
+    let (mut shell, zk) = self::shell();
+    let (_, out) = output::capture(|| shell.run_once("fill /o 3"));
+    assert!(out.ends_with("Removed 3 scratch znodes.\n"));
+    assert!(zk.exists("/o/fill-0", false).is_err());
+}
+
+#[test]
+fn touch_creates_then_bumps() {
+    let (mut shell, zk) = shell();
+    assert!(shell.execute("touch /t"));
+    assert_eq!(zk.exists("/t", false).unwrap().version, 0);
+    assert!(shell.execute("touch /t"));
+    assert_eq!(zk.exists("/t", false).unwrap().version, 1);
+}
+
+#[test]
+fn exists_quiet_is_just_the_status() {
+    let (mut shell, zk) = shell();
+    assert_eq!(output::capture(|| shell.execute("exists /a -q")), (false, String::new()));
+    assert!(shell.execute("create /a x"));
+    assert_eq!(output::capture(|| shell.execute("exists /a -q")), (true, String::new()));
+    assert!(shell.execute("exists /a"));
+
+    // errors aren't the znode missing, they're still told
+    let mut shell = Shell::with_client(Box::new(zk.clone()));
+    shell.set_retries(0);
+    zk.fail_next(1);
+    assert_eq!(output::capture(|| shell.execute("exists /a -q")), (false, "Unknown error: ConnectionLoss\n".to_string()));
+    assert!(shell.execute("disconnect"));
+    assert_eq!(output::capture(|| shell.execute("exists /a -q")), (false, "Not connected.\n".to_string()));
+}
+
+#[test]
+fn listing() {
+    let (mut shell, _) = shell();
+    assert!(shell.execute("fill /f 4"));
+    assert!(shell.execute("ls /f"));
+    assert!(shell.execute("ls /f -l --sort size --reverse"));
+    assert!(shell.execute("ls / -R --max-depth 1"));
+    assert!(!shell.execute("ls /f --sort bogus"));
+    assert!(!shell.execute("ls /missing"));
+    assert!(shell.execute("summary /"));
+    assert!(shell.execute("child_count / --sort children"));
+}
+
+#[test]
+fn listing_filters() {
+    let (mut shell, _) = shell();
+    assert!(shell.execute("fill /f 12"));
+    assert!(output::capture(|| shell.execute("create /f/lock-1 x --ephemeral")).0);
+    let ls = |shell: &mut Shell, cmd: &str| output::capture(|| shell.execute(cmd));
+
+    assert_eq!(ls(&mut shell, "ls /f --match '^fill-1[01]$'"), (true, "fill-10 fill-11\n".to_string()));
+    assert_eq!(ls(&mut shell, "ls /f --ephemeral-only"), (true, "lock-1\n".to_string()));
+    assert_eq!(ls(&mut shell, "ls /f --persistent-only -m '-[2-3]'"), (true, "fill-2 fill-3\n".to_string()));
+    assert_eq!(ls(&mut shell, "ls / -R -m 'lock|fill-0'"), (true, "/f/fill-0\n/f/lock-1\n".to_string()));
+    let (ok, out) = ls(&mut shell, "ls /f --match '(fill'");
+    assert!(!ok && out.starts_with("Bad value for --match: "));
+    assert!(!shell.execute("ls /f --ephemeral-only --persistent-only"));
+}
+
+#[test]
+fn mget() {
+    let (mut shell, _) = shell();
+    for &(path, value) in &[("/m", ""), ("/m/db-1", "a"), ("/m/db-2", "b"), ("/m/cache", "c"), ("/m/db-1/port", "1"),
+                            ("/m/db-2/port", "2"), ("/m/cache/port", "3"), ("/c", "x\ny")] {
+        assert!(shell.execute(&*format!("create {} '{}'", path, value)));
+    }
+    let mget = |shell: &mut Shell, cmd: &str| output::capture(|| shell.execute(cmd));
+
+    assert_eq!(mget(&mut shell, "mget /c /m/cache"), (true, "/c:\nx\ny\n/m/cache: c\n".to_string()));
+    assert_eq!(mget(&mut shell, "mget '/m/db-[0-9]'"), (true, "/m/db-1: a\n/m/db-2: b\n".to_string()));
+    assert_eq!(mget(&mut shell, "mget '/m/*/port' /nope"),
+               (false, "/m/cache/port: 3\n/m/db-1/port: 1\n/m/db-2/port: 2\n/nope: does not exist\n".to_string()));
+    assert_eq!(mget(&mut shell, "mget '/x/*'"), (false, "Nothing matches /x/*.\n".to_string()));
+
+    shell.set_format(Format::Json);
+    assert_eq!(mget(&mut shell, "mget /m/db-? /nope").1,
+               "[{\"path\":\"/m/db-1\",\"data\":\"a\"},{\"path\":\"/m/db-2\",\"data\":\"b\"},{\"path\":\"/nope\",\"data\":null}]\n");
+}
+
+#[test]
+fn waiting_for_znodes() {
+    let (mut shell, zk) = shell();
+    let wait = |shell: &mut Shell, cmd: &str| output::capture(|| shell.execute(cmd));
+    assert_eq!(wait(&mut shell, "wait_exists /a 0.05"), (false, "Timed out waiting for /a to exist.\n".to_string()));
+    assert_eq!(wait(&mut shell, "wait_exists /a soon"), (false, "Bad timeout: soon\n".to_string()));
+
+    let writer = zk.session();
+    let acl = shell.default_acl().clone();
+    let writes = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(50));
+        writer.create("/a", b"starting".to_vec(), acl, zookeeper::CreateMode::Persistent).unwrap();
+        thread::sleep(Duration::from_millis(50));
+        writer.set_data("/a", b"done".to_vec(), -1).unwrap();
+    });
+    assert_eq!(wait(&mut shell, "wait_exists /a 5"), (true, "".to_string()));
+    assert_eq!(wait(&mut shell, "wait_value /a done 5"), (true, "".to_string()));
+    writes.join().unwrap();
+
+    assert_eq!(wait(&mut shell, "wait_value /a starting 0.05"), (false, "Timed out waiting for /a to be starting.\n".to_string()));
+    assert!(shell.execute("wait_value /a done"));
+}
+
+#[test]
+fn wrapped_lines_keep_their_flags() {
+    let zk = MemClient::new();
+    let (shell_session, writer) = (zk.clone(), zk.session());
+    let mut shell = Shell::with_client(Box::new(zk));
+    shell.set_retries(0);
+    assert!(shell.execute("create /a x"));
+
+    let dir = env::temp_dir();
+    let (script, said) = (dir.join(format!("zk-shell-on-change-{}.sh", process::id())),
+                          dir.join(format!("zk-shell-on-change-{}.out", process::id())));
+    File::create(&script).unwrap().write_all(format!("echo \"$@\" > {}\n", said.display()).as_bytes()).unwrap();
+
+    // a change runs the command, then re-arming fails and ends on_change
+    let writes = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(200));
+        shell_session.fail_next(10);
+        writer.set_data("/a", b"y".to_vec(), -1).unwrap();
+    });
+    let line = format!("on_change /a -- sh {} --timeout 5 --dry-run", script.display());
+    let (ok, _) = output::capture(|| shell.execute(&*line));
+    writes.join().unwrap();
+    assert!(!ok);
+    let mut args = String::new();
+    File::open(&said).unwrap().read_to_string(&mut args).unwrap();
+    assert_eq!(args, "--timeout 5 --dry-run\n");
+    fs::remove_file(&script).unwrap();
+    fs::remove_file(&said).unwrap();
+}
+
+#[test]
+fn ages() {
+    let (mut shell, _) = shell();
+    assert!(shell.execute("create /locks ''"));
+    for lock in &["/locks/b", "/locks/a", "/locks/b/held"] {
+        thread::sleep(Duration::from_millis(5));
+        assert!(shell.execute(&*format!("create {} ''", lock)));
+    }
+    let paths = |shell: &mut Shell, cmd: &str| {
+        let (ok, printed) = output::capture(|| shell.execute(cmd));
+        assert!(ok);
+        printed.lines().map(|line| line.split_whitespace().last().unwrap().to_string()).collect::<Vec<_>>()
+    };
+    assert_eq!(paths(&mut shell, "age /locks"), vec!["path", "/locks", "/locks/b", "/locks/a"]);
+    thread::sleep(Duration::from_millis(5));
+    assert!(shell.execute("set /locks/b x"));
+    assert_eq!(paths(&mut shell, "age /locks -R"), vec!["path", "/locks", "/locks/a", "/locks/b/held", "/locks/b"]);
+    assert_eq!(output::capture(|| shell.execute("age /locks/a")).1, "modified   created    path\n0s         0s         /locks/a\n");
+}
+
+#[test]
+fn arguments_are_validated() {
+    let (mut shell, _) = shell();
+    assert!(shell.execute(""));
+    assert!(!shell.execute("bogus"));
+    assert!(!shell.execute("get"));
+    assert!(!shell.execute("get /a true extra"));
+    assert!(!shell.execute("get /a --bogus"));
+}
+
+#[test]
+fn read_only_refuses_writes() {
+    let (mut shell, zk) = shell();
+    assert!(shell.execute("create /a x"));
+    shell.set_read_only(true);
+    assert!(!shell.execute("set /a y"));
+    assert!(!shell.execute("rmr /a"));
+    assert!(!shell.execute("election /e --join"));
+    // --dry-run only lifts it for what it can keep from writing
+    assert!(shell.execute("set /a y --dry-run"));
+    assert!(!shell.execute("mirror /a zk://127.0.0.1:1/copy --force --dry-run"));
+    assert!(shell.execute("get /a"));
+    assert!(shell.execute("election /a"));
+    assert_eq!(data(&zk, "/a"), "x");
+}
+
+#[test]
+fn profiles() {
+    let (mut shell, zk) = shell();
+    let config = Config::parse("[prod]\nhosts = zk1:2181\nchroot = /prod\nread_only = yes\n\n[dev]\nhosts = localhost:2181").unwrap();
+    for name in config.profiles() {
+        shell.add_profile(&*name, config.profile(&*name).unwrap());
+    }
+
+    assert_eq!(shell.use_profile("prod"), Some("zk1:2181/prod".to_string()));
+    assert_eq!(shell.hosts(), "zk1:2181/prod");
+    assert!(!shell.execute("create /refused x"));
+    assert!(zk.exists("/refused", false).is_err());
+    // dev doesn't say, so it stays read-only
+    assert_eq!(shell.use_profile("dev"), Some("localhost:2181".to_string()));
+    assert!(!shell.execute("create /refused x"));
+
+    let (ok, out) = output::capture(|| shell.execute("connect --profile staging"));
+    assert!(!ok);
+    assert_eq!(out, "No profile staging, expected one of: dev, prod\n");
+    assert!(!shell.execute("connect localhost:2181 --profile dev"));
+}
+
+#[test]
+fn last_hosts() {
+    let (mut shell, _) = shell();
+    assert_eq!(shell.last_hosts(), None);
+    assert_eq!(output::capture(|| shell.execute("connect")), (false, "No hosts to connect to.\n".to_string()));
+
+    let file = env::temp_dir().join(format!("zk-shell-last-hosts-{}", process::id()));
+    shell.set_last_hosts_file(Some(file.clone()));
+    assert_eq!(shell.last_hosts(), None);
+    File::create(&file).unwrap().write_all(b"zk1:2181,zk2:2181/app\n").unwrap();
+    assert_eq!(shell.last_hosts(), Some("zk1:2181,zk2:2181/app".to_string()));
+
+    // nothing listens there, what's tried is what the file says
+    shell.set_connect_timeout(1);
+    File::create(&file).unwrap().write_all(b"127.0.0.1:1/app\n").unwrap();
+    assert!(!shell.execute("connect"));
+    assert_eq!(shell.hosts(), "127.0.0.1:1/app");
+
+    // a profile's hosts are remembered by its name, which brings its auth along
+    let mut shell = Shell::new("");
+    shell.set_connect_timeout(1);
+    shell.set_last_hosts_file(Some(file.clone()));
+    let config = Config::parse("[prod]\nhosts = 127.0.0.1:2\nchroot = /prod\nauth = digest:admin:secret\n").unwrap();
+    shell.add_profile("prod", config.profile("prod").unwrap());
+    File::create(&file).unwrap().write_all(b"profile:prod\n").unwrap();
+    assert!(!shell.execute("connect"));
+    assert_eq!(shell.hosts(), "127.0.0.1:2/prod");
+
+    let mut shell = Shell::new("");
+    shell.set_last_hosts_file(Some(file.clone()));
+    File::create(&file).unwrap().write_all(b"profile:staging\n").unwrap();
+    assert_eq!(output::capture(|| shell.execute("connect")), (false, "No profile staging, there are none in the config.\n".to_string()));
+    fs::remove_file(&file).unwrap();
+}
+
+#[test]
+fn protected_paths() {
+    let (mut shell, zk) = shell();
+    assert!(shell.execute("create /prod x && create /prod/db y && create /production z && create /dev w"));
+    shell.set_protected(vec!["/prod/".to_string(), "/zookeeper".to_string()]);
+
+    assert!(!shell.execute("set /prod v"));
+    assert!(!shell.execute("rm /prod/db"));
+    assert!(!shell.execute("rmr /"));
+    assert!(shell.execute("rm /production"));
+    assert!(shell.execute("set /dev v"));
+    assert!(shell.execute("get /prod/db"));
+    assert_eq!(data(&zk, "/prod/db"), "y");
+
+    assert!(shell.execute("set /prod/db v --i-know-what-i-am-doing"));
+    assert_eq!(data(&zk, "/prod/db"), "v");
+}
+
+#[test]
+fn system_tree() {
+    let (mut shell, zk) = shell();
+    let file = env::temp_dir().join(format!("zk-shell-system-{}", process::id()));
+    let file = file.to_str().unwrap();
+    output::capture(|| shell.execute("create /app x && create /zookeeper-ish x"));
+    zk.create("/zookeeper", vec![], shell.default_acl().clone(), zookeeper::CreateMode::Persistent).unwrap();
+    zk.create("/zookeeper/quota", vec![], shell.default_acl().clone(), zookeeper::CreateMode::Persistent).unwrap();
+
+    // writes beneath it need a yes, which a script doesn't give
+    assert!(!shell.execute("set /zookeeper/quota x"));
+    assert!(!shell.execute("create /zookeeper/mine x"));
+    assert!(zk.exists("/zookeeper/mine", false).is_err());
+    assert!(shell.execute("set /zookeeper/quota x --i-know-what-i-am-doing"));
+    assert!(shell.execute("set /zookeeper-ish y"));
+    // only the paths written count, not values that look like them
+    assert!(shell.execute("set /app /zookeeper/quota"));
+
+    assert_eq!(output::capture(|| shell.execute("find / ")).1, "/\n/app\n/zookeeper-ish\n");
+    assert_eq!(output::capture(|| shell.execute("find / quota --include-system")).1, "/zookeeper/quota\n");
+    assert_eq!(output::capture(|| shell.execute("find /zookeeper")).1, "/zookeeper\n/zookeeper/quota\n");
+
+    assert!(shell.execute(&*format!("backup {}", file)));
+    let mut archive = vec![];
+    File::open(file).unwrap().read_to_end(&mut archive).unwrap();
+    assert_eq!(backup::decode(&archive[..]).unwrap().1.len(), 2);
+    assert!(shell.execute(&*format!("backup {} --include-system", file)));
+    let mut archive = vec![];
+    File::open(file).unwrap().read_to_end(&mut archive).unwrap();
+    assert_eq!(backup::decode(&archive[..]).unwrap().1.len(), 4);
+    fs::remove_file(file).unwrap();
+}
+
+#[test]
+fn server_routing() {
+    let (mut shell, zk) = shell();
+    assert!(shell.execute("create /a x"));
+    // only reads can go to a single server
+    assert!(!shell.execute("set /a v --server 127.0.0.1:2181"));
+    assert!(!shell.execute("rm /a --server 127.0.0.1:2181"));
+    assert_eq!(data(&zk, "/a"), "x");
+    assert!(!shell.execute("get /a --server"));
+    assert!(!shell.execute("get /a --server zk1:2181,zk2:2181"));
+    assert!(!shell.execute("get /a --server zk1:2181/chroot"));
+    assert!(shell.execute("get /a"));
+}
+
+#[test]
+fn dry_run() {
+    let (mut shell, zk) = shell();
+    assert!(shell.execute("create /a x && create /a/b y"));
+
+    assert!(shell.execute("create /c z --dry-run"));
+    assert!(shell.execute("set /a v --dry-run"));
+    assert!(shell.execute("rmr /a --force --dry-run"));
+    assert!(zk.exists("/c", false).is_err());
+    assert_eq!(data(&zk, "/a"), "x");
+    assert_eq!(data(&zk, "/a/b"), "y");
+
+    // the failures are still predicted
+    assert!(!shell.execute("create /a x --dry-run"));
+    assert!(!shell.execute("set /a v 7 --dry-run"));
+    assert!(!shell.execute("rm /a --dry-run"));
+    // another ensemble's writes can't be pretended
+    assert!(!shell.execute("mirror /a zk://127.0.0.1:1/copy --force --dry-run"));
+
+    shell.set_dry_run(true);
+    let (created, out) = output::capture(|| shell.execute("create /c hello"));
+    assert!(created);
+    assert!(out.contains("would create /c (5 bytes, persistent, acl world:anyone:cdrwa)"));
+    // pretended writes build on each other
+    assert!(shell.execute("create /c/d x && rmr /c --force"));
+    shell.set_dry_run(false);
+    assert!(zk.exists("/c", false).is_err());
+    assert!(shell.execute("create /c z"));
+
+    // reads see the pretended writes, so mv's check of its copies passes
+    let (moved, out) = output::capture(|| shell.execute("mv /c /m --dry-run"));
+    assert!(moved);
+    assert!(out.contains("would create /m (1 bytes") && out.contains("would delete /c") && !out.contains("doesn't match"));
+    assert!(shell.execute("mv /a /n --recursive --dry-run"));
+    assert!(shell.execute("get /a/b && exists /c"));
+    assert!(zk.exists("/m", false).is_err() && zk.exists("/n", false).is_err());
+}
+
+#[test]
+fn audit_log() {
+    let path = env::temp_dir().join(format!("zk-shell-audit-{}", process::id()));
+    let (mut shell, _) = shell();
+    shell.set_audit_log(&path).unwrap();
+    assert!(!shell.execute("create /a 'hello world' && rm /missing"));
+    assert!(shell.execute("add_auth digest user:secret"));
+
+    let mut audit = String::new();
+    File::open(&path).unwrap().read_to_string(&mut audit).unwrap();
+    let _ = fs::remove_file(&path);
+    let lines: Vec<Vec<&str>> = audit.lines().map(|line| line.split('\t').collect()).collect();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(&lines[0][3..], &["ok", "create /a 'hello world'"]);
+    assert_eq!(&lines[1][3..], &["FAILED", "rm /missing"]);
+    assert!(!audit.contains("secret"));
+}
+
+#[test]
+fn current_server_and_reconnect() {
+    let (mut shell, _) = shell();
+    assert!(shell.execute("current_server"));
+    // a client handed to the shell has no hosts to go back to
+    assert!(!shell.execute("reconnect"));
+    shell.execute("disconnect");
+    assert!(!shell.execute("current_server"));
+}
+
+#[test]
+fn ping_and_monitor() {
+    let (mut shell, _) = shell();
+    assert!(shell.execute("ping"));
+    assert!(shell.execute("ping 2"));
+    assert!(!shell.execute("ping 0"));
+    assert!(!shell.execute("ping --all"));
+    assert!(!shell.execute("ping zk1,zk2"));
+    assert!(!shell.execute("monitor"));
+}
+
+// a server answering each four letter word in responses once, at the
+// address returned
+fn flw_server(responses: Vec<(&'static str, &'static str)>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    thread::spawn(move || {
+        for _ in 0..responses.len() {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut cmd = [0u8; 4];
+            stream.read_exact(&mut cmd).unwrap();
+            let response = responses.iter().find(|&&(word, _)| word.as_bytes() == &cmd[..]).map_or("", |&(_, response)| response);
+            stream.write_all(response.as_bytes()).unwrap();
+        }
+    });
+    address
+}
+
+#[test]
+fn isro() {
+    let (mut shell, _) = shell();
+    let zk1 = flw_server(vec![("isro", "rw")]);
+    let zk2 = flw_server(vec![("isro", "ro")]);
+    let (ok, out) = output::capture(|| shell.execute(&*format!("isro {},{}", zk1, zk2)));
+    assert!(!ok);
+    assert_eq!(out, format!("{}: read-write\n{}: read-only\n\nPartitioned: 1 of 2 servers are read-only ({}).\n", zk1, zk2, zk2));
+
+    let zk1 = flw_server(vec![("isro", "rw")]);
+    let (ok, out) = output::capture(|| shell.execute(&*format!("isro {}", zk1)));
+    assert!(ok);
+    assert_eq!(out, format!("{}: read-write\n", zk1));
+    assert!(!shell.execute("isro zk1 --all"));
+}
+
+#[test]
+fn mntr() {
+    let (mut shell, _) = shell();
+    let zk1 = flw_server(vec![("mntr", "zk_avg_latency\t1\nzk_server_state\tfollower\n")]);
+    let (ok, out) = output::capture(|| shell.execute(&*format!("mntr {}", zk1)));
+    assert!(ok);
+    assert_eq!(out, "zk_avg_latency\t1\nzk_server_state\tfollower\n");
+
+    let zk1 = flw_server(vec![("mntr", "zk_avg_latency\t1\n")]);
+    let (ok, out) = output::capture(|| shell.execute(&*format!("mntr {} --prometheus", zk1)));
+    assert!(ok);
+    assert!(out.contains(&*format!("zk_avg_latency{{server=\"{}\"}} 1\n", zk1)));
+    assert!(!shell.execute("mntr --serve-metrics http"));
+}
+
+#[test]
+fn server_config() {
+    let (mut shell, zk) = shell();
+    let zk1 = flw_server(vec![("conf", "clientPort=2181\ndataDir=/data\ntickTime=2000\nserverId=1\n")]);
+    let zk2 = flw_server(vec![("conf", "clientPort=2181\ndataDir=/data\ntickTime=3000\nserverId=2\nmaxClientCnxns=60\n")]);
+    let (ok, out) = output::capture(|| shell.execute(&*format!("server_config {},{}", zk1, zk2)));
+    assert!(ok);
+    let rows: Vec<Vec<&str>> = out.lines().map(|line| line.split_whitespace().collect()).collect();
+    assert_eq!(rows[0], vec!["SETTING", &*zk1, &*zk2]);
+    assert_eq!(rows[1], vec!["clientPort", "2181", "2181"]);
+    assert_eq!(rows[3], vec!["*", "maxClientCnxns", "-", "60"]);
+    assert_eq!(rows[4], vec!["serverId", "1", "2"]);
+    assert_eq!(rows[5], vec!["*", "tickTime", "2000", "3000"]);
+    assert_eq!(rows[7], vec!["2", "settings", "differ."]);
+
+    let zk1 = flw_server(vec![("conf", "clientPort=2181\ntickTime=2000\n")]);
+    let zk2 = flw_server(vec![("conf", "clientPort=2181\ntickTime=3000\n")]);
+    let (ok, out) = output::capture(|| shell.execute(&*format!("server_config {},{} --diff", zk1, zk2)));
+    assert!(ok);
+    assert_eq!(out.lines().nth(1).unwrap().split_whitespace().collect::<Vec<_>>(), vec!["*", "tickTime", "2000", "3000"]);
+    assert_eq!(out.lines().count(), 4);
+
+    zk.create("/zookeeper", vec![], shell.default_acl().clone(), zookeeper::CreateMode::Persistent).unwrap();
+    zk.create("/zookeeper/config", b"server.1=zk1:2888:3888:participant;2181".to_vec(), shell.default_acl().clone(),
+              zookeeper::CreateMode::Persistent).unwrap();
+    let zk1 = flw_server(vec![("conf", "clientPort=2181\n")]);
+    let (ok, out) = output::capture(|| shell.execute(&*format!("server_config {}", zk1)));
+    assert!(ok);
+    assert!(out.ends_with("/zookeeper/config (version 0):\n  server.1=zk1:2888:3888:participant;2181\n"), "{}", out);
+
+    assert!(!shell.execute("server_config"));
+    assert!(!shell.execute("server_config zk1 --all"));
+}
+
+#[test]
+fn server_watches() {
+    let (mut shell, _) = shell();
+    let zk1 = flw_server(vec![("wchp", "/config\n\t0x1\n\t0x2\n/hosts\n\t0x1\n")]);
+    let zk2 = flw_server(vec![("wchp", "/config\n\t0x3\n/locks\n\t0x3\n")]);
+    let (ok, out) = output::capture(|| shell.execute(&*format!("server_watches {},{}", zk1, zk2)));
+    assert!(ok);
+    let rows: Vec<Vec<&str>> = out.lines().map(|line| line.split_whitespace().collect()).collect();
+    assert_eq!(rows[0], vec!["5", "watches", "on", "3", "paths,", "from", "3", "sessions."]);
+    assert_eq!(&rows[2..6], &[vec!["PATH", "WATCHES"], vec!["/config", "3"], vec!["/hosts", "1"], vec!["/locks", "1"]]);
+    assert_eq!(&rows[7..11], &[vec!["SESSION", "WATCHES"], vec!["0x1", "2"], vec!["0x3", "2"], vec!["0x2", "1"]]);
+
+    let zk1 = flw_server(vec![("wchp", "/config\n\t0x1\n\t0x2\n/hosts\n\t0x1\n")]);
+    let (ok, out) = output::capture(|| shell.execute(&*format!("server_watches {} --top 1", zk1)));
+    assert!(ok);
+    assert_eq!(out.lines().count(), 7);
+    assert!(!shell.execute("server_watches"));
+    assert!(!shell.execute("server_watches --top x"));
+}
+
+#[test]
+fn sessions() {
+    let (mut shell, _) = shell();
+    let zk1 = flw_server(vec![
+        ("cons", " /10.0.0.1:39454[1](queued=0,recved=12,sent=12,sid=0x1,lop=PING,est=1433152800000,to=30000,lcxid=0x3,lzxid=0x1a,lresp=1433152800000,llat=0,minlat=0,avglat=0,maxlat=1)\n /127.0.0.1:40000[0](queued=0,recved=1,sent=0)\n"),
+        ("dump", "SessionTracker dump:\norg.apache.zookeeper.server.quorum.LearnerSessionTracker@1\nephemeral nodes dump:\nSessions with Ephemerals (0):\n"),
+    ]);
+    let zk2 = flw_server(vec![
+        ("cons", " /10.0.0.2:51000[1](queued=0,recved=3,sent=3,sid=0x2,lop=SESS,est=1433152800000,to=10000,lcxid=0x0,lzxid=0xffffffffffffffff)\n"),
+        ("dump", "SessionTracker dump:\nSession Sets (2):\nephemeral nodes dump:\nSessions with Ephemerals (2):\n0x1:\n\t/workers/w1\n\t/locks/l-0000000001\n0x3:\n\t/workers/w3\n"),
+    ]);
+    let (ok, out) = output::capture(|| shell.execute(&*format!("sessions {},{}", zk1, zk2)));
+    assert!(ok);
+    let rows: Vec<Vec<&str>> = out.lines().map(|line| line.split_whitespace().collect()).collect();
+    assert_eq!(rows[0], vec!["SESSION", "CLIENT", "SERVER", "TIMEOUT", "LAST", "ZXID", "EPHEMERALS"]);
+    assert_eq!(rows[1], vec!["0x1", "10.0.0.1:39454", &*zk1, "30000ms", "0x1a", "2"]);
+    assert_eq!(rows[2], vec!["0x2", "10.0.0.2:51000", &*zk2, "10000ms", "-", "0"]);
+    assert_eq!(rows[3], vec!["0x3", "-", "-", "-", "-", "1"]);
+    assert_eq!(rows[5], vec!["3", "sessions,", "2", "with", "ephemerals."]);
+    assert!(!shell.execute("sessions"));
+}
+
+#[test]
+fn stress_watches() {
+    let (mut shell, zk) = shell();
+    assert!(shell.execute("create /tmp x"));
+    assert!(shell.execute("stress_watches /tmp 20 --wait 1"));
+    assert_eq!(zk.get_children("/tmp", false).unwrap().len(), 0);
+    assert!(!shell.execute("stress_watches /missing 5"));
+}
+
+#[test]
+fn sizes() {
+    let (mut shell, _) = shell();
+    assert!(shell.execute("create /h '' && create /h/a x && create /h/b xx"));
+    let big: String = (0..300).map(|_| 'x').collect();
+    assert!(shell.execute(&*format!("create /h/c {}", big)));
+
+    let (ok, out) = output::capture(|| shell.execute("size_histogram /h"));
+    assert!(ok);
+    let rows: Vec<Vec<&str>> = out.lines().map(|line| line.split_whitespace().collect()).collect();
+    assert_eq!(rows[1], vec!["0", "1", "0", "0", "(0%)"]);
+    assert_eq!(rows[2], vec!["<=", "64", "2", "3", "3", "(0%)"]);
+    assert_eq!(rows[4], vec!["<=", "1K", "1", "300", "303", "(100%)"]);
+    assert!(!shell.execute("size_histogram /missing"));
+
+    let (ok, out) = output::capture(|| shell.execute("biggest /h 2"));
+    assert!(ok);
+    let lines: Vec<Vec<&str>> = out.lines().map(|line| line.split_whitespace().collect()).collect();
+    assert_eq!(lines[1], vec!["300", "/h/c"]);
+    assert_eq!(lines[2], vec!["2", "/h/b"]);
+    assert_eq!(lines[5], vec!["3", "/h"]);
+    assert_eq!(lines.len(), 7);
+}
+
+#[test]
+fn ephemerals_by_session() {
+    let (mut shell, zk) = shell();
+    assert!(shell.execute("create /w '' && create /w/ours x -e"));
+    zk.create("/w/theirs", vec![], shell.default_acl().clone(), zookeeper::CreateMode::Ephemeral).unwrap();
+    zk.create("/w/theirs-too", vec![], shell.default_acl().clone(), zookeeper::CreateMode::Ephemeral).unwrap();
+
+    let id = format!("0x{:x}", zk.session_id());
+    let (ok, out) = output::capture(|| shell.execute(&*format!("ephemerals_by_session {} /w", id)));
+    assert!(ok);
+    assert_eq!(out.lines().take(2).collect::<Vec<&str>>(), vec!["/w/theirs", "/w/theirs-too"]);
+    assert!(out.contains("2 ephemeral znode(s)"));
+    assert!(!shell.execute("ephemerals_by_session bogus"));
+}
+
+#[test]
+fn close_session() {
+    let (mut shell, zk) = shell();
+    assert!(shell.execute("create /locks '' && create /locks/ours x -e"));
+    zk.create("/locks/theirs", vec![], shell.default_acl().clone(), zookeeper::CreateMode::Ephemeral).unwrap();
+    zk.create("/theirs-too", vec![], shell.default_acl().clone(), zookeeper::CreateMode::Ephemeral).unwrap();
+
+    let id = format!("0x{:x}", zk.session_id());
+    let (ok, out) = output::capture(|| shell.execute(&*format!("close_session {} /locks --force", id)));
+    assert!(ok);
+    assert!(out.starts_with("Deleted 1 ephemeral znode(s)"));
+    assert!(zk.exists("/locks/theirs", false).is_err());
+    assert!(zk.exists("/theirs-too", false).is_ok());
+    assert!(zk.exists("/locks/ours", false).is_ok());
+
+    let (ok, out) = output::capture(|| shell.execute(&*format!("close_session {} /locks", id)));
+    assert!(ok);
+    assert!(out.contains("owns no ephemeral znodes under /locks"));
+    assert!(!shell.execute("close_session bogus"));
+    assert!(!shell.execute(&*format!("close_session {} /missing --force", id)));
+}
+
+#[test]
+fn find() {
+    let (mut shell, _) = shell();
+    assert!(shell.execute("create /f '' && create /f/worker-1 x && create /f/other x"));
+    let find = |shell: &mut Shell, args: &str| output::capture(|| shell.execute(&*format!("find {}", args)));
+
+    assert_eq!(find(&mut shell, "/f worker"), (true, "/f/worker-1\n".to_string()));
+    assert_eq!(find(&mut shell, "/f --mtime-after -1h").1.lines().count(), 3);
+    assert_eq!(find(&mut shell, "/f --ctime-before -1h").1, "");
+    assert!(!find(&mut shell, "/f --mtime-after soon").0);
+}
+
+#[test]
+fn dump_data() {
+    let (mut shell, _) = shell();
+    output::capture(|| shell.execute("create /d x && create /d/a '' && create /d/a/b '{\"k\":1}' && create /d/c yz"));
+    let dump = |shell: &mut Shell, args: &str| output::capture(|| shell.execute(&*format!("dump_data {}", args)));
+
+    assert_eq!(dump(&mut shell, "/d --max-depth 1"),
+               (true, "/d (1 bytes)\nx\n--\n/d/a (0 bytes)\n--\n/d/c (2 bytes)\nyz\n--\n".to_string()));
+    assert_eq!(dump(&mut shell, "/d /b").1, "/d/a/b (7 bytes)\n{\n  \"k\": 1\n}\n--\n");
+    assert!(!dump(&mut shell, "/d --max-depth deep").0);
+    assert!(!dump(&mut shell, "/missing").0);
+}
+
+#[test]
+fn mv() {
+    let (mut shell, zk) = shell();
+    assert!(shell.execute("create /a x && create /a/b y && create /a/b/c z && create /d w"));
+    assert!(shell.execute("create /locked x --acl read-only"));
+
+    assert!(!shell.execute("mv /a /e"));
+    assert!(!shell.execute("mv /a /d --recursive"));
+    assert!(!shell.execute("mv /a /a/b/in --recursive"));
+    assert_eq!(data(&zk, "/a/b/c"), "z");
+
+    assert!(shell.execute("mv /a /e --recursive"));
+    assert!(zk.exists("/a", false).is_err());
+    assert_eq!(data(&zk, "/e"), "x");
+    assert_eq!(data(&zk, "/e/b/c"), "z");
+
+    assert!(shell.execute("mv /locked /moved"));
+    assert_eq!(zk.acl("/moved").unwrap()[0].perms, zookeeper::perms::READ);
+
+    assert!(shell.execute("create /eph x -e"));
+    assert!(!shell.execute("mv /eph /other"));
+}
+
+#[test]
+fn json_format() {
+    let (mut shell, _) = shell();
+    shell.set_format(Format::Json);
+    assert!(shell.execute("create /a x"));
+    assert!(shell.execute("get /a"));
+    assert!(shell.execute("ls / -l"));
+    assert!(shell.execute("ls / -R"));
+    assert!(shell.execute("exists /a"));
+    assert!(!shell.execute("get /missing"));
+}
+
+#[test]
+fn stat_and_decode_zxid() {
+    let (mut shell, _) = shell();
+    assert!(shell.execute("create /a x"));
+    assert!(shell.execute("stat /a"));
+    assert!(shell.execute("decode_zxid 0x100000002"));
+    assert!(!shell.execute("decode_zxid nope"));
+}
+
+#[test]
+fn set_timeout() {
+    let (mut shell, _) = shell();
+    assert!(shell.execute("set_timeout"));
+    assert!(shell.execute("set_timeout 30"));
+    assert!(shell.execute("set_timeout 30 0"));
+    assert!(!shell.execute("set_timeout 0"));
+    assert!(!shell.execute("set_timeout 30 soon"));
+}
+
+#[test]
+fn auth_applies_to_the_session() {
+    let zk = MemClient::new();
+    let mut shell = Shell::with_client(Box::new(zk.clone()));
+    assert!(shell.add_auth("digest", b"user:secret"));
+    assert_eq!(zk.auths(), vec![("digest".to_string(), b"user:secret".to_vec())]);
+
+    assert!(shell.execute("add_auth ip 10.0.0.1"));
+    assert_eq!(zk.auths().len(), 2);
+    assert!(shell.execute("whoami"));
+}
+
+#[test]
+fn arguments_expand_variables() {
+    let (mut shell, zk) = shell();
+    env::set_var("ZK_SHELL_TEST_BUILD", "42");
+    assert!(shell.execute("create /deploys ''"));
+    assert!(shell.execute("create /deploys/$ZK_SHELL_TEST_BUILD \"build ${ZK_SHELL_TEST_BUILD} ok\""));
+    assert_eq!(data(&zk, "/deploys/42"), "build 42 ok");
+    assert!(shell.execute("set /deploys/42 '$ZK_SHELL_TEST_BUILD'"));
+    assert_eq!(data(&zk, "/deploys/42"), "$ZK_SHELL_TEST_BUILD");
+    assert!(!shell.execute("get /deploys/$ZK_SHELL_TEST_UNSET"));
+
+    // quoting survives commands that run other commands
+    assert!(shell.execute("loop 2 0 set /deploys/42 'two words'"));
+    assert_eq!(data(&zk, "/deploys/42"), "two words");
+}
+
+#[test]
+fn shell_variables() {
+    let (mut shell, zk) = shell();
+    assert!(shell.execute("set_var greeting 'hello there'"));
+    assert!(shell.execute("create /a \"$greeting\""));
+    assert_eq!(data(&zk, "/a"), "hello there");
+    assert!(!shell.execute("set_var 'bad name' x"));
+
+    assert!(shell.execute("var copy = get /a"));
+    assert_eq!(shell.variable("copy"), Some("hello there".to_string()));
+    assert!(shell.execute("create /b $copy"));
+    assert_eq!(data(&zk, "/b"), "hello there");
+
+    // nothing is set when the command fails
+    assert!(!shell.execute("var missing = get /missing"));
+    assert_eq!(shell.variable("missing"), None);
+    assert!(!shell.execute("var x get /a"));
+    assert!(shell.execute("vars"));
+}
+
+#[test]
+fn conditionals() {
+    let (mut shell, zk) = shell();
+    assert!(shell.execute("create /a x && create /b y"));
+    assert!(!shell.execute("create /a x && create /c z"));
+    assert!(zk.exists("/c", false).is_err());
+    assert!(shell.execute("get /missing || create /c z"));
+    assert_eq!(data(&zk, "/c"), "z");
+    assert!(shell.execute("get /a || create /d z"));
+    assert!(zk.exists("/d", false).is_err());
+    assert!(!shell.execute("get /a &&"));
+
+    assert!(shell.execute("if exists /a then set /a new else create /a old"));
+    assert_eq!(data(&zk, "/a"), "new");
+    assert!(shell.execute("if exists /e then set /e new else create /e old"));
+    assert_eq!(data(&zk, "/e"), "old");
+    assert!(shell.execute("if exists /missing then create /f x"));
+    assert!(zk.exists("/f", false).is_err());
+    assert!(!shell.execute("if exists /a then get /missing"));
+}
+
+#[test]
+fn sleep_and_echo() {
+    let (mut shell, _) = shell();
+    assert!(shell.execute("sleep 0"));
+    assert!(shell.execute("sleep 0.01"));
+    assert!(!shell.execute("sleep soon"));
+    assert!(!shell.execute("sleep -1"));
+
+    assert!(shell.execute("var said = echo  'a  b'   c"));
+    assert_eq!(shell.variable("said"), Some("a  b c".to_string()));
+    assert!(shell.execute("echo"));
+}
+
+#[test]
+fn retries_and_timeouts() {
+    let zk = MemClient::new();
+    let flaky = zk.clone();
+    let mut shell = Shell::with_client(Box::new(zk));
+    assert!(shell.execute("create /a x"));
+
+    flaky.fail_next(2);
+    let (ok, out) = output::capture(|| shell.execute("get /a"));
+    assert!(!ok);
+    assert_eq!(out, "Unknown error: ConnectionLoss\n");
+    flaky.fail_next(2);
+    assert!(shell.execute("get /a --retries 2"));
+
+    shell.set_retries(3);
+    flaky.fail_next(3);
+    assert!(shell.execute("get /a"));
+    flaky.fail_next(3);
+    assert!(!shell.execute("get /a --retries 0"));
+    assert!(!shell.execute("get /a --retries"));
+    assert!(!shell.execute("get /a --timeout 0"));
+
+    // the backoff outlasts the deadline
+    flaky.fail_next(20);
+    let (ok, out) = output::capture(|| shell.execute("get /a --retries 20 --timeout 1"));
+    assert!(!ok);
+    assert_eq!(out, "Timed out on /a.\n");
+    flaky.fail_next(0);
+}
+
+#[test]
+fn scripts() {
+    let (mut shell, zk) = shell();
+    let script = "# set things up\ncreate /s ''\n\ncreate /s/a x\nget /s/missing\ncreate /s/b y\n";
+    assert!(!shell.run_script(Cursor::new(script)));
+    assert!(zk.exists("/s/b", false).is_ok());
+}
+
+#[test]
+fn validated_values() {
+    let (mut shell, zk) = shell();
+    assert!(shell.execute("create /c '{\"a\": 1}' --validate json"));
+    let (ok, out) = output::capture(|| shell.execute("set /c '{\"a\": 1' --validate json"));
+    assert!(!ok);
+    // the rest is serde_json's account of it
+    assert!(out.starts_with("Refusing to write malformed JSON: "));
+    assert_eq!(data(&zk, "/c"), "{\"a\": 1}");
+    assert!(!shell.execute("set /c 'a: b: c' --validate yaml"));
+    assert!(!shell.execute("set /c x --validate xml"));
+
+    shell.set_validate(Some(Syntax::Yaml));
+    assert!(!shell.execute("set /c 'a: [1'"));
+    assert!(shell.execute("set /c 'a: [1]'"));
+    assert!(shell.execute("set /c 'a: [1' --validate none"));
+    // parents hold nothing
+    assert!(output::capture(|| shell.execute("create /empty ''")).0);
+}
+
+#[test]
+fn multi_line_values() {
+    let (mut shell, zk) = shell();
+    let script = "create /m -\n{\n  \"a\": 1\n}\n.\nset /m/child - --create\nline 1\r\nline 2\n.\ncreate /n -\n";
+    assert!(shell.run_script(Cursor::new(script)));
+    assert_eq!(zk.get_data("/m", false).unwrap().0, b"{\n  \"a\": 1\n}".to_vec());
+    assert_eq!(zk.get_data("/m/child", false).unwrap().0, b"line 1\nline 2".to_vec());
+    // the input ran out before the .
+    assert_eq!(zk.get_data("/n", false).unwrap().0, b"".to_vec());
+
+    assert!(!shell.execute("set /m -"));
+    assert_eq!(zk.get_data("/m/child", false).unwrap().0, b"line 1\nline 2".to_vec());
+}
+
+#[test]
+fn counters() {
+    let (mut shell, zk) = shell();
+    assert!(shell.execute("counter_incr /c"));
+    assert!(shell.execute("counter_incr /c 10"));
+    assert!(shell.execute("counter_decr /c 4"));
+    assert_eq!(data(&zk, "/c"), "7");
+    assert!(!shell.execute("counter_incr /c many"));
+    assert!(shell.execute("create /s text"));
+    assert!(!shell.execute("counter_get /s"));
+}
+
+#[test]
+fn locks() {
+    let (mut shell, zk) = shell();
+    assert!(!shell.execute("unlock /l"));
+    assert!(shell.execute("lock /l me"));
+    assert!(!shell.execute("lock /l"));
+    assert!(shell.execute("lock_holders /l"));
+    assert_eq!(zk.get_children("/l", false).unwrap().len(), 1);
+    assert!(shell.execute("unlock /l"));
+    assert!(zk.get_children("/l", false).unwrap().is_empty());
+}
+
+#[test]
+fn elections() {
+    let (mut shell, zk) = shell();
+    assert!(shell.execute("election /e --join candidate"));
+    assert!(!shell.execute("election /e --join"));
+    assert_eq!(zk.get_children("/e", false).unwrap().len(), 1);
+    assert!(shell.execute("election /e --leave"));
+    assert!(!shell.execute("election /e --leave"));
+    assert!(zk.get_children("/e", false).unwrap().is_empty());
+}
+
+#[test]
+fn barriers() {
+    let (mut shell, zk) = shell();
+    assert!(shell.execute("barrier_create /b/sync 1"));
+    assert!(shell.execute("barrier_wait /b/sync"));
+    assert!(zk.exists("/b/sync/ready", false).is_ok());
+    assert!(shell.execute("barrier_remove /b/sync"));
+    assert_eq!(zk.exists("/b/sync", false), Err(ZkError::NoNode));
+    let (ok, out) = output::capture(|| shell.execute("barrier_remove /b/sync"));
+    assert!(!ok);
+    assert_eq!(out, "Not in barrier /b/sync.\n");
+
+    // the other participant is another session
+    assert!(shell.execute("barrier_create /b/sync 2"));
+    let acl = shell.default_acl().clone();
+    let other = zk.create("/b/sync/p-", vec![], acl, zookeeper::CreateMode::EphemeralSequential).unwrap();
+    assert!(shell.execute("barrier_wait /b/sync"));
+    let inspector = zk.clone();
+    let leaving = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(200));
+        // still ready: we've left, the other hasn't
+        let ready = zk.exists("/b/sync/ready", false).is_ok();
+        zk.delete(&*other, -1).unwrap();
+        ready
+    });
+    assert!(shell.execute("barrier_remove /b/sync"));
+    assert!(leaving.join().unwrap());
+    assert_eq!(inspector.exists("/b/sync", false), Err(ZkError::NoNode));
+}
+
+#[test]
+fn loop_and_time_run_their_command() {
+    let (mut shell, zk) = shell();
+    assert!(shell.execute("loop 3 0 counter_incr /c"));
+    assert_eq!(data(&zk, "/c"), "3");
+    assert!(shell.execute("time counter_incr /c"));
+    assert_eq!(data(&zk, "/c"), "4");
+    assert!(!shell.execute("loop never 0 ls /"));
+    let (ok, out) = output::capture(|| shell.execute("loop 0 0 counter_incr /c"));
+    assert!(!ok);
+    assert_eq!(out, "Bad count: 0, expected a positive number or forever.\n");
+    assert_eq!(data(&zk, "/c"), "4");
+
+    // lines that fail before they dispatch anything ran no ops
+    let (ok, out) = output::capture(|| shell.execute("time if"));
+    assert!(!ok);
+    assert!(out.starts_with("real ") && !out.contains("ops"));
+    assert!(!shell.execute("time get '/c"));
+}
+
+#[test]
+fn disconnecting_drops_ephemerals() {
+    let (mut shell, zk) = shell();
+    assert!(shell.execute("create /e x -e"));
+    assert!(shell.execute("disconnect"));
+    assert_eq!(zk.exists("/e", false), Err(ZkError::NoNode));
+    assert!(!shell.execute("get /e"));
+    assert!(!shell.execute("disconnect"));
+}
+
+#[test]
+fn help() {
+    let (mut shell, _) = shell();
+    assert!(shell.execute("help"));
+    assert!(shell.execute("help ls"));
+    assert!(shell.execute("man ls"));
+    assert!(!shell.execute("help bogus"));
+
+    let ls = command::help(&*shell.registry().get("ls").unwrap());
+    assert!(ls.contains("--max-depth") && ls.contains("ls /services -l"));
+
+    assert!(shell.execute("apropos LOCK"));
+    assert!(!shell.execute("apropos bogus"));
+}
+
+#[test]
+fn completion_scripts() {
+    let (shell, _) = shell();
+    let flags = vec![
+        Flag { long: "hosts".to_string(), takes_value: true, desc: "hosts string".to_string() },
+        Flag { long: "read-only".to_string(), takes_value: false, desc: "refuse commands that write".to_string() },
+    ];
+    let bash = completion::script("bash", "zk-shell-rs", &flags, shell.registry()).unwrap();
+    assert!(bash.contains("        --hosts) ((i++)) ;;\n"));
+    assert!(bash.contains("compgen -W \"--hosts --read-only\""));
+    assert!(bash.contains("        get) [[ \"$cur\" == -* ]] && COMPREPLY=($(compgen -W \"-d --decrypt -r --raw --i-know-what-i-am-doing"));
+    assert!(bash.ends_with("complete -o default -F _zk_shell_rs zk-shell-rs\n"));
+
+    let zsh = completion::script("zsh", "zk-shell-rs", &flags, shell.registry()).unwrap();
+    assert!(zsh.starts_with("#compdef zk-shell-rs\n"));
+    assert!(zsh.contains("'--read-only:refuse commands that write'"));
+    let fish = completion::script("fish", "zk-shell-rs", &flags, shell.registry()).unwrap();
+    assert!(fish.contains("complete -c zk-shell-rs -n '_zk_shell_rs_using \"\"' -l hosts -r -d 'hosts string'\n"));
+    assert!(fish.contains("complete -c zk-shell-rs -n '_zk_shell_rs_using ls' -s R -l recursive"));
+    assert!(completion::script("powershell", "zk-shell-rs", &flags, shell.registry()).is_none());
+}
+
+#[test]
+fn prefixes_and_suggestions() {
+    let (mut shell, _) = shell();
+    assert!(shell.execute("create /a x"));
+    assert!(!shell.execute("exis /a"));
+    shell.set_prefix_matching(true);
+    assert!(shell.execute("exis /a"));
+    // exists or exit
+    assert!(!shell.execute("exi /a"));
+    assert!(shell.execute("ma ls"));
+    assert!(!shell.execute("se /a y"));
+
+    let registry = shell.registry();
+    assert_eq!(registry.with_prefix("counter_"), vec!["counter_decr", "counter_get", "counter_incr"]);
+    assert_eq!(registry.with_prefix("he"), vec!["help"]);
+    assert_eq!(registry.suggest("exsits")[0], "exists");
+    assert_eq!(registry.suggest("lss")[0], "ls");
+    assert!(registry.suggest("frobnicate").is_empty());
+}
+
+#[test]
+fn every_command_has_examples() {
+    let (shell, _) = shell();
+    for cmd in shell.registry().commands() {
+        assert!(!cmd.examples().is_empty(), "{} has no examples", cmd.name());
+        for example in cmd.examples().lines() {
+            let first = example.split_whitespace().next().unwrap_or("");
+            assert!(first == cmd.name() || cmd.aliases().contains(&first),
+                    "{}'s example runs {}", cmd.name(), example);
+        }
+    }
+}
+
+struct Hello;
+
+impl Command for Hello {
+    fn name(&self) -> &str { "hello" }
+    fn description(&self) -> &str { "Creates /hello" }
+    fn synopsis(&self) -> &str { "<data>" }
+    fn examples(&self) -> &str { "hello world" }
+    fn min_args(&self) -> usize { 1 }
+    fn max_args(&self) -> usize { 1 }
+
+    fn execute(&self, shell: &mut Shell, args: &Args) -> bool {
+        let zk = shell.zk().unwrap();
+        zk.create("/hello", args.get(0).as_bytes().to_vec(), shell.default_acl().clone(),
+                  zookeeper::CreateMode::Persistent).is_ok()
+    }
+}
+
+#[test]
+fn custom_commands() {
+    let (mut shell, zk) = shell();
+    shell.register(Hello);
+    assert!(!shell.execute("hello"));
+    assert!(shell.execute("hello world"));
+    assert_eq!(data(&zk, "/hello"), "world");
+    assert!(shell.registry().names().contains(&"hello".to_string()));
+}
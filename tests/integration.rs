@@ -0,0 +1,219 @@
+//! End-to-end tests against a real ensemble, skipped unless
+//! ZK_SHELL_TEST_HOSTS is set, i.e.:
+//!
+//!     ZK_SHELL_TEST_HOSTS=localhost:2181 cargo test --test integration
+//!
+//! Each test works under its own scratch znode, removed afterwards.
+
+extern crate zk_shell_rs;
+extern crate zookeeper;
+
+use std::env;
+use std::fs;
+use std::process;
+use std::time::Duration;
+
+use zookeeper::{Watcher, WatchedEvent, ZkError, ZooKeeper};
+
+use zk_shell_rs::Shell;
+use zk_shell_rs::config::Profile;
+use zk_shell_rs::traversal::descendants;
+
+
+struct QuietWatcher;
+
+impl Watcher for QuietWatcher {
+    fn handle(&self, _: &WatchedEvent) {}
+}
+
+// a connected shell, a session to check its work with and the scratch path
+struct Env {
+    shell: Shell,
+    zk: ZooKeeper,
+    root: String,
+}
+
+impl Env {
+    fn new(name: &str) -> Option<Env> {
+        let hosts = match env::var("ZK_SHELL_TEST_HOSTS") {
+            Ok(hosts) => hosts,
+            Err(_) => {
+                println!("ZK_SHELL_TEST_HOSTS isn't set, skipping.");
+                return None;
+            }
+        };
+
+        let mut shell = Shell::new("");
+        assert!(shell.execute(&format!("connect {}", hosts)));
+        let zk = ZooKeeper::connect(&*hosts, Duration::from_secs(5), QuietWatcher).unwrap();
+
+        let root = format!("/zk-shell-rs-test-{}-{}", process::id(), name);
+        assert!(shell.execute(&format!("create {} scratch", root)));
+        Some(Env { shell: shell, zk: zk, root: root })
+    }
+
+    fn path(&self, child: &str) -> String {
+        format!("{}/{}", self.root, child)
+    }
+
+    // runs line with {} replaced by the scratch path
+    fn run(&mut self, line: &str) -> bool {
+        let line = line.replace("{}", &*self.root);
+        self.shell.execute(&line)
+    }
+
+    fn data(&self, child: &str) -> String {
+        let (data, _) = self.zk.get_data(&*self.path(child), false).unwrap();
+        String::from_utf8(data).unwrap()
+    }
+
+    fn exists(&self, child: &str) -> bool {
+        self.zk.exists(&*self.path(child), false).is_ok()
+    }
+}
+
+impl Drop for Env {
+    fn drop(&mut self) {
+        if let Ok(paths) = descendants(&self.zk, &*self.root) {
+            for path in paths.iter().rev() {
+                let _ = self.zk.delete(path, -1);
+            }
+        }
+        let _ = self.zk.close();
+    }
+}
+
+#[test]
+fn create_get_set_rm() {
+    let mut env = match Env::new("basic") { Some(env) => env, None => return };
+    assert!(env.run("create {}/a hello"));
+    assert!(env.run("get {}/a"));
+    assert_eq!(env.data("a"), "hello");
+
+    assert!(env.run("set {}/a bye"));
+    assert_eq!(env.data("a"), "bye");
+    assert!(!env.run("set {}/a stale 0"));
+    assert!(env.run("set {}/b/c v --create --parents"));
+    assert_eq!(env.data("b/c"), "v");
+
+    assert!(!env.run("rm {}/b"));
+    assert!(env.run("rm {}/b/c"));
+    assert!(env.run("rm {}/b"));
+    assert!(!env.exists("b"));
+    assert!(!env.run("get {}/b"));
+}
+
+#[test]
+fn ephemeral_and_sequential() {
+    let mut env = match Env::new("modes") { Some(env) => env, None => return };
+    assert!(env.run("create {}/e x -e"));
+    assert!(env.run("create {}/s- x -s"));
+    let children = env.zk.get_children(&*env.root, false).unwrap();
+    assert!(children.iter().any(|child| child.starts_with("s-") && child.len() == 12));
+
+    assert!(env.run("disconnect"));
+    assert!(!env.exists("e"));
+}
+
+#[test]
+fn rmr_and_fill() {
+    let mut env = match Env::new("rmr") { Some(env) => env, None => return };
+    assert!(env.run("fill {}/f 27 --depth 3"));
+    assert_eq!(descendants(&env.zk, &*env.path("f")).unwrap().len(), 1 + 3 + 9 + 27);
+    assert!(env.run("ls {}/f -R"));
+    assert!(env.run("summary {}/f"));
+    assert!(env.run("rmr {}/f --force"));
+    assert!(!env.exists("f"));
+}
+
+#[test]
+fn touch_and_exists() {
+    let mut env = match Env::new("touch") { Some(env) => env, None => return };
+    assert!(!env.run("exists {}/t -q"));
+    assert!(env.run("touch {}/t"));
+    assert!(env.run("touch {}/t"));
+    assert_eq!(env.zk.exists(&*env.path("t"), false).unwrap().version, 1);
+    assert!(env.run("exists {}/t -q"));
+}
+
+#[test]
+fn recipes() {
+    let mut env = match Env::new("recipes") { Some(env) => env, None => return };
+    assert!(env.run("counter_incr {}/c 5"));
+    assert!(env.run("counter_decr {}/c"));
+    assert_eq!(env.data("c"), "4");
+
+    assert!(env.run("lock {}/l"));
+    assert!(env.run("lock_holders {}/l"));
+    assert!(env.run("unlock {}/l"));
+
+    assert!(env.run("election {}/e --join"));
+    assert!(env.run("election {}/e --leave"));
+
+    assert!(env.run("barrier_create {}/b 1"));
+    assert!(env.run("barrier_wait {}/b"));
+    assert!(env.run("barrier_remove {}/b"));
+    assert!(!env.exists("b"));
+}
+
+#[test]
+fn missing_paths_fail() {
+    let mut env = match Env::new("missing") { Some(env) => env, None => return };
+    for cmd in &["get", "ls", "rm", "summary", "child_count", "counter_get", "lock_holders"] {
+        assert!(!env.run(&format!("{} {{}}/missing", cmd)), "{} should fail", cmd);
+    }
+    assert_eq!(env.zk.exists(&*env.path("missing"), false), Err(ZkError::NoNode));
+}
+
+#[test]
+fn connect_remembers_the_hosts() {
+    let hosts = match env::var("ZK_SHELL_TEST_HOSTS") { Ok(hosts) => hosts, Err(_) => return };
+    let file = env::temp_dir().join(format!("zk-shell-rs-test-{}-last-hosts", process::id()));
+    let mut shell = Shell::new("");
+    shell.set_last_hosts_file(Some(file.clone()));
+    assert!(shell.execute(&format!("connect {}", hosts)));
+    assert_eq!(shell.last_hosts(), Some(hosts.clone()));
+
+    // a new shell, given no hosts, goes back to them
+    let mut shell = Shell::new("");
+    shell.set_last_hosts_file(Some(file.clone()));
+    assert!(shell.execute("connect"));
+    assert!(shell.execute("ls /"));
+
+    // a profile's by name, so its read_only comes back with them
+    let profile = Profile { hosts: hosts.clone(), auth: None, read_only: Some(true), default_acl: None };
+    shell.add_profile("test", profile.clone());
+    assert!(shell.execute("connect --profile test"));
+    assert_eq!(shell.last_hosts(), Some("profile:test".to_string()));
+    let mut shell = Shell::new("");
+    shell.set_last_hosts_file(Some(file.clone()));
+    shell.add_profile("test", profile);
+    assert!(shell.execute("connect"));
+    assert!(!shell.execute("create /zk-shell-rs-refused x"));
+    let _ = fs::remove_file(&file);
+}
+
+#[test]
+fn mirrors_to_other_ensembles() {
+    let hosts = match env::var("ZK_SHELL_TEST_HOSTS") { Ok(hosts) => hosts, Err(_) => return };
+    let mut env = match Env::new("mirror") { Some(env) => env, None => return };
+    assert!(env.run("create {}/src x && create {}/src/a y"));
+    let line = format!("mirror {{}}/src zk://{}{{}}/dst --force", hosts);
+    assert!(!env.run(&*format!("{} --dry-run", line)));
+    assert!(!env.exists("dst"));
+    env.shell.set_read_only(true);
+    assert!(!env.run(&*line));
+    assert!(!env.exists("dst"));
+    env.shell.set_read_only(false);
+    assert!(env.run(&*line));
+    assert_eq!(env.data("dst/a"), "y");
+}
+
+#[test]
+fn benchmark_sessions_keep_to_dry_run() {
+    let mut env = match Env::new("bench") { Some(env) => env, None => return };
+    assert!(env.run("benchmark {}/bench 10 --concurrency 3 --dry-run"));
+    assert!(!env.exists("bench"));
+    assert!(env.run("benchmark {}/bench 10 --concurrency 3"));
+    assert!(!env.exists("bench"));
+}
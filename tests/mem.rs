@@ -0,0 +1,240 @@
+extern crate zk_shell_rs;
+extern crate zookeeper;
+
+use std::sync::mpsc::channel;
+
+use zookeeper::{CreateMode, KeeperState, WatchedEvent, WatchedEventType, ZkError};
+
+use zk_shell_rs::client::ZkClient;
+use zk_shell_rs::events::{self, Event, EventLog};
+use zk_shell_rs::mem::MemClient;
+use zk_shell_rs::metrics::Metrics;
+use zk_shell_rs::recipes;
+use zk_shell_rs::traversal::{count_descendants, descendants, walk};
+use zk_shell_rs::util::{ensure_path, parse_session_id, parse_zxid, split_zxid};
+use zk_shell_rs::watch::ChannelWatcher;
+
+
+fn create(zk: &MemClient, path: &str, data: &str) {
+    zk.create(path, data.as_bytes().to_vec(), vec![], CreateMode::Persistent).unwrap();
+}
+
+#[test]
+fn create_requires_parent() {
+    let zk = MemClient::new();
+    assert_eq!(zk.create("/a/b", vec![], vec![], CreateMode::Persistent), Err(ZkError::NoNode));
+    create(&zk, "/a", "");
+    assert_eq!(zk.create("/a", vec![], vec![], CreateMode::Persistent), Err(ZkError::NodeExists));
+    assert_eq!(zk.create("a", vec![], vec![], CreateMode::Persistent), Err(ZkError::BadArguments));
+}
+
+#[test]
+fn sequential_names_follow_the_parent_cversion() {
+    let zk = MemClient::new();
+    create(&zk, "/q", "");
+    let first = zk.create("/q/n-", vec![], vec![], CreateMode::PersistentSequential).unwrap();
+    let second = zk.create("/q/n-", vec![], vec![], CreateMode::PersistentSequential).unwrap();
+    assert_eq!(first, "/q/n-0000000000");
+    assert_eq!(second, "/q/n-0000000001");
+}
+
+#[test]
+fn versions_are_checked() {
+    let zk = MemClient::new();
+    create(&zk, "/a", "1");
+    assert_eq!(zk.set_data("/a", b"2".to_vec(), 0).unwrap().version, 1);
+    assert_eq!(zk.set_data("/a", b"3".to_vec(), 0), Err(ZkError::BadVersion));
+    assert_eq!(zk.delete("/a", 0), Err(ZkError::BadVersion));
+    assert_eq!(zk.delete("/a", 1), Ok(()));
+}
+
+#[test]
+fn delete_refuses_non_empty_nodes() {
+    let zk = MemClient::new();
+    create(&zk, "/a", "");
+    create(&zk, "/a/b", "");
+    assert_eq!(zk.delete("/a", -1), Err(ZkError::NotEmpty));
+    assert_eq!(zk.exists("/a", false).unwrap().num_children, 1);
+}
+
+#[test]
+fn ephemerals_go_away_with_their_session() {
+    let zk = MemClient::new();
+    let other = zk.session();
+    other.create("/e", vec![], vec![], CreateMode::Ephemeral).unwrap();
+    assert_eq!(zk.exists("/e", false).unwrap().ephemeral_owner, other.session_id());
+    assert_eq!(zk.create("/e/child", vec![], vec![], CreateMode::Persistent), Err(ZkError::NoChildrenForEphemerals));
+
+    other.close();
+    assert_eq!(zk.exists("/e", false), Err(ZkError::NoNode));
+    assert_eq!(other.exists("/", false), Err(ZkError::SessionExpired));
+}
+
+#[test]
+fn watches_fire_once() {
+    let zk = MemClient::new();
+    let (tx, rx) = channel();
+    create(&zk, "/a", "");
+
+    zk.get_data_w("/a", Box::new(ChannelWatcher::new(&tx))).unwrap();
+    zk.get_children_w("/a", Box::new(ChannelWatcher::new(&tx))).unwrap();
+    zk.set_data("/a", b"x".to_vec(), -1).unwrap();
+    zk.set_data("/a", b"y".to_vec(), -1).unwrap();
+    create(&zk, "/a/b", "");
+    create(&zk, "/a/c", "");
+
+    let mut events = vec![];
+    while let Ok(event_type) = rx.try_recv() {
+        events.push(event_type);
+    }
+    assert_eq!(events, vec![WatchedEventType::NodeDataChanged, WatchedEventType::NodeChildrenChanged]);
+}
+
+#[test]
+fn session_states_are_tracked() {
+    let log = EventLog::new(10);
+    let state = |keeper_state| Event::new(&WatchedEvent {
+        event_type: WatchedEventType::None,
+        keeper_state: keeper_state,
+        path: None,
+    });
+    assert_eq!(log.state(), None);
+    log.push(state(KeeperState::SyncConnected));
+    log.push(state(KeeperState::Disconnected));
+    assert_eq!(log.state(), Some(KeeperState::Disconnected));
+    assert!(log.recent(1)[0].to_string().ends_with("Session - disconnected"));
+
+    let changed = Event::new(&WatchedEvent {
+        event_type: WatchedEventType::NodeDataChanged,
+        keeper_state: KeeperState::SyncConnected,
+        path: Some("/config".to_string()),
+    });
+    assert!(changed.to_string().ends_with(" NodeDataChanged /config connected"));
+    let json = changed.to_json();
+    assert_eq!(json["type"].as_str(), Some("NodeDataChanged"));
+    assert_eq!(json["path"].as_str(), Some("/config"));
+    assert_eq!(json["state"].as_str(), Some("connected"));
+    assert!(log.recent(1)[0].to_json()["path"].is_null());
+
+    assert_eq!(events::transition(None, KeeperState::SyncConnected), None);
+    assert_eq!(events::transition(Some(KeeperState::SyncConnected), KeeperState::SyncConnected), None);
+    assert!(events::transition(Some(KeeperState::Disconnected), KeeperState::Expired).unwrap().contains("expired"));
+}
+
+#[test]
+fn exists_watch_sees_creation() {
+    let zk = MemClient::new();
+    let (tx, rx) = channel();
+    assert_eq!(zk.exists_w("/a", Box::new(ChannelWatcher::new(&tx))), Err(ZkError::NoNode));
+    create(&zk, "/a", "");
+    assert_eq!(rx.try_recv(), Ok(WatchedEventType::NodeCreated));
+}
+
+#[test]
+fn walk_visits_parents_first_in_order() {
+    let zk = MemClient::new();
+    ensure_path(&zk, "/t/b/x", &vec![]).unwrap();
+    ensure_path(&zk, "/t/a", &vec![]).unwrap();
+
+    let mut visited = vec![];
+    walk(&zk, "/t", None, &mut |path, depth| visited.push((path.to_string(), depth))).unwrap();
+    assert_eq!(visited, vec![("/t".to_string(), 0), ("/t/a".to_string(), 1),
+                             ("/t/b".to_string(), 1), ("/t/b/x".to_string(), 2)]);
+
+    let mut shallow = 0;
+    walk(&zk, "/t", Some(1), &mut |_, _| shallow += 1).unwrap();
+    assert_eq!(shallow, 3);
+    assert_eq!(descendants(&zk, "/missing"), Err(ZkError::NoNode));
+}
+
+#[test]
+fn descendants_are_counted() {
+    let zk = MemClient::new();
+    ensure_path(&zk, "/a/b/c", &vec![]).unwrap();
+    create(&zk, "/a/d", "");
+    assert_eq!(count_descendants(&zk, "/a").unwrap(), 3);
+    assert_eq!(count_descendants(&zk, "/a/d").unwrap(), 0);
+    assert_eq!(count_descendants(&zk, "/missing"), Err(ZkError::NoNode));
+}
+
+#[test]
+fn counters_start_empty() {
+    let zk = MemClient::new();
+    create(&zk, "/c", "");
+    assert_eq!(recipes::counter_get(&zk, "/c").ok(), Some(0));
+    assert_eq!(recipes::counter_add(&zk, "/c", 5, &vec![]).ok(), Some(5));
+    assert_eq!(recipes::counter_add(&zk, "/new", -2, &vec![]).ok(), Some(-2));
+}
+
+#[test]
+fn updates_retry_when_written_in_between() {
+    let zk = MemClient::new();
+    create(&zk, "/u", "a");
+    let other = zk.session();
+    let mut calls = 0;
+    let ret = recipes::update(&zk, "/u", 3, |data| {
+        calls += 1;
+        if calls == 1 {
+            other.set_data("/u", b"b".to_vec(), -1).unwrap();
+        }
+        Some([data, b"!"].concat())
+    });
+    assert_eq!(ret, Ok(true));
+    assert_eq!(calls, 2);
+    assert_eq!(zk.get_data("/u", false).unwrap().0, b"b!".to_vec());
+
+    assert_eq!(recipes::update(&zk, "/u", 3, |_| None), Ok(false));
+    let ret = recipes::update(&zk, "/u", 0, |data| {
+        other.set_data("/u", data.to_vec(), -1).unwrap();
+        Some(vec![])
+    });
+    assert_eq!(ret, Err(ZkError::BadVersion));
+}
+
+#[test]
+fn contenders_are_ordered_by_sequence() {
+    let zk = MemClient::new();
+    let first = recipes::enqueue(&zk, "/lock", "lock-", vec![], &vec![]).unwrap();
+    let second = recipes::enqueue(&zk.session(), "/lock", "lock-", vec![], &vec![]).unwrap();
+    let contenders = recipes::contenders(&zk, "/lock").unwrap();
+    assert_eq!(contenders.len(), 2);
+    assert!(first.ends_with(&*contenders[0]));
+    assert!(second.ends_with(&*contenders[1]));
+    assert_eq!(recipes::wait_first(&zk, "/lock", &*first), Ok(true));
+}
+
+#[test]
+fn zxids_split_into_epoch_and_counter() {
+    assert_eq!(split_zxid(0x300000002), (3, 2));
+    assert_eq!(parse_zxid("0x300000002"), Some(0x300000002));
+    assert_eq!(parse_zxid("12884901890"), Some(0x300000002));
+    assert_eq!(parse_zxid("zxid"), None);
+}
+
+#[test]
+fn session_ids_parse_like_servers_log_them() {
+    assert_eq!(parse_session_id("0x14f8e2c1b9b0001"), Some(0x14f8e2c1b9b0001));
+    assert_eq!(parse_session_id("0xff00000000000001"), Some(0xff00000000000001u64 as i64));
+    assert_eq!(parse_session_id("42"), Some(42));
+    assert_eq!(parse_session_id("0x"), None);
+}
+
+#[test]
+fn metrics_group_methods_and_rank_latencies() {
+    let metrics = Metrics::new();
+    for ms in 1..101 {
+        metrics.record(if ms % 2 == 0 { "get_data" } else { "get_data_w" }, ms as f64, None);
+    }
+    metrics.record("exists_w", 5.0, Some("NoNode".to_string()));
+
+    let ops = metrics.snapshot();
+    assert_eq!(ops.len(), 2);
+    let (ref op, ref exists) = ops[0];
+    assert_eq!((&**op, exists.count, exists.failed()), ("exists", 1, 1));
+    let (ref op, ref get) = ops[1];
+    assert_eq!((&**op, get.count, get.failed()), ("get", 100, 0));
+    assert_eq!(get.percentile(50.0), 50.0);
+    assert_eq!(get.percentile(99.0), 99.0);
+    assert_eq!(get.percentile(100.0), 100.0);
+    assert_eq!(get.percentile(0.0), 1.0);
+}
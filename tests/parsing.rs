@@ -0,0 +1,487 @@
+extern crate serde_json;
+extern crate zk_shell_rs;
+
+use std::io::{Read, Write};
+use std::net::{TcpListener, UdpSocket};
+use std::thread;
+
+use zk_shell_rs::acl;
+use zk_shell_rs::config::Config;
+use zk_shell_rs::crypto;
+use zk_shell_rs::diff;
+use zk_shell_rs::discovery::{SrvRecord, exhibitor_hosts, exhibitor_url, is_truncated, parse_srv_response, resolve_hosts,
+                              srv_lookup_with, srv_query};
+use zk_shell_rs::flw::{parse_conf, parse_cons, parse_dump, parse_mntr, parse_watches};
+use zk_shell_rs::gzip;
+use zk_shell_rs::hosts::ConnectString;
+use zk_shell_rs::prometheus;
+use zk_shell_rs::shell::Syntax;
+use zk_shell_rs::shell::line::{self, Chain};
+use zk_shell_rs::util::{data_summary, format_acl, format_age, glob_match, parse_time};
+
+
+#[test]
+fn config_sections_and_defaults() {
+    let config = Config::parse("
+# defaults
+hosts = localhost:2181
+read_only = yes
+
+[staging]
+; a section
+hosts = stage1:2181,stage2:2181
+").unwrap();
+
+    assert_eq!(config.get("hosts"), Some("localhost:2181"));
+    assert!(config.get_bool("read_only"));
+    assert!(!config.get_bool("missing"));
+    assert!(config.has_section("staging"));
+    assert_eq!(config.section_get("staging", "hosts"), Some("stage1:2181,stage2:2181"));
+    assert_eq!(config.section_get("staging", "read_only"), None);
+}
+
+#[test]
+fn config_profiles() {
+    let config = Config::parse("
+hosts = localhost:2181
+
+[prod]
+hosts = zk1:2181,zk2:2181
+chroot = /prod/
+auth = digest:admin:secret
+read_only = yes
+default_acl = world:anyone:r
+
+[dev]
+hosts = localhost:2181
+").unwrap();
+
+    assert_eq!(config.profiles(), vec!["dev", "prod"]);
+    let prod = config.profile("prod").unwrap();
+    assert_eq!(prod.hosts, "zk1:2181,zk2:2181/prod");
+    assert_eq!(prod.auth, Some(("digest".to_string(), b"admin:secret".to_vec())));
+    assert_eq!(prod.read_only, Some(true));
+    assert_eq!(prod.default_acl.map(|acl| acl.len()), Some(1));
+    let dev = config.profile("dev").unwrap();
+    assert_eq!((dev.auth, dev.read_only, dev.default_acl), (None, None, None));
+
+    assert!(config.profile("").is_err());
+    assert!(config.profile("missing").is_err());
+    let bad = |section: &str| Config::parse(&*format!("[bad]\n{}", section)).unwrap().profile("bad").unwrap_err();
+    assert_eq!(bad("chroot = /x"), "profile bad has no hosts");
+    assert_eq!(bad("hosts = a:2181\nhots = b"), "profile bad: unknown setting hots");
+    assert_eq!(bad("hosts = a:2181\nauth = secret"), "profile bad: bad auth secret, expected scheme:credential");
+    assert_eq!(bad("hosts = a:2181\ntls = yes"), "profile bad: tls can't be honored, this client can't connect over TLS");
+}
+
+#[test]
+fn config_errors() {
+    assert!(Config::parse("hosts localhost").is_err());
+    assert!(Config::parse("").is_ok());
+}
+
+#[test]
+fn exhibitor_urls() {
+    assert_eq!(exhibitor_url("http://exhibitor:8080/x/y"), Some("http://exhibitor:8080/x/y".to_string()));
+    assert_eq!(exhibitor_url("http://host"), Some("http://host/exhibitor/v1/cluster/list".to_string()));
+    assert_eq!(exhibitor_url("https://host/"), Some("https://host/exhibitor/v1/cluster/list".to_string()));
+    assert_eq!(exhibitor_url("http:///x"), None);
+    assert_eq!(exhibitor_url("ftp://host/"), None);
+}
+
+#[test]
+fn exhibitor_server_lists() {
+    let list = serde_json::from_str(r#"{"servers": ["zk1", "zk2"], "port": 2181}"#).unwrap();
+    assert_eq!(exhibitor_hosts(&list), Some("zk1:2181,zk2:2181".to_string()));
+    assert_eq!(exhibitor_hosts(&serde_json::from_str(r#"{"servers": [], "port": 2181}"#).unwrap()), None);
+    assert_eq!(exhibitor_hosts(&serde_json::from_str(r#"{"servers": ["zk1"]}"#).unwrap()), None);
+}
+
+#[test]
+fn srv_responses() {
+    let mut msg = srv_query(7, "_zk._tcp.ex").unwrap();
+    assert_eq!(&msg[12..], &b"\x03_zk\x04_tcp\x02ex\x00\x00\x21\x00\x01"[..]);
+
+    // flip it into a response with one answer, its name pointing back at
+    // the question's and the target's suffix too
+    msg[2] = 0x81;
+    msg[3] = 0x80;
+    msg[7] = 1;
+    msg.extend(b"\xc0\x0c\x00\x21\x00\x01\x00\x00\x00\x3c\x00\x0b");
+    msg.extend(b"\x00\x01\x00\x02\x08\x75\x02zk\xc0\x15");
+    assert_eq!(parse_srv_response(7, &msg).unwrap(), vec![SrvRecord {
+        priority: 1,
+        weight: 2,
+        port: 2165,
+        target: "zk.ex".to_string(),
+    }]);
+
+    assert!(parse_srv_response(8, &msg).is_err());
+    assert!(parse_srv_response(7, &msg[..msg.len() - 3]).is_err());
+    assert!(!is_truncated(&msg));
+    msg[2] |= 0x02;
+    assert!(is_truncated(&msg));
+    assert_eq!(resolve_hosts("zk1:2181/chroot").unwrap(), "zk1:2181/chroot");
+
+    let long = format!("_zk._tcp.{}.ex", "x".repeat(64));
+    for bad in &["_zk..ex", "", &*long] {
+        assert!(srv_query(7, bad).is_err(), "{}", bad);
+    }
+}
+
+// a server's answer to query: one record (priority 1, weight 2, port 2165)
+// for target or, if truncated, just the TC flag
+fn srv_answer(query: &[u8], target: &str, truncated: bool) -> Vec<u8> {
+    let mut msg = query.to_vec();
+    msg[2] = if truncated { 0x83 } else { 0x81 };
+    msg[3] = 0x80;
+    if truncated {
+        return msg;
+    }
+    msg[7] = 1;
+    msg.extend(b"\xc0\x0c\x00\x21\x00\x01\x00\x00\x00\x3c");
+    let rdata: Vec<u8> = b"\x00\x01\x00\x02\x08\x75".iter().cloned()
+        .chain(target.split('.').flat_map(|label| Some(label.len() as u8).into_iter().chain(label.bytes())))
+        .chain(Some(0))
+        .collect();
+    msg.extend(&[0, rdata.len() as u8]);
+    msg.extend(rdata);
+    msg
+}
+
+#[test]
+fn srv_lookups() {
+    // nothing listens on the first one
+    let dead = UdpSocket::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+    let udp = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let server = udp.local_addr().unwrap();
+    let tcp = TcpListener::bind(server).unwrap();
+
+    let answers = thread::spawn(move || {
+        let mut buf = [0; 512];
+        let (len, client) = udp.recv_from(&mut buf).unwrap();
+        let query = buf[..len].to_vec();
+        // from elsewhere, so it doesn't count
+        UdpSocket::bind("127.0.0.1:0").unwrap().send_to(&srv_answer(&query, "forged.ex", false), client).unwrap();
+        udp.send_to(&srv_answer(&query, "", true), client).unwrap();
+
+        let (mut stream, _) = tcp.accept().unwrap();
+        let mut len = [0; 2];
+        stream.read_exact(&mut len).unwrap();
+        let mut query = vec![0; len[1] as usize];
+        stream.read_exact(&mut query).unwrap();
+        let answer = srv_answer(&query, "zk.ex", false);
+        stream.write_all(&[0, answer.len() as u8]).unwrap();
+        stream.write_all(&answer).unwrap();
+    });
+    let records = srv_lookup_with(&[dead, server], "_zk._tcp.ex").unwrap();
+    answers.join().unwrap();
+    assert_eq!(records, vec![SrvRecord { priority: 1, weight: 2, port: 2165, target: "zk.ex".to_string() }]);
+}
+
+fn tokens(s: &str) -> Result<Vec<String>, String> {
+    line::tokenize(s, &|name| match name {
+        "A" => Some("1".to_string()),
+        "EMPTY" => Some(String::new()),
+        "SPACED" => Some("x y".to_string()),
+        _ => None,
+    })
+}
+
+#[test]
+fn tokenizing() {
+    assert_eq!(tokens("  ls   /a  ").unwrap(), vec!["ls", "/a"]);
+    assert_eq!(tokens("set /a 'hello world'").unwrap(), vec!["set", "/a", "hello world"]);
+    assert_eq!(tokens("set /a \"$A and ${A}x\" $SPACED").unwrap(), vec!["set", "/a", "1 and 1x", "x y"]);
+    assert_eq!(tokens("echo '$A' \\$A \"\\$A\" a\\ b").unwrap(), vec!["echo", "$A", "$A", "$A", "a b"]);
+    assert_eq!(tokens("x '' \"$EMPTY\" $ # comment").unwrap(), vec!["x", "", "", "$"]);
+    assert_eq!(tokens("# all comment").unwrap(), Vec::<String>::new());
+    assert!(tokens("get /$MISSING").is_err());
+    assert!(tokens("get 'open").is_err());
+    assert!(tokens("get ${A").is_err());
+}
+
+#[test]
+fn quoting_round_trips() {
+    for arg in &["plain", "/a/b-c", "", "with space", "it's", "$A", "a\"b", "#x"] {
+        assert_eq!(tokens(&*line::quote(arg)).unwrap(), vec![arg.to_string()]);
+    }
+}
+
+#[test]
+fn chains() {
+    assert_eq!(line::split_chain("get /a"), ("get /a".to_string(), vec![]));
+    assert_eq!(line::split_chain("a && b || c"),
+               ("a".to_string(), vec![(Chain::And, "b".to_string()), (Chain::Or, "c".to_string())]));
+    assert_eq!(line::split_chain("set /a 'x && y' \\&& || z # && w"),
+               ("set /a 'x && y' \\&&".to_string(), vec![(Chain::Or, "z # && w".to_string())]));
+    assert_eq!(line::split_chain("a&&"), ("a".to_string(), vec![(Chain::And, "".to_string())]));
+}
+
+#[test]
+fn ifs() {
+    let some = |cond: &str, body: &str, otherwise: Option<&str>| {
+        Ok(Some((cond.to_string(), body.to_string(), otherwise.map(|s| s.to_string()))))
+    };
+    assert_eq!(line::split_if("get /if"), Ok(None));
+    assert_eq!(line::split_if("if exists /a then get /a"), some("exists /a", "get /a", None));
+    assert_eq!(line::split_if("if exists /a then get /a else create /a 'then else'"),
+               some("exists /a", "get /a", Some("create /a 'then else'")));
+    assert!(line::split_if("if exists /a").is_err());
+    assert!(line::split_if("if then get /a").is_err());
+    assert!(line::split_if("if exists /a then get /a else").is_err());
+}
+
+fn acl(spec: &str) -> Result<Vec<String>, String> {
+    acl::parse(spec).map(|acl| acl.iter().map(format_acl).collect())
+}
+
+#[test]
+fn acl_specs() {
+    assert_eq!(acl("open"), Ok(vec!["world:anyone:cdrwa".to_string()]));
+    assert_eq!(acl("read-only, creator-all"), Ok(vec!["world:anyone:r".to_string(), "auth::cdrwa".to_string()]));
+    assert_eq!(acl("ip:10.0.0.0/8:rw"), Ok(vec!["ip:10.0.0.0/8:rw".to_string()]));
+    assert_eq!(acl("ip:fe80::1/64:all"), Ok(vec!["ip:fe80::1/64:cdrwa".to_string()]));
+    assert_eq!(acl("digest:super:superpw:cdrwa"), Ok(vec!["digest:super:g9oN2HttPfn8MMWJZ2r45Np/LIA=:cdrwa".to_string()]));
+    assert_eq!(acl::digest("bob", ""), "bob:tK1fpNO1hB0F++FhHpSq0Fa4tv0=");
+
+    assert!(acl("world:someone:r").is_err());
+    assert!(acl("ip:10.0.0.300:r").is_err());
+    assert!(acl("ip:10.0.0.0/33:r").is_err());
+    assert!(acl("open,digest:nopw").is_err());
+    assert!(acl("world:anyone:rx").unwrap_err().contains("unknown perm x"));
+    assert!(acl("kerberos:x:r").is_err());
+}
+
+#[test]
+fn connect_strings() {
+    let hosts = |hosts: &str| ConnectString::parse(hosts).map(|parsed| parsed.to_string());
+    assert_eq!(hosts("localhost"), Ok("localhost:2181".to_string()));
+    assert_eq!(hosts(" zk1:2181 , zk2/chroot/a "), Ok("zk1:2181,zk2:2181/chroot/a".to_string()));
+    assert_eq!(hosts("[::1]:2182,[fe80::1]"), Ok("[::1]:2182,[fe80::1]:2181".to_string()));
+    assert_eq!(hosts("zk1:2181/"), Ok("zk1:2181".to_string()));
+
+    let parsed = ConnectString::parse("[::1]/apps").unwrap();
+    assert_eq!(parsed.servers, vec![("::1".to_string(), 2181)]);
+    assert_eq!(parsed.chroot, Some("/apps".to_string()));
+
+    assert!(hosts("").is_err());
+    assert!(hosts("zk1,,zk2").is_err());
+    assert!(hosts("zk1:port").is_err());
+    assert!(hosts("zk1:70000").is_err());
+    assert!(hosts("::1").unwrap_err().contains("brackets"));
+    assert!(hosts("[::1").is_err());
+    assert!(hosts("[zk1]:2181").is_err());
+    assert!(hosts("zk1/a/").is_err());
+}
+
+#[test]
+fn mntr_output() {
+    let stats = parse_mntr("zk_version\t3.4.6-1569965, built on 02/20/2014 09:09 GMT\nzk_avg_latency\t0\nzk_server_state\tleader\n\nbogus\n");
+    assert_eq!(stats.len(), 3);
+    assert_eq!(stats.get("zk_server_state").map(|state| &**state), Some("leader"));
+    assert_eq!(stats.get("zk_version").map(|version| &**version), Some("3.4.6-1569965, built on 02/20/2014 09:09 GMT"));
+}
+
+#[test]
+fn conf_output() {
+    let conf = parse_conf("clientPort=2181\ndataDir=/var/lib/zookeeper/version-2\nmembership: \nserver.1=zk1:2888:3888:participant;0.0.0.0:2181\nversion=100000000\n");
+    assert_eq!(conf.len(), 4);
+    assert_eq!(conf.get("clientPort").map(|port| &**port), Some("2181"));
+    assert_eq!(conf.get("server.1").map(|server| &**server), Some("zk1:2888:3888:participant;0.0.0.0:2181"));
+    assert!(!conf.contains_key("membership: "));
+}
+
+#[test]
+fn wchp_output() {
+    let watches = parse_watches("/config\n\t0x14d5bd9a8d50000\n\t0x14d5bd9a8d50001\n/empty\n\n/hosts\n\t0x14d5bd9a8d50000\n");
+    assert_eq!(watches.len(), 3);
+    assert_eq!(watches["/config"], vec!["0x14d5bd9a8d50000", "0x14d5bd9a8d50001"]);
+    assert!(watches["/empty"].is_empty());
+    assert_eq!(watches["/hosts"], vec!["0x14d5bd9a8d50000"]);
+}
+
+#[test]
+fn cons_and_dump_output() {
+    let conns = parse_cons(" /10.0.0.1:39454[1](queued=0,recved=12,sent=12,sid=0x14f8e2c1b9b0001,lop=PING,to=30000,lzxid=0x1a)\n /10.0.0.2:1[0](queued=0,recved=1,sent=0)\n");
+    assert_eq!(conns.len(), 1);
+    assert_eq!((&*conns[0].endpoint, conns[0].session_id), ("10.0.0.1:39454", 0x14f8e2c1b9b0001));
+    assert_eq!((conns[0].timeout, conns[0].last_zxid), (Some(30000), Some(0x1a)));
+
+    let ephemerals = parse_dump("SessionTracker dump:\nSession Sets (1):\n1 expire at Mon Jun 01 10:00:00 UTC 2015:\n\t0x2\nephemeral nodes dump:\nSessions with Ephemerals (1):\n0x14f8e2c1b9b0001:\n\t/workers/w1\n");
+    assert_eq!(ephemerals.len(), 1);
+    assert_eq!(ephemerals[&0x14f8e2c1b9b0001], vec!["/workers/w1"]);
+}
+
+#[test]
+fn times() {
+    let now = 1433152800000; // 2015-06-01T10:00:00Z
+    assert_eq!(parse_time("2015-06-01T10:00:00Z", now), Ok(now));
+    assert_eq!(parse_time("2015-06-01T12:00:00+02:00", now), Ok(now));
+    assert_eq!(parse_time("2015-06-01T10:00", now), Ok(now));
+    assert_eq!(parse_time("2015-06-01", now), Ok(now - 10 * 3600 * 1000));
+    assert_eq!(parse_time("1970-01-01", now), Ok(0));
+    assert_eq!(parse_time("2000-03-01", now), Ok(951868800000));
+    assert_eq!(parse_time("-5m", now), Ok(now - 5 * 60 * 1000));
+    assert_eq!(parse_time("-2h", now), Ok(now - 2 * 3600 * 1000));
+
+    for bad in &["", "-", "-m", "-5y", "yesterday", "2015-13-01", "2015-06-01T25:00", "2015-06-01T10:00+2"] {
+        assert!(parse_time(bad, now).is_err(), "{} parsed", bad);
+    }
+}
+
+#[test]
+fn gzip_round_trips() {
+    let data: Vec<u8> = (0..2000).flat_map(|i| format!("node-{}={}\n", i, i % 7).into_bytes()).collect();
+    let compressed = gzip::compress(&data[..]);
+    assert!(gzip::is_gzip(&compressed[..]));
+    assert!(compressed.len() < data.len() / 2);
+    assert_eq!(gzip::decompress(&compressed[..], gzip::MAX_VALUE_SIZE), Ok(data));
+    assert_eq!(gzip::decompress(&gzip::compress(b"")[..], 0), Ok(vec![]));
+
+    // what gzip itself writes, with a dynamic Huffman block
+    let dynamic = [0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0x05, 0xc1, 0x01, 0x01, 0x00,
+                   0x00, 0x08, 0xc3, 0xa0, 0xac, 0xec, 0xf6, 0xcf, 0x20, 0x00, 0x00, 0x54, 0x55, 0xb5, 0x6d,
+                   0x3b, 0x00, 0x80, 0xaa, 0xaa, 0xb6, 0x6d, 0x07, 0x00, 0x50, 0x55, 0xd5, 0xb6, 0xed, 0x1e,
+                   0x6a, 0x08, 0xfa, 0x40, 0x5d, 0x00, 0x00, 0x00];
+    let expected: Vec<u8> = (0..3).flat_map(|_| b"aaaaaaaaaaaaaaaabbbbbbbbbcccccd".iter().cloned()).collect();
+    assert_eq!(gzip::decompress(&dynamic, gzip::MAX_VALUE_SIZE), Ok(expected));
+
+    let mut corrupt = compressed.clone();
+    let last = corrupt.len() - 5;
+    corrupt[last] ^= 1;
+    assert!(gzip::decompress(&corrupt[..], gzip::MAX_VALUE_SIZE).is_err());
+    assert!(gzip::decompress(b"plain value", gzip::MAX_VALUE_SIZE).is_err());
+
+    // a small value can stand for far more than it's safe to read
+    let bomb = gzip::compress(&vec![0; 1 << 20][..]);
+    assert_eq!(gzip::decompress(&bomb[..], 1 << 20).map(|data| data.len()), Ok(1 << 20));
+    assert_eq!(gzip::decompress(&bomb[..], 1000), Err("decompresses to over 1000 bytes".to_string()));
+}
+
+#[test]
+fn aes_gcm() {
+    let hex = |s: &str| -> Vec<u8> { (0..s.len() / 2).map(|i| u8::from_str_radix(&s[i * 2..i * 2 + 2], 16).unwrap()).collect() };
+
+    // the AES-256 cases from the GCM spec's test vectors
+    let zero = crypto::parse_key("0000000000000000000000000000000000000000000000000000000000000000").unwrap();
+    assert_eq!(crypto::seal(&zero, &[0; 12], b""), hex("530f8afbc74536b9a963b4f1c4cb738b"));
+    assert_eq!(crypto::seal(&zero, &[0; 12], &[0; 16]),
+               hex("cea7403d4d606b6e074ec5d3baf39d18d0d1c8a799996bf0265b98b5d48ab919"));
+    let key = crypto::parse_key("feffe9928665731c6d6a8f9467308308feffe9928665731c6d6a8f9467308308").unwrap();
+    let mut nonce = [0u8; 12];
+    nonce.copy_from_slice(&hex("cafebabefacedbaddecaf888")[..]);
+    let plain = hex("d9313225f88406e5a55909c5aff5269a86a7a9531534f7da2e4c303d8a318a721c3c0c95956809532fcf0e2449a6b525b16aedf5aa0de657ba637b391aafd255");
+    let sealed = crypto::seal(&key, &nonce, &plain[..]);
+    assert_eq!(sealed, hex("522dc1f099567d07f47f37a32a84427d643a8cdcbfe5c0c97598a2bd2555d1aa8cb08e48590dbb3da7b08b1056828838c5f61e6393ba7a0abcc9f662898015ad\
+                            b094dac5d93471bdec1a502270e3cc6c"));
+    assert_eq!(crypto::open(&key, &nonce, &sealed[..]), Some(plain));
+
+    let encrypted = crypto::encrypt(&key, b"secret").unwrap();
+    assert!(crypto::is_encrypted(&encrypted[..]));
+    assert!(encrypted != crypto::encrypt(&key, b"secret").unwrap());
+    assert_eq!(crypto::decrypt(&key, &encrypted[..]), Ok(b"secret".to_vec()));
+    assert!(crypto::decrypt(&zero, &encrypted[..]).is_err());
+    let mut tampered = encrypted.clone();
+    tampered[20] ^= 1;
+    assert!(crypto::decrypt(&key, &tampered[..]).is_err());
+
+    assert!(crypto::parse_key("abc").is_err());
+}
+
+#[test]
+fn yaml_documents() {
+    let valid = [
+        "plain scalar",
+        "key: value\nother: 'quoted # not a comment'  # a comment\n",
+        "servers:\n  - host: a\n    port: 2181\n  - host: b\n    port: 2182\nretries: 3\n",
+        "list:\n- a\n- b\nnext: {x: [1, 2], y: \"z\"}\n",
+        "script: |\n  echo 'a: b'\n\n  exit\nafter: >-\n  folded\n",
+        "long: first\n  second line\nflow: [1,\n  2]\n",
+        "{\"json\": [1, 2, {\"is\": \"yaml\"}]}",
+        "---\na: 1\n...\n---\na: 2\n",
+        "base: &base\n  x: 1\nderived: *base\nit: 'it''s'\n",
+    ];
+    for doc in valid.iter() {
+        assert_eq!(Syntax::Yaml.check(doc), Ok(()), "{}", doc);
+    }
+
+    // how they're wrong is serde_yaml's to say
+    let invalid = [
+        "key: value\n  nested: bad\n",
+        "a:\n    b: 1\n  c: 2\n",
+        "a: 1\n- b\n",
+        "- a\nb: 1\n",
+        "a: 1\na: 2\n",
+        "a: [1, 2\nb: 3\n",
+        "a: \"truncat",
+        "a: {x: 1]\n",
+        "a:\n\tb: 1\n",
+        "a: b: c\n",
+        "\"quoted\" trailing\n",
+    ];
+    for doc in invalid.iter() {
+        assert!(Syntax::Yaml.check(doc).is_err(), "{}", doc);
+    }
+}
+
+#[test]
+fn unified_diffs() {
+    let old: Vec<&str> = "a b c d e f g h i j k l".split(' ').collect();
+    let new: Vec<&str> = "a B c d e f g h i j k l m".split(' ').collect();
+    assert_eq!(diff::unified(&old[..], &new[..], "old", "new", 1), vec![
+        "--- old", "+++ new",
+        "@@ -1,3 +1,3 @@", " a", "-b", "+B", " c",
+        "@@ -12 +12,2 @@", " l", "+m",
+    ]);
+    // close enough changes share a hunk
+    assert_eq!(diff::unified(&old[..4], &new[..4], "old", "new", 3)[2], "@@ -1,4 +1,4 @@");
+    assert_eq!(diff::unified(&[], &["x"], "old", "new", 3), vec!["--- old", "+++ new", "@@ -0,0 +1 @@", "+x"]);
+    assert!(diff::unified(&old[..], &old[..], "old", "new", 3).is_empty());
+    assert_eq!(diff::edits(&["a", "b"], &["b", "c"]),
+               vec![diff::Edit::Delete(0), diff::Edit::Same(1, 0), diff::Edit::Insert(1)]);
+}
+
+#[test]
+fn prometheus_exposition() {
+    let stats = parse_mntr("zk_version\t3.4.6-1569965, built on 02/20/2014 09:09 GMT\nzk_avg_latency\t0\nzk_server_state\tleader\n");
+    let text = prometheus::exposition(&[("zk1:2181".to_string(), Some(stats)), ("zk2:2181".to_string(), None)]);
+    assert_eq!(text, "# TYPE zk_avg_latency untyped
+zk_avg_latency{server=\"zk1:2181\"} 0
+# TYPE zk_server_state untyped
+zk_server_state{server=\"zk1:2181\",state=\"leader\"} 1
+# TYPE zk_up untyped
+zk_up{server=\"zk1:2181\"} 1
+zk_up{server=\"zk2:2181\"} 0
+# TYPE zk_version untyped
+zk_version{server=\"zk1:2181\",version=\"3.4.6-1569965, built on 02/20/2014 09:09 GMT\"} 1
+");
+}
+
+#[test]
+fn globs() {
+    assert!(glob_match("host-*", "host-a") && glob_match("host-*", "host-") && !glob_match("host-*", "hosts"));
+    assert!(glob_match("?b?", "abc") && !glob_match("?b?", "abcd"));
+    assert!(glob_match("n[0-2]", "n1") && !glob_match("n[0-2]", "n3") && glob_match("n[!0-2]", "n3"));
+    assert!(glob_match("[]x]", "]") && glob_match("a[b", "a[b"));
+    assert!(glob_match("*-*", "a-b-c") && glob_match("*", "") && !glob_match("", "a"));
+}
+
+#[test]
+fn data_summaries() {
+    assert_eq!(data_summary(b"abc", 3, 2), "[3 bytes, version 2]");
+    assert_eq!(data_summary(b"a", 1, 0), "[1 byte, version 0]");
+    assert_eq!(data_summary(b"aaaaaaaa", 5, 1), "[8 bytes (5 stored), version 1]");
+    assert_eq!(data_summary(b"\xffab", 3, 0), "[3 bytes, version 0, not UTF-8: invalid bytes are shown as \u{fffd}]");
+}
+
+#[test]
+fn ages() {
+    assert_eq!(format_age(0), "0s");
+    assert_eq!(format_age(-5000), "0s");
+    assert_eq!(format_age(42999), "42s");
+    assert_eq!(format_age((5 * 60 + 12) * 1000), "5m 12s");
+    assert_eq!(format_age(2 * 60 * 60 * 1000 + 59 * 1000), "2h 0m");
+    assert_eq!(format_age((3 * 24 + 4) * 60 * 60 * 1000), "3d 4h");
+}
+